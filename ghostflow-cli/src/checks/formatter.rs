@@ -35,6 +35,26 @@ lazy_static! {
 
 static LOCK_POISONED: &str = "formatter registry lock poisoned";
 
+/// The default limit on the length of paths passed to a batch formatter.
+const DEFAULT_BATCH_ARGS_LEN: usize = 32 * 1024;
+
+/// How a formatter is invoked when reformatting.
+///
+/// Checks always run formatters once per file.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+enum Protocol {
+    PerFile,
+    Batch,
+    Filter,
+}
+
+impl Default for Protocol {
+    fn default() -> Self {
+        Protocol::PerFile
+    }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Formatter {
     #[serde(default)]
@@ -44,6 +64,10 @@ pub struct Formatter {
     config_files: Vec<String>,
     #[serde(default)]
     timeout: Option<u64>,
+    #[serde(default)]
+    protocol: Protocol,
+    #[serde(default)]
+    batch_args_len: Option<usize>,
 }
 
 pub type Read = BTreeMap<String, Formatter>;
@@ -130,6 +154,18 @@ impl Formatter {
         builder.build().unwrap()
     }
 
+    fn reformat_protocol(&self) -> reformat::FormatterProtocol {
+        match self.protocol {
+            Protocol::PerFile => reformat::FormatterProtocol::PerFile,
+            Protocol::Batch => {
+                reformat::FormatterProtocol::Batch {
+                    max_args_len: self.batch_args_len.unwrap_or(DEFAULT_BATCH_ARGS_LEN),
+                }
+            },
+            Protocol::Filter => reformat::FormatterProtocol::Filter,
+        }
+    }
+
     pub fn action<I, K>(reformat: &mut Reformat, kinds: I) -> Result<(), reformat::ReformatError>
     where
         I: IntoIterator<Item = K>,
@@ -147,6 +183,7 @@ impl Formatter {
                 formatter
                     .timeout
                     .map(|timeout| reformatter.with_timeout(Duration::from_secs(timeout)));
+                reformatter.with_protocol(formatter.reformat_protocol());
                 Ok(reformatter)
             })
             .collect::<Result<Vec<_>, reformat::ReformatError>>()?;
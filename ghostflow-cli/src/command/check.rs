@@ -9,6 +9,8 @@ use crate::host::LocalService;
 mod config;
 pub use self::config::{Config, ConfigError, Read};
 
+mod format;
+
 mod list;
 use self::list::{List, ListError};
 
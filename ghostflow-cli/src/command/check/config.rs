@@ -116,7 +116,7 @@ struct CheckRead {
 #[derive(Debug, Default, Deserialize)]
 pub struct Read(Vec<CheckRead>);
 
-type CheckVec<T> = Vec<Box<T>>;
+type CheckVec<T> = Vec<(String, Box<T>)>;
 
 pub struct Config {
     branch: CheckVec<dyn BranchCheck>,
@@ -136,28 +136,28 @@ impl Config {
 
             if let Some(config) = registry.branch.get(&kind) {
                 let check = config.create(&mut value).map_err(|err| {
-                    ConfigError::invalid_configuration(CheckKind::Branch, kind, err)
+                    ConfigError::invalid_configuration(CheckKind::Branch, kind.clone(), err)
                 })?;
 
-                branch.push(check);
+                branch.push((kind, check));
                 continue;
             }
 
             if let Some(config) = registry.commit.get(&kind) {
                 let check = config.create(&mut value).map_err(|err| {
-                    ConfigError::invalid_configuration(CheckKind::Commit, kind, err)
+                    ConfigError::invalid_configuration(CheckKind::Commit, kind.clone(), err)
                 })?;
 
-                commit.push(check);
+                commit.push((kind, check));
                 continue;
             }
 
             if let Some(config) = registry.topic.get(&kind) {
                 let check = config.create(&mut value).map_err(|err| {
-                    ConfigError::invalid_configuration(CheckKind::Topic, kind, err)
+                    ConfigError::invalid_configuration(CheckKind::Topic, kind.clone(), err)
                 })?;
 
-                topic.push(check);
+                topic.push((kind, check));
                 continue;
             }
         }
@@ -172,15 +172,15 @@ impl Config {
     pub fn check_configuration(&self) -> GitCheckConfiguration {
         let mut conf = GitCheckConfiguration::new();
 
-        for check in &self.branch {
+        for (_, check) in &self.branch {
             conf.add_branch_check(check.as_ref());
         }
 
-        for check in &self.commit {
+        for (_, check) in &self.commit {
             conf.add_check(check.as_ref());
         }
 
-        for check in &self.topic {
+        for (_, check) in &self.topic {
             conf.add_topic_check(check.as_ref());
         }
 
@@ -190,10 +190,43 @@ impl Config {
     pub fn commit_check_configuration(&self) -> GitCheckConfiguration {
         let mut conf = GitCheckConfiguration::new();
 
-        for check in &self.commit {
+        for (_, check) in &self.commit {
             conf.add_check(check.as_ref());
         }
 
         conf
     }
+
+    /// A configuration for each check, along with the name of the check.
+    ///
+    /// This is used to attribute results to the check which produced them.
+    pub fn named_check_configurations(&self) -> Vec<(&str, GitCheckConfiguration)> {
+        let branch = self.branch.iter().map(|(name, check)| {
+            let mut conf = GitCheckConfiguration::new();
+            conf.add_branch_check(check.as_ref());
+            (name.as_str(), conf)
+        });
+        let topic = self.topic.iter().map(|(name, check)| {
+            let mut conf = GitCheckConfiguration::new();
+            conf.add_topic_check(check.as_ref());
+            (name.as_str(), conf)
+        });
+
+        branch
+            .chain(self.named_commit_check_configurations())
+            .chain(topic)
+            .collect()
+    }
+
+    /// A configuration for each commit check, along with the name of the check.
+    pub fn named_commit_check_configurations(&self) -> Vec<(&str, GitCheckConfiguration)> {
+        self.commit
+            .iter()
+            .map(|(name, check)| {
+                let mut conf = GitCheckConfiguration::new();
+                conf.add_check(check.as_ref());
+                (name.as_str(), conf)
+            })
+            .collect()
+    }
 }
@@ -0,0 +1,312 @@
+use std::io::{self, Write};
+
+use ghostflow::actions::check::MessageLocation;
+use git_checks_core::CheckResult;
+use git_workarea::CommitId;
+use serde::Serialize;
+use serde_json::{json, Value};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum FormatError {
+    #[error("unknown output format `{}`", format)]
+    UnknownFormat { format: String },
+    #[error("failed to write the report: {}", source)]
+    Write {
+        #[from]
+        source: serde_json::Error,
+    },
+}
+
+impl FormatError {
+    fn unknown_format(format: String) -> Self {
+        FormatError::UnknownFormat {
+            format,
+        }
+    }
+}
+
+type FormatResult<T> = Result<T, FormatError>;
+
+/// Output formats for check commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Human-readable text.
+    Human,
+    /// A JSON array of findings.
+    Json,
+    /// A SARIF 2.1.0 log.
+    Sarif,
+}
+
+impl Format {
+    /// The names of the supported formats.
+    pub const NAMES: [&'static str; 3] = ["human", "json", "sarif"];
+
+    pub fn parse(format: &str) -> FormatResult<Self> {
+        Ok(match format {
+            "human" => Format::Human,
+            "json" => Format::Json,
+            "sarif" => Format::Sarif,
+            other => return Err(FormatError::unknown_format(other.into())),
+        })
+    }
+}
+
+/// The severity of a finding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Alert,
+}
+
+impl Severity {
+    fn sarif_level(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Alert => "note",
+        }
+    }
+}
+
+/// A message from a check.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    /// The name of the check which produced the message.
+    pub check: String,
+    /// The severity of the message.
+    pub severity: Severity,
+    /// The commit the message is about.
+    pub commit: Option<String>,
+    /// The path the message is about.
+    pub path: Option<String>,
+    /// The line within the path.
+    pub line: Option<u64>,
+    /// The message.
+    pub message: String,
+}
+
+/// A collection of findings from checks.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// The names of the checks which were run.
+    checks: Vec<String>,
+    /// The findings of the checks.
+    findings: Vec<Finding>,
+}
+
+impl Report {
+    /// Add the results of a check to the report.
+    ///
+    /// The `commit` is used for messages which do not mention a commit themselves.
+    pub fn add(&mut self, check: &str, commit: Option<&CommitId>, result: &CheckResult) {
+        if !self.checks.iter().any(|name| name == check) {
+            self.checks.push(check.into());
+        }
+
+        let messages = [
+            (Severity::Error, result.errors()),
+            (Severity::Warning, result.warnings()),
+            (Severity::Alert, result.alerts()),
+        ];
+        for (severity, items) in messages {
+            self.findings.extend(items.iter().map(|message| {
+                let location = MessageLocation::parse(message);
+
+                Finding {
+                    check: check.into(),
+                    severity,
+                    commit: location
+                        .commit
+                        .or_else(|| commit.cloned())
+                        .map(|commit| commit.as_str().into()),
+                    path: location.path,
+                    line: location.line,
+                    message: message.clone(),
+                }
+            }));
+        }
+    }
+
+    /// The findings in the report.
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    /// Write the report in the given format.
+    ///
+    /// Human-readable reports are handled by the hosting service and are not supported here.
+    pub fn write<W>(&self, format: Format, out: W) -> FormatResult<()>
+    where
+        W: Write,
+    {
+        match format {
+            Format::Human => Ok(()),
+            Format::Json => Ok(serde_json::to_writer_pretty(out, &self.findings)?),
+            Format::Sarif => Ok(serde_json::to_writer_pretty(out, &self.sarif())?),
+        }
+    }
+
+    /// Write the report to standard output.
+    pub fn print(&self, format: Format) -> FormatResult<()> {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        self.write(format, &mut out)?;
+        if format != Format::Human {
+            // Failing to write the trailing newline is not interesting.
+            let _ = writeln!(out);
+        }
+        Ok(())
+    }
+
+    fn sarif(&self) -> Value {
+        let rules = self
+            .checks
+            .iter()
+            .map(|check| json!({ "id": check }))
+            .collect::<Vec<_>>();
+        let results = self
+            .findings
+            .iter()
+            .map(|finding| {
+                let mut result = json!({
+                    "ruleId": finding.check,
+                    "level": finding.severity.sarif_level(),
+                    "message": {
+                        "text": finding.message,
+                    },
+                    "properties": {
+                        "severity": finding.severity,
+                        "commit": finding.commit,
+                    },
+                });
+                if let Some(path) = finding.path.as_ref() {
+                    let mut location = json!({
+                        "artifactLocation": {
+                            "uri": path,
+                        },
+                    });
+                    if let Some(line) = finding.line {
+                        location
+                            .as_object_mut()
+                            .expect("`location` is always constructed as an object")
+                            .insert("region".into(), json!({ "startLine": line }));
+                    }
+                    result
+                        .as_object_mut()
+                        .expect("`result` is always constructed as an object")
+                        .insert(
+                            "locations".into(),
+                            json!([{ "physicalLocation": location }]),
+                        );
+                }
+                result
+            })
+            .collect::<Vec<_>>();
+
+        sarif_log(rules, results)
+    }
+}
+
+/// Create a SARIF log for a single run of ghostflow.
+pub fn sarif_log(rules: Vec<Value>, results: Vec<Value>) -> Value {
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [
+            {
+                "tool": {
+                    "driver": {
+                        "name": "ghostflow",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "rules": rules,
+                    },
+                },
+                "results": results,
+            },
+        ],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use git_checks_core::CheckResult;
+    use git_workarea::CommitId;
+    use serde_json::json;
+
+    use crate::command::check::format::{Finding, Format, Report, Severity};
+
+    fn report() -> Report {
+        let mut result = CheckResult::new();
+        result
+            .add_error("commit 0123456789ab adds trailing whitespace to `README.md` on line 3.")
+            .add_warning("the topic is large.");
+
+        let mut report = Report::default();
+        report.add(
+            "whitespace",
+            Some(&CommitId::new("fedcba9876543210")),
+            &result,
+        );
+        report
+    }
+
+    #[test]
+    fn test_report_findings() {
+        let report = report();
+
+        assert_eq!(
+            report.findings(),
+            &[
+                Finding {
+                    check: "whitespace".into(),
+                    severity: Severity::Error,
+                    commit: Some("0123456789ab".into()),
+                    path: Some("README.md".into()),
+                    line: Some(3),
+                    message: "commit 0123456789ab adds trailing whitespace to `README.md` on \
+                              line 3."
+                        .into(),
+                },
+                Finding {
+                    check: "whitespace".into(),
+                    severity: Severity::Warning,
+                    commit: Some("fedcba9876543210".into()),
+                    path: None,
+                    line: None,
+                    message: "the topic is large.".into(),
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn test_report_sarif() {
+        let report = report();
+        let mut out = Vec::new();
+        report.write(Format::Sarif, &mut out).unwrap();
+        let sarif: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+        let run = &sarif["runs"][0];
+        assert_eq!(sarif["version"], "2.1.0");
+        assert_eq!(run["tool"]["driver"]["rules"], json!([{ "id": "whitespace" }]));
+        assert_eq!(run["results"][0]["level"], "error");
+        assert_eq!(
+            run["results"][0]["locations"][0]["physicalLocation"],
+            json!({
+                "artifactLocation": {
+                    "uri": "README.md",
+                },
+                "region": {
+                    "startLine": 3,
+                },
+            }),
+        );
+        assert_eq!(run["results"][1]["level"], "warning");
+        assert!(run["results"][1].get("locations").is_none());
+    }
+}
@@ -1,57 +1,31 @@
+use std::collections::BTreeMap;
+
 use clap::{Arg, ArgMatches, Command};
 use git_checks_config::{BranchCheckConfig, CommitCheckConfig, TopicCheckConfig};
 use itertools::Itertools;
+use serde_json::json;
 use thiserror::Error;
 
+use crate::command::check::format::{sarif_log, Format, FormatError};
 use crate::exit_code::ExitCode;
 
 #[derive(Debug, Error)]
 #[non_exhaustive]
 pub enum ListError {
-    #[error("unknown check list format `{}`", format)]
-    UnknownFormat { format: String },
-}
-
-impl ListError {
-    fn unknown_format(format: String) -> Self {
-        ListError::UnknownFormat {
-            format,
-        }
-    }
+    #[error("{}", source)]
+    Format {
+        #[from]
+        source: FormatError,
+    },
 }
 
 type ListResult<T> = Result<T, ListError>;
 
 pub struct List;
 
-enum Format {
-    Human,
-}
-
-impl Format {
-    fn section(&self, section: &str) {
-        match *self {
-            Format::Human => {
-                println!("{}:", section);
-            },
-        }
-    }
-
-    fn check(&self, name: &str) {
-        match *self {
-            Format::Human => {
-                println!("\t{}", name);
-            },
-        }
-    }
-}
-
 impl List {
     pub fn run(matches: &ArgMatches) -> ListResult<ExitCode> {
-        let format = match matches.value_of("FORMAT").unwrap() {
-            "human" => Format::Human,
-            other => return Err(ListError::unknown_format(other.into())),
-        };
+        let format = Format::parse(matches.value_of("FORMAT").unwrap())?;
 
         let (commit, branch, topic) = (
             matches.is_present("COMMIT"),
@@ -60,31 +34,71 @@ impl List {
         );
         let all = !commit && !branch && !topic;
 
+        let mut sections = Vec::new();
+
         if all || commit {
-            format.section("commit checks");
-            git_checks_config::inventory::iter::<CommitCheckConfig>
+            let names = git_checks_config::inventory::iter::<CommitCheckConfig>
                 .into_iter()
                 .map(CommitCheckConfig::name)
                 .sorted()
-                .for_each(|name| format.check(name));
+                .collect::<Vec<_>>();
+            sections.push(("commit", names));
         }
 
         if all || branch {
-            format.section("branch checks");
-            git_checks_config::inventory::iter::<BranchCheckConfig>
+            let names = git_checks_config::inventory::iter::<BranchCheckConfig>
                 .into_iter()
                 .map(BranchCheckConfig::name)
                 .sorted()
-                .for_each(|name| format.check(name));
+                .collect::<Vec<_>>();
+            sections.push(("branch", names));
         }
 
         if all || topic {
-            format.section("topic checks");
-            git_checks_config::inventory::iter::<TopicCheckConfig>
+            let names = git_checks_config::inventory::iter::<TopicCheckConfig>
                 .into_iter()
                 .map(TopicCheckConfig::name)
                 .sorted()
-                .for_each(|name| format.check(name));
+                .collect::<Vec<_>>();
+            sections.push(("topic", names));
+        }
+
+        match format {
+            Format::Human => {
+                for (kind, names) in sections {
+                    println!("{} checks:", kind);
+                    for name in names {
+                        println!("\t{}", name);
+                    }
+                }
+            },
+            Format::Json => {
+                let list = sections.into_iter().collect::<BTreeMap<_, _>>();
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&list).map_err(FormatError::from)?,
+                );
+            },
+            Format::Sarif => {
+                let rules = sections
+                    .into_iter()
+                    .flat_map(|(kind, names)| {
+                        names.into_iter().map(move |name| {
+                            json!({
+                                "id": name,
+                                "properties": {
+                                    "kind": kind,
+                                },
+                            })
+                        })
+                    })
+                    .collect();
+                let log = sarif_log(rules, Vec::new());
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&log).map_err(FormatError::from)?,
+                );
+            },
         }
 
         Ok(ExitCode::Success)
@@ -99,7 +113,7 @@ impl List {
                     .long("format")
                     .help("Format for the list of checks")
                     .default_value("human")
-                    .possible_values(["human"]),
+                    .possible_values(Format::NAMES),
             )
             .arg(
                 Arg::new("BRANCH")
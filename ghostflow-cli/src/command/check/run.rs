@@ -4,6 +4,7 @@ use clap::{Arg, ArgMatches, Command};
 use thiserror::Error;
 
 use crate::checks::formatter::{Formatter, FormatterError};
+use crate::command::check::format::{Format, FormatError};
 use crate::exit_code::ExitCode;
 use crate::host::LocalService;
 
@@ -28,6 +29,11 @@ pub enum RunError {
         source: FormatterError,
     },
     #[error("{}", source)]
+    Format {
+        #[from]
+        source: FormatError,
+    },
+    #[error("{}", source)]
     Commits {
         #[from]
         source: CommitsError,
@@ -57,10 +63,11 @@ impl Run {
             .transpose()?;
 
         let config = matches.value_of("CONFIG");
+        let format = Format::parse(matches.value_of("FORMAT").unwrap())?;
 
         match matches.subcommand() {
-            Some(("commits", m)) => Ok(Commits::run(service, m, config)?),
-            Some(("topic", m)) => Ok(Topic::run(service, m, config)?),
+            Some(("commits", m)) => Ok(Commits::run(service, m, config, format)?),
+            Some(("topic", m)) => Ok(Topic::run(service, m, config, format)?),
             Some((subcmd, _)) => Err(RunError::unknown_command(subcmd.into())),
             None => Err(RunError::NoSubcommand),
         }
@@ -85,7 +92,44 @@ impl Run {
                     .number_of_values(1)
                     .multiple_occurrences(true),
             )
+            .arg(
+                Arg::new("FORMAT")
+                    .long("format")
+                    .help("Format for the check results")
+                    .default_value("human")
+                    .possible_values(Format::NAMES),
+            )
             .subcommand(Commits::subcommand())
             .subcommand(Topic::subcommand())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::command::check::run::Run;
+
+    #[test]
+    fn test_run_topic_subcommand() {
+        let matches = Run::subcommand()
+            .try_get_matches_from(vec![
+                "run", "--format", "json", "topic", "--target", "master", "HEAD",
+            ])
+            .unwrap();
+
+        assert_eq!(matches.value_of("FORMAT"), Some("json"));
+        let (name, topic) = matches.subcommand().unwrap();
+        assert_eq!(name, "topic");
+        assert_eq!(topic.value_of("TARGET"), Some("master"));
+        assert_eq!(topic.value_of("TOPIC"), Some("HEAD"));
+    }
+
+    #[test]
+    fn test_run_commits_subcommand() {
+        let matches = Run::subcommand()
+            .try_get_matches_from(vec!["run", "--format", "sarif", "commits"])
+            .unwrap();
+
+        assert_eq!(matches.value_of("FORMAT"), Some("sarif"));
+        assert_eq!(matches.subcommand_name(), Some("commits"));
+    }
+}
@@ -13,6 +13,7 @@ use itertools::Itertools;
 use rayon::prelude::*;
 use thiserror::Error;
 
+use crate::command::check::format::{Format, FormatError, Report};
 use crate::config::{Config, ConfigError};
 use crate::exit_code::ExitCode;
 use crate::host::LocalService;
@@ -59,6 +60,11 @@ pub enum CommitsError {
     },
     #[error("message building write error: {}", source)]
     BuildComment { source: std::fmt::Error },
+    #[error("{}", source)]
+    Report {
+        #[from]
+        source: FormatError,
+    },
 }
 
 impl CommitsError {
@@ -103,6 +109,7 @@ impl Commits {
         service: Arc<dyn LocalService>,
         matches: &ArgMatches,
         config: Option<&str>,
+        format: Format,
     ) -> CommitsResult<ExitCode> {
         let mut refspecs: Vec<_> = matches
             .values_of("COMMIT")
@@ -127,13 +134,7 @@ impl Commits {
         } else {
             None
         };
-        let conf;
-        let check_conf = if let Some(data) = conf_data {
-            conf = Config::from_bytes(data)?;
-            Some(conf.checks.commit_check_configuration())
-        } else {
-            None
-        };
+        let file_conf = conf_data.map(Config::from_bytes).transpose()?;
 
         let ctx = service.git_context();
         let rev_list = ctx
@@ -151,6 +152,71 @@ impl Commits {
         let identity = service.service_user().identity();
         let quiet = matches.is_present("QUIET");
 
+        if format != Format::Human {
+            let checks = revs
+                .par_lines()
+                .map(|rev| {
+                    let rev = CommitId::new(rev);
+                    let commit_conf;
+                    let conf = if let Some(ref conf) = file_conf {
+                        conf
+                    } else {
+                        let conf_data = service.config(&rev).map_err(|err| {
+                            CommitsError::read_commit_configuration(rev.clone(), err)
+                        })?;
+                        if let Some(data) = conf_data {
+                            commit_conf = Config::from_bytes(data)?;
+                            &commit_conf
+                        } else {
+                            return Ok((rev, Vec::new()));
+                        }
+                    };
+
+                    let results = conf
+                        .checks
+                        .named_commit_check_configurations()
+                        .into_iter()
+                        .map(|(name, checks)| {
+                            checks
+                                .run_commit(ctx, &rev, &identity)
+                                .map(|res| (name.to_string(), res))
+                                .map_err(|err| CommitsError::check(rev.clone(), err))
+                        })
+                        .collect::<CommitsResult<Vec<_>>>()?;
+
+                    Ok((rev, results))
+                })
+                // ::<Fallible<Vec<_>>> would be possible, but makes errors non-deterministic.
+                .collect::<Vec<_>>()
+                .into_iter()
+                .collect::<CommitsResult<Vec<_>>>()?;
+
+            let mut code = ExitCode::Success;
+            let mut report = Report::default();
+            for (rev, results) in checks {
+                // Each set of checks is judged on its own.
+                let pass = results.iter().all(|(_, res)| res.allowed() || res.pass());
+                if pass {
+                    if quiet {
+                        continue;
+                    }
+                } else {
+                    code = ExitCode::Failure;
+                }
+
+                for (name, res) in &results {
+                    report.add(name, Some(&rev), res);
+                }
+            }
+            report.print(format)?;
+
+            return Ok(code);
+        }
+
+        let check_conf = file_conf
+            .as_ref()
+            .map(|conf| conf.checks.commit_check_configuration());
+
         let checks = revs
             .par_lines()
             .map(|rev| {
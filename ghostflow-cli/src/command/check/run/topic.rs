@@ -6,10 +6,11 @@ use std::sync::Arc;
 use clap::{Arg, ArgMatches, Command};
 use ghostflow::actions::check;
 use ghostflow::host::HostingServiceError;
-use git_checks_core::GitCheckConfiguration;
+use git_checks_core::{CheckResult, GitCheckConfiguration};
 use git_workarea::{CommitId, GitError};
 use thiserror::Error;
 
+use crate::command::check::format::{Format, FormatError, Report};
 use crate::config::{Config, ConfigError};
 use crate::exit_code::ExitCode;
 use crate::host::LocalService;
@@ -46,6 +47,11 @@ pub enum TopicError {
         #[from]
         source: check::CheckError,
     },
+    #[error("{}", source)]
+    Report {
+        #[from]
+        source: FormatError,
+    },
 }
 
 impl TopicError {
@@ -73,6 +79,7 @@ impl Topic {
         service: Arc<dyn LocalService>,
         matches: &ArgMatches,
         config: Option<&str>,
+        format: Format,
     ) -> TopicResult<ExitCode> {
         let local_service = service.clone();
         let service = service.as_hosting_service();
@@ -108,17 +115,40 @@ impl Topic {
                 .config(&config_commit)
                 .map_err(|err| TopicError::read_commit_configuration(target.clone(), err))?
         };
-        let conf;
-        let check_conf = if let Some(data) = conf_data {
-            conf = Config::from_bytes(data)?;
-            conf.checks.check_configuration()
-        } else {
-            GitCheckConfiguration::new()
-        };
-
-        let check = check::Check::new(ctx, service, check_conf, &[]);
+        let conf = conf_data.map(Config::from_bytes).transpose()?;
         let head = CommitId::new(topic);
         let mr = local_service.synth_merge_request(&head, &target)?;
+
+        if format != Format::Human {
+            let mut report = Report::default();
+            let mut code = ExitCode::Success;
+            let named_checks = conf
+                .as_ref()
+                .map(|conf| conf.checks.named_check_configurations())
+                .unwrap_or_default();
+            for (name, checks) in named_checks {
+                let res: CheckResult = checks
+                    .run_topic(&ctx, "gf", &target, &head, &mr.author.identity())
+                    .map_err(check::CheckError::from)?
+                    .into();
+
+                // Each set of checks is judged on its own.
+                if !res.allowed() && !res.pass() {
+                    code = ExitCode::Failure;
+                }
+                report.add(name, None, &res);
+            }
+            report.print(format)?;
+
+            return Ok(code);
+        }
+
+        let check_conf = conf
+            .as_ref()
+            .map(|conf| conf.checks.check_configuration())
+            .unwrap_or_else(GitCheckConfiguration::new);
+
+        let check = check::Check::new(ctx, service, check_conf, &[]);
         let status = check.check_mr("gf", &target, &mr)?;
 
         Ok(match status {
@@ -128,7 +158,7 @@ impl Topic {
     }
 
     pub fn subcommand() -> Command<'static> {
-        Command::new("list")
+        Command::new("topic")
             .about("check topic")
             .arg(
                 Arg::new("TARGET")
@@ -72,6 +72,11 @@ pub enum ReformatError {
         #[from]
         source: reformat::ReformatError,
     },
+    #[error("format cache error: {}", source)]
+    FormatCache {
+        #[from]
+        source: reformat::FormatCacheError,
+    },
     #[error("config error: {}", source)]
     Config {
         #[from]
@@ -113,10 +118,15 @@ impl Reformat {
             .transpose()?;
 
         let ctx = service.git_context().clone();
+        let cache = matches
+            .value_of("CACHE")
+            .map(reformat::FormatCache::new)
+            .transpose()?
+            .map(Arc::new);
 
         let (old_commit, reformatted_commit) = match matches.subcommand() {
-            Some(("commits", m)) => Commits::run(service, m)?,
-            Some(("repo", m)) => Repo::run(service, m)?,
+            Some(("commits", m)) => Commits::run(service, m, cache)?,
+            Some(("repo", m)) => Repo::run(service, m, cache)?,
             Some((subcmd, _)) => return Err(ReformatError::unknown_command(subcmd.into())),
             None => return Err(ReformatError::MissingCommand),
         };
@@ -185,6 +195,12 @@ impl Reformat {
                     .number_of_values(1)
                     .multiple_occurrences(true),
             )
+            .arg(
+                Arg::new("CACHE")
+                    .long("cache")
+                    .help("Directory for a cache of formatter results")
+                    .takes_value(true),
+            )
             .arg(
                 Arg::new("REF")
                     .short('r')
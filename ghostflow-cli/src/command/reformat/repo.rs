@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use clap::{Arg, ArgMatches, Command};
-use ghostflow::actions::reformat::{Reformat, ReformatError};
+use ghostflow::actions::reformat::{FormatCache, Reformat, ReformatError};
 use ghostflow::host::{HostedProject, HostingServiceError};
 use git_workarea::CommitId;
 use thiserror::Error;
@@ -43,6 +43,7 @@ impl Repo {
     pub fn run(
         service: Arc<dyn LocalService>,
         matches: &ArgMatches,
+        cache: Option<Arc<FormatCache>>,
     ) -> RepoResult<(CommitId, CommitId)> {
         let local_service = service.clone();
         let service = service.as_hosting_service();
@@ -60,6 +61,9 @@ impl Repo {
         };
         let ctx = local_service.git_context();
         let mut reformat = Reformat::new(ctx.clone(), project);
+        if let Some(cache) = cache {
+            reformat.format_cache(cache);
+        }
 
         reformat::config_from_args(local_service.as_ref(), &commit, matches).and_then(|conf| {
             Ok(conf
@@ -295,6 +295,7 @@ impl LocalService for Local {
             author: self.user.clone(),
             reference: String::new(),
             remove_source_branch: false,
+            labels: Vec::new(),
         })
     }
 }
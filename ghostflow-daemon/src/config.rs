@@ -0,0 +1,336 @@
+use std::collections::hash_map::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use ghostflow::actions::commands::AccessLevel;
+use serde::Deserialize;
+use thiserror::Error;
+
+pub mod checks;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ConfigError {
+    #[error("failed to read configuration file at `{}`: {}", path.display(), source)]
+    ReadFile {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to parse YAML document: {}", source)]
+    YamlParse {
+        #[from]
+        source: serde_yaml::Error,
+    },
+    #[error("failed to perform YAML merge keys: {}", source)]
+    YamlMergeKeys {
+        #[from]
+        source: yaml_merge_keys::MergeKeyError,
+    },
+    #[error(
+        "project `{}` uses the `{}` action for {} events, but it is not configured",
+        project,
+        action,
+        event
+    )]
+    UnconfiguredAction {
+        project: String,
+        action: ActionKind,
+        event: &'static str,
+    },
+}
+
+impl ConfigError {
+    fn read_file(path: PathBuf, source: io::Error) -> Self {
+        ConfigError::ReadFile {
+            path,
+            source,
+        }
+    }
+
+    fn unconfigured_action(project: String, action: ActionKind, event: &'static str) -> Self {
+        ConfigError::UnconfiguredAction {
+            project,
+            action,
+            event,
+        }
+    }
+}
+
+type ConfigResult<T> = Result<T, ConfigError>;
+
+/// The hosting service for a project.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum HostConfig {
+    Github {
+        host: String,
+        owner: String,
+        app_id: i64,
+        private_key: PathBuf,
+        installation_id: i64,
+    },
+    Gitlab {
+        host: String,
+        token: String,
+    },
+}
+
+/// Actions which may be triggered by events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ActionKind {
+    Check,
+    Stage,
+    Unstage,
+    Data,
+    Dashboard,
+}
+
+impl fmt::Display for ActionKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            ActionKind::Check => "check",
+            ActionKind::Stage => "stage",
+            ActionKind::Unstage => "unstage",
+            ActionKind::Data => "data",
+            ActionKind::Dashboard => "dashboard",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// Which actions to run for each kind of event.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EventsConfig {
+    #[serde(default)]
+    pub merge_request: Vec<ActionKind>,
+    #[serde(default)]
+    pub push: Vec<ActionKind>,
+    /// Actions run for comments by users with the access level of the matching command.
+    #[serde(default)]
+    pub comment: Vec<ActionKind>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CheckConfig {
+    #[serde(default)]
+    pub checks: checks::Read,
+    #[serde(default)]
+    pub admins: Vec<String>,
+    pub base_name: Option<String>,
+    #[serde(default)]
+    pub post_failures_only: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdentityConfig {
+    pub name: String,
+    pub email: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StageConfig {
+    pub branch: String,
+    pub identity: IdentityConfig,
+    #[serde(default)]
+    pub quiet: bool,
+    /// Labels starting with this prefix set the priority of a merge request.
+    pub priority_label_prefix: Option<String>,
+    /// Retry topics unstaged by base branch updates after this many seconds.
+    pub retry_backoff: Option<u64>,
+    /// The number of times to retry a topic unstaged by a base branch update.
+    #[serde(default = "default_retry_attempts")]
+    pub retry_attempts: usize,
+}
+
+fn default_retry_attempts() -> usize {
+    3
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct S3Config {
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: Option<String>,
+    #[serde(default)]
+    pub prefix: String,
+    pub access_key: String,
+    pub secret_key: String,
+}
+
+/// A destination for data objects.
+///
+/// Plain strings are `rsync` destinations.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum DataDestinationConfig {
+    Rsync(String),
+    Local { local: PathBuf },
+    S3 { s3: S3Config },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DataConfig {
+    pub destinations: Vec<DataDestinationConfig>,
+    pub ref_namespace: Option<String>,
+    #[serde(default)]
+    pub keep_refs: bool,
+    pub attempts: Option<usize>,
+    /// Reject new data using weak digest algorithms.
+    #[serde(default)]
+    pub reject_weak_algorithms: bool,
+    /// A directory for a persistent data store.
+    pub store: Option<PathBuf>,
+}
+
+/// A query for live results from a dashboard.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DashboardResultsConfig {
+    pub url: String,
+    pub passed: String,
+    pub failed: String,
+    pub pending: Option<String>,
+    /// Wait up to this many seconds for the results to settle.
+    pub poll_timeout: Option<u64>,
+    /// Query the dashboard this many seconds apart while waiting for the results to settle.
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+}
+
+fn default_poll_interval() -> u64 {
+    10
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DashboardConfig {
+    pub status_name: String,
+    pub url: String,
+    pub description: String,
+    pub results: Option<DashboardResultsConfig>,
+}
+
+/// The access level of a user for commands.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccessLevelConfig {
+    None,
+    Contributor,
+    Developer,
+    Maintainer,
+    Owner,
+}
+
+impl From<AccessLevelConfig> for AccessLevel {
+    fn from(level: AccessLevelConfig) -> Self {
+        match level {
+            AccessLevelConfig::None => AccessLevel::None,
+            AccessLevelConfig::Contributor => AccessLevel::Contributor,
+            AccessLevelConfig::Developer => AccessLevel::Developer,
+            AccessLevelConfig::Maintainer => AccessLevel::Maintainer,
+            AccessLevelConfig::Owner => AccessLevel::Owner,
+        }
+    }
+}
+
+/// Commands given in merge request comments (e.g., `Do: stage`).
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommandsConfig {
+    /// The access level of users not listed in `users`.
+    #[serde(default = "default_command_access")]
+    pub default_access: AccessLevelConfig,
+    /// The access levels of specific users.
+    #[serde(default)]
+    pub users: HashMap<String, AccessLevelConfig>,
+    /// Only reply to requests for help and problems.
+    #[serde(default)]
+    pub quiet: bool,
+}
+
+fn default_command_access() -> AccessLevelConfig {
+    AccessLevelConfig::Contributor
+}
+
+/// Configuration for a single project.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProjectConfig {
+    pub host: HostConfig,
+    pub git_dir: PathBuf,
+    /// The secret used to verify webhook deliveries.
+    pub secret: Option<String>,
+    pub check: Option<CheckConfig>,
+    pub stage: Option<StageConfig>,
+    pub data: Option<DataConfig>,
+    pub dashboard: Option<DashboardConfig>,
+    pub commands: Option<CommandsConfig>,
+    #[serde(default)]
+    pub on: EventsConfig,
+}
+
+impl ProjectConfig {
+    fn is_configured(&self, action: ActionKind) -> bool {
+        match action {
+            ActionKind::Check => self.check.is_some(),
+            ActionKind::Stage | ActionKind::Unstage => self.stage.is_some(),
+            ActionKind::Data => self.data.is_some(),
+            ActionKind::Dashboard => self.dashboard.is_some(),
+        }
+    }
+
+    fn validate(&self, name: &str) -> ConfigResult<()> {
+        let events = [
+            ("merge request", &self.on.merge_request),
+            ("push", &self.on.push),
+            ("comment", &self.on.comment),
+        ];
+
+        for (event, actions) in events {
+            for &action in actions.iter() {
+                if !self.is_configured(action) {
+                    return Err(ConfigError::unconfigured_action(name.into(), action, event));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Config {
+    pub projects: HashMap<String, ProjectConfig>,
+}
+
+impl Config {
+    fn from_bytes_impl(data: &[u8]) -> ConfigResult<Self> {
+        let doc: serde_yaml::Value = serde_yaml::from_slice(data)?;
+        let doc = yaml_merge_keys::merge_keys_serde(doc)?;
+        let config: Self = serde_yaml::from_value(doc)?;
+
+        for (name, project) in &config.projects {
+            project.validate(name)?;
+        }
+
+        Ok(config)
+    }
+
+    pub fn from_bytes<D>(data: D) -> ConfigResult<Self>
+    where
+        D: AsRef<[u8]>,
+    {
+        Self::from_bytes_impl(data.as_ref())
+    }
+
+    pub fn from_path<P>(path: P) -> ConfigResult<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let data = fs::read(path).map_err(|err| ConfigError::read_file(path.into(), err))?;
+        Self::from_bytes(data)
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::hash_map::HashMap;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+
+use log::{error, info};
+use thiserror::Error;
+
+use crate::config::Config;
+use crate::event::Event;
+use crate::handler::ProjectHandler;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum DispatchError {
+    #[error("unknown project `{}`", project)]
+    UnknownProject { project: String },
+    #[error("the worker for `{}` has stopped", project)]
+    WorkerStopped { project: String },
+}
+
+impl DispatchError {
+    fn unknown_project(project: String) -> Self {
+        DispatchError::UnknownProject {
+            project,
+        }
+    }
+
+    fn worker_stopped(project: String) -> Self {
+        DispatchError::WorkerStopped {
+            project,
+        }
+    }
+}
+
+type DispatchResult<T> = Result<T, DispatchError>;
+
+struct Worker {
+    secret: Option<String>,
+    sender: Sender<Event>,
+    handle: JoinHandle<()>,
+}
+
+/// Handle events for a project until its channel is closed.
+///
+/// Retries of unstaged topics are performed when they are due, even if no events arrive.
+fn run_worker(handler: &mut ProjectHandler, receiver: &Receiver<Event>) {
+    loop {
+        let event = match handler.next_retry() {
+            // Do not let a busy queue starve retries which are already due.
+            Some(delay) if delay.is_zero() => {
+                handler.retry_unstaged();
+                continue;
+            },
+            Some(delay) => {
+                match receiver.recv_timeout(delay) {
+                    Ok(event) => event,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            },
+            None => {
+                match receiver.recv() {
+                    Ok(event) => event,
+                    Err(_) => break,
+                }
+            },
+        };
+
+        handler.handle(&event);
+    }
+}
+
+/// Sends events to per-project workers.
+///
+/// Each project has a single worker thread which handles its events in the order they arrive.
+/// Events for different projects are handled concurrently.
+pub struct Dispatcher {
+    workers: HashMap<String, Worker>,
+}
+
+impl Dispatcher {
+    pub fn new(config: Config) -> Self {
+        let workers = config
+            .projects
+            .into_iter()
+            .map(|(name, project)| {
+                let (sender, receiver) = mpsc::channel::<Event>();
+                let secret = project.secret.clone();
+                let thread_name = name.clone();
+
+                let handle = thread::Builder::new()
+                    .name(format!("ghostflow-daemon: {}", name))
+                    .spawn(move || {
+                        let mut handler = match ProjectHandler::new(thread_name.clone(), project) {
+                            Ok(handler) => handler,
+                            Err(err) => {
+                                error!(
+                                    target: "ghostflow-daemon",
+                                    "failed to set up {}; dropping its events: {:?}",
+                                    thread_name,
+                                    err,
+                                );
+
+                                for event in receiver {
+                                    error!(
+                                        target: "ghostflow-daemon",
+                                        "dropping event: {:?}",
+                                        event,
+                                    );
+                                }
+
+                                return;
+                            },
+                        };
+
+                        run_worker(&mut handler, &receiver);
+
+                        info!(
+                            target: "ghostflow-daemon",
+                            "worker for {} shutting down",
+                            thread_name,
+                        );
+                    })
+                    .expect("failed to spawn a worker thread");
+
+                (
+                    name,
+                    Worker {
+                        secret,
+                        sender,
+                        handle,
+                    },
+                )
+            })
+            .collect();
+
+        Self {
+            workers,
+        }
+    }
+
+    /// The webhook secret for a project.
+    pub fn secret(&self, project: &str) -> DispatchResult<Option<&str>> {
+        self.workers
+            .get(project)
+            .map(|worker| worker.secret.as_deref())
+            .ok_or_else(|| DispatchError::unknown_project(project.into()))
+    }
+
+    /// Queue an event for its project.
+    pub fn dispatch(&self, event: Event) -> DispatchResult<()> {
+        let project = event.project().to_string();
+        let worker = self
+            .workers
+            .get(&project)
+            .ok_or_else(|| DispatchError::unknown_project(project.clone()))?;
+
+        worker
+            .sender
+            .send(event)
+            .map_err(|_| DispatchError::worker_stopped(project))
+    }
+
+    /// A dispatcher for a single project whose events are sent to the returned channel.
+    #[cfg(test)]
+    pub fn for_test(project: &str, secret: Option<&str>) -> (Self, Receiver<Event>) {
+        let (sender, receiver) = mpsc::channel();
+        let worker = Worker {
+            secret: secret.map(Into::into),
+            sender,
+            handle: thread::spawn(|| ()),
+        };
+        let workers = [(project.into(), worker)].into_iter().collect();
+
+        (
+            Self {
+                workers,
+            },
+            receiver,
+        )
+    }
+
+    /// Wait for all queued events to be handled and stop the workers.
+    pub fn shutdown(self) {
+        for (name, worker) in self.workers {
+            drop(worker.sender);
+            if worker.handle.join().is_err() {
+                error!(
+                    target: "ghostflow-daemon",
+                    "the worker for {} panicked",
+                    name,
+                );
+            }
+        }
+    }
+}
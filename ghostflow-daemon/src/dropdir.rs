@@ -0,0 +1,238 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use log::{error, info, warn};
+use serde::Deserialize;
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::dispatch::{DispatchError, Dispatcher};
+use crate::event::{Event, EventError, EventSource};
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum DropDirError {
+    #[error("failed to create directory `{}`: {}", path.display(), source)]
+    CreateDirectory {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to list directory `{}`: {}", path.display(), source)]
+    ListDirectory {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to move `{}` to `{}`: {}", path.display(), target.display(), source)]
+    MoveFile {
+        path: PathBuf,
+        target: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+}
+
+impl DropDirError {
+    fn create_directory(path: PathBuf, source: io::Error) -> Self {
+        DropDirError::CreateDirectory {
+            path,
+            source,
+        }
+    }
+
+    fn list_directory(path: PathBuf, source: io::Error) -> Self {
+        DropDirError::ListDirectory {
+            path,
+            source,
+        }
+    }
+
+    fn move_file(path: PathBuf, target: PathBuf, source: io::Error) -> Self {
+        DropDirError::MoveFile {
+            path,
+            target,
+            source,
+        }
+    }
+}
+
+type DropDirResult<T> = Result<T, DropDirError>;
+
+/// Errors for a single dropped file.
+#[derive(Debug, Error)]
+enum DropFileError {
+    #[error("failed to read the file: {}", source)]
+    Read {
+        #[from]
+        source: io::Error,
+    },
+    #[error("failed to parse the file: {}", source)]
+    Json {
+        #[from]
+        source: serde_json::Error,
+    },
+    #[error("{}", source)]
+    Event {
+        #[from]
+        source: EventError,
+    },
+    #[error("{}", source)]
+    Dispatch {
+        #[from]
+        source: DispatchError,
+    },
+}
+
+/// The contents of a dropped event file.
+#[derive(Debug, Deserialize)]
+struct DropFile {
+    /// The service which sent the event (`github` or `gitlab`).
+    source: String,
+    /// The event kind as given in the webhook header.
+    event: String,
+    /// The webhook payload.
+    payload: Value,
+}
+
+/// A directory watched for event files.
+///
+/// Each `*.json` file in the directory is handled in name order. Once handled, it is moved into
+/// the `processed` directory or, if it could not be handled, the `failed` directory.
+pub struct DropDir {
+    path: PathBuf,
+    poll_interval: Duration,
+}
+
+const PROCESSED_DIR: &str = "processed";
+const FAILED_DIR: &str = "failed";
+
+impl DropDir {
+    pub fn new<P>(path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            path: path.into(),
+            poll_interval: Duration::from_secs(5),
+        }
+    }
+
+    /// How long to wait between scans of the directory.
+    pub fn poll_interval(&mut self, poll_interval: Duration) -> &mut Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    fn pending(&self) -> DropDirResult<Vec<PathBuf>> {
+        let entries = fs::read_dir(&self.path)
+            .map_err(|err| DropDirError::list_directory(self.path.clone(), err))?;
+
+        let mut files = entries
+            .filter_map(|entry| {
+                match entry {
+                    Ok(entry) => Some(entry.path()),
+                    Err(err) => {
+                        warn!(
+                            target: "ghostflow-daemon",
+                            "failed to read an entry in {}: {:?}",
+                            self.path.display(),
+                            err,
+                        );
+
+                        None
+                    },
+                }
+            })
+            .filter(|path| path.is_file() && path.extension().map_or(false, |ext| ext == "json"))
+            .collect::<Vec<_>>();
+        files.sort();
+
+        Ok(files)
+    }
+
+    fn handle_file(path: &Path, dispatcher: &Dispatcher) -> Result<(), DropFileError> {
+        let contents = fs::read(path)?;
+        let file: DropFile = serde_json::from_slice(&contents)?;
+        let source = EventSource::parse(&file.source)?;
+
+        if let Some(event) = Event::parse(source, &file.event, &file.payload)? {
+            dispatcher.dispatch(event)?;
+        } else {
+            info!(
+                target: "ghostflow-daemon",
+                "ignoring {} event in {}",
+                file.event,
+                path.display(),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn move_into(&self, path: &Path, dir: &str) -> DropDirResult<()> {
+        let target_dir = self.path.join(dir);
+        let file_name = path.file_name().expect("dropped files have names");
+        let target = target_dir.join(file_name);
+
+        fs::rename(path, &target).map_err(|err| DropDirError::move_file(path.into(), target, err))
+    }
+
+    /// Handle all of the files currently in the directory.
+    ///
+    /// Returns the number of files which were found.
+    pub fn scan(&self, dispatcher: &Dispatcher) -> DropDirResult<usize> {
+        let files = self.pending()?;
+
+        for path in &files {
+            let dir = match Self::handle_file(path, dispatcher) {
+                Ok(()) => PROCESSED_DIR,
+                Err(err) => {
+                    error!(
+                        target: "ghostflow-daemon",
+                        "failed to handle {}: {}",
+                        path.display(),
+                        err,
+                    );
+
+                    FAILED_DIR
+                },
+            };
+
+            self.move_into(path, dir)?;
+        }
+
+        Ok(files.len())
+    }
+
+    /// Watch the directory for event files.
+    ///
+    /// If `once` is set, the directory is scanned a single time.
+    pub fn watch(&self, dispatcher: &Dispatcher, once: bool) -> DropDirResult<()> {
+        for dir in [PROCESSED_DIR, FAILED_DIR] {
+            let path = self.path.join(dir);
+            fs::create_dir_all(&path).map_err(|err| DropDirError::create_directory(path, err))?;
+        }
+
+        info!(
+            target: "ghostflow-daemon",
+            "watching {} for events",
+            self.path.display(),
+        );
+
+        loop {
+            self.scan(dispatcher)?;
+
+            if once {
+                break;
+            }
+
+            thread::sleep(self.poll_interval);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,468 @@
+use git_workarea::CommitId;
+use serde_json::Value;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum EventError {
+    #[error("unknown event source `{}`", name)]
+    UnknownSource { name: String },
+    #[error("missing field `{}` in the `{}` payload", field, kind)]
+    MissingField { kind: String, field: &'static str },
+}
+
+impl EventError {
+    fn unknown_source(name: String) -> Self {
+        EventError::UnknownSource {
+            name,
+        }
+    }
+
+    fn missing_field(kind: &str, field: &'static str) -> Self {
+        EventError::MissingField {
+            kind: kind.into(),
+            field,
+        }
+    }
+}
+
+type EventResult<T> = Result<T, EventError>;
+
+/// The service which sent an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventSource {
+    Github,
+    Gitlab,
+}
+
+impl EventSource {
+    pub fn parse(source: &str) -> EventResult<Self> {
+        match source {
+            "github" => Ok(EventSource::Github),
+            "gitlab" => Ok(EventSource::Gitlab),
+            _ => Err(EventError::unknown_source(source.into())),
+        }
+    }
+}
+
+/// What happened to a merge request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeRequestAction {
+    Opened,
+    Updated,
+    Closed,
+    Merged,
+    Other,
+}
+
+impl MergeRequestAction {
+    /// Whether the merge request is still open after the event.
+    pub fn is_open(self) -> bool {
+        matches!(
+            self,
+            MergeRequestAction::Opened | MergeRequestAction::Updated | MergeRequestAction::Other,
+        )
+    }
+}
+
+/// The state of a merge request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeRequestState {
+    Open,
+    Closed,
+    Merged,
+}
+
+impl From<MergeRequestState> for MergeRequestAction {
+    fn from(state: MergeRequestState) -> Self {
+        // Events which do not change the merge request are only interesting for its state.
+        match state {
+            MergeRequestState::Open => MergeRequestAction::Other,
+            MergeRequestState::Closed => MergeRequestAction::Closed,
+            MergeRequestState::Merged => MergeRequestAction::Merged,
+        }
+    }
+}
+
+/// An event from a hosting service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    MergeRequest {
+        project: String,
+        id: u64,
+        action: MergeRequestAction,
+    },
+    Push {
+        project: String,
+        refname: String,
+        commit: CommitId,
+    },
+    Comment {
+        project: String,
+        id: u64,
+        author: String,
+        content: String,
+        state: MergeRequestState,
+    },
+}
+
+/// Look up a string in a JSON document.
+fn get_str<'a>(kind: &str, value: &'a Value, pointer: &'static str) -> EventResult<&'a str> {
+    value
+        .pointer(pointer)
+        .and_then(Value::as_str)
+        .ok_or_else(|| EventError::missing_field(kind, pointer))
+}
+
+/// Look up an integer in a JSON document.
+fn get_u64(kind: &str, value: &Value, pointer: &'static str) -> EventResult<u64> {
+    value
+        .pointer(pointer)
+        .and_then(Value::as_u64)
+        .ok_or_else(|| EventError::missing_field(kind, pointer))
+}
+
+impl Event {
+    /// The project the event is for.
+    pub fn project(&self) -> &str {
+        match self {
+            Event::MergeRequest {
+                project, ..
+            }
+            | Event::Push {
+                project, ..
+            }
+            | Event::Comment {
+                project, ..
+            } => project,
+        }
+    }
+
+    /// Parse an event from a webhook payload.
+    ///
+    /// The `kind` is the value of the event header for the service. Events which are not relevant
+    /// return `None`.
+    pub fn parse(source: EventSource, kind: &str, payload: &Value) -> EventResult<Option<Self>> {
+        match source {
+            EventSource::Github => Self::parse_github(kind, payload),
+            EventSource::Gitlab => Self::parse_gitlab(kind, payload),
+        }
+    }
+
+    fn parse_github(kind: &str, payload: &Value) -> EventResult<Option<Self>> {
+        let event = match kind {
+            "pull_request" => {
+                let action = match get_str(kind, payload, "/action")? {
+                    "opened" | "reopened" => MergeRequestAction::Opened,
+                    "synchronize" => MergeRequestAction::Updated,
+                    "closed" => {
+                        let merged = payload
+                            .pointer("/pull_request/merged")
+                            .and_then(Value::as_bool)
+                            .unwrap_or(false);
+                        if merged {
+                            MergeRequestAction::Merged
+                        } else {
+                            MergeRequestAction::Closed
+                        }
+                    },
+                    _ => MergeRequestAction::Other,
+                };
+
+                Event::MergeRequest {
+                    project: get_str(kind, payload, "/repository/full_name")?.into(),
+                    id: get_u64(kind, payload, "/number")?,
+                    action,
+                }
+            },
+            "push" => {
+                Event::Push {
+                    project: get_str(kind, payload, "/repository/full_name")?.into(),
+                    refname: get_str(kind, payload, "/ref")?.into(),
+                    commit: CommitId::new(get_str(kind, payload, "/after")?),
+                }
+            },
+            "issue_comment" => {
+                // Comments on plain issues are not interesting.
+                if payload.pointer("/issue/pull_request").is_none() {
+                    return Ok(None);
+                }
+                if get_str(kind, payload, "/action")? != "created" {
+                    return Ok(None);
+                }
+
+                let state = match get_str(kind, payload, "/issue/state")? {
+                    "closed" => {
+                        let merged = payload
+                            .pointer("/issue/pull_request/merged_at")
+                            .map_or(false, |merged_at| !merged_at.is_null());
+                        if merged {
+                            MergeRequestState::Merged
+                        } else {
+                            MergeRequestState::Closed
+                        }
+                    },
+                    _ => MergeRequestState::Open,
+                };
+
+                Event::Comment {
+                    project: get_str(kind, payload, "/repository/full_name")?.into(),
+                    id: get_u64(kind, payload, "/issue/number")?,
+                    author: get_str(kind, payload, "/comment/user/login")?.into(),
+                    content: get_str(kind, payload, "/comment/body")?.into(),
+                    state,
+                }
+            },
+            _ => return Ok(None),
+        };
+
+        Ok(Some(event))
+    }
+
+    fn parse_gitlab(kind: &str, payload: &Value) -> EventResult<Option<Self>> {
+        let event = match kind {
+            "Merge Request Hook" => {
+                let action = match get_str(kind, payload, "/object_attributes/action")? {
+                    "open" | "reopen" => MergeRequestAction::Opened,
+                    "update" => MergeRequestAction::Updated,
+                    "close" => MergeRequestAction::Closed,
+                    "merge" => MergeRequestAction::Merged,
+                    _ => MergeRequestAction::Other,
+                };
+
+                Event::MergeRequest {
+                    project: get_str(kind, payload, "/project/path_with_namespace")?.into(),
+                    id: get_u64(kind, payload, "/object_attributes/iid")?,
+                    action,
+                }
+            },
+            "Push Hook" => {
+                Event::Push {
+                    project: get_str(kind, payload, "/project/path_with_namespace")?.into(),
+                    refname: get_str(kind, payload, "/ref")?.into(),
+                    commit: CommitId::new(get_str(kind, payload, "/after")?),
+                }
+            },
+            "Note Hook" => {
+                if get_str(kind, payload, "/object_attributes/noteable_type")? != "MergeRequest" {
+                    return Ok(None);
+                }
+
+                let state = match get_str(kind, payload, "/merge_request/state")? {
+                    "closed" => MergeRequestState::Closed,
+                    "merged" => MergeRequestState::Merged,
+                    _ => MergeRequestState::Open,
+                };
+
+                Event::Comment {
+                    project: get_str(kind, payload, "/project/path_with_namespace")?.into(),
+                    id: get_u64(kind, payload, "/merge_request/iid")?,
+                    author: get_str(kind, payload, "/user/username")?.into(),
+                    content: get_str(kind, payload, "/object_attributes/note")?.into(),
+                    state,
+                }
+            },
+            _ => return Ok(None),
+        };
+
+        Ok(Some(event))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use git_workarea::CommitId;
+    use serde_json::json;
+
+    use crate::event::{Event, EventSource, MergeRequestAction, MergeRequestState};
+
+    #[test]
+    fn test_github_pull_request() {
+        let payload = json!({
+            "action": "closed",
+            "number": 7,
+            "pull_request": {
+                "merged": true,
+            },
+            "repository": {
+                "full_name": "owner/repo",
+            },
+        });
+
+        assert_eq!(
+            Event::parse(EventSource::Github, "pull_request", &payload).unwrap(),
+            Some(Event::MergeRequest {
+                project: "owner/repo".into(),
+                id: 7,
+                action: MergeRequestAction::Merged,
+            }),
+        );
+    }
+
+    #[test]
+    fn test_github_issue_comment_on_issue() {
+        let payload = json!({
+            "action": "created",
+            "issue": {
+                "number": 3,
+            },
+            "comment": {
+                "body": "Do: merge",
+                "user": {
+                    "login": "user",
+                },
+            },
+            "repository": {
+                "full_name": "owner/repo",
+            },
+        });
+
+        assert_eq!(
+            Event::parse(EventSource::Github, "issue_comment", &payload).unwrap(),
+            None,
+        );
+    }
+
+    #[test]
+    fn test_gitlab_push() {
+        let payload = json!({
+            "ref": "refs/heads/master",
+            "after": "0123456789abcdef0123456789abcdef01234567",
+            "project": {
+                "path_with_namespace": "group/repo",
+            },
+        });
+
+        assert_eq!(
+            Event::parse(EventSource::Gitlab, "Push Hook", &payload).unwrap(),
+            Some(Event::Push {
+                project: "group/repo".into(),
+                refname: "refs/heads/master".into(),
+                commit: CommitId::new("0123456789abcdef0123456789abcdef01234567"),
+            }),
+        );
+    }
+
+    #[test]
+    fn test_gitlab_note() {
+        let payload = json!({
+            "object_attributes": {
+                "noteable_type": "MergeRequest",
+                "note": "Do: stage",
+            },
+            "merge_request": {
+                "iid": 12,
+                "state": "opened",
+            },
+            "user": {
+                "username": "user",
+            },
+            "project": {
+                "path_with_namespace": "group/repo",
+            },
+        });
+
+        assert_eq!(
+            Event::parse(EventSource::Gitlab, "Note Hook", &payload).unwrap(),
+            Some(Event::Comment {
+                project: "group/repo".into(),
+                id: 12,
+                author: "user".into(),
+                content: "Do: stage".into(),
+                state: MergeRequestState::Open,
+            }),
+        );
+    }
+
+    #[test]
+    fn test_github_issue_comment_on_merged_pull_request() {
+        let payload = json!({
+            "action": "created",
+            "issue": {
+                "number": 3,
+                "state": "closed",
+                "pull_request": {
+                    "merged_at": "2022-01-01T00:00:00Z",
+                },
+            },
+            "comment": {
+                "body": "Do: stage",
+                "user": {
+                    "login": "user",
+                },
+            },
+            "repository": {
+                "full_name": "owner/repo",
+            },
+        });
+
+        let event = Event::parse(EventSource::Github, "issue_comment", &payload)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            event,
+            Event::Comment {
+                project: "owner/repo".into(),
+                id: 3,
+                author: "user".into(),
+                content: "Do: stage".into(),
+                state: MergeRequestState::Merged,
+            },
+        );
+    }
+
+    #[test]
+    fn test_gitlab_note_on_closed_merge_request() {
+        let payload = json!({
+            "object_attributes": {
+                "noteable_type": "MergeRequest",
+                "note": "Do: stage",
+            },
+            "merge_request": {
+                "iid": 12,
+                "state": "closed",
+            },
+            "user": {
+                "username": "user",
+            },
+            "project": {
+                "path_with_namespace": "group/repo",
+            },
+        });
+
+        let event = Event::parse(EventSource::Gitlab, "Note Hook", &payload)
+            .unwrap()
+            .unwrap();
+        if let Event::Comment {
+            state, ..
+        } = event
+        {
+            assert_eq!(state, MergeRequestState::Closed);
+            assert!(!MergeRequestAction::from(state).is_open());
+        } else {
+            panic!("unexpected event: {:?}", event);
+        }
+    }
+
+    #[test]
+    fn test_comment_state_action() {
+        assert!(MergeRequestAction::from(MergeRequestState::Open).is_open());
+        assert!(!MergeRequestAction::from(MergeRequestState::Closed).is_open());
+        assert!(!MergeRequestAction::from(MergeRequestState::Merged).is_open());
+    }
+
+    #[test]
+    fn test_gitlab_missing_field() {
+        let payload = json!({
+            "object_attributes": {
+                "action": "open",
+            },
+        });
+
+        let err = Event::parse(EventSource::Gitlab, "Merge Request Hook", &payload).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "missing field `/project/path_with_namespace` in the `Merge Request Hook` payload",
+        );
+    }
+}
@@ -0,0 +1,820 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time;
+
+use chrono::{Duration, Utc};
+use ghostflow::actions::check::{self, Check, PostWhen};
+use ghostflow::actions::commands::{
+    self, AccessLevel, AccessLevelLookup, Command, CommandHandler, CommandKind, Commands,
+    CommandsError, StaticAccessLevels,
+};
+use ghostflow::actions::dashboard::{Dashboard, DashboardError, DashboardResults};
+use ghostflow::actions::data::{
+    Data, DataActionResult, DataError, DataStore, LocalDirectoryDestination, S3Destination,
+};
+use ghostflow::actions::stage::{Stage, StageError};
+use ghostflow::host::{Commit, HostedProject, HostingService, HostingServiceError, MergeRequest};
+use ghostflow::utils::{TemplateError, TemplateString};
+use ghostflow_github::{Github, GithubError, GithubService};
+use ghostflow_gitlab::{gitlab, GitlabService};
+use git_topic_stage::{Stager, StagerError};
+use git_workarea::{CommitId, GitContext, GitError, Identity};
+use log::{error, info, warn};
+use thiserror::Error;
+
+use crate::config::checks::{Checks, ChecksError};
+use crate::config::{ActionKind, DataDestinationConfig, HostConfig, ProjectConfig};
+use crate::event::{Event, MergeRequestAction};
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum HandlerError {
+    #[error("failed to read private key at `{}`: {}", path.display(), source)]
+    ReadPrivateKey {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to connect to GitHub: {}", source)]
+    Github {
+        #[from]
+        source: GithubError,
+    },
+    #[error("failed to connect to GitLab: {}", source)]
+    Gitlab {
+        #[from]
+        source: gitlab::GitlabError,
+    },
+    #[error("failed to update the mirror of `{}`: {}", project, output)]
+    UpdateMirror { project: String, output: String },
+    #[error("failed to resolve `{}`: {}", refname, output)]
+    ResolveRef { refname: String, output: String },
+    #[error("failed to load checks: {}", source)]
+    Checks {
+        #[from]
+        source: ChecksError,
+    },
+    #[error("git error: {}", source)]
+    Git {
+        #[from]
+        source: GitError,
+    },
+    #[error("hosting service error: {}", source)]
+    HostingService {
+        #[from]
+        source: HostingServiceError,
+    },
+    #[error("stager error: {}", source)]
+    Stager {
+        #[from]
+        source: StagerError,
+    },
+    #[error("stage error: {}", source)]
+    Stage {
+        #[from]
+        source: StageError,
+    },
+    #[error("check error: {}", source)]
+    Check {
+        #[from]
+        source: check::CheckError,
+    },
+    #[error("data error: {}", source)]
+    Data {
+        #[from]
+        source: DataError,
+    },
+    #[error("dashboard error: {}", source)]
+    Dashboard {
+        #[from]
+        source: DashboardError,
+    },
+    #[error("commands error: {}", source)]
+    Commands {
+        #[from]
+        source: CommandsError,
+    },
+    #[error("invalid template: {}", source)]
+    Template {
+        #[from]
+        source: TemplateError,
+    },
+}
+
+impl HandlerError {
+    fn read_private_key(path: PathBuf, source: io::Error) -> Self {
+        HandlerError::ReadPrivateKey {
+            path,
+            source,
+        }
+    }
+
+    fn update_mirror(project: String, output: &[u8]) -> Self {
+        HandlerError::UpdateMirror {
+            project,
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
+
+    fn resolve_ref(refname: String, output: &[u8]) -> Self {
+        HandlerError::ResolveRef {
+            refname,
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
+}
+
+type HandlerResult<T> = Result<T, HandlerError>;
+
+/// The reason given to the `check` action.
+const CHECK_REASON: &str = "ghostflow-daemon";
+
+/// Connect to the hosting service for a project.
+fn connect(host: &HostConfig) -> HandlerResult<Arc<dyn HostingService>> {
+    Ok(match host {
+        HostConfig::Github {
+            host,
+            owner,
+            app_id,
+            private_key,
+            installation_id,
+        } => {
+            let key = fs::read(private_key)
+                .map_err(|err| HandlerError::read_private_key(private_key.clone(), err))?;
+            let github = Github::new_app(host, *app_id, key, [(owner.clone(), *installation_id)])?;
+            Arc::new(GithubService::new(github)?)
+        },
+        HostConfig::Gitlab {
+            host,
+            token,
+        } => {
+            let gitlab = gitlab::Gitlab::new(host, token)?;
+            Arc::new(GitlabService::new(gitlab)?)
+        },
+    })
+}
+
+/// Resolve a ref in a repository, if it exists.
+fn resolve_ref(ctx: &GitContext, refname: &str) -> HandlerResult<Option<CommitId>> {
+    let rev_parse = ctx
+        .git()
+        .arg("rev-parse")
+        .arg("--verify")
+        .arg("--quiet")
+        .arg(refname)
+        .output()
+        .map_err(|err| GitError::subcommand("rev-parse", err))?;
+    if rev_parse.status.success() {
+        Ok(Some(CommitId::new(
+            String::from_utf8_lossy(&rev_parse.stdout).trim(),
+        )))
+    } else if rev_parse.stderr.is_empty() {
+        Ok(None)
+    } else {
+        Err(HandlerError::resolve_ref(refname.into(), &rev_parse.stderr))
+    }
+}
+
+/// The command which performs the same work as an action.
+fn comment_command(action: ActionKind) -> Option<CommandKind> {
+    match action {
+        ActionKind::Check => Some(CommandKind::Check),
+        ActionKind::Stage => Some(CommandKind::Stage),
+        ActionKind::Unstage => Some(CommandKind::Unstage),
+        ActionKind::Data | ActionKind::Dashboard => None,
+    }
+}
+
+/// Log the destinations which failed to receive data.
+fn log_data_result(result: &DataActionResult) {
+    if let DataActionResult::DataPushed(reports) = result {
+        for report in reports {
+            if let Some(err) = report.error.as_ref() {
+                error!(
+                    target: "ghostflow-daemon",
+                    "failed to push data to {} after {} attempts: {}",
+                    report.destination,
+                    report.attempts,
+                    err,
+                );
+            } else {
+                info!(
+                    target: "ghostflow-daemon",
+                    "pushed {} data objects to {} ({} already present)",
+                    report.objects.len(),
+                    report.destination,
+                    report.skipped.len(),
+                );
+            }
+        }
+    }
+}
+
+/// Handles events for a single project.
+///
+/// Each project has its own handler which processes events in order so that actions never race
+/// on the same repository.
+pub struct ProjectHandler {
+    config: ProjectConfig,
+    project: HostedProject,
+    ctx: GitContext,
+    checks: Option<Checks>,
+    stage: Option<(Stage, Identity)>,
+    data: Option<Data>,
+    dashboard: Option<Dashboard>,
+}
+
+impl ProjectHandler {
+    pub fn new(name: String, config: ProjectConfig) -> HandlerResult<Self> {
+        let service = connect(&config.host)?;
+        let project = HostedProject {
+            name,
+            service,
+        };
+        let ctx = GitContext::new(&config.git_dir);
+
+        let mut handler = Self {
+            checks: None,
+            stage: None,
+            data: None,
+            dashboard: None,
+            config,
+            project,
+            ctx,
+        };
+        handler.update_mirror()?;
+
+        if let Some(check) = handler.config.check.as_ref() {
+            handler.checks = Some(Checks::load(check.checks.clone())?);
+        }
+
+        if let Some(stage) = handler.config.stage.as_ref() {
+            let identity = Identity::new(&stage.identity.name, &stage.identity.email);
+            let base_ref = format!("refs/heads/{}", stage.branch);
+            let base = resolve_ref(&handler.ctx, &base_ref)?.ok_or_else(|| {
+                HandlerError::resolve_ref(base_ref.clone(), b"no such branch")
+            })?;
+            let stage_ref = format!("refs/stage/{}/head", stage.branch);
+            let stager = if let Some(head) = resolve_ref(&handler.ctx, &stage_ref)? {
+                let stager = Stager::from_branch(&handler.ctx, base, head, identity.clone())?;
+                // Priorities and retries only live in memory; they are lost on restart.
+                if !stager.topics().is_empty() {
+                    warn!(
+                        target: "ghostflow-daemon",
+                        "resuming the stage of {} with {} topics; priorities requested by \
+                         commands and scheduled retries of unstaged topics are not restored",
+                        handler.project.name,
+                        stager.topics().len(),
+                    );
+                }
+                stager
+            } else {
+                Stager::new(&handler.ctx, base, identity.clone())
+            };
+
+            let mut action = Stage::new(stager, &stage.branch, handler.project.clone())?;
+            if stage.quiet {
+                action.quiet();
+            }
+            if let Some(prefix) = stage.priority_label_prefix.as_ref() {
+                action.priority_labels(prefix);
+            }
+            if let Some(backoff) = stage.retry_backoff {
+                action.retry_unstaged_topics(
+                    Duration::seconds(backoff as i64),
+                    stage.retry_attempts,
+                );
+            }
+            handler.stage = Some((action, identity));
+        }
+
+        if let Some(data) = handler.config.data.as_ref() {
+            let mut action = Data::new(handler.ctx.clone());
+            for destination in &data.destinations {
+                match destination {
+                    DataDestinationConfig::Rsync(destination) => {
+                        action.add_destination(destination);
+                    },
+                    DataDestinationConfig::Local {
+                        local,
+                    } => {
+                        action.add_data_destination(LocalDirectoryDestination::new(local));
+                    },
+                    DataDestinationConfig::S3 {
+                        s3,
+                    } => {
+                        let mut destination = S3Destination::new(
+                            &s3.endpoint,
+                            &s3.bucket,
+                            &s3.access_key,
+                            &s3.secret_key,
+                        );
+                        if let Some(region) = s3.region.as_ref() {
+                            destination.region(region);
+                        }
+                        destination.prefix(&s3.prefix);
+                        action.add_data_destination(destination);
+                    },
+                }
+            }
+            if let Some(attempts) = data.attempts {
+                action.attempts(attempts);
+            }
+            if let Some(ref_namespace) = data.ref_namespace.as_ref() {
+                action.ref_namespace(ref_namespace);
+            }
+            if data.keep_refs {
+                action.keep_refs();
+            }
+            if data.reject_weak_algorithms {
+                action.reject_weak_algorithms();
+            }
+            if let Some(store) = data.store.as_ref() {
+                let store = DataStore::open(store).map_err(DataError::from)?;
+                action.data_store(Arc::new(store));
+            }
+            handler.data = Some(action);
+        }
+
+        if let Some(dashboard) = handler.config.dashboard.as_ref() {
+            // Catch mistakes in the templates early rather than posting broken statuses.
+            let results_url = dashboard.results.as_ref().map(|results| &results.url);
+            let templates = [
+                &dashboard.status_name,
+                &dashboard.url,
+                &dashboard.description,
+            ];
+            for template in templates.into_iter().chain(results_url) {
+                TemplateString::parse(template.as_str())?;
+            }
+
+            let mut action = Dashboard::new(
+                handler.project.service.clone(),
+                &dashboard.status_name,
+                &dashboard.url,
+                &dashboard.description,
+            );
+            if let Some(results) = dashboard.results.as_ref() {
+                let mut query =
+                    DashboardResults::new(&results.url, &results.passed, &results.failed);
+                if let Some(pending) = results.pending.as_ref() {
+                    query.pending(pending);
+                }
+                if let Some(timeout) = results.poll_timeout {
+                    query.poll(
+                        time::Duration::from_secs(results.poll_interval),
+                        time::Duration::from_secs(timeout),
+                    );
+                }
+                action.live_status(query);
+            }
+            handler.dashboard = Some(action);
+        }
+
+        Ok(handler)
+    }
+
+    /// Handle an event.
+    ///
+    /// Errors are logged; a failing action does not prevent other actions from running.
+    pub fn handle(&mut self, event: &Event) {
+        info!(
+            target: "ghostflow-daemon",
+            "handling event for {}: {:?}",
+            self.project.name,
+            event,
+        );
+
+        if let Err(err) = self.handle_impl(event) {
+            error!(
+                target: "ghostflow-daemon",
+                "failed to handle event for {}: {:?}",
+                self.project.name,
+                err,
+            );
+        }
+    }
+
+    /// How long until the next retry of an unstaged topic is due, if any.
+    pub fn next_retry(&self) -> Option<time::Duration> {
+        let (stage, _) = self.stage.as_ref()?;
+        let when = stage.next_retry()?;
+
+        // Retries which are already due have no delay.
+        Some((when - Utc::now()).to_std().unwrap_or_default())
+    }
+
+    /// Retry staging topics whose back-off has expired.
+    ///
+    /// Errors are logged.
+    pub fn retry_unstaged(&mut self) {
+        if let Some((stage, _)) = self.stage.as_mut() {
+            info!(
+                target: "ghostflow-daemon",
+                "retrying unstaged topics for {}",
+                self.project.name,
+            );
+
+            if let Err(err) = stage.retry_unstaged(Utc::now()) {
+                error!(
+                    target: "ghostflow-daemon",
+                    "failed to retry unstaged topics for {}: {:?}",
+                    self.project.name,
+                    err,
+                );
+            }
+        }
+    }
+
+    fn handle_impl(&mut self, event: &Event) -> HandlerResult<()> {
+        self.update_mirror()?;
+
+        match event {
+            Event::MergeRequest {
+                id,
+                action,
+                ..
+            } => {
+                let mr = self.project.merge_request(*id)?;
+                let actions = self.config.on.merge_request.clone();
+                self.run_for_mr(&actions, &mr, *action);
+            },
+            Event::Comment {
+                id,
+                author,
+                content,
+                state,
+                ..
+            } => {
+                let mr = self.project.merge_request(*id)?;
+                let mr_action = MergeRequestAction::from(*state);
+                self.handle_comment(&mr, mr_action, author, content)?;
+            },
+            Event::Push {
+                refname,
+                commit,
+                ..
+            } => {
+                let commit = Commit {
+                    refname: Some(refname.clone()),
+                    ..self.project.commit(commit)?
+                };
+                let actions = self.config.on.push.clone();
+                for action in actions {
+                    if let Err(err) = self.run_for_push(action, &commit) {
+                        error!(
+                            target: "ghostflow-daemon",
+                            "failed to run the {} action for a push to {} on {}: {:?}",
+                            action,
+                            refname,
+                            self.project.name,
+                            err,
+                        );
+                    }
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    fn run_for_mr(
+        &mut self,
+        actions: &[ActionKind],
+        mr: &MergeRequest,
+        mr_action: MergeRequestAction,
+    ) {
+        for &action in actions {
+            if let Err(err) = self.run_action_for_mr(action, mr, mr_action) {
+                error!(
+                    target: "ghostflow-daemon",
+                    "failed to run the {} action for {}: {:?}",
+                    action,
+                    mr.url,
+                    err,
+                );
+            }
+        }
+    }
+
+    fn run_action_for_mr(
+        &mut self,
+        action: ActionKind,
+        mr: &MergeRequest,
+        mr_action: MergeRequestAction,
+    ) -> HandlerResult<()> {
+        match action {
+            ActionKind::Check => {
+                if !mr_action.is_open() {
+                    return Ok(());
+                }
+
+                let config = self.config.check.as_ref();
+                if let (Some(config), Some(checks)) = (config, self.checks.as_ref()) {
+                    let mut check = Check::new(
+                        self.ctx.clone(),
+                        self.project.service.clone(),
+                        checks.check_configuration(),
+                        &config.admins,
+                    );
+                    if let Some(base_name) = config.base_name.as_ref() {
+                        check = check.base_name(base_name);
+                    }
+                    if config.post_failures_only {
+                        check = check.post_when(PostWhen::Failure);
+                    }
+
+                    let base = CommitId::new(format!("refs/heads/{}", mr.target_branch));
+                    check.check_mr(CHECK_REASON, &base, mr)?;
+                }
+            },
+            ActionKind::Stage => {
+                if let Some((stage, identity)) = self.stage.as_mut() {
+                    let now = Utc::now();
+                    if !mr_action.is_open() {
+                        stage.unstage_merge_request(mr)?;
+                    } else {
+                        stage.stage_merge_request(mr, identity, now)?;
+                    }
+                }
+            },
+            ActionKind::Unstage => {
+                if let Some((stage, _)) = self.stage.as_mut() {
+                    stage.unstage_merge_request(mr)?;
+                }
+            },
+            ActionKind::Data => {
+                if let Some(data) = self.data.as_ref() {
+                    let repo = mr.source_repo.as_ref().unwrap_or(&mr.target_repo);
+                    let result = data.fetch_data(repo)?;
+                    log_data_result(&result);
+                }
+            },
+            ActionKind::Dashboard => {
+                if let Some(dashboard) = self.dashboard.as_ref() {
+                    dashboard.post_for_mr(mr)?;
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Handle a comment on a merge request.
+    ///
+    /// Commands in the comment are run first. The actions configured for comments then run if
+    /// the author has the access level of the command matching the action and the comment did
+    /// not already request that command.
+    fn handle_comment(
+        &mut self,
+        mr: &MergeRequest,
+        mr_action: MergeRequestAction,
+        author: &str,
+        content: &str,
+    ) -> HandlerResult<()> {
+        if self.config.on.comment.is_empty() && self.config.commands.is_none() {
+            return Ok(());
+        }
+
+        let service = self.project.service.clone();
+        // Replies to commands must not be treated as commands.
+        if author == service.service_user().handle {
+            return Ok(());
+        }
+
+        // The event does not identify the comment itself; use the latest one which matches.
+        let comments = service.get_mr_comments(mr)?;
+        let comment = if let Some(comment) = comments
+            .iter()
+            .rev()
+            .find(|comment| comment.author.handle == author && comment.content == content)
+        {
+            comment
+        } else {
+            warn!(
+                target: "ghostflow-daemon",
+                "failed to find the comment from {} on {}; ignoring it",
+                author,
+                mr.url,
+            );
+            return Ok(());
+        };
+
+        let mut commands = Commands::new(service, self.command_access());
+        if self.config.commands.as_ref().map_or(false, |config| config.quiet) {
+            commands.quiet();
+        }
+
+        // Commands only apply to merge requests which may still be merged.
+        let outcomes = if self.config.commands.is_some() && mr_action.is_open() {
+            let mut handler = CommentCommands {
+                handler: self,
+                mr_action,
+            };
+            commands.handle_comment(mr, comment, &mut handler)?
+        } else {
+            Vec::new()
+        };
+
+        let level = match self.command_access().access_level(mr, &comment.author) {
+            Ok(level) => level,
+            Err(err) => {
+                warn!(
+                    target: "ghostflow-daemon",
+                    "failed to look up the access level of {} on {}; ignoring the comment: {:?}",
+                    author,
+                    mr.url,
+                    err,
+                );
+                return Ok(());
+            },
+        };
+
+        let actions = self
+            .config
+            .on
+            .comment
+            .iter()
+            .copied()
+            .filter(|&action| {
+                let kind = comment_command(action);
+                let required = kind.map_or(AccessLevel::Contributor, |kind| {
+                    commands.required_access_level(kind)
+                });
+                if level < required {
+                    info!(
+                        target: "ghostflow-daemon",
+                        "not running the {} action for a comment by {} on {} ({} < {})",
+                        action,
+                        author,
+                        mr.url,
+                        level,
+                        required,
+                    );
+                    return false;
+                }
+
+                // The comment already requested the action as a command.
+                !outcomes
+                    .iter()
+                    .any(|outcome| Some(outcome.command.kind) == kind)
+            })
+            .collect::<Vec<_>>();
+        self.run_for_mr(&actions, mr, mr_action);
+
+        Ok(())
+    }
+
+    /// The access levels of users for commands and comment actions.
+    ///
+    /// Without a `commands` configuration, all users have the default access level.
+    fn command_access(&self) -> StaticAccessLevels {
+        if let Some(config) = self.config.commands.as_ref() {
+            let mut access = StaticAccessLevels::new(config.default_access.into());
+            for (user, &level) in &config.users {
+                access.user(user, level.into());
+            }
+            access
+        } else {
+            // Match the default of `default_access` in the configuration.
+            StaticAccessLevels::new(AccessLevel::Contributor)
+        }
+    }
+
+    fn run_for_push(&mut self, action: ActionKind, commit: &Commit) -> HandlerResult<()> {
+        match action {
+            ActionKind::Stage => {
+                if let Some((stage, identity)) = self.stage.as_mut() {
+                    let branch_ref = commit
+                        .refname
+                        .as_ref()
+                        .and_then(|refname| refname.strip_prefix("refs/heads/"));
+                    let stage_branch = self
+                        .config
+                        .stage
+                        .as_ref()
+                        .map(|stage| stage.branch.as_str());
+                    let now = Utc::now();
+                    if branch_ref.is_some() && branch_ref == stage_branch {
+                        stage.base_branch_update(commit, identity, now)?;
+                    }
+                }
+            },
+            ActionKind::Data => {
+                if let Some(data) = self.data.as_ref() {
+                    let result = data.fetch_data(&commit.repo)?;
+                    log_data_result(&result);
+                }
+            },
+            ActionKind::Dashboard => {
+                if let Some(dashboard) = self.dashboard.as_ref() {
+                    dashboard.post_for_commit(commit)?;
+                }
+            },
+            ActionKind::Check | ActionKind::Unstage => {
+                warn!(
+                    target: "ghostflow-daemon",
+                    "the {} action does not apply to pushes",
+                    action,
+                );
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Update the local mirror of the project.
+    fn update_mirror(&self) -> HandlerResult<()> {
+        let fetch = self
+            .ctx
+            .git()
+            .arg("fetch")
+            .arg("--prune")
+            .arg("origin")
+            .arg("+refs/heads/*:refs/heads/*")
+            .output()
+            .map_err(|err| GitError::subcommand("fetch", err))?;
+        if !fetch.status.success() {
+            return Err(HandlerError::update_mirror(
+                self.project.name.clone(),
+                &fetch.stderr,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Performs commands from comments using the actions configured for a project.
+struct CommentCommands<'a> {
+    handler: &'a mut ProjectHandler,
+    /// The state of the merge request the comment is on.
+    mr_action: MergeRequestAction,
+}
+
+impl CommentCommands<'_> {
+    fn unsupported(command: &Command) -> commands::HandlerResult {
+        Err(format!("`{}` is not supported", command.kind).into())
+    }
+}
+
+impl CommandHandler for CommentCommands<'_> {
+    fn supports(&self, kind: CommandKind) -> bool {
+        match kind {
+            CommandKind::Check => self.handler.checks.is_some(),
+            CommandKind::Stage | CommandKind::Unstage => self.handler.stage.is_some(),
+            _ => false,
+        }
+    }
+
+    fn check(&mut self, mr: &MergeRequest, _: &Command) -> commands::HandlerResult {
+        self.handler
+            .run_action_for_mr(ActionKind::Check, mr, self.mr_action)?;
+        Ok(())
+    }
+
+    fn merge(&mut self, _: &MergeRequest, command: &Command) -> commands::HandlerResult {
+        Self::unsupported(command)
+    }
+
+    fn reformat(&mut self, _: &MergeRequest, command: &Command) -> commands::HandlerResult {
+        Self::unsupported(command)
+    }
+
+    fn stage(&mut self, mr: &MergeRequest, command: &Command) -> commands::HandlerResult {
+        let priority = command
+            .arguments
+            .value("priority")
+            .map(|priority| {
+                priority
+                    .parse::<i64>()
+                    .map_err(|_| format!("invalid priority `{}`", priority))
+            })
+            .transpose()?;
+
+        if let Some((stage, identity)) = self.handler.stage.as_mut() {
+            let now = Utc::now();
+            if let Some(priority) = priority {
+                stage.stage_merge_request_with_priority(mr, priority, identity, now)?;
+            } else {
+                stage.stage_merge_request(mr, identity, now)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn test(&mut self, _: &MergeRequest, command: &Command) -> commands::HandlerResult {
+        Self::unsupported(command)
+    }
+
+    fn unstage(&mut self, mr: &MergeRequest, _: &Command) -> commands::HandlerResult {
+        if let Some((stage, _)) = self.handler.stage.as_mut() {
+            stage.unstage_merge_request(mr)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+//! ghostflow-daemon
+//!
+//! This is a service which performs workflow actions in response to events from hosting services.
+//! Events may be delivered as webhooks or dropped as files into a watched directory.
+
+use std::num::ParseIntError;
+use std::time::Duration;
+
+use clap::Arg;
+use log::LevelFilter;
+use thiserror::Error;
+
+mod config;
+use config::{Config, ConfigError};
+
+mod dispatch;
+use dispatch::Dispatcher;
+
+mod dropdir;
+use dropdir::{DropDir, DropDirError};
+
+mod event;
+mod handler;
+
+mod webhook;
+use webhook::WebhookError;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+enum SetupError {
+    #[error("non-integer poll interval {}: {}", interval, source)]
+    NonIntegerPollInterval {
+        interval: String,
+        #[source]
+        source: ParseIntError,
+    },
+    #[error("either --listen or --drop-dir must be given")]
+    NoEventSource,
+    #[error("configuration error: {}", source)]
+    Config {
+        #[from]
+        source: ConfigError,
+    },
+    #[error("webhook error: {}", source)]
+    Webhook {
+        #[from]
+        source: WebhookError,
+    },
+    #[error("drop directory error: {}", source)]
+    DropDir {
+        #[from]
+        source: DropDirError,
+    },
+}
+
+impl SetupError {
+    fn non_integer_poll_interval(interval: String, source: ParseIntError) -> Self {
+        SetupError::NonIntegerPollInterval {
+            interval,
+            source,
+        }
+    }
+}
+
+fn try_main() -> Result<(), SetupError> {
+    let matches = clap::Command::new("ghostflow-daemon")
+        .version(clap::crate_version!())
+        .author("Komeil Majidi <komeilkma@gmail.com>")
+        .about("Perform ghostflow actions in response to events")
+        .arg(
+            Arg::new("DEBUG")
+                .short('d')
+                .long("debug")
+                .help("Increase verbosity")
+                .multiple_occurrences(true),
+        )
+        .arg(
+            Arg::new("CONFIG")
+                .short('c')
+                .long("config")
+                .help("Path to the configuration file")
+                .required(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("LISTEN")
+                .short('l')
+                .long("listen")
+                .help("Address to listen on for webhooks")
+                .takes_value(true)
+                .conflicts_with("DROP_DIR"),
+        )
+        .arg(
+            Arg::new("DROP_DIR")
+                .long("drop-dir")
+                .help("Directory to watch for event files")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("ONCE")
+                .long("once")
+                .help("Handle the files in the drop directory and exit")
+                .requires("DROP_DIR"),
+        )
+        .arg(
+            Arg::new("POLL_INTERVAL")
+                .long("poll-interval")
+                .help("Number of seconds between scans of the drop directory")
+                .default_value("5")
+                .takes_value(true),
+        )
+        .get_matches();
+
+    let log_level = match matches.occurrences_of("DEBUG") {
+        0 => LevelFilter::Error,
+        1 => LevelFilter::Warn,
+        2 => LevelFilter::Info,
+        3 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+
+    env_logger::Builder::new().filter(None, log_level).init();
+    log::set_max_level(log_level);
+
+    let config = Config::from_path(
+        matches
+            .value_of("CONFIG")
+            .expect("--config is required"),
+    )?;
+    let dispatcher = Dispatcher::new(config);
+
+    let res = if let Some(address) = matches.value_of("LISTEN") {
+        webhook::serve(address, &dispatcher).map_err(SetupError::from)
+    } else if let Some(path) = matches.value_of("DROP_DIR") {
+        let interval = matches
+            .value_of("POLL_INTERVAL")
+            .expect("--poll-interval has a default");
+        let interval = interval
+            .parse::<u64>()
+            .map_err(|err| SetupError::non_integer_poll_interval(interval.into(), err))?;
+
+        DropDir::new(path)
+            .poll_interval(Duration::from_secs(interval))
+            .watch(&dispatcher, matches.is_present("ONCE"))
+            .map_err(SetupError::from)
+    } else {
+        Err(SetupError::NoEventSource)
+    };
+
+    dispatcher.shutdown();
+
+    res
+}
+
+fn main() {
+    if let Err(err) = try_main() {
+        panic!("{:?}", err);
+    }
+}
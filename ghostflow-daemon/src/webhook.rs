@@ -0,0 +1,393 @@
+use std::error::Error;
+use std::io::{self, Read};
+
+use hmac::{Hmac, Mac};
+use log::{error, info, warn};
+use serde_json::Value;
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+use tiny_http::{Method, Request, Response, Server};
+
+use crate::dispatch::{DispatchError, Dispatcher};
+use crate::event::{Event, EventError, EventSource};
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum WebhookError {
+    #[error("failed to listen on `{}`: {}", address, source)]
+    Bind {
+        address: String,
+        #[source]
+        source: Box<dyn Error + Send + Sync>,
+    },
+}
+
+impl WebhookError {
+    fn bind(address: String, source: Box<dyn Error + Send + Sync>) -> Self {
+        WebhookError::Bind {
+            address,
+            source,
+        }
+    }
+}
+
+type WebhookResult<T> = Result<T, WebhookError>;
+
+/// Errors for a single delivery.
+///
+/// These are reported back to the sender.
+#[derive(Debug, Error)]
+enum DeliveryError {
+    #[error("only POST requests are accepted")]
+    Method,
+    #[error("unknown event source")]
+    UnknownSource,
+    #[error("failed to read the request body: {}", source)]
+    ReadBody {
+        #[from]
+        source: io::Error,
+    },
+    #[error("failed to parse the payload: {}", source)]
+    Json {
+        #[from]
+        source: serde_json::Error,
+    },
+    #[error("{}", source)]
+    Event {
+        #[from]
+        source: EventError,
+    },
+    #[error("{}", source)]
+    Dispatch {
+        #[from]
+        source: DispatchError,
+    },
+    #[error("invalid signature")]
+    Signature,
+}
+
+impl DeliveryError {
+    fn status_code(&self) -> u16 {
+        match self {
+            DeliveryError::Method => 405,
+            DeliveryError::Signature => 403,
+            DeliveryError::Dispatch {
+                source: DispatchError::UnknownProject {
+                    ..
+                },
+            } => 404,
+            DeliveryError::Dispatch {
+                ..
+            } => 503,
+            _ => 400,
+        }
+    }
+}
+
+/// Find a header value on a request.
+fn header<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.as_str())
+}
+
+/// Verify a delivery against the project's secret.
+///
+/// The `header` function looks up header values of the delivery.
+fn verify<'a, H>(source: EventSource, header: H, body: &[u8], secret: &str) -> bool
+where
+    H: Fn(&str) -> Option<&'a str>,
+{
+    match source {
+        EventSource::Github => {
+            let signature = header("X-Hub-Signature-256")
+                .and_then(|value| value.strip_prefix("sha256="))
+                .and_then(|hex_value| hex::decode(hex_value).ok());
+            let signature = if let Some(signature) = signature {
+                signature
+            } else {
+                return false;
+            };
+
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+                .expect("HMAC accepts keys of any length");
+            mac.update(body);
+            mac.verify_slice(&signature).is_ok()
+        },
+        EventSource::Gitlab => {
+            // Compare in constant time so that the token cannot be guessed by timing responses.
+            header("X-Gitlab-Token").map_or(false, |token| {
+                token.as_bytes().ct_eq(secret.as_bytes()).into()
+            })
+        },
+    }
+}
+
+fn handle_delivery(request: &mut Request, dispatcher: &Dispatcher) -> Result<(), DeliveryError> {
+    if request.method() != &Method::Post {
+        return Err(DeliveryError::Method);
+    }
+
+    let mut body = Vec::new();
+    request.as_reader().read_to_end(&mut body)?;
+
+    let request: &Request = request;
+    handle_body(|name| header(request, name), &body, dispatcher)
+}
+
+/// Handle the body of a delivery.
+///
+/// The `header` function looks up header values of the delivery.
+fn handle_body<'a, H>(header: H, body: &[u8], dispatcher: &Dispatcher) -> Result<(), DeliveryError>
+where
+    H: Fn(&str) -> Option<&'a str>,
+{
+    let (source, kind) = if let Some(kind) = header("X-GitHub-Event") {
+        (EventSource::Github, kind)
+    } else if let Some(kind) = header("X-Gitlab-Event") {
+        (EventSource::Gitlab, kind)
+    } else {
+        return Err(DeliveryError::UnknownSource);
+    };
+
+    let payload: Value = serde_json::from_slice(body)?;
+
+    let event = if let Some(event) = Event::parse(source, kind, &payload)? {
+        event
+    } else {
+        info!(
+            target: "ghostflow-daemon",
+            "ignoring {} event",
+            kind,
+        );
+
+        return Ok(());
+    };
+
+    if let Some(secret) = dispatcher.secret(event.project())? {
+        if !verify(source, &header, body, secret) {
+            return Err(DeliveryError::Signature);
+        }
+    }
+
+    dispatcher.dispatch(event)?;
+
+    Ok(())
+}
+
+/// Serve webhook deliveries on an address.
+pub fn serve(address: &str, dispatcher: &Dispatcher) -> WebhookResult<()> {
+    let server = Server::http(address).map_err(|err| WebhookError::bind(address.into(), err))?;
+
+    info!(
+        target: "ghostflow-daemon",
+        "listening for webhooks on {}",
+        address,
+    );
+
+    for mut request in server.incoming_requests() {
+        let response = match handle_delivery(&mut request, dispatcher) {
+            Ok(()) => Response::from_string("accepted").with_status_code(202),
+            Err(err) => {
+                warn!(
+                    target: "ghostflow-daemon",
+                    "rejected webhook delivery: {}",
+                    err,
+                );
+
+                Response::from_string(err.to_string()).with_status_code(err.status_code())
+            },
+        };
+
+        if let Err(err) = request.respond(response) {
+            error!(
+                target: "ghostflow-daemon",
+                "failed to respond to a webhook delivery: {:?}",
+                err,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use hmac::{Hmac, Mac};
+    use serde_json::json;
+    use sha2::Sha256;
+
+    use crate::dispatch::Dispatcher;
+    use crate::event::{Event, EventSource};
+    use crate::webhook::{handle_body, verify, DeliveryError};
+
+    const SECRET: &str = "secret";
+
+    fn github_signature(body: &[u8], secret: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    fn headers<'a>(headers: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<&'a str> {
+        move |name: &str| {
+            headers
+                .iter()
+                .find(|(header, _)| header.eq_ignore_ascii_case(name))
+                .map(|(_, value)| *value)
+        }
+    }
+
+    fn gitlab_push() -> Vec<u8> {
+        let payload = json!({
+            "ref": "refs/heads/master",
+            "after": "0123456789abcdef0123456789abcdef01234567",
+            "project": {
+                "path_with_namespace": "group/repo",
+            },
+        });
+
+        serde_json::to_vec(&payload).unwrap()
+    }
+
+    #[test]
+    fn test_verify_github() {
+        let body = b"{}";
+        let signature = github_signature(body, SECRET);
+
+        let valid = [("X-Hub-Signature-256", signature.as_str())];
+        assert!(verify(EventSource::Github, headers(&valid), body, SECRET));
+        // The signature must cover the delivered body.
+        assert!(!verify(EventSource::Github, headers(&valid), b"[]", SECRET));
+        // The signature must use the project's secret.
+        assert!(!verify(EventSource::Github, headers(&valid), body, "other"));
+
+        let unprefixed = [("X-Hub-Signature-256", &signature["sha256=".len()..])];
+        assert!(!verify(EventSource::Github, headers(&unprefixed), body, SECRET));
+        let not_hex = [("X-Hub-Signature-256", "sha256=not hex")];
+        assert!(!verify(EventSource::Github, headers(&not_hex), body, SECRET));
+        assert!(!verify(EventSource::Github, headers(&[]), body, SECRET));
+    }
+
+    #[test]
+    fn test_verify_gitlab() {
+        let body = b"{}";
+
+        let valid = [("X-Gitlab-Token", SECRET)];
+        assert!(verify(EventSource::Gitlab, headers(&valid), body, SECRET));
+        let wrong = [("X-Gitlab-Token", "secreT")];
+        assert!(!verify(EventSource::Gitlab, headers(&wrong), body, SECRET));
+        let prefix = [("X-Gitlab-Token", "secre")];
+        assert!(!verify(EventSource::Gitlab, headers(&prefix), body, SECRET));
+        let empty = [("X-Gitlab-Token", "")];
+        assert!(!verify(EventSource::Gitlab, headers(&empty), body, SECRET));
+        assert!(!verify(EventSource::Gitlab, headers(&[]), body, SECRET));
+    }
+
+    #[test]
+    fn test_delivery_dispatch() {
+        let (dispatcher, receiver) = Dispatcher::for_test("group/repo", Some(SECRET));
+        let body = gitlab_push();
+
+        let delivery = [("X-Gitlab-Event", "Push Hook"), ("X-Gitlab-Token", SECRET)];
+        handle_body(headers(&delivery), &body, &dispatcher).unwrap();
+
+        let event = receiver.try_recv().unwrap();
+        assert!(matches!(event, Event::Push { .. }));
+        assert_eq!(event.project(), "group/repo");
+    }
+
+    #[test]
+    fn test_delivery_github_dispatch() {
+        let (dispatcher, receiver) = Dispatcher::for_test("owner/repo", Some(SECRET));
+        let payload = json!({
+            "action": "opened",
+            "number": 7,
+            "repository": {
+                "full_name": "owner/repo",
+            },
+        });
+        let body = serde_json::to_vec(&payload).unwrap();
+        let signature = github_signature(&body, SECRET);
+
+        let delivery = [
+            ("X-GitHub-Event", "pull_request"),
+            ("X-Hub-Signature-256", signature.as_str()),
+        ];
+        handle_body(headers(&delivery), &body, &dispatcher).unwrap();
+
+        let event = receiver.try_recv().unwrap();
+        assert!(matches!(event, Event::MergeRequest { id: 7, .. }));
+    }
+
+    #[test]
+    fn test_delivery_bad_signature() {
+        let (dispatcher, receiver) = Dispatcher::for_test("group/repo", Some(SECRET));
+        let body = gitlab_push();
+
+        let delivery = [("X-Gitlab-Event", "Push Hook"), ("X-Gitlab-Token", "wrong")];
+        let err = handle_body(headers(&delivery), &body, &dispatcher).unwrap_err();
+
+        assert!(matches!(err, DeliveryError::Signature));
+        assert_eq!(err.status_code(), 403);
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_delivery_no_secret() {
+        let (dispatcher, receiver) = Dispatcher::for_test("group/repo", None);
+        let body = gitlab_push();
+
+        // Projects without a secret accept unsigned deliveries.
+        let delivery = [("X-Gitlab-Event", "Push Hook")];
+        handle_body(headers(&delivery), &body, &dispatcher).unwrap();
+
+        assert!(receiver.try_recv().is_ok());
+    }
+
+    #[test]
+    fn test_delivery_unknown_project() {
+        let (dispatcher, receiver) = Dispatcher::for_test("other/repo", Some(SECRET));
+        let body = gitlab_push();
+
+        let delivery = [("X-Gitlab-Event", "Push Hook"), ("X-Gitlab-Token", SECRET)];
+        let err = handle_body(headers(&delivery), &body, &dispatcher).unwrap_err();
+
+        assert_eq!(err.status_code(), 404);
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_delivery_ignored_event() {
+        let (dispatcher, receiver) = Dispatcher::for_test("group/repo", Some(SECRET));
+
+        let delivery = [("X-Gitlab-Event", "Pipeline Hook")];
+        handle_body(headers(&delivery), b"{}", &dispatcher).unwrap();
+
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_delivery_unknown_source() {
+        let (dispatcher, _receiver) = Dispatcher::for_test("group/repo", None);
+
+        let err = handle_body(headers(&[]), b"{}", &dispatcher).unwrap_err();
+
+        assert!(matches!(err, DeliveryError::UnknownSource));
+        assert_eq!(err.status_code(), 400);
+    }
+
+    #[test]
+    fn test_delivery_invalid_payload() {
+        let (dispatcher, _receiver) = Dispatcher::for_test("group/repo", None);
+
+        let delivery = [("X-Gitlab-Event", "Push Hook")];
+        let err = handle_body(headers(&delivery), b"not json", &dispatcher).unwrap_err();
+
+        assert!(matches!(err, DeliveryError::Json { .. }));
+        assert_eq!(err.status_code(), 400);
+    }
+}
@@ -0,0 +1,261 @@
+use std::sync::Arc;
+
+use log::info;
+use reqwest::blocking::Client;
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use reqwest::{Method, Url};
+use serde_json::Value;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum GiteaError {
+    #[error("url parse error: {}", source)]
+    UrlParse {
+        #[from]
+        source: url::ParseError,
+    },
+    #[error("failed to send request to {}: {}", endpoint, source)]
+    SendRequest {
+        endpoint: Url,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("gitea error ({}): {}", status, response)]
+    Gitea {
+        status: reqwest::StatusCode,
+        response: String,
+    },
+    #[error("failed to read the response from {}: {}", endpoint, source)]
+    ReadResponse {
+        endpoint: Url,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("deserialize error: {}", source)]
+    Deserialize {
+        #[from]
+        source: serde_json::Error,
+    },
+}
+
+impl GiteaError {
+    fn send_request(endpoint: Url, source: reqwest::Error) -> Self {
+        GiteaError::SendRequest {
+            endpoint,
+            source,
+        }
+    }
+
+    fn gitea(status: reqwest::StatusCode, response: String) -> Self {
+        GiteaError::Gitea {
+            status,
+            response,
+        }
+    }
+
+    fn read_response(endpoint: Url, source: reqwest::Error) -> Self {
+        GiteaError::ReadResponse {
+            endpoint,
+            source,
+        }
+    }
+}
+
+pub(crate) type GiteaResult<T> = Result<T, GiteaError>;
+
+// The user agent for all queries.
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), " v", env!("CARGO_PKG_VERSION"));
+
+/// A client which can send requests to the Gitea REST API.
+///
+/// Endpoints are relative to `/api/v1/` and include any query parameters. Empty responses are
+/// returned as `Value::Null`.
+pub trait GiteaClient: Send + Sync {
+    /// The hostname of the instance.
+    fn host(&self) -> &str;
+    /// Send a `GET` request.
+    fn get(&self, endpoint: &str) -> GiteaResult<Value>;
+    /// Send a `POST` request with a JSON body.
+    fn post(&self, endpoint: &str, data: &Value) -> GiteaResult<Value>;
+}
+
+/// A client for communicating with a Gitea (or Forgejo) instance.
+#[derive(Clone)]
+pub struct Gitea {
+    /// The client used to communicate with Gitea.
+    client: Client,
+    /// The endpoint for REST queries.
+    rest_endpoint: Url,
+    /// The authorization header for requests.
+    auth_header: HeaderMap,
+}
+
+impl Gitea {
+    /// Create a new Gitea client.
+    ///
+    /// The `host` parameter is the hostname of the instance. Requests are authorized using an
+    /// access token for the user which will act as the service user.
+    pub fn new<H, T>(host: H, token: T) -> GiteaResult<Self>
+    where
+        H: AsRef<str>,
+        T: AsRef<str>,
+    {
+        let rest_endpoint = Url::parse(&format!("https://{}/api/v1/", host.as_ref()))?;
+        Ok(Self::with_endpoint(rest_endpoint, token.as_ref()))
+    }
+
+    /// Create a new Gitea client for testing purposes.
+    ///
+    /// The `endpoint` is the base URL of the server, e.g., `http://127.0.0.1:8080`.
+    #[cfg(test)]
+    pub(crate) fn new_test(endpoint: &str) -> GiteaResult<Self> {
+        let rest_endpoint = Url::parse(&format!("{}/api/v1/", endpoint))?;
+        Ok(Self::with_endpoint(rest_endpoint, "secret"))
+    }
+
+    fn with_endpoint(rest_endpoint: Url, token: &str) -> Self {
+        let mut header_value: HeaderValue = format!("token {}", token).parse().unwrap();
+        header_value.set_sensitive(true);
+
+        Gitea {
+            client: Client::new(),
+            rest_endpoint,
+            auth_header: [(header::AUTHORIZATION, header_value)]
+                .iter()
+                .cloned()
+                .collect(),
+        }
+    }
+
+    fn send(&self, method: Method, endpoint: &str, data: Option<&Value>) -> GiteaResult<Value> {
+        let endpoint = self.rest_endpoint.join(endpoint)?;
+        info!(
+            target: "gitea",
+            "sending {} request to {}",
+            method,
+            endpoint,
+        );
+
+        let mut req = self
+            .client
+            .request(method, endpoint.clone())
+            .headers(self.auth_header.clone())
+            .header(header::ACCEPT, "application/json")
+            .header(header::USER_AGENT, USER_AGENT);
+        if let Some(data) = data {
+            req = req.json(data);
+        }
+
+        let rsp = req
+            .send()
+            .map_err(|err| GiteaError::send_request(endpoint.clone(), err))?;
+        let status = rsp.status();
+        let body = rsp
+            .text()
+            .map_err(|err| GiteaError::read_response(endpoint, err))?;
+        if !status.is_success() {
+            return Err(GiteaError::gitea(status, body));
+        }
+
+        if body.trim().is_empty() {
+            Ok(Value::Null)
+        } else {
+            Ok(serde_json::from_str(&body)?)
+        }
+    }
+}
+
+impl GiteaClient for Gitea {
+    fn host(&self) -> &str {
+        self.rest_endpoint
+            .host_str()
+            .expect("the REST endpoint always has a host")
+    }
+
+    fn get(&self, endpoint: &str) -> GiteaResult<Value> {
+        self.send(Method::GET, endpoint, None)
+    }
+
+    fn post(&self, endpoint: &str, data: &Value) -> GiteaResult<Value> {
+        self.send(Method::POST, endpoint, Some(data))
+    }
+}
+
+impl<C> GiteaClient for Arc<C>
+where
+    C: GiteaClient + ?Sized,
+{
+    fn host(&self) -> &str {
+        self.as_ref().host()
+    }
+
+    fn get(&self, endpoint: &str) -> GiteaResult<Value> {
+        self.as_ref().get(endpoint)
+    }
+
+    fn post(&self, endpoint: &str, data: &Value) -> GiteaResult<Value> {
+        self.as_ref().post(endpoint, data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ghostflow::utils::http_stub;
+    use serde_json::{json, Value};
+
+    use crate::client::{Gitea, GiteaClient, GiteaError};
+
+    #[test]
+    fn test_gitea_client_requests() {
+        let (endpoint, requests) = http_stub(3, |request| {
+            match (request.method.as_str(), request.path.as_str()) {
+                ("GET", "/api/v1/user") => (200, r#"{"login": "ghostflow"}"#.into()),
+                ("POST", _) => (201, String::new()),
+                _ => (404, r#"{"message": "not found"}"#.into()),
+            }
+        });
+        let gitea = Gitea::new_test(&endpoint).unwrap();
+
+        assert_eq!(gitea.host(), "127.0.0.1");
+        assert_eq!(
+            gitea.get("user").unwrap(),
+            json!({
+                "login": "ghostflow",
+            }),
+        );
+        // Empty responses are returned as `null`.
+        let data = json!({
+            "body": "comment",
+        });
+        assert_eq!(
+            gitea.post("repos/owner/repo/issues/1/comments", &data).unwrap(),
+            Value::Null,
+        );
+        let err = gitea.get("repos/owner/missing").unwrap_err();
+        if let GiteaError::Gitea {
+            status,
+            response,
+        } = err
+        {
+            assert_eq!(status.as_u16(), 404);
+            assert_eq!(response, r#"{"message": "not found"}"#);
+        } else {
+            panic!("unexpected error: {:?}", err);
+        }
+
+        let requests = requests.iter().collect::<Vec<_>>();
+        assert_eq!(requests.len(), 3);
+        for request in &requests {
+            assert_eq!(request.header("authorization"), Some("token secret"));
+            assert_eq!(request.header("accept"), Some("application/json"));
+        }
+        assert_eq!(requests[1].method, "POST");
+        assert_eq!(requests[1].path, "/api/v1/repos/owner/repo/issues/1/comments");
+        assert_eq!(
+            serde_json::from_slice::<Value>(&requests[1].body).unwrap(),
+            data,
+        );
+        assert_eq!(requests[2].path, "/api/v1/repos/owner/missing");
+    }
+}
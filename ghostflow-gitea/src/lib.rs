@@ -0,0 +1,794 @@
+use std::collections::hash_map::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use ghostflow::host::*;
+use git_workarea::CommitId;
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+mod client;
+pub use crate::client::Gitea;
+pub use crate::client::GiteaClient;
+pub use crate::client::GiteaError;
+
+mod types;
+
+#[cfg(test)]
+mod replay;
+
+// The number of items to request per page.
+const PAGE_LIMIT: usize = 50;
+
+lazy_static! {
+    static ref CLOSES_RE: Regex = Regex::new(
+        "(?i)\\b(?:close[sd]?|fix(?:e[sd])?|resolve[sd]?):?[ \t]+\
+         (?P<project>[A-Za-z0-9_.-]+/[A-Za-z0-9_.-]+)?#(?P<issue>[0-9]+)\\b"
+    )
+    .unwrap();
+}
+
+/// Title prefixes Gitea uses to mark a pull request as a work-in-progress.
+const WIP_PREFIXES: &[&str] = &["WIP:", "[WIP]"];
+
+fn ghostflow_user(user: types::User, domain: &str) -> User {
+    let email = if user.email.is_empty() {
+        format!("{}@users.{}", user.login, domain)
+    } else {
+        user.email
+    };
+    let name = if user.full_name.is_empty() {
+        user.login.clone()
+    } else {
+        user.full_name
+    };
+
+    User {
+        handle: user.login,
+        name,
+        email,
+    }
+}
+
+fn ghostflow_repo(repo: types::Repository) -> Repo {
+    Repo {
+        name: repo.full_name,
+        url: repo.ssh_url,
+        forked_from: repo
+            .parent
+            .map(|parent| Box::new(ghostflow_repo(*parent))),
+    }
+}
+
+fn ghostflow_run_state(
+    status: types::RunStatus,
+    conclusion: Option<types::RunConclusion>,
+) -> PipelineState {
+    match status {
+        types::RunStatus::Blocked | types::RunStatus::Waiting => PipelineState::Manual,
+        types::RunStatus::Queued | types::RunStatus::InProgress => PipelineState::InProgress,
+        types::RunStatus::Completed => {
+            match conclusion {
+                Some(types::RunConclusion::Success) => PipelineState::Success,
+                Some(types::RunConclusion::Cancelled) | Some(types::RunConclusion::Skipped) => {
+                    PipelineState::Canceled
+                },
+                Some(types::RunConclusion::Failure) | None => PipelineState::Failed,
+            }
+        },
+    }
+}
+
+fn ghostflow_pipeline(run: types::WorkflowRun, repo: Repo, latest: Option<u64>) -> Pipeline {
+    Pipeline {
+        id: run.id,
+        state: ghostflow_run_state(run.status, run.conclusion),
+        commit: Commit {
+            repo,
+            id: CommitId::new(run.head_sha),
+            refname: run.head_branch,
+            last_pipeline: latest,
+        },
+    }
+}
+
+fn ghostflow_job(job: types::WorkflowJob, repo: Repo) -> PipelineJob {
+    PipelineJob {
+        id: job.id,
+        state: ghostflow_run_state(job.status, job.conclusion),
+        repo,
+        stage: job.workflow_name,
+        name: job.name,
+    }
+}
+
+fn gitea_state(state: CommitStatusState) -> &'static str {
+    match state {
+        CommitStatusState::Pending | CommitStatusState::Running => "pending",
+        CommitStatusState::Success => "success",
+        CommitStatusState::Failed => "failure",
+    }
+}
+
+fn ghostflow_state(state: types::StatusState) -> CommitStatusState {
+    match state {
+        types::StatusState::Pending => CommitStatusState::Pending,
+        types::StatusState::Success | types::StatusState::Warning => CommitStatusState::Success,
+        types::StatusState::Error | types::StatusState::Failure => CommitStatusState::Failed,
+    }
+}
+
+/// Gitea reaction names differ from the award names used elsewhere.
+fn ghostflow_award_name(content: String) -> String {
+    match content.as_str() {
+        "+1" => "thumbsup".into(),
+        "-1" => "thumbsdown".into(),
+        "hooray" => "tada".into(),
+        _ => content,
+    }
+}
+
+pub struct GiteaService {
+    gitea: Box<dyn GiteaClient>,
+    user: User,
+    /// The domain for email addresses of users which do not expose one.
+    domain: String,
+}
+
+impl GiteaService {
+    pub fn new<C>(gitea: C) -> Result<Self, HostingServiceError>
+    where
+        C: GiteaClient + 'static,
+    {
+        let domain = gitea.host().to_string();
+        let user: types::User = Self::query_impl(&gitea, "user")?;
+
+        Ok(Self {
+            user: ghostflow_user(user, &domain),
+            gitea: Box::new(gitea),
+            domain,
+        })
+    }
+
+    pub fn gitea(&self) -> &dyn GiteaClient {
+        self.gitea.as_ref()
+    }
+
+    fn query_impl<C, T>(gitea: &C, endpoint: &str) -> Result<T, HostingServiceError>
+    where
+        C: GiteaClient + ?Sized,
+        T: DeserializeOwned,
+    {
+        let value = gitea.get(endpoint).map_err(HostingServiceError::host)?;
+        serde_json::from_value(value)
+            .map_err(|err| HostingServiceError::host(GiteaError::from(err)))
+    }
+
+    fn query<T>(&self, endpoint: &str) -> Result<T, HostingServiceError>
+    where
+        T: DeserializeOwned,
+    {
+        Self::query_impl(self.gitea.as_ref(), endpoint)
+    }
+
+    fn post(&self, endpoint: &str, data: Value) -> Result<(), HostingServiceError> {
+        self.gitea
+            .post(endpoint, &data)
+            .map(|_| ())
+            .map_err(HostingServiceError::host)
+    }
+
+    /// Query all pages of an endpoint, extracting the items from each page.
+    fn query_paged_by<P, T, F>(
+        &self,
+        endpoint: &str,
+        items: F,
+    ) -> Result<Vec<T>, HostingServiceError>
+    where
+        P: DeserializeOwned,
+        F: Fn(P) -> Vec<T>,
+    {
+        let sep = if endpoint.contains('?') { '&' } else { '?' };
+        let mut results = Vec::new();
+
+        for page in 1.. {
+            let page_endpoint = format!("{}{}page={}&limit={}", endpoint, sep, page, PAGE_LIMIT);
+            let page_items = items(self.query(&page_endpoint)?);
+            let count = page_items.len();
+
+            results.extend(page_items);
+
+            if count < PAGE_LIMIT {
+                break;
+            }
+        }
+
+        Ok(results)
+    }
+
+    fn query_paged<T>(&self, endpoint: &str) -> Result<Vec<T>, HostingServiceError>
+    where
+        T: DeserializeOwned,
+    {
+        self.query_paged_by(endpoint, |items: Vec<T>| items)
+    }
+
+    fn full_repo(&self, project: &str) -> Result<types::Repository, HostingServiceError> {
+        self.query(&format!("repos/{}", project))
+    }
+
+    fn runs_for_commit(
+        &self,
+        project: &str,
+        commit: &CommitId,
+    ) -> Result<Vec<Pipeline>, HostingServiceError> {
+        let repo = self.full_repo(project)?;
+        if !repo.has_actions {
+            return Ok(Vec::new());
+        }
+
+        let endpoint = format!("repos/{}/actions/runs?head_sha={}", project, commit);
+        let runs = self.query_paged_by(&endpoint, |runs: types::WorkflowRuns| {
+            runs.workflow_runs
+        })?;
+        let repo = ghostflow_repo(repo);
+        let latest = runs.iter().map(|run| run.id).max();
+
+        Ok(runs
+            .into_iter()
+            .map(|run| ghostflow_pipeline(run, repo.clone(), latest))
+            .collect())
+    }
+}
+
+impl HostingService for GiteaService {
+    fn as_pipeline_service(self: Arc<Self>) -> Option<Arc<dyn HostedPipelineService>> {
+        Some(self as Arc<dyn HostedPipelineService>)
+    }
+
+    fn service_user(&self) -> &User {
+        &self.user
+    }
+
+    fn user(&self, project: &str, user: &str) -> Result<User, HostingServiceError> {
+        let _ = self.full_repo(project)?;
+        let user: types::User = self.query(&format!("users/{}", user))?;
+
+        Ok(ghostflow_user(user, &self.domain))
+    }
+
+    fn commit(&self, project: &str, commit: &CommitId) -> Result<Commit, HostingServiceError> {
+        let repo = self.full_repo(project)?;
+        let commit: types::Commit =
+            self.query(&format!("repos/{}/git/commits/{}", project, commit))?;
+
+        Ok(Commit {
+            repo: ghostflow_repo(repo),
+            refname: None,
+            id: CommitId::new(commit.sha),
+            last_pipeline: None,
+        })
+    }
+
+    fn merge_request(&self, project: &str, id: u64) -> Result<MergeRequest, HostingServiceError> {
+        let target_repo = ghostflow_repo(self.full_repo(project)?);
+        let pull: types::PullRequest = self.query(&format!("repos/{}/pulls/{}", project, id))?;
+
+        let source_repo = pull.head.repo.map(ghostflow_repo);
+        let commit_repo = source_repo.as_ref().unwrap_or(&target_repo).clone();
+        let work_in_progress = pull.draft
+            || WIP_PREFIXES
+                .iter()
+                .any(|prefix| pull.title.starts_with(prefix));
+
+        Ok(MergeRequest {
+            commit: Commit {
+                repo: commit_repo,
+                refname: Some(pull.head.ref_.clone()),
+                id: CommitId::new(pull.head.sha),
+                last_pipeline: None,
+            },
+            source_repo,
+            source_branch: pull.head.ref_,
+            target_repo,
+            target_branch: pull.base.ref_,
+            id: pull.number,
+            url: pull.html_url,
+            work_in_progress,
+            description: pull.body.unwrap_or_default(),
+            old_commit: None,
+            author: ghostflow_user(pull.user, &self.domain),
+            reference: format!("#{}", pull.number),
+            remove_source_branch: false,
+            labels: pull.labels.into_iter().map(|label| label.name).collect(),
+        })
+    }
+
+    fn repo(&self, project: &str) -> Result<Repo, HostingServiceError> {
+        self.full_repo(project).map(ghostflow_repo)
+    }
+
+    fn get_mr_comments(&self, mr: &MergeRequest) -> Result<Vec<Comment>, HostingServiceError> {
+        let endpoint = format!("repos/{}/issues/{}/timeline", mr.target_repo.name, mr.id);
+        let timeline: Vec<types::TimelineComment> = self.query_paged(&endpoint)?;
+
+        Ok(timeline
+            .into_iter()
+            .map(|comment| {
+                Comment {
+                    id: format!("{}", comment.id),
+                    is_system: comment.kind != types::TimelineKind::Comment,
+                    is_branch_update: comment.kind == types::TimelineKind::PullPush,
+                    created_at: comment.created_at,
+                    author: ghostflow_user(comment.user, &self.domain),
+                    content: comment.body,
+                }
+            })
+            .sorted_by(|a, b| a.created_at.cmp(&b.created_at))
+            .collect())
+    }
+
+    fn post_mr_comment(&self, mr: &MergeRequest, content: &str) -> Result<(), HostingServiceError> {
+        let endpoint = format!("repos/{}/issues/{}/comments", mr.target_repo.name, mr.id);
+        self.post(
+            &endpoint,
+            json!({
+                "body": content,
+            }),
+        )
+    }
+
+    fn get_commit_statuses(
+        &self,
+        commit: &Commit,
+    ) -> Result<Vec<CommitStatus>, HostingServiceError> {
+        let endpoint = format!("repos/{}/commits/{}/statuses", commit.repo.name, commit.id);
+        let statuses: Vec<types::CommitStatus> = self.query_paged(&endpoint)?;
+
+        Ok(statuses
+            .into_iter()
+            .map(|status| {
+                CommitStatus {
+                    state: ghostflow_state(status.status),
+                    author: ghostflow_user(status.creator, &self.domain),
+                    refname: commit.refname.clone(),
+                    name: status.context,
+                    description: status.description.unwrap_or_default(),
+                    target_url: status.target_url,
+                }
+            })
+            .collect())
+    }
+
+    fn post_commit_status(&self, status: PendingCommitStatus) -> Result<(), HostingServiceError> {
+        let endpoint = format!(
+            "repos/{}/statuses/{}",
+            status.commit.repo.name, status.commit.id,
+        );
+        let mut data = json!({
+            "state": gitea_state(status.state),
+            "context": status.name,
+            "description": status.description,
+        });
+        if let Some(target_url) = status.target_url {
+            data["target_url"] = target_url.into();
+        }
+
+        self.post(&endpoint, data)
+    }
+
+    fn get_mr_awards(&self, mr: &MergeRequest) -> Result<Vec<Award>, HostingServiceError> {
+        let endpoint = format!("repos/{}/issues/{}/reactions", mr.target_repo.name, mr.id);
+        let reactions: Vec<types::Reaction> = self.query_paged(&endpoint)?;
+
+        Ok(reactions
+            .into_iter()
+            .map(|reaction| {
+                Award {
+                    name: ghostflow_award_name(reaction.content),
+                    author: ghostflow_user(reaction.user, &self.domain),
+                }
+            })
+            .collect())
+    }
+
+    fn issues_closed_by_mr(&self, mr: &MergeRequest) -> Result<Vec<Issue>, HostingServiceError> {
+        // Gitea does not expose closing references, so find them the same way it does.
+        let target_name = mr.target_repo.name.as_str();
+        let references = CLOSES_RE
+            .captures_iter(&mr.description)
+            .map(|captures| {
+                let project = captures
+                    .name("project")
+                    .map_or(target_name, |project| project.as_str());
+                let issue = captures
+                    .name("issue")
+                    .expect("the issue is not optional")
+                    .as_str()
+                    .parse::<u64>()
+                    .expect("the issue pattern only matches digits");
+
+                (project.to_string(), issue)
+            })
+            .unique()
+            .collect::<Vec<_>>();
+
+        // Cache repositories to reduce hitting the service so much.
+        let mut issues = Vec::new();
+        let mut repos = HashMap::new();
+        for (project, id) in references {
+            let issue: types::Issue = self.query(&format!("repos/{}/issues/{}", project, id))?;
+            let repo = if let Some(repo) = repos.get(&issue.repository.full_name) {
+                repo.clone()
+            } else {
+                let repo = ghostflow_repo(self.full_repo(&issue.repository.full_name)?);
+                repos.insert(issue.repository.full_name.clone(), repo.clone());
+                repo
+            };
+            let reference = if repo.name == target_name {
+                format!("#{}", issue.number)
+            } else {
+                format!("{}#{}", repo.name, issue.number)
+            };
+
+            issues.push(Issue {
+                reference,
+                repo,
+                id: issue.number,
+                url: issue.html_url,
+                labels: issue.labels.into_iter().map(|label| label.name).collect(),
+            });
+        }
+
+        Ok(issues)
+    }
+
+    fn add_issue_labels(&self, issue: &Issue, labels: &[&str]) -> Result<(), HostingServiceError> {
+        let endpoint = format!("repos/{}/issues/{}/labels", issue.repo.name, issue.id);
+        self.post(
+            &endpoint,
+            json!({
+                "labels": labels,
+            }),
+        )
+    }
+}
+
+impl HostedPipelineService for GiteaService {
+    fn pipelines_for_mr(
+        &self,
+        mr: &MergeRequest,
+    ) -> Result<Option<Vec<Pipeline>>, HostingServiceError> {
+        let source_pipelines = if let Some(source_repo) = mr.source_repo.as_ref() {
+            if source_repo.name != mr.target_repo.name {
+                self.runs_for_commit(&source_repo.name, &mr.commit.id)?
+            } else {
+                Vec::new()
+            }
+        } else {
+            Vec::new()
+        };
+
+        let target_pipelines = self.runs_for_commit(&mr.target_repo.name, &mr.commit.id)?;
+
+        Ok(Some(
+            source_pipelines
+                .into_iter()
+                .chain(target_pipelines.into_iter())
+                .collect(),
+        ))
+    }
+
+    fn pipelines_for_commit(
+        &self,
+        commit: &Commit,
+    ) -> Result<Option<Vec<Pipeline>>, HostingServiceError> {
+        Ok(Some(self.runs_for_commit(&commit.repo.name, &commit.id)?))
+    }
+
+    fn pipeline_jobs(
+        &self,
+        pipeline: &Pipeline,
+    ) -> Result<Option<Vec<PipelineJob>>, HostingServiceError> {
+        let project = pipeline.commit.repo.name.as_str();
+        let repo = self.full_repo(project)?;
+
+        if !repo.has_actions {
+            return Ok(None);
+        }
+
+        let endpoint = format!("repos/{}/actions/runs/{}/jobs", project, pipeline.id);
+        let jobs = self.query_paged_by(&endpoint, |jobs: types::WorkflowJobs| jobs.jobs)?;
+
+        Ok(Some(
+            jobs.into_iter()
+                .map(|job| ghostflow_job(job, pipeline.commit.repo.clone()))
+                .collect(),
+        ))
+    }
+
+    fn trigger_job(
+        &self,
+        job: &PipelineJob,
+        _: Option<&str>,
+    ) -> Result<(), HostingServiceError> {
+        // Gitea does not support triggering jobs as another user.
+        let endpoint = format!("repos/{}/actions/jobs/{}/rerun", job.repo.name, job.id);
+        self.post(&endpoint, json!({}))
+    }
+}
+
+impl fmt::Debug for GiteaService {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GiteaService")
+            .field("user", &self.user.handle)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use ghostflow::host::*;
+    use git_workarea::CommitId;
+
+    use crate::replay::Replay;
+    use crate::{GiteaService, CLOSES_RE};
+
+    fn service(fixture: &str) -> (Arc<Replay>, GiteaService) {
+        let replay = Arc::new(Replay::load(fixture));
+        let service = GiteaService::new(Arc::clone(&replay)).unwrap();
+        (replay, service)
+    }
+
+    fn repo(name: &str) -> Repo {
+        Repo {
+            name: name.into(),
+            url: format!("git@gitea.example.com:{}.git", name),
+            forked_from: None,
+        }
+    }
+
+    fn mr(description: &str) -> MergeRequest {
+        let source_repo = repo("contributor/project");
+        let target_repo = repo("upstream/project");
+
+        MergeRequest {
+            source_repo: Some(source_repo.clone()),
+            source_branch: "topic".into(),
+            target_repo,
+            target_branch: "main".into(),
+            id: 7,
+            url: "https://gitea.example.com/upstream/project/pulls/7".into(),
+            work_in_progress: false,
+            description: description.into(),
+            old_commit: None,
+            commit: Commit {
+                repo: source_repo,
+                refname: Some("topic".into()),
+                id: CommitId::new("0123456789abcdef0123456789abcdef01234567"),
+                last_pipeline: None,
+            },
+            author: User {
+                handle: "contributor".into(),
+                name: "Contributor".into(),
+                email: "contributor@example.com".into(),
+            },
+            reference: "#7".into(),
+            remove_source_branch: false,
+            labels: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_closes_re() {
+        let description = "Fixes #1, closes upstream/other#2.\n\
+                           Resolved: #3\n\
+                           Mentions #4 and prefixes#5.";
+        let refs = CLOSES_RE
+            .captures_iter(description)
+            .map(|captures| {
+                (
+                    captures.name("project").map(|project| project.as_str()),
+                    captures.name("issue").unwrap().as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            refs,
+            &[
+                (None, "1"),
+                (Some("upstream/other"), "2"),
+                (None, "3"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_service_user() {
+        let (replay, service) = service("service_user");
+
+        let user = service.service_user();
+        assert_eq!(user.handle, "ghostflow");
+        assert_eq!(user.name, "Ghostflow Bot");
+        assert_eq!(user.email, "ghostflow@users.gitea.example.com");
+        replay.finish();
+    }
+
+    #[test]
+    fn test_merge_request() {
+        let (replay, service) = service("merge_request");
+
+        let mr = service.merge_request("upstream/project", 7).unwrap();
+        assert_eq!(mr.id, 7);
+        assert_eq!(mr.reference, "#7");
+        assert_eq!(mr.source_branch, "topic");
+        assert_eq!(mr.target_branch, "main");
+        assert_eq!(mr.target_repo.name, "upstream/project");
+        assert_eq!(
+            mr.source_repo.as_ref().unwrap().fork_root().name,
+            "upstream/project",
+        );
+        assert_eq!(mr.commit.repo.name, "contributor/project");
+        assert_eq!(
+            mr.commit.id.as_str(),
+            "0123456789abcdef0123456789abcdef01234567",
+        );
+        assert_eq!(mr.author.handle, "contributor");
+        assert_eq!(mr.description, "Fixes #3.");
+        assert!(mr.work_in_progress);
+        replay.finish();
+    }
+
+    #[test]
+    fn test_mr_comments() {
+        let (replay, service) = service("mr_comments");
+
+        let comments = service.get_mr_comments(&mr("")).unwrap();
+        assert_eq!(comments.len(), 3);
+        assert!(!comments[0].is_system);
+        assert_eq!(comments[0].content, "Do: merge");
+        assert_eq!(comments[0].author.handle, "maintainer");
+        assert!(comments[1].is_system);
+        assert!(comments[1].is_branch_update);
+        assert!(comments[2].is_system);
+        assert!(!comments[2].is_branch_update);
+        replay.finish();
+    }
+
+    #[test]
+    fn test_post_mr_comment() {
+        let (replay, service) = service("post_mr_comment");
+
+        service.post_mr_comment(&mr(""), "Looks good.").unwrap();
+        replay.finish();
+    }
+
+    #[test]
+    fn test_commit_statuses() {
+        let (replay, service) = service("commit_statuses");
+
+        let mr = mr("");
+        let statuses = service.get_commit_statuses(&mr.commit).unwrap();
+        assert_eq!(statuses.len(), 2);
+        assert_eq!(statuses[0].name, "ghostflow-check-main");
+        assert_eq!(statuses[0].state, CommitStatusState::Success);
+        assert_eq!(statuses[1].state, CommitStatusState::Failed);
+
+        let status = mr.create_commit_status(
+            CommitStatusState::Running,
+            "ghostflow-stage",
+            "staging the topic",
+        );
+        service.post_commit_status(status).unwrap();
+        replay.finish();
+    }
+
+    #[test]
+    fn test_mr_awards() {
+        let (replay, service) = service("mr_awards");
+
+        let awards = service.get_mr_awards(&mr("")).unwrap();
+        let names = awards
+            .iter()
+            .map(|award| (award.name.as_str(), award.author.handle.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            &[("thumbsup", "maintainer"), ("thumbsdown", "reviewer")],
+        );
+        replay.finish();
+    }
+
+    #[test]
+    fn test_issues_closed_by_mr() {
+        let (replay, service) = service("issues_closed_by_mr");
+
+        let issues = service
+            .issues_closed_by_mr(&mr("Fixes #3.\n\nCloses upstream/other#4."))
+            .unwrap();
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].reference, "#3");
+        assert_eq!(issues[0].labels, &["bug"]);
+        assert_eq!(issues[1].reference, "upstream/other#4");
+        assert_eq!(issues[1].repo.name, "upstream/other");
+
+        service.add_issue_labels(&issues[0], &["fixed"]).unwrap();
+        replay.finish();
+    }
+
+    #[test]
+    fn test_pipelines() {
+        let (replay, service) = service("pipelines");
+
+        let pipelines = Arc::new(service)
+            .as_pipeline_service()
+            .expect("gitea supports pipelines");
+        let mr = mr("");
+        let runs = pipelines.pipelines_for_mr(&mr).unwrap().unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].id, 11);
+        assert_eq!(runs[0].state, PipelineState::Success);
+        assert_eq!(runs[0].commit.repo.name, "upstream/project");
+        assert_eq!(runs[0].commit.last_pipeline, Some(12));
+        assert_eq!(runs[1].state, PipelineState::InProgress);
+
+        let jobs = pipelines.pipeline_jobs(&runs[0]).unwrap().unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].name, "build");
+        assert_eq!(jobs[0].stage.as_deref(), Some("CI"));
+        assert_eq!(jobs[0].state, PipelineState::Success);
+        assert_eq!(jobs[1].state, PipelineState::Failed);
+
+        pipelines.trigger_job(&jobs[1], None).unwrap();
+        replay.finish();
+    }
+
+    #[test]
+    fn test_trigger_job_request() {
+        use ghostflow::utils::http_stub;
+
+        use crate::Gitea;
+
+        let (endpoint, requests) = http_stub(2, |request| {
+            if request.method == "POST" {
+                (201, String::new())
+            } else {
+                (200, r#"{"id": 1, "login": "ghostflow", "full_name": "", "email": ""}"#.into())
+            }
+        });
+        let service = Arc::new(GiteaService::new(Gitea::new_test(&endpoint).unwrap()).unwrap());
+
+        // Users without a public email address are given one on the instance's domain.
+        assert_eq!(service.service_user().email, "ghostflow@users.127.0.0.1");
+
+        let job = PipelineJob {
+            repo: repo("upstream/project"),
+            state: PipelineState::Failed,
+            stage: Some("CI".into()),
+            name: "test".into(),
+            id: 21,
+        };
+        let pipelines = service
+            .as_pipeline_service()
+            .expect("gitea supports pipelines");
+        // Jobs may not be triggered as another user; the user is ignored.
+        pipelines.trigger_job(&job, Some("maintainer")).unwrap();
+
+        let requests = requests.iter().collect::<Vec<_>>();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].path, "/api/v1/user");
+        assert_eq!(requests[1].method, "POST");
+        assert_eq!(
+            requests[1].path,
+            "/api/v1/repos/upstream/project/actions/jobs/21/rerun",
+        );
+        assert_eq!(requests[1].body, b"{}");
+    }
+}
@@ -0,0 +1,97 @@
+//! Replay recorded Gitea API interactions.
+//!
+//! Fixtures live in the `fixtures` directory of the crate. Each is a JSON array of interactions
+//! which must be requested in order. `POST` interactions may record the expected request body.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::client::{GiteaClient, GiteaResult};
+
+#[derive(Debug, Deserialize)]
+struct Interaction {
+    method: String,
+    endpoint: String,
+    #[serde(default)]
+    request: Option<Value>,
+    #[serde(default)]
+    response: Value,
+}
+
+pub struct Replay {
+    name: String,
+    interactions: Mutex<VecDeque<Interaction>>,
+}
+
+impl Replay {
+    pub fn load(name: &str) -> Self {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(format!("{}.json", name));
+        let contents = fs::read(&path)
+            .unwrap_or_else(|err| panic!("failed to read {}: {:?}", path.display(), err));
+        let interactions = serde_json::from_slice(&contents)
+            .unwrap_or_else(|err| panic!("failed to parse {}: {:?}", path.display(), err));
+
+        Self {
+            name: name.into(),
+            interactions: Mutex::new(interactions),
+        }
+    }
+
+    fn next(&self, method: &str, endpoint: &str, data: Option<&Value>) -> GiteaResult<Value> {
+        let interaction = self
+            .interactions
+            .lock()
+            .expect("replay lock poisoned")
+            .pop_front()
+            .unwrap_or_else(|| {
+                panic!(
+                    "{}: unexpected request {} {}",
+                    self.name, method, endpoint,
+                )
+            });
+
+        assert_eq!(
+            (interaction.method.as_str(), interaction.endpoint.as_str()),
+            (method, endpoint),
+            "{}: request mismatch",
+            self.name,
+        );
+        if let Some(request) = interaction.request.as_ref() {
+            assert_eq!(Some(request), data, "{}: request body mismatch", self.name);
+        }
+
+        Ok(interaction.response)
+    }
+
+    /// Assert that all recorded interactions have been requested.
+    pub fn finish(&self) {
+        let interactions = self.interactions.lock().expect("replay lock poisoned");
+        assert!(
+            interactions.is_empty(),
+            "{}: unused interactions: {:?}",
+            self.name,
+            interactions,
+        );
+    }
+}
+
+impl GiteaClient for Replay {
+    fn host(&self) -> &str {
+        "gitea.example.com"
+    }
+
+    fn get(&self, endpoint: &str) -> GiteaResult<Value> {
+        self.next("GET", endpoint, None)
+    }
+
+    fn post(&self, endpoint: &str, data: &Value) -> GiteaResult<Value> {
+        self.next("POST", endpoint, Some(data))
+    }
+}
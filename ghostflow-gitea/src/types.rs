@@ -0,0 +1,189 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct User {
+    pub id: u64,
+    pub login: String,
+    #[serde(default)]
+    pub full_name: String,
+    #[serde(default)]
+    pub email: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Owner {
+    pub login: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Repository {
+    pub id: u64,
+    pub name: String,
+    pub full_name: String,
+    pub owner: Owner,
+    pub ssh_url: String,
+    #[serde(default)]
+    pub parent: Option<Box<Repository>>,
+    #[serde(default)]
+    pub has_actions: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Commit {
+    pub sha: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PullBranch {
+    #[serde(rename = "ref")]
+    pub ref_: String,
+    pub sha: String,
+    #[serde(default)]
+    pub repo: Option<Repository>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PullRequest {
+    pub number: u64,
+    pub html_url: String,
+    pub title: String,
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub draft: bool,
+    pub head: PullBranch,
+    pub base: PullBranch,
+    pub user: User,
+    #[serde(default)]
+    pub labels: Vec<Label>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum TimelineKind {
+    #[serde(rename = "comment")]
+    Comment,
+    #[serde(rename = "pull_push")]
+    PullPush,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TimelineComment {
+    pub id: u64,
+    #[serde(rename = "type")]
+    pub kind: TimelineKind,
+    #[serde(default)]
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub user: User,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Reaction {
+    pub user: User,
+    pub content: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Label {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IssueRepository {
+    pub full_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Issue {
+    pub number: u64,
+    pub html_url: String,
+    #[serde(default)]
+    pub labels: Vec<Label>,
+    pub repository: IssueRepository,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum StatusState {
+    #[serde(rename = "pending")]
+    Pending,
+    #[serde(rename = "success")]
+    Success,
+    #[serde(rename = "error")]
+    Error,
+    #[serde(rename = "failure")]
+    Failure,
+    #[serde(rename = "warning")]
+    Warning,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommitStatus {
+    pub status: StatusState,
+    pub context: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub target_url: Option<String>,
+    pub creator: User,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum RunStatus {
+    #[serde(rename = "queued")]
+    Queued,
+    #[serde(rename = "waiting")]
+    Waiting,
+    #[serde(rename = "blocked")]
+    Blocked,
+    #[serde(rename = "in_progress")]
+    InProgress,
+    #[serde(rename = "completed")]
+    Completed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum RunConclusion {
+    #[serde(rename = "success")]
+    Success,
+    #[serde(rename = "failure")]
+    Failure,
+    #[serde(rename = "cancelled")]
+    Cancelled,
+    #[serde(rename = "skipped")]
+    Skipped,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WorkflowRun {
+    pub id: u64,
+    pub head_sha: String,
+    #[serde(default)]
+    pub head_branch: Option<String>,
+    pub status: RunStatus,
+    #[serde(default)]
+    pub conclusion: Option<RunConclusion>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WorkflowRuns {
+    pub workflow_runs: Vec<WorkflowRun>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WorkflowJob {
+    pub id: u64,
+    pub name: String,
+    #[serde(default)]
+    pub workflow_name: Option<String>,
+    pub status: RunStatus,
+    #[serde(default)]
+    pub conclusion: Option<RunConclusion>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WorkflowJobs {
+    pub jobs: Vec<WorkflowJob>,
+}
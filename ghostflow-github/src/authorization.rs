@@ -311,6 +311,14 @@ impl GithubAuthorization {
         }))
     }
 
+    /// Authorization with a fixed token for testing against a stub server.
+    #[cfg(test)]
+    pub(crate) fn new_test() -> Self {
+        GithubAuthorization::Action(GithubActionAuth {
+            token: "test-token".into(),
+        })
+    }
+
     pub(crate) fn current_user(&self, client: &Client) -> GithubResult<CurrentUser> {
         match *self {
             GithubAuthorization::App(ref auth) => {
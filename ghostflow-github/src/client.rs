@@ -9,7 +9,7 @@ use itertools::Itertools;
 use log::{info, warn};
 use reqwest::blocking::Client;
 use reqwest::header::{self, HeaderMap, HeaderValue};
-use reqwest::Url;
+use reqwest::{Method, StatusCode, Url};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use thiserror::Error;
@@ -214,6 +214,19 @@ impl Github {
         Self::new_impl(host.as_ref(), authorization)
     }
 
+    /// Create a new Github client which communicates with a test server.
+    ///
+    /// The `endpoint` is the base URL of the server, e.g., `http://127.0.0.1:8080`.
+    #[cfg(test)]
+    pub(crate) fn new_test(endpoint: &str) -> GithubResult<Self> {
+        Ok(Github {
+            client: Client::new(),
+            rest_endpoint: Url::parse(&format!("{}/", endpoint))?,
+            gql_endpoint: Url::parse(&format!("{}/graphql", endpoint))?,
+            authorization: GithubAuthorization::new_test(),
+        })
+    }
+
     pub(crate) fn app_id(&self) -> Option<i64> {
         self.authorization.app_id()
     }
@@ -255,19 +268,81 @@ impl Github {
     }
 
     pub(crate) fn post<D>(&self, owner: &str, endpoint: &str, data: &D) -> GithubResult<Value>
+    where
+        D: Serialize,
+    {
+        self.send_data(Method::POST, owner, endpoint, data)
+    }
+
+    pub(crate) fn patch<D>(&self, owner: &str, endpoint: &str, data: &D) -> GithubResult<Value>
+    where
+        D: Serialize,
+    {
+        self.send_data(Method::PATCH, owner, endpoint, data)
+    }
+
+    /// Send a REST request with a JSON body.
+    fn send_data<D>(
+        &self,
+        method: Method,
+        owner: &str,
+        endpoint: &str,
+        data: &D,
+    ) -> GithubResult<Value>
     where
         D: Serialize,
     {
         let endpoint = Url::parse(&format!("{}{}", self.rest_endpoint, endpoint))?;
         let rsp = self
             .client
-            .post(endpoint.clone())
+            .request(method, endpoint.clone())
             .headers(self.installation_auth_header(owner)?)
             .headers(Self::rest_accept_headers())
             .header(header::USER_AGENT, USER_AGENT)
             .json(data)
             .send()
             .map_err(|err| GithubError::send_request(endpoint, err))?;
+
+        Self::rest_response(rsp)
+    }
+
+    /// Send a REST `GET` request.
+    fn get_impl(&self, owner: &str, endpoint: &str) -> GithubResult<Value> {
+        let endpoint = Url::parse(&format!("{}{}", self.rest_endpoint, endpoint))?;
+        info!(
+            target: "github",
+            "sending REST query to {}",
+            endpoint,
+        );
+        let rsp = self
+            .client
+            .get(endpoint.clone())
+            .headers(self.installation_auth_header(owner)?)
+            .headers(Self::rest_accept_headers())
+            .header(header::USER_AGENT, USER_AGENT)
+            .send()
+            .map_err(|err| GithubError::send_request(endpoint, err))?;
+        if rsp.status().is_server_error() {
+            warn!(
+                target: "github",
+                "service error {} for REST query; retrying with backoff",
+                rsp.status().as_u16(),
+            );
+            return Err(GithubError::github_service(rsp.status()));
+        }
+
+        Self::rest_response(rsp)
+    }
+
+    /// Send a REST `GET` request.
+    pub(crate) fn get(&self, owner: &str, endpoint: &str) -> GithubResult<Value> {
+        retry_with_backoff(|| self.get_impl(owner, endpoint))
+    }
+
+    /// Extract the JSON body of a REST response.
+    ///
+    /// Some endpoints respond without content; these are returned as `Value::Null`.
+    fn rest_response(rsp: reqwest::blocking::Response) -> GithubResult<Value> {
         if !rsp.status().is_success() {
             let err = rsp
                 .text()
@@ -275,6 +350,10 @@ impl Github {
             return Err(GithubError::github(err));
         }
 
+        if rsp.status() == StatusCode::NO_CONTENT {
+            return Ok(Value::Null);
+        }
+
         rsp.json().map_err(GithubError::json_response)
     }
 
@@ -2,18 +2,22 @@
 #![allow(unused_variables)]
 
 use std::fmt::{self, Debug};
+use std::mem;
+use std::sync::Arc;
 
 use chrono::Utc;
 use ghostflow::host::*;
 use git_workarea::{CommitId, GitContext};
 use graphql_client::GraphQLQuery;
-use log::{error, warn};
-use serde_json::json;
+use log::{error, info, warn};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
 use thiserror::Error;
 
 use crate::authorization::CurrentUser;
-use crate::client::Github;
+use crate::client::{Github, GithubError};
 use crate::queries;
+use crate::workflows;
 
 const WORK_IN_PROGRESS_PREFIXES: &[&str] = &["WIP", "wip"];
 
@@ -233,6 +237,7 @@ fn extract_run_state_rest(state: CommitStatusState) -> (Option<&'static str>, &'
 /// have been granted to the application:
 ///
 ///   - Read & write
+///     * Actions
 ///     * Checks
 ///     * Issues
 ///     * Pull requests
@@ -250,26 +255,73 @@ pub struct GithubService {
 }
 
 const GITHUB_CHECK_RUN_MESSAGE_LIMIT: usize = 65535;
-const GITHUB_OVERFLOW_INDICATOR: &str = "**Contents exceed GitHub check limits**\n\n";
-
-fn trim_to_check_run_limit(text: String) -> String {
-    if text.len() > GITHUB_CHECK_RUN_MESSAGE_LIMIT {
-        warn!(
-            target: "github",
-            "Check results comment exceeding limits: {}",
-            text,
-        );
+/// Space reserved on each page of check run text for the page header.
+const GITHUB_CHECK_RUN_PAGE_RESERVE: usize = 64;
+/// The maximum number of annotations which may be sent in a single check run request.
+const GITHUB_CHECK_RUN_ANNOTATION_LIMIT: usize = 50;
 
-        format!(
-            "{}{}",
-            // Prepend because we have no idea what the Markdown parser state will be where we
-            // truncate the intended message.
-            GITHUB_OVERFLOW_INDICATOR,
-            &text[..GITHUB_CHECK_RUN_MESSAGE_LIMIT - GITHUB_OVERFLOW_INDICATOR.len()],
-        )
-    } else {
-        text
+/// Split text into pages which fit within the check run text limits.
+///
+/// Pages are split on line boundaries where possible. When more than one page is required, each
+/// is prefixed with a header indicating its position.
+fn check_run_pages(text: &str) -> Vec<String> {
+    if text.len() <= GITHUB_CHECK_RUN_MESSAGE_LIMIT {
+        return vec![text.into()];
+    }
+
+    let limit = GITHUB_CHECK_RUN_MESSAGE_LIMIT - GITHUB_CHECK_RUN_PAGE_RESERVE;
+    let mut pages = Vec::new();
+    let mut page = String::new();
+    for line in text.split_inclusive('\n') {
+        let mut line = line;
+        while !line.is_empty() {
+            if page.len() + line.len() <= limit {
+                page.push_str(line);
+                break;
+            }
+
+            if !page.is_empty() {
+                pages.push(mem::take(&mut page));
+                continue;
+            }
+
+            // The line does not fit on a page by itself; split it at a character boundary.
+            let mut end = limit;
+            while !line.is_char_boundary(end) {
+                end -= 1;
+            }
+            pages.push(line[..end].into());
+            line = &line[end..];
+        }
+    }
+    if !page.is_empty() {
+        pages.push(page);
     }
+
+    let count = pages.len();
+    pages
+        .into_iter()
+        .enumerate()
+        .map(|(idx, page)| format!("**Part {} of {}**\n\n{}", idx + 1, count, page))
+        .collect()
+}
+
+/// The REST representation of a review annotation.
+fn check_run_annotation(annotation: &ReviewAnnotation) -> Value {
+    let level = match annotation.level {
+        AnnotationLevel::Notice => "notice",
+        AnnotationLevel::Warning => "warning",
+        AnnotationLevel::Failure => "failure",
+    };
+    let line = annotation.line.unwrap_or(1);
+
+    json!({
+        "path": annotation.path,
+        "start_line": line,
+        "end_line": line,
+        "annotation_level": level,
+        "message": annotation.message,
+    })
 }
 
 impl GithubService {
@@ -383,55 +435,111 @@ impl GithubService {
     }
 
     /// Create a check run.
+    ///
+    /// Text which exceeds the check run limit is split into pages and each page is published as
+    /// its own check run. GitHub replaces the text of a check run on every update, so only
+    /// annotations are sent as updates; they are attached to the first check run in batches along
+    /// with its text.
     fn post_check_run(
         &self,
         status: PendingCommitStatus,
-        description: Option<String>,
+        description: Option<&str>,
+        annotations: &[ReviewAnnotation],
     ) -> Result<(), HostingServiceError> {
         let project = &status.commit.repo.name;
         let (owner, name) = Self::split_project(project)?;
 
         let endpoint = format!("repos/{}/{}/check-runs", owner, name);
         let (conclusion, status_state) = extract_run_state_rest(status.state);
-        let output = if let Some(description) = description {
-            let description = trim_to_check_run_limit(description);
-
-            json!({
-                "title": status.name,
-                "summary": status.description,
-                "text": description,
-            })
+        let pages = description.map(check_run_pages).unwrap_or_default();
+        let texts = if pages.is_empty() {
+            vec![None]
         } else {
-            json!({
-                "title": status.name,
-                "summary": status.description,
-            })
-        };
-        let mut data = if let Some(conclusion) = conclusion {
-            json!({
-                "name": status.name,
-                "head_sha": status.commit.id.as_str(),
-                "status": status_state,
-                "conclusion": conclusion,
-                "completed_at": Utc::now(),
-                "output": output,
-            })
-        } else {
-            json!({
-                "name": status.name,
-                "head_sha": status.commit.id.as_str(),
-                "status": status_state,
-                "output": output,
-            })
+            pages.iter().map(|page| Some(page.as_str())).collect()
         };
-        if let Some(target_url) = status.target_url {
-            data.as_object_mut()
-                .expect("`data` is always constructed as an object")
-                .insert("details_url".into(), target_url.into());
+        let page_count = texts.len();
+        let annotations = annotations
+            .iter()
+            .map(check_run_annotation)
+            .collect::<Vec<_>>();
+
+        for (idx, text) in texts.into_iter().enumerate() {
+            let run_name = if idx == 0 {
+                status.name.into()
+            } else {
+                format!("{} (part {} of {})", status.name, idx + 1, page_count)
+            };
+            let output = |batch: Option<&[Value]>| {
+                let mut output = json!({
+                    "title": run_name,
+                    "summary": status.description,
+                });
+                let output_obj = output
+                    .as_object_mut()
+                    .expect("`output` is always constructed as an object");
+                if let Some(text) = text {
+                    output_obj.insert("text".into(), text.into());
+                }
+                if let Some(batch) = batch {
+                    output_obj.insert("annotations".into(), batch.into());
+                }
+                output
+            };
+            let mut batches = if idx == 0 {
+                annotations
+                    .chunks(GITHUB_CHECK_RUN_ANNOTATION_LIMIT)
+                    .collect::<Vec<_>>()
+            } else {
+                Vec::new()
+            }
+            .into_iter();
+
+            let mut data = if let Some(conclusion) = conclusion {
+                json!({
+                    "name": run_name,
+                    "head_sha": status.commit.id.as_str(),
+                    "status": status_state,
+                    "conclusion": conclusion,
+                    "completed_at": Utc::now(),
+                    "output": output(batches.next()),
+                })
+            } else {
+                json!({
+                    "name": run_name,
+                    "head_sha": status.commit.id.as_str(),
+                    "status": status_state,
+                    "output": output(batches.next()),
+                })
+            };
+            if let Some(target_url) = status.target_url {
+                data.as_object_mut()
+                    .expect("`data` is always constructed as an object")
+                    .insert("details_url".into(), target_url.into());
+            }
+            let check_run = self
+                .github
+                .post(owner, &endpoint, &data)
+                .map_err(HostingServiceError::host)?;
+
+            if batches.len() > 0 {
+                let id = check_run
+                    .pointer("/id")
+                    .and_then(Value::as_u64)
+                    .ok_or_else(|| {
+                        GithubHostError::no_check_run_id(status.commit.id.clone(), project.clone())
+                    })?;
+                let update_endpoint = format!("{}/{}", endpoint, id);
+
+                for batch in batches {
+                    let data = json!({
+                        "output": output(Some(batch)),
+                    });
+                    self.github
+                        .patch(owner, &update_endpoint, &data)
+                        .map_err(HostingServiceError::host)?;
+                }
+            }
         }
-        self.github
-            .post(owner, &endpoint, &data)
-            .map_err(HostingServiceError::host)?;
 
         /*
         let vars = queries::repository_id::Variables {
@@ -498,6 +606,106 @@ impl GithubService {
     pub fn github(&self) -> &Github {
         &self.github
     }
+
+    /// Query a REST endpoint.
+    fn rest_query<T>(&self, owner: &str, endpoint: &str) -> Result<T, HostingServiceError>
+    where
+        T: DeserializeOwned,
+    {
+        let value = self
+            .github
+            .get(owner, endpoint)
+            .map_err(HostingServiceError::host)?;
+        serde_json::from_value(value)
+            .map_err(|err| HostingServiceError::host(GithubError::from(err)))
+    }
+
+    /// Query all pages of a REST endpoint.
+    ///
+    /// The `items` function extracts the total count and items from each page.
+    fn rest_query_paged<P, T, F>(
+        &self,
+        owner: &str,
+        endpoint: &str,
+        items: F,
+    ) -> Result<Vec<T>, HostingServiceError>
+    where
+        P: DeserializeOwned,
+        F: Fn(P) -> (usize, Vec<T>),
+    {
+        let sep = if endpoint.contains('?') { '&' } else { '?' };
+        let mut results = Vec::new();
+
+        for page in 1.. {
+            let page_endpoint = format!(
+                "{}{}per_page={}&page={}",
+                endpoint,
+                sep,
+                workflows::PER_PAGE,
+                page,
+            );
+            let (total_count, page_items) = items(self.rest_query(owner, &page_endpoint)?);
+            let is_empty = page_items.is_empty();
+
+            results.extend(page_items);
+
+            if is_empty || results.len() >= total_count {
+                break;
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Query a workflow run.
+    fn workflow_run(
+        &self,
+        project: &str,
+        run: u64,
+    ) -> Result<workflows::WorkflowRun, HostingServiceError> {
+        let (owner, name) = Self::split_project(project)?;
+        let endpoint = format!("repos/{}/{}/actions/runs/{}", owner, name, run);
+        self.rest_query(owner, &endpoint)
+    }
+
+    /// The workflow runs for a commit in a repository.
+    fn runs_for_commit(
+        &self,
+        repo: &Repo,
+        commit: &CommitId,
+    ) -> Result<Vec<Pipeline>, HostingServiceError> {
+        let (owner, name) = Self::split_project(&repo.name)?;
+
+        let endpoint = format!(
+            "repos/{}/{}/actions/runs?head_sha={}",
+            owner,
+            name,
+            commit.as_str(),
+        );
+        let runs = self.rest_query_paged(owner, &endpoint, |runs: workflows::WorkflowRuns| {
+            (runs.total_count, runs.workflow_runs)
+        })?;
+        let latest = runs.iter().map(|run| run.id).max();
+
+        Ok(runs
+            .into_iter()
+            .map(|run| {
+                Pipeline {
+                    state: workflows::pipeline_state(
+                        run.status.as_deref(),
+                        run.conclusion.as_deref(),
+                    ),
+                    commit: Commit {
+                        repo: repo.clone(),
+                        refname: run.head_branch,
+                        id: CommitId::new(run.head_sha),
+                        last_pipeline: latest,
+                    },
+                    id: run.id,
+                }
+            })
+            .collect())
+    }
 }
 
 #[derive(Debug, Error)]
@@ -527,6 +735,8 @@ enum GithubHostError {
     NoIssuesClosedByPrEdges { pull: u64, project: String },
     #[error("no closing issues found on pr {}#{}", project, pull)]
     NoClosingIssues { pull: u64, project: String },
+    #[error("no identifier for the check run on {}@{}", project, commit)]
+    NoCheckRunId { commit: CommitId, project: String },
 }
 
 impl GithubHostError {
@@ -611,6 +821,13 @@ impl GithubHostError {
             project,
         }
     }
+
+    fn no_check_run_id(commit: CommitId, project: String) -> Self {
+        GithubHostError::NoCheckRunId {
+            commit,
+            project,
+        }
+    }
 }
 
 impl From<GithubHostError> for HostingServiceError {
@@ -649,6 +866,10 @@ impl From<GithubServiceError> for HostingServiceError {
 }
 
 impl HostingService for GithubService {
+    fn as_pipeline_service(self: Arc<Self>) -> Option<Arc<dyn HostedPipelineService>> {
+        Some(self as Arc<dyn HostedPipelineService>)
+    }
+
     fn fetch_mr(&self, git: &GitContext, mr: &MergeRequest) -> Result<(), HostingServiceError> {
         git.fetch(&mr.target_repo.url, [&format!("refs/pull/{}/head", mr.id)])
             .map_err(HostingServiceError::fetch)
@@ -771,9 +992,19 @@ impl HostingService for GithubService {
                     head_ref_oid,
                     author,
                     is_draft,
+                    labels,
                 } = pull;
 
                 let target_repo = self.repo(target_repo)?;
+                let labels = labels
+                    .and_then(|labels| labels.names)
+                    .map(|names| {
+                        names
+                            .into_iter()
+                            .filter_map(|label| label.map(|label| label.name))
+                            .collect()
+                    })
+                    .unwrap_or_else(Vec::new);
 
                 Ok(MergeRequest {
                     // TODO(github): Is this `None` if the source repo is also the target repo?
@@ -807,6 +1038,7 @@ impl HostingService for GithubService {
                         .into(),
                     reference: format!("#{}", id),
                     remove_source_branch: false,
+                    labels,
                 })
             })
     }
@@ -1069,16 +1301,26 @@ impl HostingService for GithubService {
     }
 
     fn post_commit_status(&self, status: PendingCommitStatus) -> Result<(), HostingServiceError> {
-        self.post_check_run(status, None)
+        self.post_check_run(status, None, &[])
     }
 
     fn post_review(
         &self,
         status: PendingCommitStatus,
-        _: &MergeRequest,
+        mr: &MergeRequest,
+        description: &str,
+    ) -> Result<(), HostingServiceError> {
+        self.post_check_run(status, Some(description), &[])
+    }
+
+    fn post_review_with_annotations(
+        &self,
+        status: PendingCommitStatus,
+        mr: &MergeRequest,
         description: &str,
+        annotations: &[ReviewAnnotation],
     ) -> Result<(), HostingServiceError> {
-        self.post_check_run(status, Some(description.into()))
+        self.post_check_run(status, Some(description), annotations)
     }
 
     fn get_mr_awards(&self, mr: &MergeRequest) -> Result<Vec<Award>, HostingServiceError> {
@@ -1333,6 +1575,111 @@ impl HostingService for GithubService {
     }
 }
 
+impl HostedPipelineService for GithubService {
+    fn pipelines_for_mr(
+        &self,
+        mr: &MergeRequest,
+    ) -> Result<Option<Vec<Pipeline>>, HostingServiceError> {
+        // Workflows for pull requests run in the target repository. Workflows in forks are not
+        // considered since the application is usually not installed there.
+        self.runs_for_commit(&mr.target_repo, &mr.commit.id).map(Some)
+    }
+
+    fn pipelines_for_commit(
+        &self,
+        commit: &Commit,
+    ) -> Result<Option<Vec<Pipeline>>, HostingServiceError> {
+        self.runs_for_commit(&commit.repo, &commit.id).map(Some)
+    }
+
+    fn pipeline_jobs(
+        &self,
+        pipeline: &Pipeline,
+    ) -> Result<Option<Vec<PipelineJob>>, HostingServiceError> {
+        let project = &pipeline.commit.repo.name;
+        let (owner, name) = Self::split_project(project)?;
+
+        // Jobs do not know the name of their workflow, so get it from the run.
+        let run = self.workflow_run(project, pipeline.id)?;
+
+        let endpoint = format!("repos/{}/{}/actions/runs/{}/jobs", owner, name, pipeline.id);
+        let jobs = self.rest_query_paged(owner, &endpoint, |jobs: workflows::WorkflowJobs| {
+            (jobs.total_count, jobs.jobs)
+        })?;
+
+        Ok(Some(
+            jobs.into_iter()
+                .map(|job| {
+                    PipelineJob {
+                        repo: pipeline.commit.repo.clone(),
+                        state: workflows::pipeline_state(
+                            Some(&job.status),
+                            job.conclusion.as_deref(),
+                        ),
+                        stage: run.name.clone(),
+                        name: job.name,
+                        id: job.id,
+                    }
+                })
+                .collect(),
+        ))
+    }
+
+    fn trigger_job(
+        &self,
+        job: &PipelineJob,
+        _: Option<&str>,
+    ) -> Result<(), HostingServiceError> {
+        // GitHub does not support triggering jobs as another user.
+        let project = &job.repo.name;
+        let (owner, name) = Self::split_project(project)?;
+
+        match job.state {
+            state if state.is_complete() => {
+                let endpoint = format!("repos/{}/{}/actions/jobs/{}/rerun", owner, name, job.id);
+                self.github
+                    .post(owner, &endpoint, &json!({}))
+                    .map_err(HostingServiceError::host)?;
+            },
+            PipelineState::Manual => {
+                // Runs which are waiting for approval (e.g., from first-time contributors to
+                // forks) are started by approving the run.
+                let endpoint = format!("repos/{}/{}/actions/jobs/{}", owner, name, job.id);
+                let job_info: workflows::WorkflowJob = self.rest_query(owner, &endpoint)?;
+                let run = self.workflow_run(project, job_info.run_id)?;
+
+                if run.conclusion.as_deref() == Some("action_required") {
+                    let endpoint = format!(
+                        "repos/{}/{}/actions/runs/{}/approve",
+                        owner, name, run.id,
+                    );
+                    self.github
+                        .post(owner, &endpoint, &json!({}))
+                        .map_err(HostingServiceError::host)?;
+                } else {
+                    warn!(
+                        target: "github",
+                        "job {} in {} is waiting on a deployment review; not triggering",
+                        job.id,
+                        project,
+                    );
+                }
+            },
+            _ => {
+                // The job is already queued or running.
+                info!(
+                    target: "github",
+                    "job {} in {} is already in progress; not triggering",
+                    job.id,
+                    project,
+                );
+            },
+        }
+
+        Ok(())
+    }
+}
+
 impl Debug for GithubService {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("GithubService")
@@ -1343,35 +1690,358 @@ impl Debug for GithubService {
 
 #[cfg(test)]
 mod test {
+    use std::fs;
+    use std::path::Path;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use ghostflow::host::{Commit, MergeRequest, Repo, User};
+    use ghostflow::utils::{http_stub, StubRequest};
+    use git_workarea::CommitId;
+    use serde::Deserialize;
+    use serde_json::Value;
+
+    use super::GithubService;
+    use crate::client::Github;
+
+    /// An interaction recorded in a fixture.
+    #[derive(Debug, Deserialize)]
+    struct Interaction {
+        method: String,
+        endpoint: String,
+        #[serde(default)]
+        request: Option<Value>,
+        #[serde(default = "Interaction::default_status")]
+        status: u16,
+        #[serde(default)]
+        response: Value,
+    }
+
+    impl Interaction {
+        fn default_status() -> u16 {
+            200
+        }
+    }
+
+    /// A service talking to a server which replays the interactions recorded in a fixture.
+    ///
+    /// Fixtures live in the `fixtures` directory of the crate. Each is a JSON array of
+    /// interactions which must be requested in order.
+    struct Fixture {
+        name: String,
+        service: GithubService,
+        expected: Vec<(String, String, Option<Value>)>,
+        requests: mpsc::Receiver<StubRequest>,
+    }
+
+    impl Fixture {
+        fn load(name: &str) -> Self {
+            let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("fixtures")
+                .join(format!("{}.json", name));
+            let contents = fs::read(&path)
+                .unwrap_or_else(|err| panic!("failed to read {}: {:?}", path.display(), err));
+            let interactions: Vec<Interaction> = serde_json::from_slice(&contents)
+                .unwrap_or_else(|err| panic!("failed to parse {}: {:?}", path.display(), err));
+
+            let expected = interactions
+                .iter()
+                .map(|interaction| {
+                    (
+                        interaction.method.clone(),
+                        format!("/{}", interaction.endpoint),
+                        interaction.request.clone(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let mut responses = interactions
+                .into_iter()
+                .map(|interaction| (interaction.status, interaction.response.to_string()));
+            let (endpoint, requests) = http_stub(expected.len(), move |_| {
+                responses.next().expect("more requests than interactions")
+            });
+
+            Self {
+                name: name.into(),
+                service: service(&endpoint),
+                expected,
+                requests,
+            }
+        }
+
+        /// Check that every interaction was requested as recorded.
+        fn finish(self) {
+            for (method, path, body) in self.expected {
+                let request = self
+                    .requests
+                    .recv_timeout(Duration::from_secs(5))
+                    .unwrap_or_else(|_| {
+                        panic!("{}: missing request {} {}", self.name, method, path)
+                    });
+                assert_eq!(request.method, method, "{}: {}", self.name, path);
+                assert_eq!(request.path, path, "{}", self.name);
+                if let Some(body) = body {
+                    assert_eq!(
+                        serde_json::from_slice::<Value>(&request.body).unwrap(),
+                        body,
+                        "{}: {}",
+                        self.name,
+                        path,
+                    );
+                }
+            }
+        }
+    }
+
+    fn service(endpoint: &str) -> GithubService {
+        GithubService {
+            github: Github::new_test(endpoint).unwrap(),
+            user: User {
+                handle: "ghostflow".into(),
+                name: "Ghostflow".into(),
+                email: "ghostflow@example.com".into(),
+            },
+        }
+    }
+
+    fn repo(name: &str) -> Repo {
+        Repo {
+            name: name.into(),
+            url: format!("git@github.com:{}.git", name),
+            forked_from: None,
+        }
+    }
+
+    fn mr() -> MergeRequest {
+        let source_repo = repo("contributor/project");
+
+        MergeRequest {
+            source_repo: Some(source_repo.clone()),
+            source_branch: "topic".into(),
+            target_repo: repo("upstream/project"),
+            target_branch: "main".into(),
+            id: 7,
+            url: "https://github.com/upstream/project/pull/7".into(),
+            work_in_progress: false,
+            description: String::new(),
+            old_commit: None,
+            commit: Commit {
+                repo: source_repo,
+                refname: Some("topic".into()),
+                id: CommitId::new("0123456789abcdef0123456789abcdef01234567"),
+                last_pipeline: None,
+            },
+            author: User {
+                handle: "contributor".into(),
+                name: "Contributor".into(),
+                email: "contributor@example.com".into(),
+            },
+            reference: "#7".into(),
+            remove_source_branch: false,
+            labels: Vec::new(),
+        }
+    }
+
     #[test]
-    fn test_github_trim() {
-        use super::{
-            trim_to_check_run_limit, GITHUB_CHECK_RUN_MESSAGE_LIMIT, GITHUB_OVERFLOW_INDICATOR,
-        };
+    fn test_github_check_run_pages() {
+        use super::{check_run_pages, GITHUB_CHECK_RUN_MESSAGE_LIMIT};
 
         let just_short_enough = format!("{:width$}", 0, width = GITHUB_CHECK_RUN_MESSAGE_LIMIT);
         assert_eq!(just_short_enough.len(), GITHUB_CHECK_RUN_MESSAGE_LIMIT);
-        let long_text = format!("{:width$}", 0, width = GITHUB_CHECK_RUN_MESSAGE_LIMIT + 1);
-        assert!(long_text.len() > GITHUB_CHECK_RUN_MESSAGE_LIMIT);
-        let long_text_trimmed = format!(
-            "{}{:width$}",
-            GITHUB_OVERFLOW_INDICATOR,
-            "", // the `0` will be overflowed out, so fill with blanks.
-            width = GITHUB_CHECK_RUN_MESSAGE_LIMIT - GITHUB_OVERFLOW_INDICATOR.len(),
-        );
-        assert_eq!(long_text_trimmed.len(), GITHUB_CHECK_RUN_MESSAGE_LIMIT);
 
         let cases = [
-            ("", ""),
-            ("short", "short"),
-            (&just_short_enough, &just_short_enough),
-            (&long_text, &long_text_trimmed),
+            ("", 1),
+            ("short", 1),
+            (&just_short_enough, 1),
         ];
 
         for (input, expected) in cases {
-            let actual = trim_to_check_run_limit(input.into());
-            assert!(actual.len() <= GITHUB_CHECK_RUN_MESSAGE_LIMIT);
-            assert_eq!(actual, expected);
+            let actual = check_run_pages(input);
+            assert_eq!(actual.len(), expected);
+            assert_eq!(actual[0], input);
         }
     }
+
+    #[test]
+    fn test_github_check_run_pages_lines() {
+        use super::{check_run_pages, GITHUB_CHECK_RUN_MESSAGE_LIMIT};
+
+        let line = format!("{:width$}\n", 0, width = 99);
+        let text = line.repeat(2 * GITHUB_CHECK_RUN_MESSAGE_LIMIT / line.len());
+
+        let pages = check_run_pages(&text);
+        assert_eq!(pages.len(), 3);
+        for (idx, page) in pages.iter().enumerate() {
+            assert!(page.len() <= GITHUB_CHECK_RUN_MESSAGE_LIMIT);
+            let header = format!("**Part {} of 3**\n\n", idx + 1);
+            assert!(page.starts_with(&header));
+            assert!(page[header.len()..].split_inclusive('\n').all(|l| l == line));
+        }
+        let rejoined = pages
+            .iter()
+            .enumerate()
+            .map(|(idx, page)| &page[format!("**Part {} of 3**\n\n", idx + 1).len()..])
+            .collect::<String>();
+        assert_eq!(rejoined, text);
+    }
+
+    #[test]
+    fn test_github_check_run_pages_long_line() {
+        use super::{check_run_pages, GITHUB_CHECK_RUN_MESSAGE_LIMIT};
+
+        let text = "é".repeat(GITHUB_CHECK_RUN_MESSAGE_LIMIT);
+
+        let pages = check_run_pages(&text);
+        assert_eq!(pages.len(), 3);
+        for page in &pages {
+            assert!(page.len() <= GITHUB_CHECK_RUN_MESSAGE_LIMIT);
+        }
+    }
+
+    #[test]
+    fn test_github_check_run_requests() {
+        use ghostflow::host::{AnnotationLevel, CommitStatusState, ReviewAnnotation};
+
+        use super::{check_run_pages, GITHUB_CHECK_RUN_MESSAGE_LIMIT};
+
+        let (endpoint, requests) = http_stub(4, |request| {
+            if request.method == "POST" {
+                (201, r#"{"id": 7}"#.into())
+            } else {
+                (200, "{}".into())
+            }
+        });
+        let service = service(&endpoint);
+
+        let commit = Commit {
+            repo: Repo {
+                name: "owner/repo".into(),
+                url: "git@example.com:owner/repo.git".into(),
+                forked_from: None,
+            },
+            refname: None,
+            id: CommitId::new("0123456789abcdef0123456789abcdef01234567"),
+            last_pipeline: None,
+        };
+        let status = commit.create_commit_status(CommitStatusState::Failed, "check", "failed");
+        let line = format!("{:width$}\n", 0, width = 99);
+        let text = line.repeat(GITHUB_CHECK_RUN_MESSAGE_LIMIT / line.len() + 1);
+        let annotations = (0..120)
+            .map(|idx| ReviewAnnotation {
+                commit: None,
+                path: format!("file-{}.txt", idx),
+                line: Some(1),
+                level: AnnotationLevel::Failure,
+                message: "bad".into(),
+            })
+            .collect::<Vec<_>>();
+
+        service
+            .post_check_run(status, Some(&text), &annotations)
+            .unwrap();
+
+        let pages = check_run_pages(&text);
+        assert_eq!(pages.len(), 2);
+        let requests = requests.iter().collect::<Vec<_>>();
+        assert_eq!(requests.len(), 4);
+        let bodies = requests
+            .iter()
+            .map(|request| serde_json::from_slice::<Value>(&request.body).unwrap())
+            .collect::<Vec<_>>();
+        let annotation_count = |body: &Value| {
+            body.pointer("/output/annotations")
+                .and_then(Value::as_array)
+                .map(Vec::len)
+        };
+
+        // The first check run carries the first page and the first batch of annotations.
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/repos/owner/repo/check-runs");
+        assert_eq!(bodies[0]["name"], "check");
+        assert_eq!(bodies[0]["conclusion"], "failure");
+        assert_eq!(bodies[0]["output"]["text"], pages[0].as_str());
+        assert_eq!(annotation_count(&bodies[0]), Some(50));
+
+        // The remaining annotations are sent as updates which keep the same text.
+        let updates = [(&requests[1], &bodies[1], 50), (&requests[2], &bodies[2], 20)];
+        for (request, body, count) in updates {
+            assert_eq!(request.method, "PATCH");
+            assert_eq!(request.path, "/repos/owner/repo/check-runs/7");
+            assert_eq!(body["output"]["title"], "check");
+            assert_eq!(body["output"]["text"], pages[0].as_str());
+            assert_eq!(annotation_count(body), Some(count));
+        }
+
+        // The second page is its own check run.
+        assert_eq!(requests[3].method, "POST");
+        assert_eq!(requests[3].path, "/repos/owner/repo/check-runs");
+        assert_eq!(bodies[3]["name"], "check (part 2 of 2)");
+        assert_eq!(bodies[3]["conclusion"], "failure");
+        assert_eq!(bodies[3]["output"]["text"], pages[1].as_str());
+        assert_eq!(annotation_count(&bodies[3]), None);
+    }
+
+    #[test]
+    fn test_github_pipelines() {
+        use ghostflow::host::{HostedPipelineService, PipelineState};
+
+        let fixture = Fixture::load("pipelines");
+        let pipelines = &fixture.service;
+
+        // Runs are looked up in the target repository.
+        let runs = pipelines.pipelines_for_mr(&mr()).unwrap().unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].id, 11);
+        assert_eq!(runs[0].state, PipelineState::Success);
+        assert_eq!(runs[0].commit.repo.name, "upstream/project");
+        assert_eq!(runs[0].commit.refname.as_deref(), Some("topic"));
+        assert_eq!(runs[0].commit.last_pipeline, Some(12));
+        assert_eq!(runs[1].state, PipelineState::InProgress);
+
+        let jobs = pipelines.pipeline_jobs(&runs[0]).unwrap().unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].id, 21);
+        assert_eq!(jobs[0].name, "build");
+        assert_eq!(jobs[0].stage.as_deref(), Some("CI"));
+        assert_eq!(jobs[0].state, PipelineState::Success);
+        assert_eq!(jobs[1].id, 22);
+        assert_eq!(jobs[1].state, PipelineState::Failed);
+
+        // Completed jobs are rerun.
+        pipelines.trigger_job(&jobs[1], Some("maintainer")).unwrap();
+
+        fixture.finish();
+    }
+
+    #[test]
+    fn test_github_trigger_job_approval() {
+        use ghostflow::host::{HostedPipelineService, PipelineJob, PipelineState};
+
+        let fixture = Fixture::load("trigger_job");
+        let pipelines = &fixture.service;
+        let job = |id, state| {
+            PipelineJob {
+                repo: repo("upstream/project"),
+                state,
+                stage: Some("CI".into()),
+                name: "test".into(),
+                id,
+            }
+        };
+
+        // Runs awaiting approval are approved.
+        pipelines
+            .trigger_job(&job(31, PipelineState::Manual), None)
+            .unwrap();
+        // Runs waiting on a deployment review are left alone.
+        pipelines
+            .trigger_job(&job(32, PipelineState::Manual), None)
+            .unwrap();
+        // Jobs which are already running are not touched.
+        pipelines
+            .trigger_job(&job(33, PipelineState::InProgress), None)
+            .unwrap();
+
+        fixture.finish();
+    }
 }
@@ -2,8 +2,10 @@
 mod authorization;
 mod client;
 pub(crate) mod queries;
+mod workflows;
 pub use crate::client::Github;
 pub use crate::client::GithubError;
 pub use crate::queries::RateLimitInfo;
 mod ghostflow;
+pub use crate::ghostflow::GithubService;
 
@@ -0,0 +1,100 @@
+//! REST types for GitHub Actions.
+//!
+//! GitHub Actions is not exposed over GraphQL, so workflow runs and jobs are queried over REST.
+
+use ghostflow::host::PipelineState;
+use log::error;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct WorkflowRun {
+    pub(crate) id: u64,
+    pub(crate) name: Option<String>,
+    pub(crate) head_sha: String,
+    pub(crate) head_branch: Option<String>,
+    pub(crate) status: Option<String>,
+    pub(crate) conclusion: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct WorkflowRuns {
+    pub(crate) total_count: usize,
+    pub(crate) workflow_runs: Vec<WorkflowRun>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct WorkflowJob {
+    pub(crate) id: u64,
+    pub(crate) run_id: u64,
+    pub(crate) name: String,
+    pub(crate) status: String,
+    pub(crate) conclusion: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct WorkflowJobs {
+    pub(crate) total_count: usize,
+    pub(crate) jobs: Vec<WorkflowJob>,
+}
+
+/// The number of items to request per page.
+pub(crate) const PER_PAGE: usize = 100;
+
+/// Compute the state of a workflow run or job.
+pub(crate) fn pipeline_state(status: Option<&str>, conclusion: Option<&str>) -> PipelineState {
+    match (status, conclusion) {
+        (Some("completed"), Some("success")) | (Some("completed"), Some("neutral")) => {
+            PipelineState::Success
+        },
+        (Some("completed"), Some("cancelled"))
+        | (Some("completed"), Some("skipped"))
+        | (Some("completed"), Some("stale")) => PipelineState::Canceled,
+        (Some("completed"), Some("action_required")) | (Some("waiting"), _) => {
+            PipelineState::Manual
+        },
+        (Some("completed"), Some("failure"))
+        | (Some("completed"), Some("timed_out"))
+        | (Some("completed"), Some("startup_failure")) => PipelineState::Failed,
+        (Some("queued"), _)
+        | (Some("in_progress"), _)
+        | (Some("requested"), _)
+        | (Some("pending"), _) => PipelineState::InProgress,
+        (status, conclusion) => {
+            error!(
+                target: "github",
+                "new GitHub workflow state: {:?} ({:?})",
+                status,
+                conclusion,
+            );
+            PipelineState::Failed
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ghostflow::host::PipelineState;
+
+    use crate::workflows::pipeline_state;
+
+    #[test]
+    fn test_pipeline_state() {
+        let cases = [
+            (Some("completed"), Some("success"), PipelineState::Success),
+            (Some("completed"), Some("neutral"), PipelineState::Success),
+            (Some("completed"), Some("cancelled"), PipelineState::Canceled),
+            (Some("completed"), Some("skipped"), PipelineState::Canceled),
+            (Some("completed"), Some("failure"), PipelineState::Failed),
+            (Some("completed"), Some("timed_out"), PipelineState::Failed),
+            (Some("completed"), Some("action_required"), PipelineState::Manual),
+            (Some("waiting"), None, PipelineState::Manual),
+            (Some("queued"), None, PipelineState::InProgress),
+            (Some("in_progress"), None, PipelineState::InProgress),
+            (None, None, PipelineState::Failed),
+        ];
+
+        for (status, conclusion, expected) in cases {
+            assert_eq!(pipeline_state(status, conclusion), expected);
+        }
+    }
+}
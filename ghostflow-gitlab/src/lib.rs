@@ -344,6 +344,7 @@ impl GitlabService {
             author,
             reference,
             remove_source_branch: mr.force_remove_source_branch.unwrap_or(false),
+            labels: mr.labels,
         })
     }
 
@@ -642,6 +643,34 @@ impl HostedPipelineService for GitlabService {
         ))
     }
 
+    fn pipelines_for_commit(
+        &self,
+        commit: &Commit,
+    ) -> Result<Option<Vec<Pipeline>>, HostingServiceError> {
+        let project = self.full_project(commit.repo.name.as_str())?;
+
+        if project.builds_access_level == types::AccessLevel::Disabled {
+            return Ok(None);
+        }
+
+        let endpoint = api::projects::pipelines::Pipelines::builder()
+            .project(commit.repo.name.as_str())
+            .sha(commit.id.as_str())
+            .build()
+            .unwrap();
+        let endpoint = api::paged(endpoint, api::Pagination::All);
+        let pipelines: Vec<types::Pipeline> = self.query(&endpoint)?;
+        let repo = self.repo_from_project(project)?;
+        let latest = pipelines.iter().map(|pipeline| pipeline.id).max();
+
+        Ok(Some(
+            pipelines
+                .into_iter()
+                .map(|pipeline| ghostflow_pipeline(pipeline, repo.clone(), latest))
+                .collect(),
+        ))
+    }
+
     fn pipeline_jobs(
         &self,
         pipeline: &Pipeline,
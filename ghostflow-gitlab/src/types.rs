@@ -125,6 +125,8 @@ pub struct MergeRequest {
     pub pipeline: Option<MergeRequestPipeline>,
     pub force_remove_source_branch: Option<bool>,
     pub author: Author,
+    #[serde(default)]
+    pub labels: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use ghostflow::host::{HostedProject, HostingService};
+use ghostflow_github::{Github, GithubError, GithubService};
+use ghostflow_gitlab::{gitlab, GitlabService};
+use ghostflow_queue_runner::{JobCommand, QueueRunner, QueueRunnerError};
+use serde::Deserialize;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ConfigError {
+    #[error("failed to read configuration file at `{}`: {}", path.display(), source)]
+    ReadFile {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to parse YAML document: {}", source)]
+    YamlParse {
+        #[from]
+        source: serde_yaml::Error,
+    },
+    #[error("the command for `{}` jobs is empty", kind)]
+    EmptyCommand { kind: String },
+    #[error("failed to read private key at `{}`: {}", path.display(), source)]
+    ReadPrivateKey {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("github error: {}", source)]
+    Github {
+        #[from]
+        source: GithubError,
+    },
+    #[error("gitlab error: {}", source)]
+    Gitlab {
+        #[from]
+        source: gitlab::GitlabError,
+    },
+    #[error("queue runner error: {}", source)]
+    QueueRunner {
+        #[from]
+        source: QueueRunnerError,
+    },
+}
+
+impl ConfigError {
+    fn read_file(path: PathBuf, source: io::Error) -> Self {
+        ConfigError::ReadFile {
+            path,
+            source,
+        }
+    }
+
+    fn empty_command(kind: String) -> Self {
+        ConfigError::EmptyCommand {
+            kind,
+        }
+    }
+
+    fn read_private_key(path: PathBuf, source: io::Error) -> Self {
+        ConfigError::ReadPrivateKey {
+            path,
+            source,
+        }
+    }
+}
+
+type ConfigResult<T> = Result<T, ConfigError>;
+
+/// The hosting service for a project.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum HostConfig {
+    Github {
+        host: String,
+        owner: String,
+        app_id: i64,
+        private_key: PathBuf,
+        installation_id: i64,
+    },
+    Gitlab {
+        host: String,
+        token: String,
+    },
+}
+
+/// Where to report job results.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReportConfig {
+    pub host: HostConfig,
+    pub project: String,
+}
+
+/// The command to run for a kind of job.
+#[derive(Debug, Clone, Deserialize)]
+pub struct JobConfig {
+    /// The command and its arguments; the path to the job file is appended.
+    pub command: Vec<String>,
+    /// The number of seconds the command may run.
+    pub timeout: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Config {
+    /// The queue directory written to by the `TestJobs` action.
+    pub queue: PathBuf,
+    /// The number of seconds after which a claimed job is considered abandoned.
+    pub stale_after: Option<i64>,
+    pub status_name: Option<String>,
+    /// The JSON pointer to the kind of a job.
+    pub kind_field: Option<String>,
+    /// The JSON pointer to the merge request ID of a job.
+    pub mr_field: Option<String>,
+    #[serde(default)]
+    pub quiet: bool,
+    pub report: Option<ReportConfig>,
+    pub jobs: BTreeMap<String, JobConfig>,
+}
+
+impl Config {
+    pub fn from_path<P>(path: P) -> ConfigResult<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_path_impl(path.as_ref())
+    }
+
+    fn from_path_impl(path: &Path) -> ConfigResult<Self> {
+        let contents = fs::read(path).map_err(|err| ConfigError::read_file(path.into(), err))?;
+        Ok(serde_yaml::from_slice(&contents)?)
+    }
+
+    /// Create the queue runner described by the configuration.
+    pub fn runner(&self) -> ConfigResult<QueueRunner> {
+        let mut runner = QueueRunner::new(&self.queue)?;
+
+        for (kind, job) in &self.jobs {
+            let (program, args) = job
+                .command
+                .split_first()
+                .ok_or_else(|| ConfigError::empty_command(kind.clone()))?;
+            let mut command = JobCommand::new(program);
+            for arg in args {
+                command.arg(arg);
+            }
+            if let Some(timeout) = job.timeout {
+                command.timeout(Duration::from_secs(timeout));
+            }
+            runner.add_command(kind, command);
+        }
+
+        if let Some(report) = self.report.as_ref() {
+            runner.report_to(HostedProject {
+                name: report.project.clone(),
+                service: connect(&report.host)?,
+            });
+        }
+        if let Some(stale_after) = self.stale_after {
+            runner.stale_after(chrono::Duration::seconds(stale_after));
+        }
+        if let Some(status_name) = self.status_name.as_ref() {
+            runner.status_name(status_name);
+        }
+        if let Some(kind_field) = self.kind_field.as_ref() {
+            runner.kind_pointer(kind_field);
+        }
+        if let Some(mr_field) = self.mr_field.as_ref() {
+            runner.mr_pointer(mr_field);
+        }
+        if self.quiet {
+            runner.quiet();
+        }
+
+        Ok(runner)
+    }
+}
+
+/// Connect to a hosting service.
+fn connect(host: &HostConfig) -> ConfigResult<Arc<dyn HostingService>> {
+    Ok(match host {
+        HostConfig::Github {
+            host,
+            owner,
+            app_id,
+            private_key,
+            installation_id,
+        } => {
+            let key = fs::read(private_key)
+                .map_err(|err| ConfigError::read_private_key(private_key.clone(), err))?;
+            let github = Github::new_app(host, *app_id, key, [(owner.clone(), *installation_id)])?;
+            Arc::new(GithubService::new(github)?)
+        },
+        HostConfig::Gitlab {
+            host,
+            token,
+        } => {
+            let gitlab = gitlab::Gitlab::new(host, token)?;
+            Arc::new(GitlabService::new(gitlab)?)
+        },
+    })
+}
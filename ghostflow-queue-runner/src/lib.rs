@@ -0,0 +1,12 @@
+//! ghostflow-queue-runner
+//!
+//! This library consumes the job files queued by the `TestJobs` action. Jobs are claimed
+//! atomically so that multiple runners may share a queue, run using a command configured for
+//! their kind, and their results are reported back to the merge request they were queued for.
+
+mod runner;
+pub use self::runner::JobCommand;
+pub use self::runner::JobOutcome;
+pub use self::runner::JobReport;
+pub use self::runner::QueueRunner;
+pub use self::runner::QueueRunnerError;
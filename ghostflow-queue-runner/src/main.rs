@@ -0,0 +1,134 @@
+//! ghostflow-queue-runner
+//!
+//! This is a service which runs the job files queued by ghostflow's `TestJobs` action and reports
+//! their results to the associated merge requests.
+
+use std::num::ParseIntError;
+use std::thread;
+use std::time::Duration;
+
+use chrono::Utc;
+use clap::Arg;
+use ghostflow_queue_runner::QueueRunnerError;
+use log::{info, LevelFilter};
+use thiserror::Error;
+
+mod config;
+use config::{Config, ConfigError};
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+enum SetupError {
+    #[error("non-integer poll interval {}: {}", interval, source)]
+    NonIntegerPollInterval {
+        interval: String,
+        #[source]
+        source: ParseIntError,
+    },
+    #[error("configuration error: {}", source)]
+    Config {
+        #[from]
+        source: ConfigError,
+    },
+    #[error("queue runner error: {}", source)]
+    QueueRunner {
+        #[from]
+        source: QueueRunnerError,
+    },
+}
+
+impl SetupError {
+    fn non_integer_poll_interval(interval: String, source: ParseIntError) -> Self {
+        SetupError::NonIntegerPollInterval {
+            interval,
+            source,
+        }
+    }
+}
+
+fn try_main() -> Result<(), SetupError> {
+    let matches = clap::Command::new("ghostflow-queue-runner")
+        .version(clap::crate_version!())
+        .author("Komeil Majidi <komeilkma@gmail.com>")
+        .about("Run test jobs queued by ghostflow")
+        .arg(
+            Arg::new("DEBUG")
+                .short('d')
+                .long("debug")
+                .help("Increase verbosity")
+                .multiple_occurrences(true),
+        )
+        .arg(
+            Arg::new("CONFIG")
+                .short('c')
+                .long("config")
+                .help("Path to the configuration file")
+                .required(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("ONCE")
+                .long("once")
+                .help("Run the jobs in the queue and exit"),
+        )
+        .arg(
+            Arg::new("POLL_INTERVAL")
+                .long("poll-interval")
+                .help("Number of seconds between scans of the queue")
+                .default_value("5")
+                .takes_value(true),
+        )
+        .get_matches();
+
+    let log_level = match matches.occurrences_of("DEBUG") {
+        0 => LevelFilter::Error,
+        1 => LevelFilter::Warn,
+        2 => LevelFilter::Info,
+        3 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+
+    env_logger::Builder::new().filter(None, log_level).init();
+    log::set_max_level(log_level);
+
+    let config = Config::from_path(
+        matches
+            .value_of("CONFIG")
+            .expect("--config is required"),
+    )?;
+    let runner = config.runner()?;
+
+    let interval = matches
+        .value_of("POLL_INTERVAL")
+        .expect("--poll-interval has a default");
+    let interval = interval
+        .parse::<u64>()
+        .map_err(|err| SetupError::non_integer_poll_interval(interval.into(), err))?;
+    let once = matches.is_present("ONCE");
+
+    loop {
+        runner.reclaim_stale(Utc::now())?;
+        for report in runner.run_all()? {
+            info!(
+                target: "ghostflow-queue-runner",
+                "job {} {:?}",
+                report.name,
+                report.outcome,
+            );
+        }
+
+        if once {
+            break;
+        }
+
+        thread::sleep(Duration::from_secs(interval));
+    }
+
+    Ok(())
+}
+
+fn main() {
+    if let Err(err) = try_main() {
+        panic!("{:?}", err);
+    }
+}
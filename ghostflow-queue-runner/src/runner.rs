@@ -0,0 +1,1252 @@
+//! The queue runner.
+//!
+//! The queue directory is laid out as:
+//!
+//!   - `*.json`: jobs waiting to be run (as written by `TestJobs`);
+//!   - `running/`: claimed jobs along with a `.claim` file recording when they were claimed (the
+//!     claim file is written before the job is moved here);
+//!   - `done/`: jobs which succeeded;
+//!   - `failed/`: jobs which failed, timed out, or could not be run;
+//!   - `logs/`: the output of each job's command.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use ghostflow::host::{CommitStatusState, HostedProject, MergeRequest};
+use log::{error, info, warn};
+use serde_json::{json, Value};
+use thiserror::Error;
+use wait_timeout::ChildExt;
+
+/// Errors which may occur when running queued jobs.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum QueueRunnerError {
+    /// Failure to create a queue directory.
+    #[error("failed to create the queue directory {}: {}", path.display(), source)]
+    CreateDirectory {
+        /// The path to the directory.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to list a queue directory.
+    #[error("failed to list the queue directory {}: {}", path.display(), source)]
+    ListDirectory {
+        /// The path to the directory.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to claim a job.
+    #[error("failed to claim the job {}: {}", path.display(), source)]
+    ClaimJob {
+        /// The path to the job.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to move a job file.
+    #[error("failed to move the job {} to {}: {}", path.display(), target.display(), source)]
+    MoveJob {
+        /// The path to the job.
+        path: PathBuf,
+        /// The target path.
+        target: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to create a log file.
+    #[error("failed to create the log file {}: {}", path.display(), source)]
+    CreateLog {
+        /// The path to the log file.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to run a job's command.
+    #[error("failed to run the command for a `{}` job: {}", kind, source)]
+    RunCommand {
+        /// The kind of the job.
+        kind: String,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+}
+
+impl QueueRunnerError {
+    fn create_directory(path: PathBuf, source: io::Error) -> Self {
+        QueueRunnerError::CreateDirectory {
+            path,
+            source,
+        }
+    }
+
+    fn list_directory(path: PathBuf, source: io::Error) -> Self {
+        QueueRunnerError::ListDirectory {
+            path,
+            source,
+        }
+    }
+
+    fn claim_job(path: PathBuf, source: io::Error) -> Self {
+        QueueRunnerError::ClaimJob {
+            path,
+            source,
+        }
+    }
+
+    fn move_job(path: PathBuf, target: PathBuf, source: io::Error) -> Self {
+        QueueRunnerError::MoveJob {
+            path,
+            target,
+            source,
+        }
+    }
+
+    fn create_log(path: PathBuf, source: io::Error) -> Self {
+        QueueRunnerError::CreateLog {
+            path,
+            source,
+        }
+    }
+
+    fn run_command(kind: String, source: io::Error) -> Self {
+        QueueRunnerError::RunCommand {
+            kind,
+            source,
+        }
+    }
+}
+
+type QueueRunnerResult<T> = Result<T, QueueRunnerError>;
+
+const RUNNING_DIR: &str = "running";
+const DONE_DIR: &str = "done";
+const FAILED_DIR: &str = "failed";
+const LOG_DIR: &str = "logs";
+
+/// How long to wait for a killed command to exit.
+const ZOMBIE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A command to run for a kind of job.
+///
+/// The path to the job file is passed as the last argument to the command. The command's output
+/// is written to the job's log file.
+#[derive(Debug, Clone)]
+pub struct JobCommand {
+    /// The program to run.
+    program: PathBuf,
+    /// Arguments to the program.
+    args: Vec<String>,
+    /// How long the command may run.
+    timeout: Option<Duration>,
+}
+
+impl JobCommand {
+    /// Create a new job command.
+    pub fn new<P>(program: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            timeout: None,
+        }
+    }
+
+    /// Add an argument to the command.
+    pub fn arg<A>(&mut self, arg: A) -> &mut Self
+    where
+        A: Into<String>,
+    {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Kill the command if it runs for longer than the timeout.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+/// The outcome of a job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobOutcome {
+    /// The job's command succeeded.
+    Success,
+    /// The job's command failed.
+    Failed {
+        /// The exit code of the command (if it exited normally).
+        code: Option<i32>,
+    },
+    /// The job's command ran past its timeout and was killed.
+    TimedOut,
+    /// No command is configured for the kind of job.
+    UnknownKind,
+    /// The job's command could not be started.
+    CommandError,
+    /// The job file could not be parsed.
+    Invalid,
+}
+
+impl JobOutcome {
+    /// Whether the job succeeded or not.
+    pub fn is_success(self) -> bool {
+        matches!(self, JobOutcome::Success)
+    }
+
+    fn description(self) -> String {
+        match self {
+            JobOutcome::Success => "succeeded".into(),
+            JobOutcome::Failed {
+                code: Some(code),
+            } => format!("failed with exit code {}", code),
+            JobOutcome::Failed {
+                code: None,
+            } => "failed due to a signal".into(),
+            JobOutcome::TimedOut => "timed out".into(),
+            JobOutcome::UnknownKind => "could not be run; its kind is not configured".into(),
+            JobOutcome::CommandError => "could not be run; its command failed to start".into(),
+            JobOutcome::Invalid => "could not be run; it is not valid JSON".into(),
+        }
+    }
+}
+
+/// A report of a job which has been run.
+#[derive(Debug, Clone)]
+pub struct JobReport {
+    /// The name of the job file.
+    pub name: String,
+    /// The kind of the job (if known).
+    pub kind: Option<String>,
+    /// The outcome of the job.
+    pub outcome: JobOutcome,
+    /// The path to the job's log.
+    pub log: PathBuf,
+}
+
+/// A runner for job files queued by the `TestJobs` action.
+pub struct QueueRunner {
+    /// The queue directory.
+    queue: PathBuf,
+    /// The commands to run for each kind of job.
+    commands: BTreeMap<String, JobCommand>,
+    /// The project to report results to.
+    project: Option<HostedProject>,
+    /// The prefix for the names of commit statuses.
+    status_name: String,
+    /// The JSON pointer to the kind of a job.
+    kind_pointer: String,
+    /// The JSON pointer to the merge request ID of a job.
+    mr_pointer: String,
+    /// How long a job may be claimed before it is considered abandoned.
+    stale_after: chrono::Duration,
+    /// The number of log lines to include in comments.
+    log_tail: usize,
+    /// Whether the runner should create informational comments or not.
+    ///
+    /// Failures always create comments.
+    quiet: bool,
+}
+
+impl QueueRunner {
+    /// Create a new runner for a queue directory.
+    pub fn new<Q>(queue: Q) -> QueueRunnerResult<Self>
+    where
+        Q: AsRef<Path>,
+    {
+        Self::new_impl(queue.as_ref())
+    }
+
+    fn new_impl(queue: &Path) -> QueueRunnerResult<Self> {
+        for dir in [RUNNING_DIR, DONE_DIR, FAILED_DIR, LOG_DIR] {
+            let path = queue.join(dir);
+            fs::create_dir_all(&path)
+                .map_err(|err| QueueRunnerError::create_directory(path, err))?;
+        }
+
+        Ok(Self {
+            queue: queue.to_path_buf(),
+            commands: BTreeMap::new(),
+            project: None,
+            status_name: "ghostflow-test".into(),
+            kind_pointer: "/kind".into(),
+            mr_pointer: "/merge_request".into(),
+            stale_after: chrono::Duration::hours(6),
+            log_tail: 20,
+            quiet: false,
+        })
+    }
+
+    /// Set the command to run for a kind of job.
+    pub fn add_command<K>(&mut self, kind: K, command: JobCommand) -> &mut Self
+    where
+        K: Into<String>,
+    {
+        self.commands.insert(kind.into(), command);
+        self
+    }
+
+    /// Report job results to merge requests on a project.
+    ///
+    /// Jobs with a merge request ID get a commit status named `{status_name}-{kind}` and, on
+    /// failure, a comment with the end of the job's log.
+    pub fn report_to(&mut self, project: HostedProject) -> &mut Self {
+        self.project = Some(project);
+        self
+    }
+
+    /// The prefix for commit status names (`ghostflow-test` by default).
+    pub fn status_name<N>(&mut self, status_name: N) -> &mut Self
+    where
+        N: Into<String>,
+    {
+        self.status_name = status_name.into();
+        self
+    }
+
+    /// The JSON pointer to the kind of a job (`/kind` by default).
+    pub fn kind_pointer<P>(&mut self, pointer: P) -> &mut Self
+    where
+        P: Into<String>,
+    {
+        self.kind_pointer = pointer.into();
+        self
+    }
+
+    /// The JSON pointer to the merge request ID of a job (`/merge_request` by default).
+    pub fn mr_pointer<P>(&mut self, pointer: P) -> &mut Self
+    where
+        P: Into<String>,
+    {
+        self.mr_pointer = pointer.into();
+        self
+    }
+
+    /// How long a job may be claimed before another runner may reclaim it.
+    ///
+    /// This should be longer than the longest timeout of any command. Defaults to 6 hours.
+    pub fn stale_after(&mut self, stale_after: chrono::Duration) -> &mut Self {
+        self.stale_after = stale_after;
+        self
+    }
+
+    /// The number of log lines to include in failure comments.
+    pub fn log_tail(&mut self, log_tail: usize) -> &mut Self {
+        self.log_tail = log_tail;
+        self
+    }
+
+    /// Reduce the number of comments made by the runner.
+    pub fn quiet(&mut self) -> &mut Self {
+        self.quiet = true;
+        self
+    }
+
+    /// Return abandoned jobs to the queue.
+    ///
+    /// Jobs claimed for longer than `stale_after` are assumed to belong to a runner which has
+    /// crashed. Returns the number of jobs which were reclaimed. Claims left behind by runners
+    /// which stopped before moving their job are removed once they are stale as well.
+    pub fn reclaim_stale(&self, now: DateTime<Utc>) -> QueueRunnerResult<usize> {
+        let running = self.queue.join(RUNNING_DIR);
+        let is_active = |claimed_at: Option<DateTime<Utc>>| {
+            claimed_at.map_or(false, |claimed_at| claimed_at + self.stale_after > now)
+        };
+        let mut reclaimed = 0;
+
+        for path in job_files(&running)? {
+            let claim_path = claim_path(&path);
+            let claimed_at = claimed_at(&claim_path);
+
+            if is_active(claimed_at) {
+                continue;
+            }
+            if claimed_at.is_none() {
+                // Claims are recorded before jobs are moved, so this job was not claimed by a
+                // runner.
+                warn!(
+                    target: "ghostflow-queue-runner",
+                    "the job {} is running without a claim",
+                    path.display(),
+                );
+            }
+
+            let target = self.queue.join(path.file_name().expect("job files have names"));
+            match fs::rename(&path, &target) {
+                Ok(()) => (),
+                // Another runner reclaimed it first.
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(QueueRunnerError::move_job(path, target, err)),
+            }
+            // The claim is removed after the job is back in the queue so that no other runner may
+            // claim it in the meantime.
+            let _ = fs::remove_file(&claim_path);
+
+            warn!(
+                target: "ghostflow-queue-runner",
+                "reclaimed the abandoned job {}",
+                target.display(),
+            );
+            reclaimed += 1;
+        }
+
+        for claim_path in files_with_extension(&running, "claim")? {
+            // Strip the `.claim` extension to get the job's path.
+            if claim_path.with_extension("").exists() || is_active(claimed_at(&claim_path)) {
+                continue;
+            }
+
+            if fs::remove_file(&claim_path).is_ok() {
+                warn!(
+                    target: "ghostflow-queue-runner",
+                    "removed the abandoned claim {}",
+                    claim_path.display(),
+                );
+            }
+        }
+
+        Ok(reclaimed)
+    }
+
+    /// Claim and run the next job in the queue.
+    ///
+    /// Jobs are run in name order. Returns `None` if the queue is empty.
+    pub fn run_next(&self) -> QueueRunnerResult<Option<JobReport>> {
+        for path in job_files(&self.queue)? {
+            if let Some(claimed) = self.claim(&path)? {
+                return self.run_job(&claimed).map(Some);
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Run jobs until the queue is empty.
+    pub fn run_all(&self) -> QueueRunnerResult<Vec<JobReport>> {
+        let mut reports = Vec::new();
+        while let Some(report) = self.run_next()? {
+            reports.push(report);
+        }
+        Ok(reports)
+    }
+
+    /// Claim a job.
+    ///
+    /// The claim is recorded before the job is moved into `running/` so that it is never
+    /// mistaken for an abandoned job. Only one runner may create the claim file for a job.
+    ///
+    /// Returns `None` if another runner claimed it first.
+    fn claim(&self, path: &Path) -> QueueRunnerResult<Option<PathBuf>> {
+        let target = self
+            .queue
+            .join(RUNNING_DIR)
+            .join(path.file_name().expect("job files have names"));
+        let claim_path = claim_path(&target);
+
+        let mut claim_file = match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&claim_path)
+        {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => return Ok(None),
+            Err(err) => return Err(QueueRunnerError::claim_job(path.into(), err)),
+        };
+        let claim = json!({
+            "claimed_at": Utc::now().to_rfc3339(),
+            "pid": std::process::id(),
+        });
+        if let Err(err) = claim_file.write_all(claim.to_string().as_bytes()) {
+            let _ = fs::remove_file(&claim_path);
+            return Err(QueueRunnerError::claim_job(path.into(), err));
+        }
+
+        match fs::rename(path, &target) {
+            Ok(()) => Ok(Some(target)),
+            Err(err) => {
+                let _ = fs::remove_file(&claim_path);
+
+                if err.kind() == io::ErrorKind::NotFound {
+                    // Another runner claimed and finished the job already.
+                    Ok(None)
+                } else {
+                    Err(QueueRunnerError::claim_job(path.into(), err))
+                }
+            },
+        }
+    }
+
+    /// Run a claimed job.
+    fn run_job(&self, path: &Path) -> QueueRunnerResult<JobReport> {
+        let name = path
+            .file_stem()
+            .expect("job files have names")
+            .to_string_lossy()
+            .into_owned();
+        let log = self.queue.join(LOG_DIR).join(format!("{}.log", name));
+
+        let job = fs::read(path)
+            .ok()
+            .and_then(|contents| serde_json::from_slice::<Value>(&contents).ok());
+        let kind = job
+            .as_ref()
+            .and_then(|job| job.pointer(&self.kind_pointer))
+            .and_then(Value::as_str)
+            .map(String::from);
+        let mr = job
+            .as_ref()
+            .and_then(|job| job.pointer(&self.mr_pointer))
+            .and_then(Value::as_u64)
+            .and_then(|id| self.merge_request(id));
+
+        info!(
+            target: "ghostflow-queue-runner",
+            "running job {} ({})",
+            name,
+            kind.as_deref().unwrap_or("unknown kind"),
+        );
+
+        let outcome = match (job.as_ref(), kind.as_ref()) {
+            (None, _) => JobOutcome::Invalid,
+            (Some(_), Some(kind)) => {
+                if let Some(command) = self.commands.get(kind) {
+                    if let Some(mr) = mr.as_ref() {
+                        self.send_mr_commit_status(
+                            mr,
+                            kind,
+                            CommitStatusState::Running,
+                            &format!("running the {} job", kind),
+                        );
+                    }
+
+                    match self.run_command(kind, command, path, &log) {
+                        Ok(outcome) => outcome,
+                        Err(err) => {
+                            error!(
+                                target: "ghostflow-queue-runner",
+                                "failed to run the command for job {}: {:?}",
+                                name,
+                                err,
+                            );
+
+                            // Leave the error in the log so that it is part of the report.
+                            let _ = fs::write(&log, format!("{}\n", err));
+                            JobOutcome::CommandError
+                        },
+                    }
+                } else {
+                    JobOutcome::UnknownKind
+                }
+            },
+            (Some(_), None) => JobOutcome::UnknownKind,
+        };
+
+        let dir = if outcome.is_success() {
+            DONE_DIR
+        } else {
+            FAILED_DIR
+        };
+        let target = self.queue.join(dir).join(path.file_name().expect("job files have names"));
+        match fs::rename(path, &target) {
+            Ok(()) => (),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                warn!(
+                    target: "ghostflow-queue-runner",
+                    "the job {} was reclaimed while it was running",
+                    name,
+                );
+            },
+            Err(err) => return Err(QueueRunnerError::move_job(path.into(), target, err)),
+        }
+        let _ = fs::remove_file(claim_path(path));
+
+        let report = JobReport {
+            name,
+            kind,
+            outcome,
+            log,
+        };
+
+        if let Some(mr) = mr.as_ref() {
+            self.report(mr, &report);
+        }
+
+        Ok(report)
+    }
+
+    /// Run the command for a job.
+    fn run_command(
+        &self,
+        kind: &str,
+        command: &JobCommand,
+        path: &Path,
+        log: &Path,
+    ) -> QueueRunnerResult<JobOutcome> {
+        let stdout =
+            File::create(log).map_err(|err| QueueRunnerError::create_log(log.into(), err))?;
+        let stderr = stdout
+            .try_clone()
+            .map_err(|err| QueueRunnerError::create_log(log.into(), err))?;
+
+        let mut cmd = Command::new(&command.program);
+        cmd.args(&command.args)
+            .arg(path)
+            .env("GHOSTFLOW_JOB_KIND", kind)
+            .stdin(Stdio::null())
+            .stdout(stdout)
+            .stderr(stderr);
+        // Run the command in its own process group so that any processes it starts may be killed
+        // along with it.
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            cmd.process_group(0);
+        }
+        let mut child = cmd
+            .spawn()
+            .map_err(|err| QueueRunnerError::run_command(kind.into(), err))?;
+
+        let status = if let Some(timeout) = command.timeout {
+            let status = child
+                .wait_timeout(timeout)
+                .map_err(|err| QueueRunnerError::run_command(kind.into(), err))?;
+            if let Some(status) = status {
+                status
+            } else {
+                kill_command(&mut child)
+                    .map_err(|err| QueueRunnerError::run_command(kind.into(), err))?;
+                let timed_out_status = child
+                    .wait_timeout(ZOMBIE_TIMEOUT)
+                    .map_err(|err| QueueRunnerError::run_command(kind.into(), err))?;
+                if timed_out_status.is_none() {
+                    warn!(
+                        target: "ghostflow-queue-runner",
+                        "leaving a zombie '{}' process; it did not respond to kill",
+                        kind,
+                    );
+                }
+
+                return Ok(JobOutcome::TimedOut);
+            }
+        } else {
+            child
+                .wait()
+                .map_err(|err| QueueRunnerError::run_command(kind.into(), err))?
+        };
+
+        Ok(if status.success() {
+            JobOutcome::Success
+        } else {
+            JobOutcome::Failed {
+                code: status.code(),
+            }
+        })
+    }
+
+    /// Look up a merge request on the reporting project.
+    fn merge_request(&self, id: u64) -> Option<MergeRequest> {
+        let project = self.project.as_ref()?;
+        match project.merge_request(id) {
+            Ok(mr) => Some(mr),
+            Err(err) => {
+                error!(
+                    target: "ghostflow-queue-runner",
+                    "failed to fetch mr {} for {}: {:?}",
+                    id,
+                    project.name,
+                    err,
+                );
+
+                None
+            },
+        }
+    }
+
+    /// Report the result of a job to its merge request.
+    fn report(&self, mr: &MergeRequest, report: &JobReport) {
+        let kind = report.kind.as_deref().unwrap_or("unknown");
+        let state = if report.outcome.is_success() {
+            CommitStatusState::Success
+        } else {
+            CommitStatusState::Failed
+        };
+        let desc = format!("the {} job {}", kind, report.outcome.description());
+        self.send_mr_commit_status(mr, kind, state, &desc);
+
+        let mut content = format!("The `{}` job {}.", kind, report.outcome.description());
+        if report.outcome.is_success() {
+            if !self.quiet {
+                self.send_mr_comment(mr, &content);
+            }
+            return;
+        }
+
+        let tail = log_tail(&report.log, self.log_tail);
+        if !tail.is_empty() {
+            content.push_str(&format!(
+                "\n\nThe end of the log:\n\n```\n{}\n```",
+                tail.join("\n"),
+            ));
+        }
+        self.send_mr_comment(mr, &content);
+    }
+
+    /// Set the commit status for a job on a merge request.
+    fn send_mr_commit_status(
+        &self,
+        mr: &MergeRequest,
+        kind: &str,
+        state: CommitStatusState,
+        desc: &str,
+    ) {
+        let project = if let Some(project) = self.project.as_ref() {
+            project
+        } else {
+            return;
+        };
+
+        let name = format!("{}-{}", self.status_name, kind);
+        let status = mr.create_commit_status(state, &name, desc);
+        if let Err(err) = project.service.post_commit_status(status) {
+            warn!(
+                target: "ghostflow-queue-runner",
+                "failed to post a commit status for mr {} on {} for '{}': {:?}",
+                mr.id,
+                mr.commit.id,
+                desc,
+                err,
+            );
+        }
+    }
+
+    /// Send a comment to a merge request.
+    fn send_mr_comment(&self, mr: &MergeRequest, content: &str) {
+        let project = if let Some(project) = self.project.as_ref() {
+            project
+        } else {
+            return;
+        };
+
+        if let Err(err) = project.service.post_mr_comment(mr, content) {
+            error!(
+                target: "ghostflow-queue-runner",
+                "failed to post a comment to merge request: {}, {}: {:?}",
+                project.name,
+                mr.id,
+                err,
+            );
+        }
+    }
+}
+
+/// Kill a command along with the processes it started.
+#[cfg(unix)]
+fn kill_command(child: &mut Child) -> io::Result<()> {
+    // The command leads its own process group; signal the entire group.
+    let group = -(child.id() as libc::pid_t);
+    if unsafe { libc::kill(group, libc::SIGKILL) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Kill a command.
+#[cfg(not(unix))]
+fn kill_command(child: &mut Child) -> io::Result<()> {
+    child.kill()
+}
+
+/// The `*.json` files in a directory, in name order.
+fn job_files(dir: &Path) -> QueueRunnerResult<Vec<PathBuf>> {
+    files_with_extension(dir, "json")
+}
+
+/// The files in a directory with the given extension, in name order.
+fn files_with_extension(dir: &Path, extension: &str) -> QueueRunnerResult<Vec<PathBuf>> {
+    let entries =
+        fs::read_dir(dir).map_err(|err| QueueRunnerError::list_directory(dir.into(), err))?;
+
+    let mut files = entries
+        .filter_map(|entry| match entry {
+            Ok(entry) => Some(entry.path()),
+            Err(err) => {
+                warn!(
+                    target: "ghostflow-queue-runner",
+                    "failed to read an entry in {}: {:?}",
+                    dir.display(),
+                    err,
+                );
+
+                None
+            },
+        })
+        .filter(|path| path.is_file() && path.extension().map_or(false, |ext| ext == extension))
+        .collect::<Vec<_>>();
+    files.sort();
+
+    Ok(files)
+}
+
+/// The path to the claim file for a running job.
+fn claim_path(path: &Path) -> PathBuf {
+    path.with_extension("json.claim")
+}
+
+/// When a job was claimed.
+///
+/// Claim files are written once when the job is claimed, so the modification time of the file is
+/// used if its contents cannot be read.
+fn claimed_at(path: &Path) -> Option<DateTime<Utc>> {
+    read_claim(path).or_else(|| {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .map(DateTime::<Utc>::from)
+    })
+}
+
+/// When a job was claimed, according to its claim file.
+fn read_claim(path: &Path) -> Option<DateTime<Utc>> {
+    let contents = fs::read(path).ok()?;
+    let claim: Value = serde_json::from_slice(&contents).ok()?;
+    let claimed_at = claim.pointer("/claimed_at").and_then(Value::as_str)?;
+
+    DateTime::parse_from_rfc3339(claimed_at)
+        .ok()
+        .map(|claimed_at| claimed_at.with_timezone(&Utc))
+}
+
+/// The last lines of a log file.
+fn log_tail(path: &Path, count: usize) -> Vec<String> {
+    let file = if let Ok(file) = File::open(path) {
+        file
+    } else {
+        return Vec::new();
+    };
+
+    let lines = BufReader::new(file)
+        .lines()
+        .filter_map(Result::ok)
+        .collect::<Vec<_>>();
+    let skip = lines.len().saturating_sub(count);
+
+    lines.into_iter().skip(skip).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use chrono::Utc;
+    use ghostflow::host::{
+        Award, Comment, Commit, CommitStatus, CommitStatusState, HostedProject, HostingService,
+        HostingServiceError, Issue, MergeRequest, PendingCommitStatus, Repo, User,
+    };
+    use git_workarea::CommitId;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use crate::runner::{JobCommand, JobOutcome, QueueRunner};
+
+    /// A hosting service with a single merge request which records what is posted to it.
+    struct RecordingService {
+        user: User,
+        mr: MergeRequest,
+        statuses: Mutex<Vec<(CommitStatusState, String, String)>>,
+        comments: Mutex<Vec<String>>,
+    }
+
+    impl RecordingService {
+        fn new() -> Arc<Self> {
+            let user = User {
+                handle: "ghostflow".into(),
+                name: "Ghostflow".into(),
+                email: "ghostflow@example.com".into(),
+            };
+            let repo = Repo {
+                name: "project".into(),
+                url: "https://example.com/project.git".into(),
+                forked_from: None,
+            };
+            let mr = MergeRequest {
+                source_repo: Some(repo.clone()),
+                source_branch: "topic".into(),
+                target_repo: repo.clone(),
+                target_branch: "main".into(),
+                id: 1,
+                url: "https://example.com/project/merge_requests/1".into(),
+                work_in_progress: false,
+                description: String::new(),
+                old_commit: None,
+                commit: Commit {
+                    repo,
+                    refname: Some("topic".into()),
+                    id: CommitId::new("0123456789abcdef0123456789abcdef01234567"),
+                    last_pipeline: None,
+                },
+                author: user.clone(),
+                reference: "!1".into(),
+                remove_source_branch: false,
+                labels: Vec::new(),
+            };
+
+            Arc::new(Self {
+                user,
+                mr,
+                statuses: Mutex::new(Vec::new()),
+                comments: Mutex::new(Vec::new()),
+            })
+        }
+
+        fn statuses(&self) -> Vec<(CommitStatusState, String, String)> {
+            self.statuses.lock().unwrap().clone()
+        }
+
+        fn comments(&self) -> Vec<String> {
+            self.comments.lock().unwrap().clone()
+        }
+    }
+
+    impl HostingService for RecordingService {
+        fn service_user(&self) -> &User {
+            &self.user
+        }
+
+        fn user(&self, _: &str, _: &str) -> Result<User, HostingServiceError> {
+            Ok(self.user.clone())
+        }
+
+        fn commit(&self, _: &str, commit: &CommitId) -> Result<Commit, HostingServiceError> {
+            Ok(Commit {
+                id: commit.clone(),
+                ..self.mr.commit.clone()
+            })
+        }
+
+        fn merge_request(&self, _: &str, id: u64) -> Result<MergeRequest, HostingServiceError> {
+            assert_eq!(id, self.mr.id);
+            Ok(self.mr.clone())
+        }
+
+        fn repo(&self, _: &str) -> Result<Repo, HostingServiceError> {
+            Ok(self.mr.target_repo.clone())
+        }
+
+        fn get_mr_comments(&self, _: &MergeRequest) -> Result<Vec<Comment>, HostingServiceError> {
+            Ok(Vec::new())
+        }
+
+        fn post_mr_comment(
+            &self,
+            _: &MergeRequest,
+            content: &str,
+        ) -> Result<(), HostingServiceError> {
+            self.comments.lock().unwrap().push(content.into());
+            Ok(())
+        }
+
+        fn get_commit_statuses(
+            &self,
+            _: &Commit,
+        ) -> Result<Vec<CommitStatus>, HostingServiceError> {
+            Ok(Vec::new())
+        }
+
+        fn post_commit_status(
+            &self,
+            status: PendingCommitStatus,
+        ) -> Result<(), HostingServiceError> {
+            self.statuses.lock().unwrap().push((
+                status.state,
+                status.name.into(),
+                status.description.into(),
+            ));
+            Ok(())
+        }
+
+        fn get_mr_awards(&self, _: &MergeRequest) -> Result<Vec<Award>, HostingServiceError> {
+            Ok(Vec::new())
+        }
+
+        fn issues_closed_by_mr(&self, _: &MergeRequest) -> Result<Vec<Issue>, HostingServiceError> {
+            Ok(Vec::new())
+        }
+
+        fn add_issue_labels(&self, _: &Issue, _: &[&str]) -> Result<(), HostingServiceError> {
+            Ok(())
+        }
+    }
+
+    fn queue_job(queue: &Path, name: &str, job: serde_json::Value) {
+        fs::write(queue.join(format!("{}.json", name)), job.to_string()).unwrap();
+    }
+
+    fn shell(script: &str) -> JobCommand {
+        let mut command = JobCommand::new("sh");
+        command.arg("-c").arg(script).arg("job");
+        command
+    }
+
+    #[test]
+    fn test_run_jobs() {
+        let tempdir = TempDir::new().unwrap();
+        let queue = tempdir.path();
+
+        let mut runner = QueueRunner::new(queue).unwrap();
+        runner
+            .add_command("pass", shell("echo \"passing $GHOSTFLOW_JOB_KIND $1\""))
+            .add_command("fail", shell("echo failing >&2; exit 3"));
+
+        queue_job(queue, "1", json!({"kind": "pass"}));
+        queue_job(queue, "2", json!({"kind": "fail"}));
+        queue_job(queue, "3", json!({"kind": "deploy"}));
+        fs::write(queue.join("4.json"), "not json").unwrap();
+
+        let reports = runner.run_all().unwrap();
+        let outcomes = reports
+            .iter()
+            .map(|report| (report.name.as_str(), report.outcome))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outcomes,
+            [
+                ("1", JobOutcome::Success),
+                (
+                    "2",
+                    JobOutcome::Failed {
+                        code: Some(3),
+                    },
+                ),
+                ("3", JobOutcome::UnknownKind),
+                ("4", JobOutcome::Invalid),
+            ],
+        );
+
+        assert!(queue.join("done/1.json").is_file());
+        assert!(queue.join("failed/2.json").is_file());
+        assert!(queue.join("failed/3.json").is_file());
+        assert!(queue.join("failed/4.json").is_file());
+        assert_eq!(fs::read_dir(queue.join("running")).unwrap().count(), 0);
+
+        let log = fs::read_to_string(&reports[0].log).unwrap();
+        assert_eq!(
+            log,
+            format!("passing pass {}\n", queue.join("running/1.json").display()),
+        );
+        assert_eq!(fs::read_to_string(&reports[1].log).unwrap(), "failing\n");
+    }
+
+    #[test]
+    fn test_run_timeout() {
+        let tempdir = TempDir::new().unwrap();
+        let queue = tempdir.path();
+
+        let mut command = shell("sleep 10");
+        command.timeout(Duration::from_millis(100));
+        let mut runner = QueueRunner::new(queue).unwrap();
+        runner.add_command("slow", command);
+
+        queue_job(queue, "slow", json!({"kind": "slow"}));
+
+        let report = runner.run_next().unwrap().unwrap();
+        assert_eq!(report.outcome, JobOutcome::TimedOut);
+        assert!(queue.join("failed/slow.json").is_file());
+        assert!(runner.run_next().unwrap().is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_timeout_kills_children() {
+        let tempdir = TempDir::new().unwrap();
+        let queue = tempdir.path();
+        let marker = queue.join("marker");
+
+        let mut command = shell(&format!("(sleep 1; touch '{}') & wait", marker.display()));
+        command.timeout(Duration::from_millis(100));
+        let mut runner = QueueRunner::new(queue).unwrap();
+        runner.add_command("slow", command);
+
+        queue_job(queue, "slow", json!({"kind": "slow"}));
+
+        let report = runner.run_next().unwrap().unwrap();
+        assert_eq!(report.outcome, JobOutcome::TimedOut);
+
+        // The background process was killed along with the shell.
+        std::thread::sleep(Duration::from_secs(2));
+        assert!(!marker.exists());
+    }
+
+    #[test]
+    fn test_reclaim_stale() {
+        let tempdir = TempDir::new().unwrap();
+        let queue = tempdir.path();
+
+        let mut runner = QueueRunner::new(queue).unwrap();
+        runner.stale_after(chrono::Duration::minutes(10));
+
+        let now = Utc::now();
+        let claim = |name: &str, claimed_at: chrono::DateTime<Utc>| {
+            queue_job(&queue.join("running"), name, json!({"kind": "pass"}));
+            let claim = json!({
+                "claimed_at": claimed_at.to_rfc3339(),
+                "pid": 0,
+            });
+            fs::write(
+                queue.join(format!("running/{}.json.claim", name)),
+                claim.to_string(),
+            )
+            .unwrap();
+        };
+        claim("abandoned", now - chrono::Duration::hours(1));
+        claim("active", now - chrono::Duration::minutes(1));
+        // Claims whose runner stopped before moving the job.
+        claim("orphaned", now - chrono::Duration::hours(1));
+        claim("claiming", now - chrono::Duration::minutes(1));
+        for name in ["orphaned", "claiming"] {
+            fs::rename(
+                queue.join(format!("running/{}.json", name)),
+                queue.join(format!("{}.json", name)),
+            )
+            .unwrap();
+        }
+
+        assert_eq!(runner.reclaim_stale(now).unwrap(), 1);
+        assert!(queue.join("abandoned.json").is_file());
+        assert!(!queue.join("running/abandoned.json.claim").exists());
+        assert!(queue.join("running/active.json").is_file());
+        assert!(queue.join("running/active.json.claim").is_file());
+        assert!(!queue.join("running/orphaned.json.claim").exists());
+        assert!(queue.join("running/claiming.json.claim").is_file());
+    }
+
+    #[test]
+    fn test_claim_existing() {
+        let tempdir = TempDir::new().unwrap();
+        let queue = tempdir.path();
+
+        let mut runner = QueueRunner::new(queue).unwrap();
+        runner.add_command("pass", shell("true"));
+
+        queue_job(queue, "1", json!({"kind": "pass"}));
+        // Another runner is claiming the job.
+        fs::write(queue.join("running/1.json.claim"), "{}").unwrap();
+
+        assert!(runner.run_next().unwrap().is_none());
+        assert!(queue.join("1.json").is_file());
+
+        fs::remove_file(queue.join("running/1.json.claim")).unwrap();
+        let report = runner.run_next().unwrap().unwrap();
+        assert_eq!(report.outcome, JobOutcome::Success);
+        assert_eq!(fs::read_dir(queue.join("running")).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_run_spawn_failure() {
+        let tempdir = TempDir::new().unwrap();
+        let queue = tempdir.path();
+
+        let mut runner = QueueRunner::new(queue).unwrap();
+        runner.add_command("missing", JobCommand::new(queue.join("no-such-program")));
+
+        queue_job(queue, "missing", json!({"kind": "missing"}));
+
+        // The job fails rather than erroring out and leaving the job claimed.
+        let report = runner.run_next().unwrap().unwrap();
+        assert_eq!(report.outcome, JobOutcome::CommandError);
+        assert!(queue.join("failed/missing.json").is_file());
+        assert_eq!(fs::read_dir(queue.join("running")).unwrap().count(), 0);
+        assert!(!fs::read_to_string(&report.log).unwrap().is_empty());
+        assert!(runner.run_next().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_report_to_mr() {
+        let tempdir = TempDir::new().unwrap();
+        let queue = tempdir.path();
+        let service = RecordingService::new();
+
+        let mut runner = QueueRunner::new(queue).unwrap();
+        runner
+            .add_command("pass", shell("echo passing"))
+            .add_command("fail", shell("echo one; echo two; echo three; exit 1"))
+            .add_command("missing", JobCommand::new(queue.join("no-such-program")))
+            .report_to(HostedProject {
+                name: "project".into(),
+                service: service.clone(),
+            })
+            .status_name("queue")
+            .log_tail(2);
+
+        queue_job(queue, "1", json!({"kind": "pass", "merge_request": 1}));
+        queue_job(queue, "2", json!({"kind": "fail", "merge_request": 1}));
+        queue_job(queue, "3", json!({"kind": "missing", "merge_request": 1}));
+        // Jobs without a merge request are not reported.
+        queue_job(queue, "4", json!({"kind": "pass"}));
+
+        let reports = runner.run_all().unwrap();
+        assert_eq!(reports.len(), 4);
+
+        let status = |state, name: &str, desc: &str| -> (CommitStatusState, String, String) {
+            (state, name.into(), desc.into())
+        };
+        assert_eq!(
+            service.statuses(),
+            [
+                status(CommitStatusState::Running, "queue-pass", "running the pass job"),
+                status(CommitStatusState::Success, "queue-pass", "the pass job succeeded"),
+                status(CommitStatusState::Running, "queue-fail", "running the fail job"),
+                status(
+                    CommitStatusState::Failed,
+                    "queue-fail",
+                    "the fail job failed with exit code 1",
+                ),
+                status(
+                    CommitStatusState::Running,
+                    "queue-missing",
+                    "running the missing job",
+                ),
+                status(
+                    CommitStatusState::Failed,
+                    "queue-missing",
+                    "the missing job could not be run; its command failed to start",
+                ),
+            ],
+        );
+
+        let comments = service.comments();
+        assert_eq!(comments.len(), 3);
+        assert_eq!(comments[0], "The `pass` job succeeded.");
+        assert_eq!(
+            comments[1],
+            "The `fail` job failed with exit code 1.\n\n\
+             The end of the log:\n\n\
+             ```\n\
+             two\n\
+             three\n\
+             ```",
+        );
+        assert!(comments[2].starts_with(
+            "The `missing` job could not be run; its command failed to start.\n\n\
+             The end of the log:",
+        ));
+    }
+}
@@ -1,5 +1,6 @@
 pub mod check;
 pub mod clone;
+pub mod commands;
 pub mod dashboard;
 pub mod data;
 pub mod follow;
@@ -14,11 +14,14 @@ use std::sync::Arc;
 use git_checks_core::{CheckResult, GitCheckConfiguration};
 use git_workarea::{CommitId, GitContext};
 use itertools::Itertools;
+use lazy_static::lazy_static;
 use log::{info, warn};
+use regex::Regex;
 use thiserror::Error;
 
 use crate::host::{
-    CommitStatusState, HostingService, HostingServiceError, MergeRequest, PendingCommitStatus,
+    AnnotationLevel, CommitStatusState, HostingService, HostingServiceError, MergeRequest,
+    PendingCommitStatus, ReviewAnnotation,
 };
 use crate::utils::mr::{self, CommitMergeRequestState};
 
@@ -110,6 +113,79 @@ impl Default for PostWhen {
     }
 }
 
+lazy_static! {
+    static ref COMMIT_RE: Regex = Regex::new("^commit (?P<commit>[0-9a-f]{7,40})\\b").unwrap();
+    static ref PATH_RE: Regex = Regex::new("`(?P<path>[^`]+)`").unwrap();
+    static ref LINE_RE: Regex = Regex::new("\\bline (?P<line>[0-9]+)\\b").unwrap();
+}
+
+/// The location within a topic which a check message refers to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageLocation {
+    /// The commit the message is about.
+    pub commit: Option<CommitId>,
+    /// The path the message is about.
+    pub path: Option<String>,
+    /// The line within the path.
+    pub line: Option<u64>,
+}
+
+impl MessageLocation {
+    /// Extract the location from a check message.
+    ///
+    /// Check messages are free-form text. Messages about a commit start with `commit <sha>` and
+    /// the first quoted string in the message is taken as the path.
+    pub fn parse(message: &str) -> Self {
+        let commit = COMMIT_RE
+            .captures(message)
+            .and_then(|captures| captures.name("commit"))
+            .map(|commit| CommitId::new(commit.as_str()));
+        let path = PATH_RE
+            .captures(message)
+            .and_then(|captures| captures.name("path"))
+            .map(|path| path.as_str().into());
+        let line = LINE_RE
+            .captures(message)
+            .and_then(|captures| captures.name("line"))
+            .and_then(|line| line.as_str().parse().ok());
+
+        MessageLocation {
+            commit,
+            path,
+            line,
+        }
+    }
+}
+
+/// Extract an annotation from a check message.
+///
+/// Messages are only annotations if they mention both a commit and a path.
+fn message_annotation(message: &str, level: AnnotationLevel) -> Option<ReviewAnnotation> {
+    let location = MessageLocation::parse(message);
+
+    Some(ReviewAnnotation {
+        commit: Some(location.commit?),
+        path: location.path?,
+        line: location.line,
+        level,
+        message: message.into(),
+    })
+}
+
+/// Extract annotations from the errors and warnings of a check result.
+fn result_annotations(result: &CheckResult) -> Vec<ReviewAnnotation> {
+    let errors = result
+        .errors()
+        .iter()
+        .filter_map(|error| message_annotation(error, AnnotationLevel::Failure));
+    let warnings = result
+        .warnings()
+        .iter()
+        .filter_map(|warning| message_annotation(warning, AnnotationLevel::Warning));
+
+    errors.chain(warnings).collect()
+}
+
 /// Implementation of the `check` action.
 pub struct Check<'a> {
     /// The context to use for checking commits.
@@ -307,8 +383,10 @@ impl<'a> Check<'a> {
             let pass = result.pass();
 
             if self.post_when.should_post(status.state) {
+                let annotations = result_annotations(&result);
                 let comment = self.check_result_comment(result, true)?;
-                self.service.post_review(status, mr, &comment)?;
+                self.service
+                    .post_review_with_annotations(status, mr, &comment, &annotations)?;
             }
 
             if pass {
@@ -391,3 +469,56 @@ impl<'a> Debug for Check<'a> {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use git_workarea::CommitId;
+
+    use crate::actions::check::message_annotation;
+    use crate::host::{AnnotationLevel, ReviewAnnotation};
+
+    #[test]
+    fn test_message_annotation() {
+        let message = "commit 0123456789ab adds trailing whitespace to `src/lib.rs` on line 12.";
+
+        assert_eq!(
+            message_annotation(message, AnnotationLevel::Failure),
+            Some(ReviewAnnotation {
+                commit: Some(CommitId::new("0123456789ab")),
+                path: "src/lib.rs".into(),
+                line: Some(12),
+                level: AnnotationLevel::Failure,
+                message: message.into(),
+            }),
+        );
+    }
+
+    #[test]
+    fn test_message_annotation_no_line() {
+        let message = "commit 0123456789ab adds the `bin/tool` path which is executable.";
+
+        assert_eq!(
+            message_annotation(message, AnnotationLevel::Warning),
+            Some(ReviewAnnotation {
+                commit: Some(CommitId::new("0123456789ab")),
+                path: "bin/tool".into(),
+                line: None,
+                level: AnnotationLevel::Warning,
+                message: message.into(),
+            }),
+        );
+    }
+
+    #[test]
+    fn test_message_annotation_unrelated() {
+        let messages = [
+            "the merge request is marked as a work-in-progress.",
+            "commit 0123456789ab has an invalid summary line.",
+            "the `topic` branch is not allowed.",
+        ];
+
+        for message in messages {
+            assert_eq!(message_annotation(message, AnnotationLevel::Failure), None);
+        }
+    }
+}
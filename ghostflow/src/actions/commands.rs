@@ -0,0 +1,810 @@
+//! The `commands` action.
+//!
+//! This action scans merge request comments for command lines such as `Do: merge` or
+//! `Do: test --stage=build`. Commands are checked against the access level of the comment's
+//! author and then dispatched to a handler which performs the matching action. Help, errors, and
+//! unknown commands are reported back to the merge request as a comment.
+
+use std::collections::btree_map::BTreeMap;
+use std::collections::hash_map::HashMap;
+use std::error::Error;
+use std::fmt::{self, Write as _};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use thiserror::Error;
+
+use crate::host::{Comment, HostingService, HostingServiceError, MergeRequest, User};
+
+/// Errors which may occur when handling commands.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum CommandsError {
+    /// Failure to look up the access level of a user.
+    #[error("failed to look up the access level of {}: {}", user, source)]
+    AccessLevel {
+        /// The user whose access level was requested.
+        user: String,
+        /// The source of the error.
+        #[source]
+        source: Box<dyn Error + Send + Sync>,
+    },
+    /// The hosting service returned an error.
+    #[error("hosting service error: {}", source)]
+    HostingService {
+        /// The source of the error.
+        #[from]
+        source: HostingServiceError,
+    },
+    /// Failure to build a reply comment.
+    #[error("message building write error: {}", source)]
+    BuildComment {
+        /// The source of the error.
+        #[from]
+        source: fmt::Error,
+    },
+}
+
+impl CommandsError {
+    fn access_level(user: String, source: Box<dyn Error + Send + Sync>) -> Self {
+        CommandsError::AccessLevel {
+            user,
+            source,
+        }
+    }
+}
+
+type CommandsResult<T> = Result<T, CommandsError>;
+
+/// Access levels a user may have on a project.
+///
+/// Levels are ordered; a higher level includes the permissions of all lower levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AccessLevel {
+    /// No access to the project.
+    None,
+    /// The user may comment on and contribute to the project.
+    Contributor,
+    /// The user may push to the project.
+    Developer,
+    /// The user may manage the project.
+    Maintainer,
+    /// The user owns the project.
+    Owner,
+}
+
+impl fmt::Display for AccessLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            AccessLevel::None => "none",
+            AccessLevel::Contributor => "contributor",
+            AccessLevel::Developer => "developer",
+            AccessLevel::Maintainer => "maintainer",
+            AccessLevel::Owner => "owner",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// A method of looking up the access level of a user.
+pub trait AccessLevelLookup {
+    /// The access level of a user for a merge request's target project.
+    fn access_level(
+        &self,
+        mr: &MergeRequest,
+        user: &User,
+    ) -> Result<AccessLevel, Box<dyn Error + Send + Sync>>;
+}
+
+/// An access level lookup using a fixed table of users.
+#[derive(Debug, Clone)]
+pub struct StaticAccessLevels {
+    /// The access level of users.
+    levels: HashMap<String, AccessLevel>,
+    /// The access level of users not in the table.
+    default: AccessLevel,
+}
+
+impl StaticAccessLevels {
+    /// Create a new lookup table where unknown users have the given access level.
+    pub fn new(default: AccessLevel) -> Self {
+        Self {
+            levels: HashMap::new(),
+            default,
+        }
+    }
+
+    /// Set the access level of a user.
+    pub fn user<H>(&mut self, handle: H, level: AccessLevel) -> &mut Self
+    where
+        H: Into<String>,
+    {
+        self.levels.insert(handle.into(), level);
+        self
+    }
+}
+
+impl AccessLevelLookup for StaticAccessLevels {
+    fn access_level(
+        &self,
+        _: &MergeRequest,
+        user: &User,
+    ) -> Result<AccessLevel, Box<dyn Error + Send + Sync>> {
+        Ok(self
+            .levels
+            .get(&user.handle)
+            .copied()
+            .unwrap_or(self.default))
+    }
+}
+
+/// The commands which may be requested.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CommandKind {
+    /// Run the checks on the merge request.
+    Check,
+    /// Merge the merge request.
+    Merge,
+    /// Reformat the merge request.
+    Reformat,
+    /// Add the merge request to the stage.
+    Stage,
+    /// Test the merge request.
+    Test,
+    /// Remove the merge request from the stage.
+    Unstage,
+}
+
+/// An argument accepted by a command.
+///
+/// Arguments are given as `--name=value`; a value is always required.
+struct ArgumentSpec {
+    /// The name of the argument.
+    name: &'static str,
+    /// A description of the argument.
+    help: &'static str,
+}
+
+const NO_ARGUMENTS: &[ArgumentSpec] = &[];
+const STAGE_ARGUMENTS: &[ArgumentSpec] = &[ArgumentSpec {
+    name: "priority",
+    help: "integrate the merge request before topics with a lower priority",
+}];
+const TEST_ARGUMENTS: &[ArgumentSpec] = &[ArgumentSpec {
+    name: "stage",
+    help: "only run the tests in the given stage",
+}];
+
+impl CommandKind {
+    const ALL: &'static [Self] = &[
+        CommandKind::Check,
+        CommandKind::Merge,
+        CommandKind::Reformat,
+        CommandKind::Stage,
+        CommandKind::Test,
+        CommandKind::Unstage,
+    ];
+
+    /// The name of the command.
+    pub fn name(self) -> &'static str {
+        match self {
+            CommandKind::Check => "check",
+            CommandKind::Merge => "merge",
+            CommandKind::Reformat => "reformat",
+            CommandKind::Stage => "stage",
+            CommandKind::Test => "test",
+            CommandKind::Unstage => "unstage",
+        }
+    }
+
+    /// Look up a command by its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|kind| kind.name() == name)
+    }
+
+    /// The access level required to use the command by default.
+    pub fn default_access_level(self) -> AccessLevel {
+        match self {
+            CommandKind::Check => AccessLevel::Contributor,
+            CommandKind::Reformat
+            | CommandKind::Stage
+            | CommandKind::Test
+            | CommandKind::Unstage => AccessLevel::Developer,
+            CommandKind::Merge => AccessLevel::Maintainer,
+        }
+    }
+
+    fn help(self) -> &'static str {
+        match self {
+            CommandKind::Check => "run the checks on the merge request",
+            CommandKind::Merge => "merge the merge request",
+            CommandKind::Reformat => "reformat the commits of the merge request",
+            CommandKind::Stage => "add the merge request to the stage",
+            CommandKind::Test => "test the merge request",
+            CommandKind::Unstage => "remove the merge request from the stage",
+        }
+    }
+
+    fn arguments(self) -> &'static [ArgumentSpec] {
+        match self {
+            CommandKind::Stage => STAGE_ARGUMENTS,
+            CommandKind::Test => TEST_ARGUMENTS,
+            _ => NO_ARGUMENTS,
+        }
+    }
+}
+
+impl fmt::Display for CommandKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Arguments given to a command.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Arguments {
+    /// The named arguments and their values.
+    named: BTreeMap<String, String>,
+}
+
+impl Arguments {
+    /// Whether a named argument was given.
+    pub fn is_present(&self, name: &str) -> bool {
+        self.named.contains_key(name)
+    }
+
+    /// The value of a named argument.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.named.get(name).map(String::as_str)
+    }
+}
+
+/// A command requested in a comment.
+#[derive(Debug, Clone)]
+pub struct Command {
+    /// The command to perform.
+    pub kind: CommandKind,
+    /// The arguments to the command.
+    pub arguments: Arguments,
+    /// The user who requested the command.
+    pub author: User,
+    /// The ID of the comment containing the command.
+    pub comment_id: String,
+}
+
+/// Problems with a command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParseError {
+    /// The command is not known.
+    UnknownCommand(String),
+    /// The argument is not accepted by the command.
+    UnknownArgument(CommandKind, String),
+    /// The argument requires a value.
+    MissingValue(CommandKind, String),
+    /// Positional arguments are not supported.
+    UnexpectedPositional(CommandKind, String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::UnknownCommand(name) => write!(f, "unknown command `{}`", name),
+            ParseError::UnknownArgument(kind, arg) => {
+                write!(f, "`{}` does not accept the `--{}` argument", kind, arg)
+            },
+            ParseError::MissingValue(kind, arg) => {
+                write!(f, "the `--{}` argument to `{}` requires a value", arg, kind)
+            },
+            ParseError::UnexpectedPositional(kind, arg) => {
+                write!(f, "unexpected argument `{}` to `{}`", arg, kind)
+            },
+        }
+    }
+}
+
+/// A parsed command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Line {
+    /// A request for help.
+    Help,
+    /// A command.
+    Command(CommandKind, Arguments),
+}
+
+/// The prefix for command lines.
+const COMMAND_PREFIX: &str = "Do:";
+
+/// Extract the command lines from a comment.
+fn command_lines(content: &str) -> impl Iterator<Item = &str> {
+    content.lines().filter_map(|line| {
+        line.trim()
+            .strip_prefix(COMMAND_PREFIX)
+            .map(str::trim)
+            .filter(|command| !command.is_empty())
+    })
+}
+
+/// Parse a single command line.
+fn parse_line(line: &str) -> Result<Line, ParseError> {
+    let mut words = line.split_whitespace();
+    let name = words.next().unwrap_or_default();
+
+    if name == "help" {
+        return Ok(Line::Help);
+    }
+
+    let kind =
+        CommandKind::from_name(name).ok_or_else(|| ParseError::UnknownCommand(name.into()))?;
+    let specs = kind.arguments();
+    let mut arguments = Arguments::default();
+
+    for word in words {
+        let arg = word
+            .strip_prefix("--")
+            .ok_or_else(|| ParseError::UnexpectedPositional(kind, word.into()))?;
+        let (arg_name, value) = arg.split_once('=').unwrap_or((arg, ""));
+
+        if !specs.iter().any(|spec| spec.name == arg_name) {
+            return Err(ParseError::UnknownArgument(kind, arg_name.into()));
+        }
+        if value.is_empty() {
+            return Err(ParseError::MissingValue(kind, arg_name.into()));
+        }
+
+        arguments.named.insert(arg_name.into(), value.into());
+    }
+
+    Ok(Line::Command(kind, arguments))
+}
+
+/// The result of a handled command.
+pub type HandlerResult = Result<(), Box<dyn Error + Send + Sync>>;
+
+/// Performs the actions requested by commands.
+///
+/// Commands which are not supported by the handler are reported as such to the requester.
+pub trait CommandHandler {
+    /// Whether the handler supports a command.
+    fn supports(&self, kind: CommandKind) -> bool;
+
+    /// Run the checks on a merge request.
+    fn check(&mut self, mr: &MergeRequest, command: &Command) -> HandlerResult;
+    /// Merge a merge request.
+    fn merge(&mut self, mr: &MergeRequest, command: &Command) -> HandlerResult;
+    /// Reformat a merge request.
+    fn reformat(&mut self, mr: &MergeRequest, command: &Command) -> HandlerResult;
+    /// Add a merge request to the stage.
+    ///
+    /// The `--priority` argument, if given, sets the integration priority of the merge request.
+    fn stage(&mut self, mr: &MergeRequest, command: &Command) -> HandlerResult;
+    /// Test a merge request.
+    ///
+    /// The `--stage` argument, if given, limits the tests to those in a single stage.
+    fn test(&mut self, mr: &MergeRequest, command: &Command) -> HandlerResult;
+    /// Remove a merge request from the stage.
+    fn unstage(&mut self, mr: &MergeRequest, command: &Command) -> HandlerResult;
+}
+
+/// What happened to a requested command.
+#[derive(Debug)]
+pub enum CommandStatus {
+    /// The command was performed.
+    Completed,
+    /// The author of the command does not have the required access level.
+    Denied {
+        /// The access level of the author.
+        level: AccessLevel,
+        /// The access level required by the command.
+        required: AccessLevel,
+    },
+    /// The handler does not support the command.
+    Unsupported,
+    /// The handler failed to perform the command.
+    Failed {
+        /// The error from the handler.
+        error: Box<dyn Error + Send + Sync>,
+    },
+}
+
+/// The outcome of a requested command.
+#[derive(Debug)]
+pub struct CommandOutcome {
+    /// The command.
+    pub command: Command,
+    /// What happened to the command.
+    pub status: CommandStatus,
+}
+
+/// Implementation of the `commands` action.
+pub struct Commands<L> {
+    /// The service which hosts the project.
+    service: Arc<dyn HostingService>,
+    /// The method used to look up access levels.
+    access: L,
+    /// Access levels required for commands which differ from the default.
+    required: HashMap<CommandKind, AccessLevel>,
+    /// Whether to only post comments on errors.
+    quiet: bool,
+}
+
+impl<L> Commands<L>
+where
+    L: AccessLevelLookup,
+{
+    /// Create a new commands action.
+    pub fn new(service: Arc<dyn HostingService>, access: L) -> Self {
+        Self {
+            service,
+            access,
+            required: HashMap::new(),
+            quiet: false,
+        }
+    }
+
+    /// Require an access level to use a command.
+    pub fn require(&mut self, kind: CommandKind, level: AccessLevel) -> &mut Self {
+        self.required.insert(kind, level);
+        self
+    }
+
+    /// Reduce the number of comments made by the action.
+    ///
+    /// Completed commands are not acknowledged; only help and problems are reported.
+    pub fn quiet(&mut self) -> &mut Self {
+        self.quiet = true;
+        self
+    }
+
+    /// The access level required to use a command.
+    pub fn required_access_level(&self, kind: CommandKind) -> AccessLevel {
+        self.required
+            .get(&kind)
+            .copied()
+            .unwrap_or_else(|| kind.default_access_level())
+    }
+
+    /// Handle the commands in comments on a merge request.
+    ///
+    /// Only comments created after `since`, if given, are considered. System comments and
+    /// comments made by the service user are ignored.
+    pub fn handle_mr<H>(
+        &self,
+        mr: &MergeRequest,
+        since: Option<DateTime<Utc>>,
+        handler: &mut H,
+    ) -> CommandsResult<Vec<CommandOutcome>>
+    where
+        H: CommandHandler,
+    {
+        let service_user = &self.service.service_user().handle;
+        let comments = self.service.get_mr_comments(mr)?;
+
+        let mut outcomes = Vec::new();
+        for comment in comments {
+            if comment.is_system || &comment.author.handle == service_user {
+                continue;
+            }
+
+            if since.map_or(false, |since| comment.created_at <= since) {
+                continue;
+            }
+
+            outcomes.extend(self.handle_comment(mr, &comment, handler)?);
+        }
+
+        Ok(outcomes)
+    }
+
+    /// Handle the commands in a single comment.
+    pub fn handle_comment<H>(
+        &self,
+        mr: &MergeRequest,
+        comment: &Comment,
+        handler: &mut H,
+    ) -> CommandsResult<Vec<CommandOutcome>>
+    where
+        H: CommandHandler,
+    {
+        let mut outcomes = Vec::new();
+        let mut errors = Vec::new();
+        let mut want_help = false;
+
+        let lines = command_lines(&comment.content)
+            .map(parse_line)
+            .collect::<Vec<_>>();
+        if lines.is_empty() {
+            return Ok(outcomes);
+        }
+
+        let mut level = None;
+        for line in lines {
+            let (kind, arguments) = match line {
+                Ok(Line::Help) => {
+                    want_help = true;
+                    continue;
+                },
+                Ok(Line::Command(kind, arguments)) => (kind, arguments),
+                Err(err) => {
+                    errors.push(err.to_string());
+                    want_help = true;
+                    continue;
+                },
+            };
+
+            let command = Command {
+                kind,
+                arguments,
+                author: comment.author.clone(),
+                comment_id: comment.id.clone(),
+            };
+
+            let level = if let Some(level) = level {
+                level
+            } else {
+                let author = &comment.author;
+                let author_level = self
+                    .access
+                    .access_level(mr, author)
+                    .map_err(|err| CommandsError::access_level(author.handle.clone(), err))?;
+                level = Some(author_level);
+                author_level
+            };
+
+            let status = self.dispatch(mr, &command, level, handler);
+            match &status {
+                CommandStatus::Completed => (),
+                CommandStatus::Denied {
+                    level,
+                    required,
+                } => {
+                    errors.push(format!(
+                        "`{}` requires the {} access level; you have {}",
+                        kind, required, level,
+                    ));
+                },
+                CommandStatus::Unsupported => {
+                    errors.push(format!("`{}` is not supported for this project", kind));
+                },
+                CommandStatus::Failed {
+                    error,
+                } => {
+                    errors.push(format!("`{}` failed: {}", kind, error));
+                },
+            }
+
+            outcomes.push(CommandOutcome {
+                command,
+                status,
+            });
+        }
+
+        self.reply(mr, comment, &outcomes, &errors, want_help)?;
+
+        Ok(outcomes)
+    }
+
+    /// Dispatch a command to its handler.
+    fn dispatch<H>(
+        &self,
+        mr: &MergeRequest,
+        command: &Command,
+        level: AccessLevel,
+        handler: &mut H,
+    ) -> CommandStatus
+    where
+        H: CommandHandler,
+    {
+        let required = self.required_access_level(command.kind);
+        if level < required {
+            info!(
+                target: "ghostflow/commands",
+                "denying `{}` on {} for {} ({} < {})",
+                command.kind,
+                mr.url,
+                command.author.handle,
+                level,
+                required,
+            );
+
+            return CommandStatus::Denied {
+                level,
+                required,
+            };
+        }
+
+        if !handler.supports(command.kind) {
+            return CommandStatus::Unsupported;
+        }
+
+        info!(
+            target: "ghostflow/commands",
+            "running `{}` on {} for {}",
+            command.kind,
+            mr.url,
+            command.author.handle,
+        );
+
+        let res = match command.kind {
+            CommandKind::Check => handler.check(mr, command),
+            CommandKind::Merge => handler.merge(mr, command),
+            CommandKind::Reformat => handler.reformat(mr, command),
+            CommandKind::Stage => handler.stage(mr, command),
+            CommandKind::Test => handler.test(mr, command),
+            CommandKind::Unstage => handler.unstage(mr, command),
+        };
+
+        match res {
+            Ok(()) => CommandStatus::Completed,
+            Err(error) => {
+                warn!(
+                    target: "ghostflow/commands",
+                    "failed to run `{}` on {}: {:?}",
+                    command.kind,
+                    mr.url,
+                    error,
+                );
+
+                CommandStatus::Failed {
+                    error,
+                }
+            },
+        }
+    }
+
+    /// The help text for the available commands.
+    fn help_text(&self) -> CommandsResult<String> {
+        let mut help = format!(
+            "Commands are given on lines starting with `{}` in a comment:\n\n",
+            COMMAND_PREFIX,
+        );
+
+        for &kind in CommandKind::ALL {
+            writeln!(
+                help,
+                "  - `{}`: {} (requires {} access)",
+                kind,
+                kind.help(),
+                self.required_access_level(kind),
+            )?;
+            for arg in kind.arguments() {
+                writeln!(help, "    - `--{}=<value>`: {}", arg.name, arg.help)?;
+            }
+        }
+        write!(help, "  - `help`: show this message")?;
+
+        Ok(help)
+    }
+
+    /// Reply to a comment with the results of its commands.
+    fn reply(
+        &self,
+        mr: &MergeRequest,
+        comment: &Comment,
+        outcomes: &[CommandOutcome],
+        errors: &[String],
+        want_help: bool,
+    ) -> CommandsResult<()> {
+        let completed = outcomes
+            .iter()
+            .filter(|outcome| matches!(outcome.status, CommandStatus::Completed))
+            .map(|outcome| format!("`{}`", outcome.command.kind))
+            .collect::<Vec<_>>();
+
+        if errors.is_empty() && !want_help && (self.quiet || completed.is_empty()) {
+            return Ok(());
+        }
+
+        let mut content = format!("@{}:", comment.author.handle);
+        if !completed.is_empty() && !self.quiet {
+            write!(content, "\n\nCompleted: {}.", completed.join(", "))?;
+        }
+        if !errors.is_empty() {
+            write!(content, "\n\nErrors:\n")?;
+            for error in errors {
+                write!(content, "\n  - {}", error)?;
+            }
+        }
+        if want_help {
+            write!(content, "\n\n{}", self.help_text()?)?;
+        }
+
+        self.service.post_mr_comment(mr, &content)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::actions::commands::{
+        command_lines, parse_line, Arguments, CommandKind, Line, ParseError,
+    };
+
+    fn arguments(args: &[(&str, &str)]) -> Arguments {
+        Arguments {
+            named: args
+                .iter()
+                .map(|&(name, value)| (name.into(), value.into()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_command_lines() {
+        let content = "Looks good.\n\
+                       \n\
+                       Do: merge\n  \
+                       Do:   test --stage=build  \n\
+                       Do:\n\
+                       Not a Do: command";
+
+        assert_eq!(
+            command_lines(content).collect::<Vec<_>>(),
+            &["merge", "test --stage=build"],
+        );
+    }
+
+    #[test]
+    fn test_parse_simple() {
+        assert_eq!(
+            parse_line("merge"),
+            Ok(Line::Command(CommandKind::Merge, Arguments::default())),
+        );
+        assert_eq!(
+            parse_line("unstage"),
+            Ok(Line::Command(CommandKind::Unstage, Arguments::default())),
+        );
+        assert_eq!(parse_line("help"), Ok(Line::Help));
+    }
+
+    #[test]
+    fn test_parse_arguments() {
+        assert_eq!(
+            parse_line("test --stage=build"),
+            Ok(Line::Command(
+                CommandKind::Test,
+                arguments(&[("stage", "build")]),
+            )),
+        );
+        assert_eq!(
+            parse_line("stage --priority=10"),
+            Ok(Line::Command(
+                CommandKind::Stage,
+                arguments(&[("priority", "10")]),
+            )),
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse_line("deploy"),
+            Err(ParseError::UnknownCommand("deploy".into())),
+        );
+        assert_eq!(
+            parse_line("merge --force"),
+            Err(ParseError::UnknownArgument(
+                CommandKind::Merge,
+                "force".into(),
+            )),
+        );
+        assert_eq!(
+            parse_line("test --stage"),
+            Err(ParseError::MissingValue(CommandKind::Test, "stage".into())),
+        );
+        assert_eq!(
+            parse_line("test --stage="),
+            Err(ParseError::MissingValue(CommandKind::Test, "stage".into())),
+        );
+        assert_eq!(
+            parse_line("stage now"),
+            Err(ParseError::UnexpectedPositional(
+                CommandKind::Stage,
+                "now".into(),
+            )),
+        );
+    }
+}
@@ -3,13 +3,21 @@
 //! The `dashboard` action.
 //!
 //! This action adds a status to a merge request or commit with a link to the dashboard showing CI
-//! results for the code.
+//! results for the code. Optionally, the state of the status may be synchronized with results
+//! queried from the dashboard.
 
 use std::borrow::Cow;
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::fmt::{self, Debug};
+use std::io;
+use std::process::Command;
 use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
+use log::warn;
+use serde_json::Value;
 use thiserror::Error;
 
 use crate::host::{Commit, CommitStatusState, HostingService, HostingServiceError, MergeRequest};
@@ -26,23 +34,304 @@ pub enum DashboardError {
         #[from]
         source: HostingServiceError,
     },
+    /// Failure to run `curl`.
+    #[error("failed to construct curl command: {}", source)]
+    Command {
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to query the dashboard results.
+    #[error("failed to query dashboard results from {} (status: {:?}): {}", url, status, output)]
+    Query {
+        /// The URL queried.
+        url: String,
+        /// The HTTP status of the response.
+        status: Option<u16>,
+        /// The response or error output.
+        output: String,
+    },
+    /// The dashboard results are not valid JSON.
+    #[error("invalid dashboard results from {}: {}", url, source)]
+    InvalidResults {
+        /// The URL queried.
+        url: String,
+        /// The source of the error.
+        #[source]
+        source: serde_json::Error,
+    },
+    /// The dashboard results do not contain a count.
+    #[error("dashboard results from {} do not have a count at `{}`", url, path)]
+    MissingCount {
+        /// The URL queried.
+        url: String,
+        /// The path to the count.
+        path: String,
+    },
+}
+
+impl DashboardError {
+    fn command(source: io::Error) -> Self {
+        DashboardError::Command {
+            source,
+        }
+    }
+
+    fn query(url: String, status: Option<u16>, output: &[u8]) -> Self {
+        DashboardError::Query {
+            url,
+            status,
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
+
+    fn invalid_results(url: String, source: serde_json::Error) -> Self {
+        DashboardError::InvalidResults {
+            url,
+            source,
+        }
+    }
+
+    fn missing_count(url: String, path: String) -> Self {
+        DashboardError::MissingCount {
+            url,
+            path,
+        }
+    }
 }
 
 type DashboardActionResult<T> = Result<T, DashboardError>;
 
+/// A query for results from a dashboard.
+///
+/// The URL is a template using the same replacements as the dashboard status. The response must be
+/// a JSON document. Counts are read from it using paths of object keys (or array indices)
+/// separated by `.` (e.g., `summary.tests.passed`).
+///
+/// By default, the dashboard is queried once. With `DashboardResults::poll`, it is queried until
+/// the results settle instead.
+#[derive(Debug, Clone)]
+pub struct DashboardResults {
+    /// The URL to query for results.
+    url: TemplateString,
+    /// The path to the number of passing tests.
+    passed: String,
+    /// The path to the number of failing tests.
+    failed: String,
+    /// The path to the number of pending tests.
+    pending: Option<String>,
+    /// How to poll the dashboard for settled results.
+    poll: Option<Polling>,
+}
+
+/// How to poll a dashboard for results.
+#[derive(Debug, Clone, Copy)]
+struct Polling {
+    /// How long to wait between queries.
+    interval: Duration,
+    /// How long to wait for the results to settle.
+    timeout: Duration,
+}
+
+impl DashboardResults {
+    /// Create a new results query.
+    pub fn new<U, P, F>(url: U, passed: P, failed: F) -> Self
+    where
+        U: Into<String>,
+        P: Into<String>,
+        F: Into<String>,
+    {
+        Self {
+            url: TemplateString::new(url.into()),
+            passed: passed.into(),
+            failed: failed.into(),
+            pending: None,
+            poll: None,
+        }
+    }
+
+    /// The path to the number of pending tests.
+    ///
+    /// If there are pending tests, the status is marked as running.
+    pub fn pending<P>(&mut self, pending: P) -> &mut Self
+    where
+        P: Into<String>,
+    {
+        self.pending = Some(pending.into());
+        self
+    }
+
+    /// Poll the dashboard until the results settle.
+    ///
+    /// Results are settled once there are some results and none are pending. The dashboard is
+    /// queried every `interval` until then or until `timeout` has passed; the last results are
+    /// used either way.
+    pub fn poll(&mut self, interval: Duration, timeout: Duration) -> &mut Self {
+        self.poll = Some(Polling {
+            interval,
+            timeout,
+        });
+        self
+    }
+
+    /// Query the dashboard for results.
+    ///
+    /// Returns `None` if the dashboard does not have results (i.e., a 404 response). While
+    /// polling, `on_change` is called with unsettled results whenever they change.
+    fn query<F>(
+        &self,
+        data: &HashMap<&str, Cow<str>>,
+        mut on_change: F,
+    ) -> DashboardActionResult<Option<ResultCounts>>
+    where
+        F: FnMut(&ResultCounts) -> DashboardActionResult<()>,
+    {
+        let url = self.url.replace(data);
+        let poll = if let Some(poll) = self.poll {
+            poll
+        } else {
+            return self.query_url(url);
+        };
+
+        let deadline = Instant::now() + poll.timeout;
+        let mut last = None;
+        loop {
+            let counts = self.query_url(url.clone())?;
+            if counts.map_or(false, |counts| counts.settled()) {
+                return Ok(counts);
+            }
+            if let Some(counts) = counts {
+                if last != Some(counts) {
+                    on_change(&counts)?;
+                    last = Some(counts);
+                }
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(counts);
+            }
+            thread::sleep(poll.interval.min(remaining));
+        }
+    }
+
+    /// Query a URL for results.
+    fn query_url(&self, url: String) -> DashboardActionResult<Option<ResultCounts>> {
+        let curl = Command::new("curl")
+            .arg("--silent")
+            .arg("--show-error")
+            .arg("--location")
+            .arg("--header")
+            .arg("Accept: application/json")
+            .arg("--write-out")
+            .arg("\n%{http_code}")
+            .arg(&url)
+            .output()
+            .map_err(DashboardError::command)?;
+        if !curl.status.success() {
+            return Err(DashboardError::query(url, None, &curl.stderr));
+        }
+
+        // The HTTP status code is written on the last line.
+        let (body, code) = curl
+            .stdout
+            .iter()
+            .rposition(|&byte| byte == b'\n')
+            .map(|idx| (&curl.stdout[..idx], &curl.stdout[idx + 1..]))
+            .unwrap_or((&[][..], &curl.stdout[..]));
+        let status = String::from_utf8_lossy(code).trim().parse::<u16>().ok();
+        match status {
+            Some(404) => return Ok(None),
+            Some(code) if (200..300).contains(&code) => (),
+            status => return Err(DashboardError::query(url, status, body)),
+        }
+
+        let results: Value = serde_json::from_slice(body)
+            .map_err(|err| DashboardError::invalid_results(url.clone(), err))?;
+        let count = |path: &str| {
+            json_path(&results, path)
+                .and_then(Value::as_u64)
+                .ok_or_else(|| DashboardError::missing_count(url.clone(), path.into()))
+        };
+
+        Ok(Some(ResultCounts {
+            passed: count(&self.passed)?,
+            failed: count(&self.failed)?,
+            pending: self.pending.as_deref().map(count).transpose()?,
+        }))
+    }
+}
+
+/// Look up a value in a JSON document using a `.`-separated path.
+fn json_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .filter(|segment| !segment.is_empty())
+        .try_fold(value, |value, segment| {
+            match value {
+                Value::Object(map) => map.get(segment),
+                Value::Array(array) => {
+                    segment
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|idx| array.get(idx))
+                },
+                _ => None,
+            }
+        })
+}
+
+/// Counts of results from a dashboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ResultCounts {
+    /// The number of passing tests.
+    passed: u64,
+    /// The number of failing tests.
+    failed: u64,
+    /// The number of pending tests.
+    pending: Option<u64>,
+}
+
+impl ResultCounts {
+    /// Whether the results are final.
+    fn settled(&self) -> bool {
+        self.pending.unwrap_or(0) == 0 && (self.passed > 0 || self.failed > 0)
+    }
+
+    fn state(&self) -> CommitStatusState {
+        if self.failed > 0 {
+            CommitStatusState::Failed
+        } else if self.pending.unwrap_or(0) > 0 {
+            CommitStatusState::Running
+        } else if self.passed > 0 {
+            CommitStatusState::Success
+        } else {
+            CommitStatusState::Pending
+        }
+    }
+
+    fn summary(&self) -> String {
+        let mut summary = format!("{} passed, {} failed", self.passed, self.failed);
+        if let Some(pending) = self.pending {
+            summary.push_str(&format!(", {} pending", pending));
+        }
+        summary
+    }
+}
+
 /// A `dashboard` action.
 ///
 /// Some projects use an external "dashboard" for collating results from testing. This action posts
-/// a commit status to a merge request or commit containing a link to the external dashboard. The
-/// status is always in a passing state; no attempt is made to try and synchronize the status'
-/// state with that of the dashboard itself.
+/// a commit status to a merge request or commit containing a link to the external dashboard. By
+/// default, the status is always in a passing state; no attempt is made to try and synchronize the
+/// status' state with that of the dashboard itself. If a results query is given with
+/// `Dashboard::live_status`, the dashboard is queried and the status is pending, running, passing,
+/// or failing based on the counts it reports. The counts are appended to the description.
 ///
 /// Each of the `status_name`, `url`, and `description` fields are "templates" which may use
 /// `{field}` references to expand to values within the context of the commit or merge request (see
 /// the relevant methods for the available expansions. Field names which are unknown are ignored
-/// and expand to nothing.
-///
-/// No escaping mechanisms are currently available.
+/// and expand to nothing. Filters, defaults, and escaping are supported as described by
+/// `TemplateString`.
 pub struct Dashboard {
     /// The service which hosts the project.
     service: Arc<dyn HostingService>,
@@ -52,6 +341,8 @@ pub struct Dashboard {
     url: TemplateString,
     /// The description to use for the status.
     description: TemplateString,
+    /// The query for live results from the dashboard.
+    results: Option<DashboardResults>,
 }
 
 impl Dashboard {
@@ -72,9 +363,16 @@ impl Dashboard {
             status_name: TemplateString::new(status_name.into()),
             url: TemplateString::new(url.into()),
             description: TemplateString::new(description.into()),
+            results: None,
         }
     }
 
+    /// Synchronize the status with results from the dashboard.
+    pub fn live_status(&mut self, results: DashboardResults) -> &mut Self {
+        self.results = Some(results);
+        self
+    }
+
     fn status_name(&self, data: &HashMap<&str, Cow<str>>) -> String {
         self.status_name.replace(data)
     }
@@ -87,6 +385,75 @@ impl Dashboard {
         self.description.replace(data)
     }
 
+    /// Post the status using `post`.
+    ///
+    /// When polling for results, a pending status is posted first and updated as the results
+    /// change. If the results cannot be queried, the status is left pending with the error in its
+    /// description.
+    fn post_status<F>(&self, data: &HashMap<&str, Cow<str>>, post: F) -> DashboardActionResult<()>
+    where
+        F: Fn(CommitStatusState, &str) -> Result<(), HostingServiceError>,
+    {
+        let description = self.description(data);
+        let results = if let Some(results) = self.results.as_ref() {
+            results
+        } else {
+            return Ok(post(CommitStatusState::Success, &description)?);
+        };
+
+        if results.poll.is_some() {
+            post(
+                CommitStatusState::Pending,
+                &format!("{} (waiting for results)", description),
+            )?;
+        }
+
+        let posted = Cell::new(None);
+        let on_change = |counts: &ResultCounts| -> DashboardActionResult<()> {
+            posted.set(Some(*counts));
+            Ok(post(
+                counts.state(),
+                &format!("{} ({})", description, counts.summary()),
+            )?)
+        };
+        let (state, description) = match results.query(data, on_change) {
+            Ok(Some(counts)) => {
+                // The last results were already posted while polling.
+                if posted.get() == Some(counts) {
+                    return Ok(());
+                }
+
+                (
+                    counts.state(),
+                    format!("{} ({})", description, counts.summary()),
+                )
+            },
+            Ok(None) => {
+                (
+                    CommitStatusState::Pending,
+                    format!("{} (no results yet)", description),
+                )
+            },
+            Err(err @ DashboardError::HostingService {
+                ..
+            }) => return Err(err),
+            Err(err) => {
+                warn!(
+                    target: "ghostflow/dashboard",
+                    "failed to query dashboard results: {}",
+                    err,
+                );
+
+                (
+                    CommitStatusState::Pending,
+                    format!("{} (failed to query results: {})", description, err),
+                )
+            },
+        };
+
+        Ok(post(state, &description)?)
+    }
+
     /// Post a dashboard status for a commit.
     ///
     /// Available replacements:
@@ -120,14 +487,13 @@ impl Dashboard {
         };
 
         let status_name = self.status_name(&data);
-        let description = self.description(&data);
         let url = self.url(&data);
 
-        let mut status =
-            commit.create_commit_status(CommitStatusState::Success, &status_name, &description);
-        status.target_url = Some(&url);
-
-        Ok(self.service.post_commit_status(status)?)
+        self.post_status(&data, |state, description| {
+            let mut status = commit.create_commit_status(state, &status_name, description);
+            status.target_url = Some(&url);
+            self.service.post_commit_status(status)
+        })
     }
 
     /// Post a dashboard status for a merge request.
@@ -158,14 +524,13 @@ impl Dashboard {
         };
 
         let status_name = self.status_name(&data);
-        let description = self.description(&data);
         let url = self.url(&data);
 
-        let mut status =
-            mr.create_commit_status(CommitStatusState::Success, &status_name, &description);
-        status.target_url = Some(&url);
-
-        Ok(self.service.post_commit_status(status)?)
+        self.post_status(&data, |state, description| {
+            let mut status = mr.create_commit_status(state, &status_name, description);
+            status.target_url = Some(&url);
+            self.service.post_commit_status(status)
+        })
     }
 
     /// Post a dashboard status for a merge request with altered data.
@@ -204,14 +569,13 @@ impl Dashboard {
         };
 
         let status_name = self.status_name(&data);
-        let description = self.description(&data);
         let url = self.url(&data);
 
-        let mut status =
-            mr.create_commit_status(CommitStatusState::Success, &status_name, &description);
-        status.target_url = Some(&url);
-
-        Ok(self.service.post_commit_status(status)?)
+        self.post_status(&data, |state, description| {
+            let mut status = mr.create_commit_status(state, &status_name, description);
+            status.target_url = Some(&url);
+            self.service.post_commit_status(status)
+        })
     }
 }
 
@@ -221,6 +585,7 @@ impl Debug for Dashboard {
             .field("status_name", &self.status_name)
             .field("url", &self.url)
             .field("description", &self.description)
+            .field("results", &self.results)
             .finish()
     }
 }
@@ -3,25 +3,57 @@
 //! The `data` action.
 //!
 //! This action fetches data objects pushed to a repository under `refs/data/` (or another
-//! namespace) and pushes them using `rsync` to multiple destinations.
+//! namespace) and pushes them to multiple destinations. Destinations may be `rsync` targets, local
+//! directories, S3-compatible object stores, or any other implementation of `DataDestination`.
+//!
+//! Data refs are named `refs/data/{algorithm}/{hash}`. The MD5, SHA256, SHA512, SHA3-256,
+//! SHA3-512, BLAKE2B, BLAKE2S, and BLAKE3 algorithms are supported by default and others may be
+//! registered using `Data::add_digest`. Data refs may point to blobs or to trees; objects are
+//! streamed to disk while being hashed so that large objects are never held in memory.
+//!
+//! Optionally, a persistent `DataStore` may be used to keep track of which objects each
+//! destination already has so that only new objects are uploaded.
 
-use std::fs::{self, OpenOptions};
-use std::io::{self, Write};
-use std::os::unix::fs::OpenOptionsExt;
-use std::path::PathBuf;
-use std::process::Command;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
-use digest::Digest;
 use git_workarea::{GitContext, GitError};
 use itertools::Itertools;
 use log::{error, info, warn};
-use md5::Md5;
-use sha2::{Sha256, Sha512};
-use tempfile::TempDir;
+use tempfile::{NamedTempFile, TempDir, TempPath};
 use thiserror::Error;
 
 use crate::host::Repo;
 
+mod algorithm;
+pub use self::algorithm::DataDigest;
+pub use self::algorithm::DataHasher;
+pub use self::algorithm::DigestAlgorithm;
+pub use self::algorithm::WEAK_ALGORITHMS;
+use self::algorithm::{DigestRegistry, HashingWriter};
+
+mod cat_file;
+use self::cat_file::CatFile;
+
+mod destination;
+pub use self::destination::DataDestination;
+pub use self::destination::DataDestinationError;
+pub use self::destination::DataObject;
+pub use self::destination::LocalDirectoryDestination;
+pub use self::destination::RsyncDestination;
+pub use self::destination::S3Destination;
+
+mod store;
+pub use self::store::DataStore;
+pub use self::store::DataStoreError;
+pub use self::store::DataStoreGc;
+
 /// Errors which may occur when handling data refs.
 #[derive(Debug, Error)]
 #[non_exhaustive]
@@ -82,30 +114,6 @@ pub enum DataError {
         #[source]
         source: io::Error,
     },
-    /// Failure to construct a sync command.
-    #[error("failed to construct sync command for {}: {}", command, source)]
-    SyncCommand {
-        /// The name of the command.
-        command: &'static str,
-        /// The source of the error.
-        #[source]
-        source: io::Error,
-    },
-    /// Failure to perform an `rsync` operation.
-    #[error(
-        "failed to rsync data to {} (status: {:?}): {}",
-        destination,
-        status,
-        output
-    )]
-    Rsync {
-        /// The requested destination.
-        destination: String,
-        /// The return code from `rsync`.
-        status: Option<i32>,
-        /// Output from `rsync`.
-        output: String,
-    },
     /// Failure to detect the object type of a ref.
     #[error("failed to get the type of {}: {}", refname, output)]
     ObjectType {
@@ -114,12 +122,21 @@ pub enum DataError {
         /// Output from `git cat-file`.
         output: String,
     },
-    /// Failure to get the contents of a ref.
-    #[error("failed to get the contents of {}: {}", refname, output)]
-    ObjectContents {
+    /// Failure to stream the contents of an object.
+    #[error("failed to stream the contents of {}: {}", refname, source)]
+    StreamObject {
+        /// The object requested.
+        refname: String,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to list the entries of a tree.
+    #[error("failed to list the tree {}: {}", refname, output)]
+    ListTree {
         /// The refname requested.
         refname: String,
-        /// Output from `git cat-file`.
+        /// Output from `git ls-tree`.
         output: String,
     },
     /// A data ref points to an unsupported object type.
@@ -138,6 +155,30 @@ pub enum DataError {
         /// Output from `git update-ref`.
         output: String,
     },
+    /// Failure to list local data refs.
+    #[error("failed to list local data refs under {}: {}", namespace, output)]
+    ListLocalDataRefs {
+        /// The namespace listed.
+        namespace: String,
+        /// Output from `git for-each-ref`.
+        output: String,
+    },
+    /// Failure to read a keep manifest.
+    #[error("failed to read data manifest {}: {}", path.display(), source)]
+    ReadManifest {
+        /// The path to the manifest.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to use the data store.
+    #[error("data store error: {}", source)]
+    DataStore {
+        /// The source of the error.
+        #[from]
+        source: DataStoreError,
+    },
     /// Failure to execute a `git` command.
     #[error("git error: {}", source)]
     Git {
@@ -192,30 +233,22 @@ impl DataError {
         }
     }
 
-    fn sync_command(command: &'static str, source: io::Error) -> Self {
-        DataError::SyncCommand {
-            command,
-            source,
-        }
-    }
-
-    fn rsync(destination: String, status: Option<i32>, output: &[u8]) -> Self {
-        DataError::Rsync {
-            destination,
-            status,
+    fn object_type(refname: String, output: &[u8]) -> Self {
+        DataError::ObjectType {
+            refname,
             output: String::from_utf8_lossy(output).into(),
         }
     }
 
-    fn object_type(refname: String, output: &[u8]) -> Self {
-        DataError::ObjectType {
+    fn stream_object(refname: String, source: io::Error) -> Self {
+        DataError::StreamObject {
             refname,
-            output: String::from_utf8_lossy(output).into(),
+            source,
         }
     }
 
-    fn object_contents(refname: String, output: &[u8]) -> Self {
-        DataError::ObjectContents {
+    fn list_tree(refname: String, output: &[u8]) -> Self {
+        DataError::ListTree {
             refname,
             output: String::from_utf8_lossy(output).into(),
         }
@@ -234,6 +267,20 @@ impl DataError {
             output: String::from_utf8_lossy(output).into(),
         }
     }
+
+    fn list_local_data_refs(namespace: String, output: &[u8]) -> Self {
+        DataError::ListLocalDataRefs {
+            namespace,
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
+
+    fn read_manifest(path: PathBuf, source: io::Error) -> Self {
+        DataError::ReadManifest {
+            path,
+            source,
+        }
+    }
 }
 
 type DataResult<T> = Result<T, DataError>;
@@ -243,23 +290,60 @@ type DataResult<T> = Result<T, DataError>;
 pub struct Data {
     /// The context to use for fetching data refs.
     ctx: GitContext,
-    /// The `rsync` destinations to upload data to.
-    destinations: Vec<String>,
+    /// The destinations to upload data to.
+    destinations: Vec<Box<dyn DataDestination>>,
+    /// The persistent store for data objects.
+    store: Option<Arc<DataStore>>,
+    /// The digest algorithms which may be used.
+    digests: DigestRegistry,
+    /// Digest algorithms which are not accepted for new data.
+    rejected_algorithms: BTreeSet<String>,
     /// The reference namespace to use for data.
     ref_namespace: String,
     /// Whether to keep refs on remotes or not.
     keep_refs: bool,
+    /// The number of times to try storing data in a destination.
+    attempts: usize,
+    /// How long to wait before retrying a destination.
+    ///
+    /// The delay is multiplied by the number of failed attempts.
+    retry_delay: Duration,
+}
+
+/// The result of pushing data to a destination.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DestinationReport {
+    /// The name of the destination.
+    pub destination: String,
+    /// The keys of the objects stored in the destination.
+    pub objects: Vec<String>,
+    /// The keys of the objects skipped because the destination already has them.
+    pub skipped: Vec<String>,
+    /// The number of attempts made.
+    pub attempts: usize,
+    /// The error from the last attempt if the destination failed.
+    pub error: Option<String>,
+}
+
+impl DestinationReport {
+    /// Whether the data was stored in the destination.
+    pub fn success(&self) -> bool {
+        self.error.is_none()
+    }
 }
 
 /// The result of the data action.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DataActionResult {
     /// No data was found in the repository.
     NoData,
     /// No destinations are configured.
     NoDestinations,
-    /// Data was successfully pushed to the destinations.
-    DataPushed,
+    /// Data was pushed to the destinations.
+    ///
+    /// Each destination is reported on individually; a failing destination does not prevent
+    /// pushing to the others.
+    DataPushed(Vec<DestinationReport>),
 }
 
 impl Data {
@@ -268,23 +352,93 @@ impl Data {
         Self {
             ctx,
             destinations: Vec::new(),
+            store: None,
+            digests: DigestRegistry::default(),
+            rejected_algorithms: BTreeSet::new(),
             ref_namespace: "data".into(),
             keep_refs: false,
+            attempts: 3,
+            retry_delay: Duration::from_secs(1),
         }
     }
 
-    /// Add a destination for the data.
+    /// Add an `rsync` destination for the data.
     pub fn add_destination<D>(&mut self, destination: D) -> &mut Self
     where
         D: Into<String>,
     {
-        self.destinations.push(destination.into());
+        self.add_data_destination(RsyncDestination::new(destination))
+    }
+
+    /// Add a destination for the data.
+    pub fn add_data_destination<D>(&mut self, destination: D) -> &mut Self
+    where
+        D: DataDestination + 'static,
+    {
+        self.destinations.push(Box::new(destination));
+        self
+    }
+
+    /// Set how many times to try storing data in each destination.
+    ///
+    /// By default, each destination is tried 3 times.
+    pub fn attempts(&mut self, attempts: usize) -> &mut Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    /// Set the delay before retrying a failed destination.
+    ///
+    /// The delay is multiplied by the number of failed attempts. Defaults to one second.
+    pub fn retry_delay(&mut self, delay: Duration) -> &mut Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Use a persistent store for data objects.
+    ///
+    /// Objects are added to the store as they are fetched and only objects which a destination
+    /// has not already received are uploaded to it.
+    pub fn data_store(&mut self, store: Arc<DataStore>) -> &mut Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Add a digest algorithm for data objects.
+    ///
+    /// Data refs using the given algorithm name will be verified using the digest. Registering a
+    /// name which is already known replaces the existing implementation.
+    pub fn add_digest<N, D>(&mut self, name: N, digest: D) -> &mut Self
+    where
+        N: Into<String>,
+        D: DataDigest + 'static,
+    {
+        self.digests.add(name, Arc::new(digest));
+        self
+    }
+
+    /// Reject new data using the given digest algorithm.
+    pub fn reject_algorithm<N>(&mut self, name: N) -> &mut Self
+    where
+        N: Into<String>,
+    {
+        self.rejected_algorithms.insert(name.into());
+        self
+    }
+
+    /// Reject new data using weak digest algorithms.
+    ///
+    /// See `WEAK_ALGORITHMS` for the list of algorithms which are rejected.
+    pub fn reject_weak_algorithms(&mut self) -> &mut Self {
+        self.rejected_algorithms
+            .extend(WEAK_ALGORITHMS.iter().map(|&name| name.into()));
         self
     }
 
     /// Preserve data refs found on remote servers.
     ///
-    /// By default, data which has been successfully fetched will be deleted from the given remote.
+    /// By default, data which has been successfully mirrored to every destination will be deleted
+    /// from the given remote.
     pub fn keep_refs(&mut self) -> &mut Self {
         self.keep_refs = true;
         self
@@ -359,36 +513,19 @@ impl Data {
             return Ok(DataActionResult::NoDestinations);
         }
 
-        if !self.keep_refs {
-            // Delete the refs from the remote server.
-            let delete_refs = self
-                .ctx
-                .git()
-                .arg("push")
-                .arg("--atomic")
-                .arg("--porcelain")
-                .arg("--delete")
-                .arg(&repo.url)
-                .args(&data_refs)
-                .output()
-                .map_err(|err| GitError::subcommand("push", err))?;
-            if !delete_refs.status.success() {
-                return Err(DataError::delete_remote_ref(
-                    data_refs,
-                    repo.url.clone(),
-                    &delete_refs.stderr,
-                ));
-            }
-        }
-
         // Create a temporary directory to store data objects to push to the destinations.
         let tempdir = TempDir::new_in(self.ctx.gitdir())
             .map_err(|err| DataError::create_temp_directory(self.ctx.gitdir().into(), err))?;
 
+        // Stream objects out of the repository rather than reading them into memory.
+        let mut cat_file = CatFile::new(&self.ctx)
+            .map_err(|err| GitError::subcommand("cat-file --batch", err))?;
+
         // Compute the number of path parts in the ref namespace.
         let namespace_parts = 1 + self.ref_namespace.chars().filter(|&ch| ch == '/').count();
         let mut valid_refs = Vec::new();
-        for data_ref in data_refs {
+        let mut objects = Vec::new();
+        for &data_ref in &data_refs {
             // Extract the algorithm and hash parts from the refname.
             let ref_parts = data_ref
                 .splitn(3 + namespace_parts, '/')
@@ -408,146 +545,478 @@ impl Data {
                 self.delete_ref(data_ref)?;
                 continue;
             };
-            let (contents, hash) = match digest_str {
-                "MD5" => self.hash_blob::<Md5>(data_ref)?,
-                "SHA256" => self.hash_blob::<Sha256>(data_ref)?,
-                "SHA512" => self.hash_blob::<Sha512>(data_ref)?,
-                _ => {
-                    error!(
-                        target: "ghostflow/data",
-                        "unsupported digest algorithm {}; ignoring",
+            if self.rejected_algorithms.contains(digest_str) {
+                warn!(
+                    target: "ghostflow/data",
+                    "rejecting {} because the {} algorithm is not accepted",
+                    data_ref,
+                    digest_str,
+                );
+
+                self.lenient_delete_ref(data_ref);
+                continue;
+            }
+            let digest = if let Some(digest) = self.digests.get(digest_str) {
+                digest
+            } else {
+                error!(
+                    target: "ghostflow/data",
+                    "unsupported digest algorithm {}; ignoring",
+                    digest_str,
+                );
+                continue;
+            };
+            let header = cat_file
+                .header(data_ref)
+                .map_err(|err| DataError::stream_object(data_ref.into(), err))?
+                .ok_or_else(|| DataError::object_type(data_ref.into(), b"object is missing"))?;
+            let verified = match header.type_.as_str() {
+                "blob" => {
+                    let (partial, hash) =
+                        self.stream_object(&mut cat_file, data_ref, digest, tempdir.path())?;
+
+                    if expected_hash == hash {
+                        let object = DataObject {
+                            algorithm: digest_str.into(),
+                            hash,
+                        };
+                        Some(vec![(partial, object)])
+                    } else {
+                        warn!(
+                            target: "ghostflow/data",
+                            "failed to verify {} hash; expected {}, actually {}",
+                            data_ref,
+                            expected_hash,
+                            hash,
+                        );
+
+                        None
+                    }
+                },
+                "tree" => {
+                    cat_file
+                        .skip()
+                        .map_err(|err| DataError::stream_object(data_ref.into(), err))?;
+                    self.stream_tree(
+                        &mut cat_file,
+                        data_ref,
                         digest_str,
-                    );
-                    continue;
+                        expected_hash,
+                        digest,
+                        tempdir.path(),
+                    )?
+                },
+                type_ => {
+                    // Other object types are not supported.
+                    return Err(DataError::unsupported_object_type(
+                        data_ref.into(),
+                        type_.into(),
+                    ));
                 },
             };
-            let hash_matches = expected_hash == hash;
-
-            if hash_matches {
-                let output_dir = tempdir.path().join(digest_str);
-                fs::create_dir_all(&output_dir)
-                    .map_err(|err| DataError::create_directory(output_dir.clone(), err))?;
-
-                let output_path = output_dir.join(&hash);
-
-                // Write to the data file.
-                {
-                    let mut output_file = OpenOptions::new()
-                        .mode(0o444)
-                        .write(true)
-                        .create_new(true)
-                        .open(&output_path)
-                        .map_err(|err| DataError::create_file(output_path.clone(), err))?;
-
-                    output_file
-                        .write_all(&contents)
-                        .map_err(|err| DataError::write_file(output_path.clone(), err))?;
+
+            if let Some(verified) = verified {
+                for (partial, object) in verified {
+                    self.place_object(partial, tempdir.path(), &object)?;
+                    if !objects.contains(&object) {
+                        objects.push(object);
+                    }
                 }
 
                 valid_refs.push(data_ref);
+            } else {
+                self.lenient_delete_ref(data_ref);
+            }
+        }
+
+        let reports = self
+            .destinations
+            .iter()
+            .map(|destination| self.push(destination.as_ref(), tempdir.path(), &objects))
+            .collect::<DataResult<Vec<_>>>()?;
+        if let Some(store) = self.store.as_ref() {
+            store.save()?;
+        }
+
+        if !self.keep_refs {
+            // Only remove the data once every destination has it; otherwise the refs are left
+            // on the remote so that the next run fetches and mirrors them again.
+            if reports.iter().all(DestinationReport::success) {
+                self.delete_remote_refs(repo, &data_refs)?;
+                valid_refs
+                    .into_iter()
+                    .for_each(|refname| self.lenient_delete_ref(refname));
             } else {
                 warn!(
                     target: "ghostflow/data",
-                    "failed to verify {} hash; expected {}, actually {}",
-                    data_ref,
-                    expected_hash,
-                    hash,
+                    "keeping data refs in {} because some destinations failed",
+                    repo.url,
                 );
-
-                self.lenient_delete_ref(data_ref);
             }
         }
 
-        let mut source = tempdir.path().as_os_str().to_os_string();
-        // We want to sync the contents of this directory, so add the trailing slash.
-        source.push("/");
-        self.destinations
-            .iter()
-            .map(|destination| {
-                // Push the data to the remote server.
-                let rsync = Command::new("rsync")
-                    .arg("--recursive")
-                    .arg("--perms")
-                    .arg("--times")
-                    .arg("--verbose")
-                    .arg(&source)
-                    .arg(destination)
-                    .output()
-                    .map_err(|err| DataError::sync_command("rsync", err))?;
-                if !rsync.status.success() {
-                    return Err(DataError::rsync(
-                        destination.into(),
-                        rsync.status.code(),
-                        &rsync.stderr,
-                    ));
-                }
+        Ok(DataActionResult::DataPushed(reports))
+    }
 
-                Ok(())
-            })
-            .collect::<Vec<DataResult<_>>>()
+    /// Delete data refs from a remote repository.
+    fn delete_remote_refs(&self, repo: &Repo, data_refs: &[&str]) -> DataResult<()> {
+        let delete_refs = self
+            .ctx
+            .git()
+            .arg("push")
+            .arg("--atomic")
+            .arg("--porcelain")
+            .arg("--delete")
+            .arg(&repo.url)
+            .args(data_refs)
+            .output()
+            .map_err(|err| GitError::subcommand("push", err))?;
+        if !delete_refs.status.success() {
+            return Err(DataError::delete_remote_ref(
+                data_refs.to_vec(),
+                repo.url.clone(),
+                &delete_refs.stderr,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Push objects to a destination, skipping those it already has.
+    fn push(
+        &self,
+        destination: &dyn DataDestination,
+        root: &Path,
+        objects: &[DataObject],
+    ) -> DataResult<DestinationReport> {
+        let store = if let Some(store) = self.store.as_ref() {
+            store
+        } else {
+            return Ok(self.store(destination, root, objects));
+        };
+
+        let name = destination.name();
+        let pending = store
+            .pending(&name, objects)
             .into_iter()
-            .collect::<DataResult<Vec<_>>>()?;
+            .cloned()
+            .collect::<Vec<_>>();
+        let skipped = objects
+            .iter()
+            .filter(|object| !pending.contains(object))
+            .map(DataObject::key)
+            .collect();
 
-        if !self.keep_refs {
-            valid_refs
-                .into_iter()
-                .for_each(|refname| self.lenient_delete_ref(refname));
+        if pending.is_empty() {
+            return Ok(DestinationReport {
+                destination: name,
+                objects: Vec::new(),
+                skipped,
+                attempts: 0,
+                error: None,
+            });
         }
 
-        Ok(DataActionResult::DataPushed)
+        // Only offer the pending objects to the destination.
+        let staging = TempDir::new_in(self.ctx.gitdir())
+            .map_err(|err| DataError::create_temp_directory(self.ctx.gitdir().into(), err))?;
+        store.export(staging.path(), &pending)?;
+
+        let mut report = self.store(destination, staging.path(), &pending);
+        if report.success() {
+            store.mark_uploaded(&name, &pending);
+        }
+        report.skipped = skipped;
+
+        Ok(report)
+    }
+
+    /// Store objects in a destination, retrying on failure.
+    fn store(
+        &self,
+        destination: &dyn DataDestination,
+        root: &Path,
+        objects: &[DataObject],
+    ) -> DestinationReport {
+        let name = destination.name();
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+
+            match destination.store(root, objects) {
+                Ok(()) => {
+                    return DestinationReport {
+                        destination: name,
+                        objects: objects.iter().map(DataObject::key).collect(),
+                        skipped: Vec::new(),
+                        attempts: attempt,
+                        error: None,
+                    };
+                },
+                Err(err) => {
+                    warn!(
+                        target: "ghostflow/data",
+                        "failed to store data in {} (attempt {} of {}): {:?}",
+                        name,
+                        attempt,
+                        self.attempts,
+                        err,
+                    );
+
+                    if attempt >= self.attempts {
+                        return DestinationReport {
+                            destination: name,
+                            objects: Vec::new(),
+                            skipped: Vec::new(),
+                            attempts: attempt,
+                            error: Some(err.to_string()),
+                        };
+                    }
+
+                    thread::sleep(self.retry_delay * attempt as u32);
+                },
+            }
+        }
     }
 
-    /// Hash a git object using a digest.
-    fn hash_blob<D>(&self, refname: &str) -> DataResult<(Vec<u8>, String)>
+    /// Drop objects from the data store which are no longer referenced.
+    ///
+    /// Objects are kept if a local data ref names them or if they are listed in one of the given
+    /// manifests. Manifests contain one `{algorithm}/{hash}` key per line; blank lines and lines
+    /// starting with `#` are ignored.
+    ///
+    /// Returns `None` if no data store is in use.
+    pub fn gc<P>(&self, manifests: &[P]) -> DataResult<Option<DataStoreGc>>
     where
-        D: Digest,
-        digest::Output<D>: std::fmt::LowerHex,
+        P: AsRef<Path>,
     {
-        let contents = self.blob_contents(refname)?;
+        let store = if let Some(store) = self.store.as_ref() {
+            store
+        } else {
+            return Ok(None);
+        };
+
+        let data_ref_ns = format!("refs/{}/", self.ref_namespace);
+        let for_each_ref = self
+            .ctx
+            .git()
+            .arg("for-each-ref")
+            .arg("--format=%(refname)")
+            .arg(&data_ref_ns)
+            .output()
+            .map_err(|err| GitError::subcommand("for-each-ref", err))?;
+        if !for_each_ref.status.success() {
+            return Err(DataError::list_local_data_refs(
+                data_ref_ns,
+                &for_each_ref.stderr,
+            ));
+        }
+
+        let mut live = String::from_utf8_lossy(&for_each_ref.stdout)
+            .lines()
+            .filter_map(|refname| refname.strip_prefix(&data_ref_ns))
+            .map(Into::into)
+            .collect::<BTreeSet<String>>();
 
-        // Compute the hash of the contents.
-        let mut digest = D::new();
-        digest.update(&contents);
-        Ok((contents, format!("{:x}", digest.finalize())))
+        for manifest in manifests {
+            let path = manifest.as_ref();
+            let contents = fs::read_to_string(path)
+                .map_err(|err| DataError::read_manifest(path.into(), err))?;
+            live.extend(
+                contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(Into::into),
+            );
+        }
+
+        let gc = store.gc(&live)?;
+
+        info!(
+            target: "ghostflow/data",
+            "dropped {} data objects ({} blobs) from {}",
+            gc.dropped_keys.len(),
+            gc.removed_blobs,
+            store.root().display(),
+        );
+
+        Ok(Some(gc))
     }
 
-    fn blob_contents(&self, refname: &str) -> DataResult<Vec<u8>> {
-        // Get the type of the object.
-        let cat_file = self
+    /// Stream an object into a temporary file, computing its hash.
+    fn stream_object(
+        &self,
+        cat_file: &mut CatFile,
+        refname: &str,
+        digest: &dyn DataDigest,
+        dir: &Path,
+    ) -> DataResult<(TempPath, String)> {
+        let file =
+            NamedTempFile::new_in(dir).map_err(|err| DataError::create_file(dir.into(), err))?;
+        let mut writer = HashingWriter::new(digest, BufWriter::new(file));
+        cat_file
+            .copy_to(&mut writer)
+            .map_err(|err| DataError::stream_object(refname.into(), err))?;
+
+        let (hash, file) = writer.finish();
+        let file = file
+            .into_inner()
+            .map_err(|err| DataError::write_file(dir.into(), err.into_error()))?;
+
+        Ok((file.into_temp_path(), hash))
+    }
+
+    /// Stream the entries of a tree into temporary files.
+    ///
+    /// Each blob in the tree is hashed individually. The tree is verified by hashing a listing of
+    /// its entries in the same format as the `sha256sum` family of tools: one `{hash}  {path}`
+    /// line per blob, sorted by path. Paths are hashed as their raw bytes. The listing is stored as
+    /// an object named by the hash in the ref so that the tree may be reconstructed from the
+    /// destination.
+    ///
+    /// Returns `None` if the tree contains anything other than blobs or does not match the
+    /// expected hash.
+    fn stream_tree(
+        &self,
+        cat_file: &mut CatFile,
+        refname: &str,
+        algorithm: &str,
+        expected_hash: &str,
+        digest: &dyn DataDigest,
+        dir: &Path,
+    ) -> DataResult<Option<Vec<(TempPath, DataObject)>>> {
+        let ls_tree = self
             .ctx
             .git()
-            .arg("cat-file")
-            .arg("-t")
+            .arg("ls-tree")
+            .arg("-r")
+            .arg("-z")
+            .arg("--full-tree")
             .arg(refname)
             .output()
-            .map_err(|err| GitError::subcommand("cat-file -t", err))?;
-        if !cat_file.status.success() {
-            return Err(DataError::object_type(refname.into(), &cat_file.stderr));
-        }
-        let object_type = String::from_utf8_lossy(&cat_file.stdout);
-
-        if object_type.trim() == "blob" {
-            // Get the contents of the file.
-            let cat_file = self
-                .ctx
-                .git()
-                .arg("cat-file")
-                .arg("blob")
-                .arg(refname)
-                .output()
-                .map_err(|err| GitError::subcommand("cat-file blob", err))?;
-            if !cat_file.status.success() {
-                return Err(DataError::object_contents(refname.into(), &cat_file.stderr));
+            .map_err(|err| GitError::subcommand("ls-tree", err))?;
+        if !ls_tree.status.success() {
+            return Err(DataError::list_tree(refname.into(), &ls_tree.stderr));
+        }
+
+        let mut entries = Vec::new();
+        for entry in ls_tree.stdout.split(|&byte| byte == 0) {
+            if entry.is_empty() {
+                continue;
             }
 
-            Ok(cat_file.stdout)
-        } else {
-            // Other object types are not supported.
-            Err(DataError::unsupported_object_type(
-                refname.into(),
-                object_type.trim().into(),
-            ))
+            // Entries are of the form `<mode> SP <type> SP <object> TAB <path>`. The path is
+            // not necessarily valid UTF-8.
+            let tab = entry
+                .iter()
+                .position(|&byte| byte == b'\t')
+                .ok_or_else(|| DataError::list_tree(refname.into(), entry))?;
+            let info = String::from_utf8_lossy(&entry[..tab]);
+            let path = &entry[tab + 1..];
+            let (type_, object) = info
+                .splitn(3, ' ')
+                .skip(1)
+                .tuples()
+                .next()
+                .ok_or_else(|| DataError::list_tree(refname.into(), entry))?;
+            let entry_name = format!("{}:{}", refname, String::from_utf8_lossy(path));
+            if type_ != "blob" {
+                // Submodules and the like cannot be mirrored.
+                warn!(
+                    target: "ghostflow/data",
+                    "rejecting {} because {} is a {}, not a blob",
+                    refname,
+                    entry_name,
+                    type_,
+                );
+
+                return Ok(None);
+            }
+
+            cat_file
+                .header(object)
+                .map_err(|err| DataError::stream_object(entry_name.clone(), err))?
+                .ok_or_else(|| DataError::object_type(entry_name.clone(), b"object is missing"))?;
+            let (partial, hash) = self.stream_object(cat_file, &entry_name, digest, dir)?;
+
+            entries.push((path.to_vec(), hash, partial));
+        }
+        entries.sort_by(|(lhs, _, _), (rhs, _, _)| lhs.cmp(rhs));
+
+        let mut listing = Vec::new();
+        for (path, hash, _) in &entries {
+            listing.extend_from_slice(hash.as_bytes());
+            listing.extend_from_slice(b"  ");
+            listing.extend_from_slice(path);
+            listing.push(b'\n');
+        }
+        let hash = digest.hash(&listing);
+        if expected_hash != hash {
+            warn!(
+                target: "ghostflow/data",
+                "failed to verify {} tree hash; expected {}, actually {}",
+                refname,
+                expected_hash,
+                hash,
+            );
+
+            return Ok(None);
+        }
+
+        let mut listing_file =
+            NamedTempFile::new_in(dir).map_err(|err| DataError::create_file(dir.into(), err))?;
+        listing_file
+            .write_all(&listing)
+            .map_err(|err| DataError::write_file(listing_file.path().into(), err))?;
+
+        let listing_object = DataObject {
+            algorithm: algorithm.into(),
+            hash,
+        };
+        let objects = entries
+            .into_iter()
+            .map(|(_, hash, partial)| {
+                let object = DataObject {
+                    algorithm: algorithm.into(),
+                    hash,
+                };
+                (partial, object)
+            })
+            .chain(std::iter::once((
+                listing_file.into_temp_path(),
+                listing_object,
+            )))
+            .collect();
+
+        Ok(Some(objects))
+    }
+
+    /// Move a verified object into place under the output directory.
+    fn place_object(&self, partial: TempPath, root: &Path, object: &DataObject) -> DataResult<()> {
+        let output_dir = root.join(&object.algorithm);
+        fs::create_dir_all(&output_dir)
+            .map_err(|err| DataError::create_directory(output_dir.clone(), err))?;
+
+        // Objects are content-addressed, so an existing file already has the right contents. The
+        // partial file is removed when dropped.
+        let output_path = root.join(object.key());
+        if output_path.exists() {
+            return Ok(());
         }
+
+        partial
+            .persist(&output_path)
+            .map_err(|err| DataError::create_file(output_path.clone(), err.error))?;
+        fs::set_permissions(&output_path, fs::Permissions::from_mode(0o444))
+            .map_err(|err| DataError::write_file(output_path.clone(), err))?;
+
+        if let Some(store) = self.store.as_ref() {
+            store.insert_file(object, &output_path)?;
+        }
+
+        Ok(())
     }
 
     /// Delete a local ref.
@@ -0,0 +1,229 @@
+//! Digest algorithms for verifying data objects.
+//!
+//! Data refs are named `refs/data/{algorithm}/{hash}`. The algorithm name selects the digest used
+//! to verify the contents of the object.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, LowerHex};
+use std::io::{self, Write};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use blake2::{Blake2b512, Blake2s256};
+use digest::Digest;
+use md5::Md5;
+use sha2::{Sha256, Sha512};
+use sha3::{Sha3_256, Sha3_512};
+
+/// A digest algorithm which may be used to verify data objects.
+pub trait DataDigest: Debug + Send + Sync {
+    /// Create a hasher for incrementally hashing contents.
+    fn hasher(&self) -> Box<dyn DataHasher>;
+
+    /// Compute the hash of the contents as a lowercase hexadecimal string.
+    fn hash(&self, contents: &[u8]) -> String {
+        let mut hasher = self.hasher();
+        hasher.update(contents);
+        hasher.finish()
+    }
+}
+
+/// An in-progress hash computation.
+pub trait DataHasher {
+    /// Add data to the hash.
+    fn update(&mut self, data: &[u8]);
+
+    /// Compute the hash as a lowercase hexadecimal string.
+    fn finish(self: Box<Self>) -> String;
+}
+
+struct DigestHasher<D> {
+    digest: D,
+}
+
+impl<D> DataHasher for DigestHasher<D>
+where
+    D: Digest,
+    digest::Output<D>: LowerHex,
+{
+    fn update(&mut self, data: &[u8]) {
+        self.digest.update(data);
+    }
+
+    fn finish(self: Box<Self>) -> String {
+        format!("{:x}", self.digest.finalize())
+    }
+}
+
+/// A `DataDigest` implementation for any `Digest`.
+pub struct DigestAlgorithm<D> {
+    _digest: PhantomData<fn() -> D>,
+}
+
+impl<D> DigestAlgorithm<D> {
+    /// Create a new digest algorithm.
+    pub fn new() -> Self {
+        Self {
+            _digest: PhantomData,
+        }
+    }
+}
+
+impl<D> Default for DigestAlgorithm<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D> Debug for DigestAlgorithm<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DigestAlgorithm")
+            .field("digest", &std::any::type_name::<D>())
+            .finish()
+    }
+}
+
+impl<D> DataDigest for DigestAlgorithm<D>
+where
+    D: Digest + 'static,
+    digest::Output<D>: LowerHex,
+{
+    fn hasher(&self) -> Box<dyn DataHasher> {
+        Box::new(DigestHasher {
+            digest: D::new(),
+        })
+    }
+}
+
+/// A writer which hashes all data written through it.
+pub(crate) struct HashingWriter<W> {
+    hasher: Box<dyn DataHasher>,
+    inner: W,
+}
+
+impl<W> HashingWriter<W> {
+    pub(crate) fn new(digest: &dyn DataDigest, inner: W) -> Self {
+        Self {
+            hasher: digest.hasher(),
+            inner,
+        }
+    }
+
+    /// Finish hashing and return the hash along with the inner writer.
+    pub(crate) fn finish(self) -> (String, W) {
+        (self.hasher.finish(), self.inner)
+    }
+}
+
+impl<W> Write for HashingWriter<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Algorithms which are considered too weak to accept new data with.
+pub const WEAK_ALGORITHMS: &[&str] = &["MD5", "SHA1"];
+
+/// The set of digest algorithms known to the data action.
+#[derive(Debug, Clone)]
+pub(crate) struct DigestRegistry {
+    digests: BTreeMap<String, Arc<dyn DataDigest>>,
+}
+
+impl DigestRegistry {
+    /// Add a digest algorithm to the registry.
+    ///
+    /// Replaces any existing algorithm with the same name.
+    pub(crate) fn add<N>(&mut self, name: N, digest: Arc<dyn DataDigest>)
+    where
+        N: Into<String>,
+    {
+        self.digests.insert(name.into(), digest);
+    }
+
+    /// Look up a digest algorithm by name.
+    pub(crate) fn get(&self, name: &str) -> Option<&dyn DataDigest> {
+        self.digests.get(name).map(AsRef::as_ref)
+    }
+}
+
+impl Default for DigestRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            digests: BTreeMap::new(),
+        };
+
+        registry.add("MD5", Arc::new(DigestAlgorithm::<Md5>::new()));
+        registry.add("SHA256", Arc::new(DigestAlgorithm::<Sha256>::new()));
+        registry.add("SHA512", Arc::new(DigestAlgorithm::<Sha512>::new()));
+        registry.add("SHA3-256", Arc::new(DigestAlgorithm::<Sha3_256>::new()));
+        registry.add("SHA3-512", Arc::new(DigestAlgorithm::<Sha3_512>::new()));
+        registry.add("BLAKE2B", Arc::new(DigestAlgorithm::<Blake2b512>::new()));
+        registry.add("BLAKE2S", Arc::new(DigestAlgorithm::<Blake2s256>::new()));
+        registry.add("BLAKE3", Arc::new(DigestAlgorithm::<blake3::Hasher>::new()));
+
+        registry
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use crate::actions::data::algorithm::{DigestRegistry, HashingWriter};
+
+    #[test]
+    fn test_builtin_digests() {
+        let registry = DigestRegistry::default();
+        let cases = [
+            ("MD5", "5d41402abc4b2a76b9719d911017c592"),
+            (
+                "SHA256",
+                "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+            ),
+            (
+                "SHA3-256",
+                "3338be694f50c5f338814986cdf0686453a888b84f424d792af4b9202398f392",
+            ),
+            (
+                "BLAKE2S",
+                "19213bacc58dee6dbde3ceb9a47cbb330b3d86f8cca8997eb00be456f140ca25",
+            ),
+        ];
+
+        for (name, expected) in cases {
+            let digest = registry.get(name).unwrap();
+            assert_eq!(digest.hash(b"hello"), expected);
+        }
+
+        let blake3 = registry.get("BLAKE3").unwrap();
+        assert_eq!(
+            blake3.hash(b"hello"),
+            blake3::hash(b"hello").to_hex().as_str(),
+        );
+        assert!(registry.get("SHA1").is_none());
+    }
+
+    #[test]
+    fn test_hashing_writer() {
+        let registry = DigestRegistry::default();
+        let digest = registry.get("SHA256").unwrap();
+
+        let mut writer = HashingWriter::new(digest, Vec::new());
+        writer.write_all(b"hel").unwrap();
+        writer.write_all(b"lo").unwrap();
+        let (hash, contents) = writer.finish();
+
+        assert_eq!(contents, b"hello");
+        assert_eq!(hash, digest.hash(b"hello"));
+    }
+}
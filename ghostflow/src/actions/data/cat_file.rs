@@ -0,0 +1,191 @@
+//! Streaming access to git objects.
+//!
+//! Objects are read from a long-running `git cat-file --batch` process so that large objects are
+//! never held in memory.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Stdio};
+
+use git_workarea::GitContext;
+
+/// The header of an object from `git cat-file --batch`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ObjectHeader {
+    /// The type of the object.
+    pub(crate) type_: String,
+}
+
+/// A `git cat-file --batch` process.
+pub(crate) struct CatFile {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+    /// The number of bytes of the current object which have not been read.
+    pending: Option<u64>,
+}
+
+fn invalid_data<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+impl CatFile {
+    /// Start a `git cat-file --batch` process.
+    pub(crate) fn new(ctx: &GitContext) -> io::Result<Self> {
+        let mut child = ctx
+            .git()
+            .arg("cat-file")
+            .arg("--batch")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("spawned with stdin");
+        let stdout = child.stdout.take().expect("spawned with stdout");
+
+        Ok(Self {
+            child,
+            stdin: Some(stdin),
+            stdout: BufReader::new(stdout),
+            pending: None,
+        })
+    }
+
+    /// Request an object.
+    ///
+    /// Returns `None` if the object does not exist. Otherwise, the contents of the object must be
+    /// read using `copy_to` or `skip` before requesting another object.
+    pub(crate) fn header(&mut self, spec: &str) -> io::Result<Option<ObjectHeader>> {
+        if self.pending.is_some() {
+            self.skip()?;
+        }
+
+        {
+            let stdin = self.stdin.as_mut().expect("stdin is only closed on drop");
+            writeln!(stdin, "{}", spec)?;
+            stdin.flush()?;
+        }
+
+        let mut line = String::new();
+        if self.stdout.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "git cat-file exited early",
+            ));
+        }
+        let line = line.trim_end_matches('\n');
+
+        // Output is either `<oid> <type> <size>` or `<spec> missing` (or `ambiguous`).
+        let mut parts = line.rsplitn(3, ' ');
+        let last = parts.next();
+        let type_ = parts.next();
+        match (type_, last) {
+            (Some(type_), Some(size)) if parts.next().is_some() => {
+                self.pending = Some(size.parse().map_err(invalid_data)?);
+                Ok(Some(ObjectHeader {
+                    type_: type_.into(),
+                }))
+            },
+            (_, Some("missing")) | (_, Some("ambiguous")) => Ok(None),
+            _ => Err(invalid_data(format!("unexpected cat-file output: {}", line))),
+        }
+    }
+
+    /// Copy the contents of the current object into a writer.
+    pub(crate) fn copy_to<W>(&mut self, out: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let size = self.pending.take().unwrap_or(0);
+        let copied = io::copy(&mut (&mut self.stdout).take(size), out)?;
+        if copied != size {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated object from git cat-file",
+            ));
+        }
+
+        // Each object is followed by a newline.
+        let mut newline = [0];
+        self.stdout.read_exact(&mut newline)?;
+
+        Ok(())
+    }
+
+    /// Skip the contents of the current object.
+    pub(crate) fn skip(&mut self) -> io::Result<()> {
+        self.copy_to(&mut io::sink())
+    }
+}
+
+impl Drop for CatFile {
+    fn drop(&mut self) {
+        // Closing stdin makes `git cat-file` exit.
+        self.stdin.take();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    use git_workarea::GitContext;
+    use tempfile::TempDir;
+
+    use crate::actions::data::cat_file::CatFile;
+
+    #[test]
+    fn test_cat_file_stream() {
+        let tempdir = TempDir::new().unwrap();
+        let status = Command::new("git")
+            .arg("init")
+            .arg("--bare")
+            .arg("--quiet")
+            .arg(tempdir.path())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let ctx = GitContext::new(tempdir.path());
+
+        let blob = {
+            let mut hash_object = ctx
+                .git()
+                .arg("hash-object")
+                .arg("-w")
+                .arg("--stdin")
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap();
+            hash_object
+                .stdin
+                .take()
+                .unwrap()
+                .write_all(b"hello\nworld")
+                .unwrap();
+            let output = hash_object.wait_with_output().unwrap();
+            String::from_utf8(output.stdout).unwrap().trim().to_string()
+        };
+
+        let mut cat_file = CatFile::new(&ctx).unwrap();
+        let missing = "0000000000000000000000000000000000000000";
+        assert_eq!(cat_file.header(missing).unwrap(), None);
+
+        let header = cat_file.header(&blob).unwrap().unwrap();
+        assert_eq!(header.type_, "blob");
+        let mut contents = Vec::new();
+        cat_file.copy_to(&mut contents).unwrap();
+        assert_eq!(contents, b"hello\nworld");
+
+        // Unread contents are skipped automatically.
+        cat_file.header(&blob).unwrap().unwrap();
+        cat_file.header(&blob).unwrap().unwrap();
+        let mut contents = Vec::new();
+        cat_file.copy_to(&mut contents).unwrap();
+        assert_eq!(contents, b"hello\nworld");
+    }
+}
@@ -0,0 +1,583 @@
+//! Destinations for data objects.
+//!
+//! Data objects are stored under keys of the form `{algorithm}/{hash}`. Destinations receive a
+//! directory containing all of the objects in this layout along with the list of objects it
+//! contains.
+
+use std::error::Error;
+use std::fmt::Debug;
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use thiserror::Error;
+
+/// Errors which may occur when storing data objects in a destination.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum DataDestinationError {
+    /// Failure to construct a command.
+    #[error("failed to construct {} command: {}", command, source)]
+    Command {
+        /// The name of the command.
+        command: &'static str,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to perform an `rsync` operation.
+    #[error("failed to rsync data (status: {:?}): {}", status, output)]
+    Rsync {
+        /// The return code from `rsync`.
+        status: Option<i32>,
+        /// Output from `rsync`.
+        output: String,
+    },
+    /// Failure to create a directory.
+    #[error("failed to create directory {}: {}", path.display(), source)]
+    CreateDirectory {
+        /// The path to the directory.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to copy an object.
+    #[error("failed to copy data object to {}: {}", path.display(), source)]
+    CopyObject {
+        /// The path to the destination file.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to upload an object to an object store.
+    #[error("failed to upload {} (status: {:?}): {}", key, status, output)]
+    Upload {
+        /// The key of the object.
+        key: String,
+        /// The return code from `curl`.
+        status: Option<i32>,
+        /// Output from `curl`.
+        output: String,
+    },
+    /// Another error from a destination.
+    #[error("{}", source)]
+    Other {
+        /// The source of the error.
+        #[source]
+        source: Box<dyn Error + Send + Sync>,
+    },
+}
+
+impl DataDestinationError {
+    fn command(command: &'static str, source: io::Error) -> Self {
+        DataDestinationError::Command {
+            command,
+            source,
+        }
+    }
+
+    fn rsync(status: Option<i32>, output: &[u8]) -> Self {
+        DataDestinationError::Rsync {
+            status,
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
+
+    fn create_directory(path: PathBuf, source: io::Error) -> Self {
+        DataDestinationError::CreateDirectory {
+            path,
+            source,
+        }
+    }
+
+    fn copy_object(path: PathBuf, source: io::Error) -> Self {
+        DataDestinationError::CopyObject {
+            path,
+            source,
+        }
+    }
+
+    fn upload(key: String, status: Option<i32>, output: &[u8]) -> Self {
+        DataDestinationError::Upload {
+            key,
+            status,
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
+
+    /// Wrap an error from a custom destination.
+    pub fn other<E>(source: E) -> Self
+    where
+        E: Into<Box<dyn Error + Send + Sync>>,
+    {
+        DataDestinationError::Other {
+            source: source.into(),
+        }
+    }
+}
+
+/// A verified data object to store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataObject {
+    /// The digest algorithm used to verify the object.
+    pub algorithm: String,
+    /// The hash of the object.
+    pub hash: String,
+}
+
+impl DataObject {
+    /// The key of the object within a destination.
+    pub fn key(&self) -> String {
+        format!("{}/{}", self.algorithm, self.hash)
+    }
+}
+
+/// A location to store data objects.
+pub trait DataDestination: Debug + Send + Sync {
+    /// A description of the destination for reporting.
+    fn name(&self) -> String;
+
+    /// Store data objects.
+    ///
+    /// The `root` directory contains each object at the path given by its key.
+    fn store(&self, root: &Path, objects: &[DataObject]) -> Result<(), DataDestinationError>;
+}
+
+/// A destination which uses `rsync` to mirror data objects.
+#[derive(Debug, Clone)]
+pub struct RsyncDestination {
+    /// The `rsync` destination.
+    destination: String,
+}
+
+impl RsyncDestination {
+    /// Create a new `rsync` destination.
+    pub fn new<D>(destination: D) -> Self
+    where
+        D: Into<String>,
+    {
+        Self {
+            destination: destination.into(),
+        }
+    }
+}
+
+impl DataDestination for RsyncDestination {
+    fn name(&self) -> String {
+        self.destination.clone()
+    }
+
+    fn store(&self, root: &Path, _: &[DataObject]) -> Result<(), DataDestinationError> {
+        let mut source = root.as_os_str().to_os_string();
+        // We want to sync the contents of this directory, so add the trailing slash.
+        source.push("/");
+
+        let rsync = Command::new("rsync")
+            .arg("--recursive")
+            .arg("--perms")
+            .arg("--times")
+            .arg("--verbose")
+            .arg(&source)
+            .arg(&self.destination)
+            .output()
+            .map_err(|err| DataDestinationError::command("rsync", err))?;
+        if !rsync.status.success() {
+            return Err(DataDestinationError::rsync(
+                rsync.status.code(),
+                &rsync.stderr,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// A destination which copies data objects into a local directory.
+#[derive(Debug, Clone)]
+pub struct LocalDirectoryDestination {
+    /// The directory to store objects in.
+    path: PathBuf,
+}
+
+impl LocalDirectoryDestination {
+    /// Create a new local directory destination.
+    pub fn new<P>(path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            path: path.into(),
+        }
+    }
+}
+
+impl DataDestination for LocalDirectoryDestination {
+    fn name(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn store(&self, root: &Path, objects: &[DataObject]) -> Result<(), DataDestinationError> {
+        for object in objects {
+            let key = object.key();
+            let target = self.path.join(&key);
+
+            // Objects are content-addressed, so existing objects are already correct.
+            if target.exists() {
+                continue;
+            }
+
+            let dir = self.path.join(&object.algorithm);
+            fs::create_dir_all(&dir)
+                .map_err(|err| DataDestinationError::create_directory(dir, err))?;
+
+            // Copy to a temporary name and move it into place so that partial objects are never
+            // visible.
+            let partial = self.path.join(format!("{}.partial", key));
+            fs::copy(root.join(&key), &partial)
+                .map_err(|err| DataDestinationError::copy_object(partial.clone(), err))?;
+            fs::set_permissions(&partial, fs::Permissions::from_mode(0o444))
+                .map_err(|err| DataDestinationError::copy_object(partial.clone(), err))?;
+            fs::rename(&partial, &target)
+                .map_err(|err| DataDestinationError::copy_object(target, err))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A destination which uploads data objects to an S3-compatible object store.
+///
+/// Objects are uploaded using `curl` with AWS signature version 4 authentication. Path-style
+/// addressing (`{endpoint}/{bucket}/{key}`) is used so that self-hosted stores such as MinIO work.
+#[derive(Clone)]
+pub struct S3Destination {
+    /// The endpoint of the object store.
+    endpoint: String,
+    /// The bucket to store objects in.
+    bucket: String,
+    /// The region of the bucket.
+    region: String,
+    /// A prefix for object keys.
+    prefix: String,
+    /// The access key.
+    access_key: String,
+    /// The secret key.
+    secret_key: String,
+}
+
+impl S3Destination {
+    /// Create a new S3 destination.
+    pub fn new<E, B, A, S>(endpoint: E, bucket: B, access_key: A, secret_key: S) -> Self
+    where
+        E: Into<String>,
+        B: Into<String>,
+        A: Into<String>,
+        S: Into<String>,
+    {
+        Self {
+            endpoint: endpoint.into(),
+            bucket: bucket.into(),
+            region: "us-east-1".into(),
+            prefix: String::new(),
+            access_key: access_key.into(),
+            secret_key: secret_key.into(),
+        }
+    }
+
+    /// Set the region of the bucket.
+    ///
+    /// Defaults to `us-east-1`.
+    pub fn region<R>(&mut self, region: R) -> &mut Self
+    where
+        R: Into<String>,
+    {
+        self.region = region.into();
+        self
+    }
+
+    /// Set a prefix for object keys.
+    pub fn prefix<P>(&mut self, prefix: P) -> &mut Self
+    where
+        P: Into<String>,
+    {
+        self.prefix = prefix.into();
+        self
+    }
+
+    fn object_url(&self, key: &str) -> String {
+        format!(
+            "{}/{}/{}{}",
+            self.endpoint.trim_end_matches('/'),
+            self.bucket,
+            self.prefix,
+            key,
+        )
+    }
+}
+
+impl Debug for S3Destination {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("S3Destination")
+            .field("endpoint", &self.endpoint)
+            .field("bucket", &self.bucket)
+            .field("region", &self.region)
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
+
+/// Quote a value for use in a curl configuration file.
+///
+/// Within double quotes, curl treats backslashes as escapes, so backslashes, quotes, and
+/// whitespace control characters need to be escaped to be passed through as-is.
+fn curl_config_quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for ch in value.chars() {
+        match ch {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\t' => quoted.push_str("\\t"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\x0b' => quoted.push_str("\\v"),
+            ch => quoted.push(ch),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+impl DataDestination for S3Destination {
+    fn name(&self) -> String {
+        format!("{}/{}/{}", self.endpoint, self.bucket, self.prefix)
+    }
+
+    fn store(&self, root: &Path, objects: &[DataObject]) -> Result<(), DataDestinationError> {
+        for object in objects {
+            let key = object.key();
+            let mut curl = Command::new("curl")
+                .arg("--silent")
+                .arg("--show-error")
+                .arg("--fail")
+                .arg("--config")
+                .arg("-")
+                .arg("--aws-sigv4")
+                .arg(format!("aws:amz:{}:s3", self.region))
+                .arg("--upload-file")
+                .arg(root.join(&key))
+                .arg(self.object_url(&key))
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .map_err(|err| DataDestinationError::command("curl", err))?;
+
+            // Pass credentials through the configuration so they do not appear in the process
+            // list.
+            {
+                let mut stdin = curl.stdin.take().expect("spawned with stdin");
+                let user = format!("{}:{}", self.access_key, self.secret_key);
+                writeln!(stdin, "user = {}", curl_config_quote(&user))
+                    .map_err(|err| DataDestinationError::command("curl", err))?;
+            }
+
+            let output = curl
+                .wait_with_output()
+                .map_err(|err| DataDestinationError::command("curl", err))?;
+            if !output.status.success() {
+                return Err(DataDestinationError::upload(
+                    key,
+                    output.status.code(),
+                    &output.stderr,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::path::Path;
+    use std::sync::mpsc;
+
+    use sha2::{Digest, Sha256};
+    use tempfile::TempDir;
+
+    use crate::actions::data::destination::{
+        curl_config_quote, DataDestination, DataObject, LocalDirectoryDestination, S3Destination,
+    };
+    use crate::tests::utils::{http_stub, StubRequest};
+
+    fn objects(root: &Path) -> Vec<DataObject> {
+        let object = DataObject {
+            algorithm: "SHA256".into(),
+            hash: "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824".into(),
+        };
+        fs::create_dir_all(root.join(&object.algorithm)).unwrap();
+        fs::write(root.join(object.key()), "hello").unwrap();
+        vec![object]
+    }
+
+    #[test]
+    fn test_local_directory_destination() {
+        let source = TempDir::new().unwrap();
+        let target = TempDir::new().unwrap();
+        let objects = objects(source.path());
+
+        let destination = LocalDirectoryDestination::new(target.path());
+        destination.store(source.path(), &objects).unwrap();
+        // Storing again is a no-op.
+        destination.store(source.path(), &objects).unwrap();
+
+        let stored = fs::read_to_string(target.path().join(objects[0].key())).unwrap();
+        assert_eq!(stored, "hello");
+        assert!(!target
+            .path()
+            .join(format!("{}.partial", objects[0].key()))
+            .exists());
+    }
+
+    /// A minimal stand-in for an S3-compatible object store.
+    ///
+    /// It accepts signed requests and reports each of them.
+    fn object_store(requests: usize) -> (String, mpsc::Receiver<StubRequest>) {
+        http_stub(requests, |request| {
+            let authorized = request
+                .header("authorization")
+                .map_or(false, |value| {
+                    value.to_ascii_lowercase().starts_with("aws4-hmac-sha256")
+                });
+            let status = if authorized {
+                200
+            } else {
+                403
+            };
+            (status, String::new())
+        })
+    }
+
+    #[test]
+    fn test_s3_destination() {
+        let source = TempDir::new().unwrap();
+        let objects = objects(source.path());
+        let (endpoint, requests) = object_store(1);
+
+        let mut destination = S3Destination::new(endpoint, "bucket", "access", "secret");
+        destination.prefix("data/");
+        destination.store(source.path(), &objects).unwrap();
+
+        let request = requests.recv().unwrap();
+        assert_eq!(request.method, "PUT");
+        assert_eq!(request.path, format!("/bucket/data/{}", objects[0].key()));
+        assert_eq!(request.body, b"hello");
+    }
+
+    #[test]
+    fn test_curl_config_quote() {
+        assert_eq!(curl_config_quote("plain"), r#""plain""#);
+        assert_eq!(
+            curl_config_quote("a\"b\\c\td\ne"),
+            r#""a\"b\\c\td\ne""#,
+        );
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut block = [0; 64];
+        block[..key.len()].copy_from_slice(key);
+        let pad = |value: u8| block.iter().map(|byte| byte ^ value).collect::<Vec<_>>();
+
+        let inner = Sha256::new()
+            .chain_update(pad(0x36))
+            .chain_update(data)
+            .finalize();
+        Sha256::new()
+            .chain_update(pad(0x5c))
+            .chain_update(inner)
+            .finalize()
+            .to_vec()
+    }
+
+    /// Check the AWS signature of a request made by curl.
+    ///
+    /// Returns the access key the request was signed for.
+    fn verify_signature(request: &StubRequest, secret_key: &str) -> String {
+        let authorization = request.header("authorization").unwrap();
+        let mut parts = authorization
+            .strip_prefix("AWS4-HMAC-SHA256 Credential=")
+            .unwrap()
+            .split(", ");
+        let credential = parts.next().unwrap();
+        let signed_headers = parts
+            .next()
+            .and_then(|part| part.strip_prefix("SignedHeaders="))
+            .unwrap();
+        let signature = parts
+            .next()
+            .and_then(|part| part.strip_prefix("Signature="))
+            .unwrap();
+        let (access_key, scope) = credential.split_once('/').unwrap();
+
+        let canonical_headers = signed_headers
+            .split(';')
+            .map(|name| format!("{}:{}\n", name, request.header(name).unwrap()))
+            .collect::<String>();
+        // Unless curl says otherwise, it signs uploads as an empty payload.
+        let empty_hash = format!("{:x}", Sha256::digest(b""));
+        let payload_hash = request
+            .header("x-amz-content-sha256")
+            .unwrap_or(&empty_hash);
+        let (path, query) = request
+            .path
+            .split_once('?')
+            .unwrap_or((request.path.as_str(), ""));
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            request.method, path, query, canonical_headers, signed_headers, payload_hash,
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{:x}",
+            request.header("x-amz-date").unwrap(),
+            scope,
+            Sha256::digest(canonical_request.as_bytes()),
+        );
+
+        let key = scope
+            .split('/')
+            .fold(format!("AWS4{}", secret_key).into_bytes(), |key, part| {
+                hmac_sha256(&key, part.as_bytes())
+            });
+        assert_eq!(
+            hex(&hmac_sha256(&key, string_to_sign.as_bytes())),
+            signature,
+        );
+
+        access_key.into()
+    }
+
+    #[test]
+    fn test_s3_destination_quoted_credentials() {
+        let source = TempDir::new().unwrap();
+        let objects = objects(source.path());
+        let (endpoint, requests) = object_store(1);
+        let access_key = r#"acc"ess\key"#;
+        let secret_key = r#"sec"ret\key\"#;
+
+        let destination = S3Destination::new(endpoint, "bucket", access_key, secret_key);
+        destination.store(source.path(), &objects).unwrap();
+
+        let request = requests.recv().unwrap();
+        assert_eq!(verify_signature(&request, secret_key), access_key);
+    }
+}
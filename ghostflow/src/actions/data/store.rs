@@ -0,0 +1,673 @@
+//! A persistent, content-addressed store for data objects.
+//!
+//! Objects are stored once under the SHA256 of their contents. Each data object key
+//! (`{algorithm}/{hash}`) is an alias for a stored blob, so the same content pushed under
+//! multiple algorithms is stored once. A manifest records the aliases and which objects have been
+//! uploaded to each destination so that objects are only uploaded once.
+//!
+//! Multiple processes may share a store. Updates to the manifest and garbage collection hold an
+//! exclusive lock on the store and merge in changes saved by other processes.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use digest::Digest;
+use fs2::FileExt;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tempfile::{Builder, NamedTempFile};
+use thiserror::Error;
+
+use crate::actions::data::destination::DataObject;
+
+/// Errors which may occur when using a data store.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum DataStoreError {
+    /// Failure to create a directory.
+    #[error("failed to create directory {}: {}", path.display(), source)]
+    CreateDirectory {
+        /// The path to the directory.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to read the manifest.
+    #[error("failed to read the data store manifest {}: {}", path.display(), source)]
+    ReadManifest {
+        /// The path to the manifest.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// The manifest is not valid.
+    #[error("invalid data store manifest {}: {}", path.display(), source)]
+    InvalidManifest {
+        /// The path to the manifest.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: serde_json::Error,
+    },
+    /// Failure to write the manifest.
+    #[error("failed to write the data store manifest {}: {}", path.display(), source)]
+    WriteManifest {
+        /// The path to the manifest.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to read an object.
+    #[error("failed to read data object {}: {}", path.display(), source)]
+    ReadObject {
+        /// The path to the object.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to write an object.
+    #[error("failed to write data object {}: {}", path.display(), source)]
+    WriteObject {
+        /// The path to the object.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to export an object.
+    #[error("failed to export data object to {}: {}", path.display(), source)]
+    ExportObject {
+        /// The path the object was exported to.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to list the stored objects.
+    #[error("failed to list data objects in {}: {}", path.display(), source)]
+    ListObjects {
+        /// The path to the object directory.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to lock the store.
+    #[error("failed to lock the data store {}: {}", path.display(), source)]
+    Lock {
+        /// The path to the lock file.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to remove an object.
+    #[error("failed to remove data object {}: {}", path.display(), source)]
+    RemoveObject {
+        /// The path to the object.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// An object is not in the store.
+    #[error("data object {} is not in the store", key)]
+    MissingObject {
+        /// The key of the object.
+        key: String,
+    },
+}
+
+impl DataStoreError {
+    fn create_directory(path: PathBuf, source: io::Error) -> Self {
+        DataStoreError::CreateDirectory {
+            path,
+            source,
+        }
+    }
+
+    fn read_manifest(path: PathBuf, source: io::Error) -> Self {
+        DataStoreError::ReadManifest {
+            path,
+            source,
+        }
+    }
+
+    fn invalid_manifest(path: PathBuf, source: serde_json::Error) -> Self {
+        DataStoreError::InvalidManifest {
+            path,
+            source,
+        }
+    }
+
+    fn write_manifest(path: PathBuf, source: io::Error) -> Self {
+        DataStoreError::WriteManifest {
+            path,
+            source,
+        }
+    }
+
+    fn read_object(path: PathBuf, source: io::Error) -> Self {
+        DataStoreError::ReadObject {
+            path,
+            source,
+        }
+    }
+
+    fn write_object(path: PathBuf, source: io::Error) -> Self {
+        DataStoreError::WriteObject {
+            path,
+            source,
+        }
+    }
+
+    fn export_object(path: PathBuf, source: io::Error) -> Self {
+        DataStoreError::ExportObject {
+            path,
+            source,
+        }
+    }
+
+    fn list_objects(path: PathBuf, source: io::Error) -> Self {
+        DataStoreError::ListObjects {
+            path,
+            source,
+        }
+    }
+
+    fn lock(path: PathBuf, source: io::Error) -> Self {
+        DataStoreError::Lock {
+            path,
+            source,
+        }
+    }
+
+    fn remove_object(path: PathBuf, source: io::Error) -> Self {
+        DataStoreError::RemoveObject {
+            path,
+            source,
+        }
+    }
+
+    fn missing_object(key: String) -> Self {
+        DataStoreError::MissingObject {
+            key,
+        }
+    }
+}
+
+type DataStoreResult<T> = Result<T, DataStoreError>;
+
+/// The manifest of a data store.
+#[derive(Debug, Default)]
+struct Manifest {
+    /// Map of object keys to the stored blob.
+    aliases: BTreeMap<String, String>,
+    /// The object keys uploaded to each destination.
+    uploaded: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Manifest {
+    fn from_json(value: Value) -> Result<Self, serde_json::Error> {
+        let aliases = serde_json::from_value(value.get("aliases").cloned().unwrap_or_default())?;
+        let uploaded = serde_json::from_value(value.get("uploaded").cloned().unwrap_or_default())?;
+
+        Ok(Self {
+            aliases,
+            uploaded,
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "aliases": self.aliases,
+            "uploaded": self.uploaded,
+        })
+    }
+
+    /// Read a manifest from disk.
+    ///
+    /// A missing manifest is empty.
+    fn read(path: &Path) -> DataStoreResult<Self> {
+        match fs::read(path) {
+            Ok(contents) => {
+                serde_json::from_slice(&contents)
+                    .and_then(Self::from_json)
+                    .map_err(|err| DataStoreError::invalid_manifest(path.into(), err))
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(DataStoreError::read_manifest(path.into(), err)),
+        }
+    }
+
+    /// Merge in the entries of another manifest.
+    ///
+    /// Aliases for blobs which do not exist are ignored; another process may have removed them.
+    fn merge<F>(&mut self, other: Self, blob_exists: F)
+    where
+        F: Fn(&str) -> bool,
+    {
+        for (key, blob) in other.aliases {
+            if blob_exists(&blob) {
+                self.aliases.entry(key).or_insert(blob);
+            }
+        }
+        for (destination, keys) in other.uploaded {
+            self.uploaded.entry(destination).or_default().extend(keys);
+        }
+    }
+}
+
+/// The result of a garbage collection pass on a data store.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DataStoreGc {
+    /// Object keys which were dropped.
+    pub dropped_keys: Vec<String>,
+    /// The number of stored blobs which were removed.
+    pub removed_blobs: usize,
+}
+
+/// A persistent, content-addressed store for data objects.
+#[derive(Debug)]
+pub struct DataStore {
+    /// The root of the store.
+    root: PathBuf,
+    /// The manifest of the store.
+    manifest: Mutex<Manifest>,
+}
+
+const MANIFEST_NAME: &str = "manifest.json";
+const LOCK_NAME: &str = "manifest.lock";
+const OBJECTS_DIR: &str = "objects";
+/// The prefix of temporary files for objects which are still being written.
+const TEMP_PREFIX: &str = ".tmp";
+
+impl DataStore {
+    /// Open a data store in the given directory.
+    ///
+    /// The directory is created if it does not exist.
+    pub fn open<P>(root: P) -> DataStoreResult<Self>
+    where
+        P: Into<PathBuf>,
+    {
+        let root = root.into();
+        let objects = root.join(OBJECTS_DIR);
+        fs::create_dir_all(&objects)
+            .map_err(|err| DataStoreError::create_directory(objects, err))?;
+
+        let manifest = Manifest::read(&root.join(MANIFEST_NAME))?;
+
+        Ok(Self {
+            root,
+            manifest: Mutex::new(manifest),
+        })
+    }
+
+    /// The root directory of the store.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Manifest> {
+        self.manifest.lock().expect("data store manifest lock poisoned")
+    }
+
+    /// Take the exclusive lock on the store shared with other processes.
+    ///
+    /// The lock is held until the returned file is closed.
+    fn lock_store(&self) -> DataStoreResult<File> {
+        let path = self.root.join(LOCK_NAME);
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(&path)
+            .map_err(|err| DataStoreError::lock(path.clone(), err))?;
+        file.lock_exclusive()
+            .map_err(|err| DataStoreError::lock(path, err))?;
+
+        Ok(file)
+    }
+
+    fn blob_path(&self, blob: &str) -> PathBuf {
+        self.root.join(OBJECTS_DIR).join(blob)
+    }
+
+    /// Merge in the manifest saved by other processes.
+    ///
+    /// The store lock must be held.
+    fn merge_saved(&self, manifest: &mut Manifest) -> DataStoreResult<()> {
+        let saved = Manifest::read(&self.root.join(MANIFEST_NAME))?;
+        manifest.merge(saved, |blob| self.blob_path(blob).exists());
+        Ok(())
+    }
+
+    /// Write a manifest to disk.
+    ///
+    /// The store lock must be held.
+    fn write_manifest(&self, manifest: &Manifest) -> DataStoreResult<()> {
+        let path = self.root.join(MANIFEST_NAME);
+
+        let mut file = NamedTempFile::new_in(&self.root)
+            .map_err(|err| DataStoreError::write_manifest(path.clone(), err))?;
+        serde_json::to_writer_pretty(&mut file, &manifest.to_json())
+            .map_err(|err| DataStoreError::write_manifest(path.clone(), err.into()))?;
+        file.persist(&path)
+            .map_err(|err| DataStoreError::write_manifest(path, err.error))?;
+
+        Ok(())
+    }
+
+    /// Add an object to the store.
+    ///
+    /// Returns `true` if the contents were not already stored under any key.
+    pub fn insert(&self, object: &DataObject, contents: &[u8]) -> DataStoreResult<bool> {
+        self.insert_reader(object, contents)
+    }
+
+    /// Add an object to the store from a file.
+    ///
+    /// The file is streamed into the store rather than read into memory.
+    pub fn insert_file(&self, object: &DataObject, path: &Path) -> DataStoreResult<bool> {
+        let file = File::open(path).map_err(|err| DataStoreError::read_object(path.into(), err))?;
+        self.insert_reader(object, file)
+    }
+
+    fn insert_reader<R>(&self, object: &DataObject, mut contents: R) -> DataStoreResult<bool>
+    where
+        R: Read,
+    {
+        // Stream the contents into a temporary file in the store, hashing along the way.
+        let dir = self.root.join(OBJECTS_DIR);
+        let mut file = Builder::new()
+            .prefix(TEMP_PREFIX)
+            .tempfile_in(&dir)
+            .map_err(|err| DataStoreError::write_object(dir.clone(), err))?;
+        let mut digest = Sha256::new();
+        let mut buf = [0; 64 * 1024];
+        loop {
+            let nread = contents
+                .read(&mut buf)
+                .map_err(|err| DataStoreError::write_object(file.path().into(), err))?;
+            if nread == 0 {
+                break;
+            }
+            digest.update(&buf[..nread]);
+            file.write_all(&buf[..nread])
+                .map_err(|err| DataStoreError::write_object(file.path().into(), err))?;
+        }
+
+        let blob = format!("{:x}", digest.finalize());
+        let path = self.blob_path(&blob);
+
+        // If the contents are already stored, the temporary file is discarded.
+        let is_new = !path.exists();
+        if is_new {
+            file.persist(&path)
+                .map_err(|err| DataStoreError::write_object(path.clone(), err.error))?;
+        }
+
+        self.lock().aliases.insert(object.key(), blob);
+
+        Ok(is_new)
+    }
+
+    /// Whether an object is in the store.
+    pub fn contains(&self, object: &DataObject) -> bool {
+        self.lock().aliases.contains_key(&object.key())
+    }
+
+    /// The objects which have not been uploaded to a destination.
+    pub fn pending<'a>(&self, destination: &str, objects: &'a [DataObject]) -> Vec<&'a DataObject> {
+        let manifest = self.lock();
+        let uploaded = manifest.uploaded.get(destination);
+
+        objects
+            .iter()
+            .filter(|object| {
+                uploaded
+                    .map(|uploaded| !uploaded.contains(&object.key()))
+                    .unwrap_or(true)
+            })
+            .collect()
+    }
+
+    /// Record that objects have been uploaded to a destination.
+    pub fn mark_uploaded<'a, I>(&self, destination: &str, objects: I)
+    where
+        I: IntoIterator<Item = &'a DataObject>,
+    {
+        self.lock()
+            .uploaded
+            .entry(destination.into())
+            .or_default()
+            .extend(objects.into_iter().map(DataObject::key));
+    }
+
+    /// Export objects into a directory using the `{algorithm}/{hash}` layout.
+    ///
+    /// Objects are hard linked where possible.
+    pub fn export<'a, I>(&self, dir: &Path, objects: I) -> DataStoreResult<()>
+    where
+        I: IntoIterator<Item = &'a DataObject>,
+    {
+        for object in objects {
+            let key = object.key();
+            let blob = self
+                .lock()
+                .aliases
+                .get(&key)
+                .cloned()
+                .ok_or_else(|| DataStoreError::missing_object(key.clone()))?;
+
+            let algo_dir = dir.join(&object.algorithm);
+            fs::create_dir_all(&algo_dir)
+                .map_err(|err| DataStoreError::create_directory(algo_dir, err))?;
+
+            let target = dir.join(&key);
+            if target.exists() {
+                continue;
+            }
+            let source = self.blob_path(&blob);
+            fs::hard_link(&source, &target)
+                .or_else(|_| fs::copy(&source, &target).map(|_| ()))
+                .map_err(|err| DataStoreError::export_object(target, err))?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the manifest to disk.
+    ///
+    /// Entries saved by other processes since the store was opened are kept.
+    pub fn save(&self) -> DataStoreResult<()> {
+        let _lock = self.lock_store()?;
+        let mut manifest = self.lock();
+        self.merge_saved(&mut manifest)?;
+        self.write_manifest(&manifest)
+    }
+
+    /// Drop objects which are not referenced.
+    ///
+    /// Any key not in `live` is dropped from the store. Stored blobs which no longer have any keys
+    /// are removed. Objects which are still being written are kept. The manifest is saved
+    /// afterwards.
+    pub fn gc(&self, live: &BTreeSet<String>) -> DataStoreResult<DataStoreGc> {
+        let mut result = DataStoreGc::default();
+
+        {
+            let _lock = self.lock_store()?;
+            let mut manifest = self.lock();
+            self.merge_saved(&mut manifest)?;
+
+            let (kept, dropped): (BTreeMap<_, _>, BTreeMap<_, _>) = manifest
+                .aliases
+                .iter()
+                .map(|(key, blob)| (key.clone(), blob.clone()))
+                .partition(|(key, _)| live.contains(key));
+            manifest.aliases = kept;
+            result.dropped_keys = dropped.into_keys().collect();
+
+            for uploaded in manifest.uploaded.values_mut() {
+                uploaded.retain(|key| live.contains(key));
+            }
+            manifest.uploaded.retain(|_, uploaded| !uploaded.is_empty());
+
+            let referenced = manifest.aliases.values().collect::<BTreeSet<_>>();
+            let objects_dir = self.root.join(OBJECTS_DIR);
+            let entries = fs::read_dir(&objects_dir)
+                .map_err(|err| DataStoreError::list_objects(objects_dir.clone(), err))?;
+            for entry in entries {
+                let entry = entry
+                    .map_err(|err| DataStoreError::list_objects(objects_dir.clone(), err))?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if referenced.contains(&name) || name.starts_with(TEMP_PREFIX) {
+                    continue;
+                }
+
+                let path = entry.path();
+                fs::remove_file(&path).map_err(|err| DataStoreError::remove_object(path, err))?;
+                result.removed_blobs += 1;
+            }
+
+            self.write_manifest(&manifest)?;
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use crate::actions::data::destination::DataObject;
+    use crate::actions::data::store::DataStore;
+
+    const HELLO_SHA512: &str = concat!(
+        "9b71d224bd62f3785d96d46ad3ea3d73319bfbc2890caadae2dff72519673ca7",
+        "2323c3d99ba5c11d7c7acc6e14b8c5da0c4663475c2e5c3adef46f73bcdec043",
+    );
+
+    fn object(algorithm: &str, hash: &str) -> DataObject {
+        DataObject {
+            algorithm: algorithm.into(),
+            hash: hash.into(),
+        }
+    }
+
+    #[test]
+    fn test_data_store_aliases() {
+        let tempdir = TempDir::new().unwrap();
+        let store = DataStore::open(tempdir.path()).unwrap();
+        let md5 = object("MD5", "5d41402abc4b2a76b9719d911017c592");
+        let sha512 = object("SHA512", HELLO_SHA512);
+
+        assert!(store.insert(&md5, b"hello").unwrap());
+        assert!(!store.insert(&sha512, b"hello").unwrap());
+        assert!(store.contains(&md5));
+        assert!(store.contains(&sha512));
+
+        let blobs = fs::read_dir(tempdir.path().join("objects")).unwrap().count();
+        assert_eq!(blobs, 1);
+
+        let export = TempDir::new().unwrap();
+        store.export(export.path(), [&md5, &sha512]).unwrap();
+        assert_eq!(fs::read(export.path().join(md5.key())).unwrap(), b"hello");
+        assert_eq!(fs::read(export.path().join(sha512.key())).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_data_store_uploads() {
+        let tempdir = TempDir::new().unwrap();
+        let objects = [
+            object("MD5", "5d41402abc4b2a76b9719d911017c592"),
+            object("MD5", "7d793037a0760186574b0282f2f435e7"),
+        ];
+
+        {
+            let store = DataStore::open(tempdir.path()).unwrap();
+            store.insert(&objects[0], b"hello").unwrap();
+            store.insert(&objects[1], b"world").unwrap();
+            assert_eq!(store.pending("dest", &objects).len(), 2);
+            store.mark_uploaded("dest", &objects[..1]);
+            store.save().unwrap();
+        }
+
+        // The manifest persists between instances.
+        let store = DataStore::open(tempdir.path()).unwrap();
+        assert_eq!(store.pending("dest", &objects), vec![&objects[1]]);
+        assert_eq!(store.pending("other", &objects).len(), 2);
+    }
+
+    #[test]
+    fn test_data_store_gc() {
+        let tempdir = TempDir::new().unwrap();
+        let store = DataStore::open(tempdir.path()).unwrap();
+        let hello_md5 = object("MD5", "5d41402abc4b2a76b9719d911017c592");
+        let hello_sha512 = object("SHA512", HELLO_SHA512);
+        let world = object("MD5", "7d793037a0760186574b0282f2f435e7");
+        store.insert(&hello_md5, b"hello").unwrap();
+        store.insert(&hello_sha512, b"hello").unwrap();
+        store.insert(&world, b"world").unwrap();
+
+        let live = [hello_sha512.key()].into_iter().collect::<BTreeSet<_>>();
+        let gc = store.gc(&live).unwrap();
+
+        assert_eq!(gc.dropped_keys, vec![hello_md5.key(), world.key()]);
+        assert_eq!(gc.removed_blobs, 1);
+        assert!(store.contains(&hello_sha512));
+        assert!(!store.contains(&hello_md5));
+        assert!(!store.contains(&world));
+    }
+
+    #[test]
+    fn test_data_store_gc_keeps_temporary_files() {
+        let tempdir = TempDir::new().unwrap();
+        let store = DataStore::open(tempdir.path()).unwrap();
+        let partial = tempdir.path().join("objects").join(".tmpABC123");
+        fs::write(&partial, b"hel").unwrap();
+
+        let gc = store.gc(&BTreeSet::new()).unwrap();
+
+        assert_eq!(gc.removed_blobs, 0);
+        assert!(partial.exists());
+    }
+
+    #[test]
+    fn test_data_store_shared() {
+        let tempdir = TempDir::new().unwrap();
+        let hello = object("MD5", "5d41402abc4b2a76b9719d911017c592");
+        let world = object("MD5", "7d793037a0760186574b0282f2f435e7");
+
+        let first = DataStore::open(tempdir.path()).unwrap();
+        let second = DataStore::open(tempdir.path()).unwrap();
+        first.insert(&hello, b"hello").unwrap();
+        first.mark_uploaded("dest", [&hello]);
+        first.save().unwrap();
+        second.insert(&world, b"world").unwrap();
+        second.save().unwrap();
+
+        // Saving one store keeps the entries saved by the other.
+        let store = DataStore::open(tempdir.path()).unwrap();
+        assert!(store.contains(&hello));
+        assert!(store.contains(&world));
+        let objects = [hello.clone(), world.clone()];
+        assert_eq!(store.pending("dest", &objects), vec![&world]);
+
+        // Garbage collection sees entries saved by other stores.
+        let live = [hello.key()].into_iter().collect::<BTreeSet<_>>();
+        let gc = first.gc(&live).unwrap();
+        assert_eq!(gc.dropped_keys, vec![world.key()]);
+        assert_eq!(gc.removed_blobs, 1);
+    }
+}
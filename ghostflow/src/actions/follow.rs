@@ -4,9 +4,13 @@
 //! This action pushes into a ref namespace on the remote that tracks a branch at a coarser
 //! interval than every commit. Its intended use case is to keep a stable reference across a longer
 //! timespan so that asynchronous external tools all use the same commit.
+//!
+//! Optionally, a dated history of follow refs may be kept and pruned after a retention window, and
+//! follow refs may be restricted to fast-forward updates.
 
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
 use git_workarea::{GitContext, GitError};
-use log::info;
+use log::{info, warn};
 use thiserror::Error;
 
 /// Errors which may occur when updating a follow ref.
@@ -23,6 +27,22 @@ pub enum FollowError {
         /// Output from `git push`.
         output: String,
     },
+    /// A follow ref would move to a commit which does not contain its current commit.
+    #[error("refusing to move {} to {} because it is not a fast-forward", refname, branch)]
+    NotFastForward {
+        /// The branch to follow.
+        branch: String,
+        /// The refname which would have been rewound.
+        refname: String,
+    },
+    /// Failure to list history refs.
+    #[error("failed to list history refs under {}: {}", prefix, output)]
+    ListHistory {
+        /// The prefix of the history refs.
+        prefix: String,
+        /// Output from `git ls-remote`.
+        output: String,
+    },
     /// Failure to execute a `git` command.
     #[error("git error: {}", source)]
     Git {
@@ -40,10 +60,27 @@ impl FollowError {
             output: String::from_utf8_lossy(output).into(),
         }
     }
+
+    fn not_fast_forward(branch: String, refname: String) -> Self {
+        FollowError::NotFastForward {
+            branch,
+            refname,
+        }
+    }
+
+    fn list_history(prefix: String, output: &[u8]) -> Self {
+        FollowError::ListHistory {
+            prefix,
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
 }
 
 type FollowResult<T> = Result<T, FollowError>;
 
+/// The format of timestamps used in history refnames.
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
 /// Implementation of the `follow` action.
 #[derive(Debug)]
 pub struct Follow {
@@ -53,6 +90,12 @@ pub struct Follow {
     branch: String,
     /// The reference namespace to use for data.
     ref_namespace: String,
+    /// Whether to keep a history of follow refs.
+    history: bool,
+    /// How long to keep history refs.
+    retention: Option<Duration>,
+    /// Whether to refuse updates which are not fast-forwards.
+    fast_forward_only: bool,
 }
 
 impl Follow {
@@ -65,6 +108,9 @@ impl Follow {
             ctx,
             branch: branch.into(),
             ref_namespace: "follow".into(),
+            history: false,
+            retention: None,
+            fast_forward_only: false,
         }
     }
 
@@ -79,16 +125,72 @@ impl Follow {
         self
     }
 
+    /// Keep a dated history of follow refs.
+    ///
+    /// Each update also pushes to `refs/{namespace}-history/{branch}/{name}/{timestamp}` where the
+    /// timestamp is of the form `20210102T030405Z`. History refs are kept in a separate namespace
+    /// because a ref may not also be a directory of refs.
+    pub fn keep_history(&mut self) -> &mut Self {
+        self.history = true;
+        self
+    }
+
+    /// Prune history refs older than the given duration.
+    ///
+    /// Implies `keep_history`. History refs are pruned when the same name is updated.
+    pub fn history_retention(&mut self, retention: Duration) -> &mut Self {
+        self.history = true;
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Refuse to update follow refs unless the update is a fast-forward.
+    ///
+    /// Use `Follow::force_update` to explicitly allow moving a follow ref backwards.
+    pub fn fast_forward_only(&mut self) -> &mut Self {
+        self.fast_forward_only = true;
+        self
+    }
+
     /// Update the remote ref using the given name.
+    ///
+    /// History refs, if kept, use the current time.
     pub fn update<N>(&self, name: N) -> FollowResult<()>
     where
         N: AsRef<str>,
     {
-        self.update_impl(name.as_ref())
+        self.update_impl(name.as_ref(), Utc::now(), false)
+    }
+
+    /// Update the remote ref using the given name as of a given time.
+    ///
+    /// The `when` is used for history refs.
+    pub fn update_at<N>(&self, name: N, when: DateTime<Utc>) -> FollowResult<()>
+    where
+        N: AsRef<str>,
+    {
+        self.update_impl(name.as_ref(), when, false)
+    }
+
+    /// Update the remote ref using the given name, even if it is not a fast-forward.
+    pub fn force_update<N>(&self, name: N) -> FollowResult<()>
+    where
+        N: AsRef<str>,
+    {
+        self.update_impl(name.as_ref(), Utc::now(), true)
+    }
+
+    /// Update the remote ref using the given name as of a given time, even if it is not a
+    /// fast-forward.
+    pub fn force_update_at<N>(&self, name: N, when: DateTime<Utc>) -> FollowResult<()>
+    where
+        N: AsRef<str>,
+    {
+        self.update_impl(name.as_ref(), when, true)
     }
 
     /// Non-generic version of `update`.
-    fn update_impl(&self, name: &str) -> FollowResult<()> {
+    fn update_impl(&self, name: &str, when: DateTime<Utc>, allow_rewind: bool) -> FollowResult<()> {
         info!(
             target: "ghostflow/follow",
             "following {} into {}",
@@ -97,6 +199,43 @@ impl Follow {
         );
 
         let refname = format!("refs/{}/{}/{}", self.ref_namespace, self.branch, name);
+        let force = if !self.fast_forward_only || allow_rewind {
+            "+"
+        } else {
+            ""
+        };
+        let mut refspecs = vec![format!("{}refs/heads/{}:{}", force, self.branch, refname)];
+
+        if self.history {
+            let history_prefix = format!(
+                "refs/{}-history/{}/{}/",
+                self.ref_namespace, self.branch, name,
+            );
+            refspecs.push(format!(
+                "+refs/heads/{}:{}{}",
+                self.branch,
+                history_prefix,
+                when.format(TIMESTAMP_FORMAT),
+            ));
+
+            if let Some(retention) = self.retention {
+                let cutoff = when - retention;
+                let stale = self
+                    .history_refs(&history_prefix)?
+                    .into_iter()
+                    .filter(|(_, timestamp)| *timestamp < cutoff)
+                    .map(|(refname, _)| {
+                        info!(
+                            target: "ghostflow/follow",
+                            "pruning {}",
+                            refname,
+                        );
+
+                        format!(":{}", refname)
+                    });
+                refspecs.extend(stale);
+            }
+        }
 
         let push = self
             .ctx
@@ -105,10 +244,17 @@ impl Follow {
             .arg("--atomic")
             .arg("--porcelain")
             .arg("origin")
-            .arg(format!("+refs/heads/{}:{}", self.branch, refname))
+            .args(&refspecs)
             .output()
             .map_err(|err| GitError::subcommand("push", err))?;
         if !push.status.success() {
+            if Self::is_rewind(&push.stdout, &refname) {
+                return Err(FollowError::not_fast_forward(
+                    self.branch.clone(),
+                    refname,
+                ));
+            }
+
             return Err(FollowError::push(
                 self.branch.clone(),
                 refname,
@@ -118,4 +264,52 @@ impl Follow {
 
         Ok(())
     }
+
+    /// Whether `git push --porcelain` output indicates that a ref was rejected for not being a
+    /// fast-forward.
+    fn is_rewind(output: &[u8], refname: &str) -> bool {
+        let target = format!(":{}\t", refname);
+        String::from_utf8_lossy(output).lines().any(|line| {
+            line.starts_with('!')
+                && line.contains(&target)
+                && (line.contains("non-fast-forward") || line.contains("fetch first"))
+        })
+    }
+
+    /// The history refs on the remote under a prefix along with their timestamps.
+    fn history_refs(&self, prefix: &str) -> FollowResult<Vec<(String, DateTime<Utc>)>> {
+        let ls_remote = self
+            .ctx
+            .git()
+            .arg("ls-remote")
+            .arg("--refs")
+            .arg("origin")
+            .arg(format!("{}*", prefix))
+            .output()
+            .map_err(|err| GitError::subcommand("ls-remote", err))?;
+        if !ls_remote.status.success() {
+            return Err(FollowError::list_history(prefix.into(), &ls_remote.stderr));
+        }
+
+        Ok(String::from_utf8_lossy(&ls_remote.stdout)
+            .lines()
+            .filter_map(|line| line.split_once('\t').map(|(_, refname)| refname))
+            .filter_map(|refname| {
+                let timestamp = refname.strip_prefix(prefix)?;
+                match NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT) {
+                    Ok(timestamp) => Some((refname.into(), Utc.from_utc_datetime(&timestamp))),
+                    Err(err) => {
+                        warn!(
+                            target: "ghostflow/follow",
+                            "ignoring history ref {} with an invalid timestamp: {}",
+                            refname,
+                            err,
+                        );
+
+                        None
+                    },
+                }
+            })
+            .collect())
+    }
 }
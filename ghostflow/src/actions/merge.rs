@@ -7,12 +7,14 @@
 //! There are multiple implementations of it which allow handling of more complicated merge
 //! strategies.
 
+use std::io;
+
 use git_workarea::{CommitId, GitError, WorkAreaError};
 use itertools::Itertools;
 use thiserror::Error;
 
 use crate::host::HostingServiceError;
-use crate::utils::mr;
+use crate::utils::{mr, SigningError};
 
 /// Errors which may occur when merging a merge request.
 #[derive(Debug, Error)]
@@ -45,6 +47,33 @@ pub enum MergeError {
         /// Output from `git log`.
         output: String,
     },
+    /// Could not list the commits of a merge request topic.
+    #[error("failed to list commits on topic: {}", output)]
+    ListTopic {
+        /// Output from `git rev-list`.
+        output: String,
+    },
+    /// Could not read the information of a commit.
+    #[error("failed to read information for {}: {}", commit, output)]
+    CommitInfo {
+        /// The commit.
+        commit: CommitId,
+        /// Output from `git log`.
+        output: String,
+    },
+    /// Could not write a commit message to `git commit-tree`.
+    #[error("failed to write the commit message to commit-tree: {}", source)]
+    WriteCommitMessage {
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Could not create a commit.
+    #[error("failed to create a commit: {}", output)]
+    CommitTree {
+        /// Output from `git commit-tree`.
+        output: String,
+    },
     /// An internal error.
     ///
     /// This should not happen. Please report an issue if this is returned.
@@ -75,6 +104,13 @@ pub enum MergeError {
         #[from]
         source: HostingServiceError,
     },
+    /// Failure to sign a commit.
+    #[error("signing error: {}", source)]
+    Signing {
+        /// The source of the error.
+        #[from]
+        source: SigningError,
+    },
     /// Errors from internal utility functions.
     #[error("mr utilities error: {}", source)]
     Utility {
@@ -112,6 +148,31 @@ impl MergeError {
             output: String::from_utf8_lossy(output).into(),
         }
     }
+
+    fn list_topic(output: &[u8]) -> Self {
+        MergeError::ListTopic {
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
+
+    fn commit_info(commit: CommitId, output: &[u8]) -> Self {
+        MergeError::CommitInfo {
+            commit,
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
+
+    fn write_commit_message(source: io::Error) -> Self {
+        MergeError::WriteCommitMessage {
+            source,
+        }
+    }
+
+    fn commit_tree(output: &[u8]) -> Self {
+        MergeError::CommitTree {
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
 }
 
 pub(crate) type MergeResult<T> = Result<T, MergeError>;
@@ -171,19 +232,36 @@ mod policy;
 pub use self::policy::MergePolicy;
 pub use self::policy::MergePolicyFilter;
 
+mod approval;
+pub use self::approval::ApprovalGroup;
+pub use self::approval::ApprovalPolicy;
+pub use self::approval::ApprovalPolicyFilter;
+pub use self::approval::ApprovalRequirement;
+
 mod settings;
 pub use self::settings::IntoBranch;
 pub use self::settings::MergeActionResult;
 pub use self::settings::MergeSettings;
 pub use self::settings::MergeTopology;
 
+mod preview;
+pub use self::preview::MergePreview;
+pub use self::preview::MergePreviewOutcome;
+
 mod prelude_impl;
 
 mod trailers;
 
 mod simple;
 pub use self::simple::Merge;
-mod backport;
+pub(crate) mod backport;
 pub use self::backport::MergeBackport;
 pub use self::backport::MergeMany;
 
+mod train;
+pub use self::train::MergeTrain;
+pub use self::train::MergeTrainError;
+pub use self::train::MergeTrainOutcome;
+pub use self::train::MergeTrainStatus;
+pub use self::train::MergeTrainTests;
+
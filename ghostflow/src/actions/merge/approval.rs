@@ -0,0 +1,441 @@
+//! Approval-counting merge policies.
+//!
+//! These policies count the `Reviewed-by` and `Acked-by` trailers on a merge request and require
+//! a minimum number of them before a merge is allowed. Any `Rejected-by` trailer vetoes the merge.
+//! Trailers which come from the author of the merge request or which could not be associated with
+//! a user on the hosting service are not counted, but are still kept for the merge commit.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::actions::merge::{MergePolicy, MergePolicyFilter};
+use crate::host::{MergeRequest, User};
+use crate::utils::Trailer;
+
+/// Trailer tokens which count as an approval.
+const APPROVAL_TOKENS: &[&str] = &["Reviewed-by", "Acked-by"];
+/// Trailer tokens which veto a merge.
+const REJECTION_TOKENS: &[&str] = &["Rejected-by"];
+
+/// A named group of users.
+///
+/// A group is satisfied if any of its members approves the merge request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApprovalGroup {
+    /// The name of the group.
+    name: String,
+    /// The handles of the members of the group.
+    members: BTreeSet<String>,
+}
+
+impl ApprovalGroup {
+    /// Create a new group.
+    pub fn new<N, I, M>(name: N, members: I) -> Self
+    where
+        N: Into<String>,
+        I: IntoIterator<Item = M>,
+        M: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            members: members.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// The name of the group.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether a user is a member of the group.
+    pub fn contains(&self, handle: &str) -> bool {
+        self.members.contains(handle)
+    }
+}
+
+/// An approval which is required in addition to the approval count.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ApprovalRequirement {
+    /// A specific user must approve the merge request.
+    User(String),
+    /// At least one member of a group must approve the merge request.
+    Group(ApprovalGroup),
+}
+
+impl ApprovalRequirement {
+    /// Require approval from a specific user by handle.
+    pub fn user<U>(handle: U) -> Self
+    where
+        U: Into<String>,
+    {
+        ApprovalRequirement::User(handle.into())
+    }
+
+    /// Require approval from at least one member of a group.
+    pub fn group(group: ApprovalGroup) -> Self {
+        ApprovalRequirement::Group(group)
+    }
+
+    /// Whether the requirement is satisfied by a set of approvers.
+    fn is_satisfied_by(&self, approvers: &BTreeSet<String>) -> bool {
+        match self {
+            ApprovalRequirement::User(handle) => approvers.contains(handle),
+            ApprovalRequirement::Group(group) => {
+                approvers.iter().any(|approver| group.contains(approver))
+            },
+        }
+    }
+
+    /// A description of the missing approval.
+    fn missing_reason(&self) -> String {
+        match self {
+            ApprovalRequirement::User(handle) => format!("it requires approval from @{}", handle),
+            ApprovalRequirement::Group(group) => {
+                format!(
+                    "it requires approval from a member of the `{}` group",
+                    group.name,
+                )
+            },
+        }
+    }
+}
+
+/// A merge policy which requires a number of approvals before merging.
+#[derive(Debug, Clone)]
+pub struct ApprovalPolicy {
+    /// The number of distinct approvals required.
+    required: usize,
+    /// Additional approvals required per target branch.
+    branch_requirements: HashMap<String, Vec<ApprovalRequirement>>,
+}
+
+impl ApprovalPolicy {
+    /// Create a new policy requiring `required` approvals.
+    pub fn new(required: usize) -> Self {
+        Self {
+            required,
+            branch_requirements: HashMap::new(),
+        }
+    }
+
+    /// Add a required approval for merge requests targeting the given branch.
+    pub fn require_for_branch<B>(
+        &mut self,
+        branch: B,
+        requirement: ApprovalRequirement,
+    ) -> &mut Self
+    where
+        B: Into<String>,
+    {
+        self.branch_requirements
+            .entry(branch.into())
+            .or_insert_with(Vec::new)
+            .push(requirement);
+        self
+    }
+
+    /// The number of approvals required.
+    pub fn required(&self) -> usize {
+        self.required
+    }
+}
+
+impl MergePolicy for ApprovalPolicy {
+    type Filter = ApprovalPolicyFilter;
+
+    fn for_mr(&self, mr: &MergeRequest) -> Self::Filter {
+        ApprovalPolicyFilter {
+            required: self.required,
+            author: mr.author.handle.clone(),
+            requirements: self
+                .branch_requirements
+                .get(&mr.target_branch)
+                .cloned()
+                .unwrap_or_default(),
+            trailers: Vec::new(),
+            approvers: BTreeSet::new(),
+            rejecters: Vec::new(),
+        }
+    }
+}
+
+/// The filter for a merge request under an `ApprovalPolicy`.
+#[derive(Debug, Clone)]
+pub struct ApprovalPolicyFilter {
+    /// The number of distinct approvals required.
+    required: usize,
+    /// The handle of the author of the merge request.
+    author: String,
+    /// Additional approvals required for the target branch.
+    requirements: Vec<ApprovalRequirement>,
+    /// The trailers to use in the merge commit message.
+    trailers: Vec<Trailer>,
+    /// The handles of users who have approved the merge request.
+    approvers: BTreeSet<String>,
+    /// The users who have rejected the merge request.
+    rejecters: Vec<String>,
+}
+
+impl MergePolicyFilter for ApprovalPolicyFilter {
+    fn process_trailer(&mut self, trailer: &Trailer, user: Option<&User>) {
+        // Trailers which cannot be tied to a user are not trusted and authors may not approve (or
+        // veto) their own merge requests. Such trailers are kept, but are not counted.
+        let counted = user.filter(|user| user.handle != self.author);
+
+        if let Some(user) = counted {
+            let token = trailer.token.as_str();
+            if APPROVAL_TOKENS.contains(&token) {
+                self.approvers.insert(user.handle.clone());
+            } else if REJECTION_TOKENS.contains(&token) {
+                if !self.rejecters.contains(&user.handle) {
+                    self.rejecters.push(user.handle.clone());
+                }
+                return;
+            }
+        }
+
+        self.trailers.push(trailer.clone());
+    }
+
+    fn result(self) -> Result<Vec<Trailer>, Vec<String>> {
+        let mut reasons = self
+            .rejecters
+            .iter()
+            .map(|handle| format!("it has been rejected by @{}", handle))
+            .collect::<Vec<_>>();
+
+        let approvals = self.approvers.len();
+        if approvals < self.required {
+            reasons.push(format!(
+                "it has {} of {} required approvals",
+                approvals, self.required,
+            ));
+        }
+
+        reasons.extend(
+            self.requirements
+                .iter()
+                .filter(|requirement| !requirement.is_satisfied_by(&self.approvers))
+                .map(ApprovalRequirement::missing_reason),
+        );
+
+        if reasons.is_empty() {
+            Ok(self.trailers)
+        } else {
+            Err(reasons)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use git_workarea::CommitId;
+
+    use crate::actions::merge::{
+        ApprovalGroup, ApprovalPolicy, ApprovalRequirement, MergePolicy, MergePolicyFilter,
+    };
+    use crate::host::{Commit, MergeRequest, Repo, User};
+    use crate::utils::Trailer;
+
+    fn make_user(handle: &str) -> User {
+        User {
+            handle: handle.into(),
+            name: format!("{} name", handle),
+            email: format!("{}@example.com", handle),
+        }
+    }
+
+    fn make_mr(target_branch: &str, author: &str) -> MergeRequest {
+        let repo = Repo {
+            name: "base".into(),
+            url: "base".into(),
+            forked_from: None,
+        };
+
+        MergeRequest {
+            source_repo: Some(repo.clone()),
+            source_branch: "topic".into(),
+            target_repo: repo.clone(),
+            target_branch: target_branch.into(),
+            id: 1,
+            url: "url".into(),
+            work_in_progress: false,
+            description: String::new(),
+            old_commit: None,
+            commit: Commit {
+                repo,
+                refname: Some("topic".into()),
+                id: CommitId::new("0000000000000000000000000000000000000000"),
+                last_pipeline: None,
+            },
+            author: make_user(author),
+            reference: "!1".into(),
+            remove_source_branch: false,
+            labels: Vec::new(),
+        }
+    }
+
+    fn trailer(token: &str, user: &User) -> Trailer {
+        Trailer::new(token, format!("{}", user.identity()))
+    }
+
+    fn run_policy(
+        policy: &ApprovalPolicy,
+        mr: &MergeRequest,
+        trailers: &[(Trailer, Option<User>)],
+    ) -> Result<Vec<Trailer>, Vec<String>> {
+        let mut filter = policy.for_mr(mr);
+        trailers
+            .iter()
+            .for_each(|(trailer, user)| filter.process_trailer(trailer, user.as_ref()));
+        filter.result()
+    }
+
+    #[test]
+    fn test_approval_policy_enough_approvals() {
+        let policy = ApprovalPolicy::new(2);
+        let mr = make_mr("master", "author");
+        let reviewer = make_user("reviewer");
+        let acker = make_user("acker");
+        let trailers = [
+            (trailer("Reviewed-by", &reviewer), Some(reviewer.clone())),
+            (trailer("Acked-by", &acker), Some(acker.clone())),
+        ];
+
+        let result = run_policy(&policy, &mr, &trailers).unwrap();
+        assert_eq!(
+            result,
+            vec![
+                trailer("Reviewed-by", &reviewer),
+                trailer("Acked-by", &acker),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_approval_policy_duplicate_approvals() {
+        let policy = ApprovalPolicy::new(2);
+        let mr = make_mr("master", "author");
+        let reviewer = make_user("reviewer");
+        let trailers = [
+            (trailer("Reviewed-by", &reviewer), Some(reviewer.clone())),
+            (trailer("Acked-by", &reviewer), Some(reviewer.clone())),
+        ];
+
+        let reasons = run_policy(&policy, &mr, &trailers).unwrap_err();
+        assert_eq!(reasons, vec!["it has 1 of 2 required approvals"]);
+    }
+
+    #[test]
+    fn test_approval_policy_ignore_author_and_unknown() {
+        let policy = ApprovalPolicy::new(1);
+        let mr = make_mr("master", "author");
+        let author = make_user("author");
+        let unknown = make_user("unknown");
+        let trailers = [
+            (trailer("Reviewed-by", &author), Some(author.clone())),
+            (trailer("Reviewed-by", &unknown), None),
+        ];
+
+        let reasons = run_policy(&policy, &mr, &trailers).unwrap_err();
+        assert_eq!(reasons, vec!["it has 0 of 1 required approvals"]);
+    }
+
+    #[test]
+    fn test_approval_policy_author_and_unknown_pass_through() {
+        let policy = ApprovalPolicy::new(1);
+        let mr = make_mr("master", "author");
+        let author = make_user("author");
+        let unknown = make_user("unknown");
+        let reviewer = make_user("reviewer");
+        let trailers = [
+            (trailer("Reviewed-by", &author), Some(author.clone())),
+            (trailer("Rejected-by", &author), Some(author.clone())),
+            (trailer("Tested-by", &unknown), None),
+            (trailer("Rejected-by", &unknown), None),
+            (trailer("Reviewed-by", &reviewer), Some(reviewer.clone())),
+        ];
+
+        // The trailers are kept, but neither approve nor veto the merge request.
+        let result = run_policy(&policy, &mr, &trailers).unwrap();
+        assert_eq!(
+            result,
+            trailers
+                .iter()
+                .map(|(trailer, _)| trailer.clone())
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    #[test]
+    fn test_approval_policy_rejection_vetoes() {
+        let policy = ApprovalPolicy::new(1);
+        let mr = make_mr("master", "author");
+        let reviewer = make_user("reviewer");
+        let rejecter = make_user("rejecter");
+        let trailers = [
+            (trailer("Reviewed-by", &reviewer), Some(reviewer.clone())),
+            (trailer("Rejected-by", &rejecter), Some(rejecter.clone())),
+        ];
+
+        let reasons = run_policy(&policy, &mr, &trailers).unwrap_err();
+        assert_eq!(reasons, vec!["it has been rejected by @rejecter"]);
+    }
+
+    #[test]
+    fn test_approval_policy_other_trailers_pass_through() {
+        let policy = ApprovalPolicy::new(1);
+        let mr = make_mr("master", "author");
+        let reviewer = make_user("reviewer");
+        let tester = make_user("tester");
+        let trailers = [
+            (trailer("Tested-by", &tester), Some(tester.clone())),
+            (trailer("Reviewed-by", &reviewer), Some(reviewer.clone())),
+        ];
+
+        let result = run_policy(&policy, &mr, &trailers).unwrap();
+        assert_eq!(
+            result,
+            vec![
+                trailer("Tested-by", &tester),
+                trailer("Reviewed-by", &reviewer),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_approval_policy_branch_requirements() {
+        let mut policy = ApprovalPolicy::new(1);
+        policy
+            .require_for_branch("release", ApprovalRequirement::user("maintainer"))
+            .require_for_branch(
+                "release",
+                ApprovalRequirement::group(ApprovalGroup::new("docs", vec!["writer"])),
+            );
+        let reviewer = make_user("reviewer");
+        let trailers = [(trailer("Reviewed-by", &reviewer), Some(reviewer.clone()))];
+
+        // Other branches are unaffected.
+        let mr = make_mr("master", "author");
+        run_policy(&policy, &mr, &trailers).unwrap();
+
+        let mr = make_mr("release", "author");
+        let reasons = run_policy(&policy, &mr, &trailers).unwrap_err();
+        assert_eq!(
+            reasons,
+            vec![
+                "it requires approval from @maintainer",
+                "it requires approval from a member of the `docs` group",
+            ],
+        );
+
+        let maintainer = make_user("maintainer");
+        let writer = make_user("writer");
+        let trailers = [
+            (trailer("Reviewed-by", &reviewer), Some(reviewer.clone())),
+            (trailer("Acked-by", &maintainer), Some(maintainer.clone())),
+            (trailer("Acked-by", &writer), Some(writer)),
+        ];
+        run_policy(&policy, &mr, &trailers).unwrap();
+    }
+}
@@ -4,14 +4,28 @@
 //! This action may be used when a merge request targets a branch, but also contains pieces which
 //! need to be backported to other branches. It performs all of the relevant merges and pushes them
 //! to the repository at once.
+//!
+//! Backports may also be requested by the merge request itself using `Backport:` directives in
+//! its description or comments:
+//!
+//! ```text
+//! Backport: release-2.4
+//! Backport: release-2.3:HEAD~2
+//! ```
+//!
+//! The optional commit is resolved relative to the merge request where `HEAD` refers to the
+//! merge request's commit.
 
+use std::collections::btree_map::{BTreeMap, Entry};
 use std::collections::hash_map::HashMap;
 use std::collections::hash_set::HashSet;
+use std::iter;
 
 use chrono::{DateTime, Utc};
 use either::{Left, Right};
-use git_workarea::{CommitId, GitContext, Identity};
-use log::debug;
+use git_workarea::{CommitId, GitContext, GitError, Identity};
+use itertools::Itertools;
+use log::{debug, error};
 use topological_sort::TopologicalSort;
 
 use crate::actions::merge::prelude_impl::*;
@@ -42,12 +56,61 @@ where
     }
 }
 
+/// The prefix for backport directive lines.
+const BACKPORT_DIRECTIVE: &str = "Backport:";
+
+/// A request to backport a merge request into a branch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct BackportDirective {
+    /// The branch to backport into.
+    pub(crate) branch: String,
+    /// The commit to backport (relative to the merge request's `HEAD`).
+    pub(crate) commit: Option<String>,
+}
+
+/// Parse backport directives from the content of a description or comment.
+///
+/// Malformed directives are returned as errors containing the offending line.
+pub(crate) fn parse_backport_directives(content: &str) -> Vec<Result<BackportDirective, String>> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter_map(|line| {
+            let value = line.strip_prefix(BACKPORT_DIRECTIVE)?.trim();
+            let (branch, commit) = match value.split_once(':') {
+                Some((branch, commit)) => (branch.trim(), Some(commit.trim())),
+                None => (value, None),
+            };
+
+            if branch.is_empty() || branch.contains(char::is_whitespace) {
+                return Some(Err(line.into()));
+            }
+            let commit = match commit {
+                Some("") => return Some(Err(line.into())),
+                Some(commit) if commit.contains(char::is_whitespace) => {
+                    return Some(Err(line.into()));
+                },
+                commit => commit.map(Into::into),
+            };
+
+            Some(Ok(BackportDirective {
+                branch: branch.into(),
+                commit,
+            }))
+        })
+        .collect()
+}
+
 /// Merge a merge request into multiple target branches.
 pub struct MergeMany {
     /// The context to use for Git actions.
     ctx: GitContext,
     /// The project of the target branches.
     project: HostedProject,
+    /// The users who may request backports.
+    ///
+    /// If `None`, anyone may request backports.
+    backport_requesters: Option<HashSet<String>>,
 }
 
 impl MergeMany {
@@ -56,9 +119,56 @@ impl MergeMany {
         Self {
             ctx,
             project,
+            backport_requesters: None,
         }
     }
 
+    /// Restrict the users who may request backports using directives.
+    ///
+    /// Directives in the description are attributed to the author of the merge request.
+    pub fn backport_requesters<I, U>(&mut self, handles: I) -> &mut Self
+    where
+        I: IntoIterator<Item = U>,
+        U: Into<String>,
+    {
+        self.backport_requesters = Some(handles.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Merge a merge request into its target branch and any branches requested by directives.
+    ///
+    /// Each requested branch must have an entry in `settings`, as must the target branch of the
+    /// merge request. All directives are checked before anything is merged; any problems are
+    /// reported together in a single comment.
+    pub fn merge_mr_with_directives<'a, P>(
+        &self,
+        mr: &MergeRequest,
+        who: &Identity,
+        when: DateTime<Utc>,
+        settings: &'a [MergeSettings<P>],
+    ) -> MergeResult<MergeActionResult>
+    where
+        P: MergePolicy + 'a,
+    {
+        let backports = match self.backports_from_directives(mr, settings)? {
+            Ok(backports) => backports,
+            Err(problems) => {
+                self.send_mr_comment(
+                    mr,
+                    &format!(
+                        "This merge request may not be merged because of invalid backport \
+                         requests:\n\n  \
+                         - {}",
+                        problems.into_iter().join("\n  - "),
+                    ),
+                );
+                return Ok(MergeActionResult::Failed);
+            },
+        };
+
+        self.merge_mr(mr, who, when, backports)
+    }
+
     /// Merge a merge request into multiple target branches.
     ///
     /// Information for the merge commit is gathered from the comment stream as well as the merge
@@ -165,8 +275,192 @@ impl MergeMany {
         let refs_status = refs.iter().map(|(&branch, &(ref commit, settings))| {
             (branch.into(), commit.clone(), settings.into_branches())
         });
-        let push_refs = merger.perform_update_merges(sorter, refs_status, &info, renamer)?;
+        let signing_keys = refs
+            .iter()
+            .map(|(&branch, &(_, settings))| (branch, settings.signing_key()))
+            .collect();
+        let push_refs =
+            merger.perform_update_merges(sorter, refs_status, &info, renamer, &signing_keys)?;
 
         merger.push_refs(quiet, push_refs)
     }
+
+    /// Gather the backports requested by the directives on a merge request.
+    ///
+    /// Returns the list of problems with the requests if any are invalid.
+    fn backports_from_directives<'a, P>(
+        &self,
+        mr: &MergeRequest,
+        settings: &'a [MergeSettings<P>],
+    ) -> MergeResult<Result<Vec<MergeBackport<'a, P>>, Vec<String>>> {
+        let settings_for =
+            move |branch: &str| settings.iter().find(|settings| settings.branch() == branch);
+
+        let mut problems = Vec::new();
+        let mut requested = BTreeMap::new();
+
+        if let Some(target) = settings_for(&mr.target_branch) {
+            requested.insert(target.branch(), (target, None));
+        } else {
+            problems.push(format!(
+                "the target branch `{}` does not accept merges",
+                mr.target_branch,
+            ));
+        }
+
+        self.project.service.fetch_mr(&self.ctx, mr)?;
+
+        let comments = self.project.service.get_mr_comments(mr)?;
+        let comments = comments
+            .iter()
+            // Comments from before the last update do not apply to the current topic.
+            .rev()
+            .take_while(|comment| !comment.is_branch_update)
+            .filter(|comment| !comment.is_system)
+            .collect::<Vec<_>>();
+        let directives = iter::once((&mr.author.handle, mr.description.as_str()))
+            .chain(
+                comments
+                    .into_iter()
+                    .rev()
+                    .map(|comment| (&comment.author.handle, comment.content.as_str())),
+            )
+            .flat_map(|(handle, content)| {
+                parse_backport_directives(content)
+                    .into_iter()
+                    .map(move |directive| (handle, directive))
+            });
+
+        for (handle, directive) in directives {
+            let directive = match directive {
+                Ok(directive) => directive,
+                Err(line) => {
+                    problems.push(format!("`{}` is not a valid backport directive", line));
+                    continue;
+                },
+            };
+            let request = match directive.commit.as_ref() {
+                Some(commit) => format!("{}:{}", directive.branch, commit),
+                None => directive.branch.clone(),
+            };
+
+            if let Some(requesters) = self.backport_requesters.as_ref() {
+                if !requesters.contains(handle) {
+                    problems.push(format!(
+                        "@{} is not allowed to request backports (`{}`)",
+                        handle, request,
+                    ));
+                    continue;
+                }
+            }
+
+            let branch_settings = if let Some(branch_settings) = settings_for(&directive.branch) {
+                branch_settings
+            } else {
+                problems.push(format!(
+                    "`{}` is not a branch which accepts backports",
+                    directive.branch,
+                ));
+                continue;
+            };
+
+            let commit = if let Some(commit) = directive.commit.as_ref() {
+                if let Some(commit_id) = self.resolve_commit(mr, commit)? {
+                    let commit_state = mr::commit_state(
+                        &self.ctx,
+                        mr,
+                        &commit_id,
+                        &CommitId::new(branch_settings.branch()),
+                    )?;
+                    match commit_state {
+                        CommitMergeRequestState::OnMergeRequest => Some(commit_id),
+                        CommitMergeRequestState::OnTarget => {
+                            problems.push(format!(
+                                "`{}` is already on `{}`",
+                                commit, directive.branch,
+                            ));
+                            continue;
+                        },
+                        CommitMergeRequestState::Unrelated => {
+                            problems.push(format!(
+                                "`{}` is not part of this merge request (`{}`)",
+                                commit, request,
+                            ));
+                            continue;
+                        },
+                    }
+                } else {
+                    problems.push(format!("`{}` does not name a commit (`{}`)", commit, request));
+                    continue;
+                }
+            } else {
+                None
+            };
+
+            match requested.entry(branch_settings.branch()) {
+                Entry::Vacant(entry) => {
+                    entry.insert((branch_settings, commit));
+                },
+                Entry::Occupied(mut entry) => {
+                    let existing = &mut entry.get_mut().1;
+                    if existing.is_none() {
+                        *existing = commit;
+                    } else if commit.is_some() && *existing != commit {
+                        problems.push(format!(
+                            "`{}` is requested with multiple commits",
+                            directive.branch,
+                        ));
+                    }
+                },
+            }
+        }
+
+        Ok(if problems.is_empty() {
+            Ok(requested
+                .into_iter()
+                .map(|(_, (settings, commit))| MergeBackport::new(settings, commit))
+                .collect())
+        } else {
+            Err(problems)
+        })
+    }
+
+    /// Resolve a commit relative to a merge request.
+    fn resolve_commit(&self, mr: &MergeRequest, commit: &str) -> MergeResult<Option<CommitId>> {
+        let rev = match commit.strip_prefix("HEAD") {
+            Some(rest) => format!("{}{}", mr.commit.id, rest),
+            None => commit.into(),
+        };
+
+        let rev_parse = self
+            .ctx
+            .git()
+            .arg("rev-parse")
+            .arg("--verify")
+            .arg("--quiet")
+            .arg(format!("{}^{{commit}}", rev))
+            .output()
+            .map_err(|err| GitError::subcommand("rev-parse", err))?;
+
+        Ok(if rev_parse.status.success() {
+            Some(CommitId::new(
+                String::from_utf8_lossy(&rev_parse.stdout).trim(),
+            ))
+        } else {
+            None
+        })
+    }
+
+    /// Send a comment to a merge request.
+    fn send_mr_comment(&self, mr: &MergeRequest, content: &str) {
+        if let Err(err) = self.project.service.post_mr_comment(mr, content) {
+            error!(
+                target: "ghostflow/merge",
+                "failed to post a comment to merge request: {}, {}: {:?}",
+                self.project.name,
+                mr.id,
+                err,
+            );
+        }
+    }
 }
@@ -0,0 +1,157 @@
+//! Previews of merges.
+//!
+//! A preview describes what merging a merge request would do without creating any commits or
+//! pushing anything.
+
+use git_workarea::CommitId;
+use itertools::Itertools;
+
+use crate::actions::merge::MergeTopology;
+
+/// What would happen when merging a merge request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergePreviewOutcome {
+    /// A merge commit would be created.
+    Merge {
+        /// The message of the merge commit.
+        commit_message: String,
+    },
+    /// The branch would be fast-forwarded to the merge request.
+    FastForward {
+        /// The commit the branch would point to.
+        commit: CommitId,
+    },
+    /// The topic would be squashed into a single commit.
+    Squash {
+        /// The message of the squashed commit.
+        commit_message: String,
+    },
+    /// The topic would be rebased onto the branch.
+    Rebase {
+        /// The number of commits which would be rebased.
+        commits: usize,
+        /// The trailers appended to each rebased commit.
+        trailers: String,
+    },
+    /// The merge policy rejects the merge.
+    Rejected {
+        /// The reasons given by the merge policy.
+        reasons: Vec<String>,
+    },
+    /// The merge request conflicts with the branch.
+    Conflicts {
+        /// The conflicting paths.
+        paths: Vec<String>,
+    },
+    /// The merge request may not be merged for another reason.
+    Unmergeable {
+        /// Why the merge request may not be merged.
+        reason: String,
+    },
+}
+
+impl MergePreviewOutcome {
+    /// Whether the merge would succeed or not.
+    pub fn is_mergeable(&self) -> bool {
+        matches!(
+            self,
+            MergePreviewOutcome::Merge { .. }
+                | MergePreviewOutcome::FastForward { .. }
+                | MergePreviewOutcome::Squash { .. }
+                | MergePreviewOutcome::Rebase { .. }
+        )
+    }
+}
+
+/// A preview of merging a merge request into a branch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergePreview {
+    /// The branch which would be merged into.
+    pub branch: String,
+    /// The configured merge topology.
+    pub topology: MergeTopology,
+    /// What would happen.
+    pub outcome: MergePreviewOutcome,
+    /// The merges which would update "into" branches, as `(source, target)` pairs.
+    pub update_merges: Vec<(String, String)>,
+}
+
+impl MergePreview {
+    /// Render the preview as a comment.
+    pub fn render(&self) -> String {
+        let mut content = format!(
+            "Merge preview for `{}` (nothing has been merged or pushed):\n\n",
+            self.branch,
+        );
+
+        match &self.outcome {
+            MergePreviewOutcome::Merge {
+                commit_message,
+            } => {
+                content.push_str(&format!(
+                    "The topic would be merged with the following commit message:\n\n```\n{}```",
+                    commit_message,
+                ));
+            },
+            MergePreviewOutcome::FastForward {
+                commit,
+            } => {
+                content.push_str(&format!(
+                    "`{}` would be fast-forwarded to {}.",
+                    self.branch, commit,
+                ));
+            },
+            MergePreviewOutcome::Squash {
+                commit_message,
+            } => {
+                content.push_str(&format!(
+                    "The topic would be squashed into a single commit with the following \
+                     message:\n\n```\n{}```",
+                    commit_message,
+                ));
+            },
+            MergePreviewOutcome::Rebase {
+                commits,
+                trailers,
+            } => {
+                content.push_str(&format!(
+                    "The {} commit(s) of the topic would be rebased onto `{}` with the following \
+                     trailers appended:\n\n```\n{}```",
+                    commits, self.branch, trailers,
+                ));
+            },
+            MergePreviewOutcome::Rejected {
+                reasons,
+            } => {
+                content.push_str(&format!(
+                    "The topic may not be merged because:\n\n  - {}",
+                    reasons.iter().join("\n  - "),
+                ));
+            },
+            MergePreviewOutcome::Conflicts {
+                paths,
+            } => {
+                content.push_str(&format!(
+                    "The topic conflicts with `{}` in the following paths:\n\n  - `{}`",
+                    self.branch,
+                    paths.iter().join("`\n  - `"),
+                ));
+            },
+            MergePreviewOutcome::Unmergeable {
+                reason,
+            } => content.push_str(reason),
+        }
+
+        if self.outcome.is_mergeable() && !self.update_merges.is_empty() {
+            content.push_str(&format!(
+                "\n\nThe following branches would then be updated:\n\n  - {}",
+                self.update_merges
+                    .iter()
+                    .map(|(source, target)| format!("`{}` into `{}`", source, target))
+                    .join("\n  - "),
+            ));
+        }
+
+        content
+    }
+}
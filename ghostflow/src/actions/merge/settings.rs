@@ -1,19 +1,23 @@
 use std::collections::HashMap;
+use std::io::Write;
 use std::iter;
+use std::process::Stdio;
 
 use chrono::{DateTime, Utc};
 use either::{Either, Left, Right};
-use git_workarea::{CommitId, GitContext, GitError, Identity, MergeStatus};
+use git_workarea::{CommitId, GitContext, GitError, Identity, MergeCommand, MergeStatus};
 use itertools::Itertools;
 use log::{debug, error, info, warn};
 use topological_sort::TopologicalSort;
 
 use crate::actions::merge::trailers::ParseTrailers;
 use crate::actions::merge::{
-    InternalMergeError, MergeError, MergePolicy, MergePolicyFilter, MergeResult,
+    InternalMergeError, MergeError, MergePolicy, MergePolicyFilter, MergePreview,
+    MergePreviewOutcome, MergeResult,
 };
 use crate::host::{HostedProject, MergeRequest, User};
-use crate::utils::Trailer;
+use crate::utils::conflicts::ConflictReport;
+use crate::utils::{signing, SigningKey, Trailer};
 
 /// Information about how to merge into a branch.
 #[derive(Debug, Clone)]
@@ -99,11 +103,22 @@ pub enum MergeTopology {
     /// The topic branch will be pushed directly to the target branch. If it cannot be pushed, it
     /// will result in a failure to merge.
     FastForwardOnly,
+    /// Squash the topic into a single commit.
+    ///
+    /// The commit message is the topic summary from the merge request's `message` block (or a
+    /// summary of the topic's commits if there is none) followed by the trailers from the merge
+    /// policy. The commit is authored by the author of the merge request.
+    Squash,
+    /// Rebase the topic onto the target branch and fast forward.
+    ///
+    /// Each commit in the topic keeps its authorship, but has the trailers from the merge policy
+    /// appended to its message. Topics containing merge commits may not be rebased.
+    RebaseThenFastForward,
 }
 
 impl MergeTopology {
     fn allow_fast_forward(self) -> bool {
-        !matches!(self, Self::NoFastForward)
+        matches!(self, Self::FastForwardIfPossible | Self::FastForwardOnly)
     }
 
     fn must_fast_forward(self) -> bool {
@@ -139,6 +154,8 @@ pub struct MergeSettings<P> {
     ///
     /// This controls how the topic is merged into the target branch.
     merge_topology: MergeTopology,
+    /// The key to sign commits created by the merge with.
+    signing_key: Option<SigningKey>,
 }
 
 impl<P> MergeSettings<P> {
@@ -156,6 +173,7 @@ impl<P> MergeSettings<P> {
             log_limit: None,
             elide_branch_name: false,
             merge_topology: MergeTopology::NoFastForward,
+            signing_key: None,
         }
     }
 
@@ -202,6 +220,20 @@ impl<P> MergeSettings<P> {
         self
     }
 
+    /// Sign commits created when merging.
+    ///
+    /// This includes merge commits, squashed and rebased commits, and the merges into "into"
+    /// branches. Fast-forward merges push the topic's commits as-is and are not signed.
+    pub fn sign_with(&mut self, key: Option<SigningKey>) -> &mut Self {
+        self.signing_key = key;
+        self
+    }
+
+    /// The key used to sign commits.
+    pub fn signing_key(&self) -> Option<&SigningKey> {
+        self.signing_key.as_ref()
+    }
+
     /// The name of the branch to use in merge commits.
     pub fn merge_name(&self) -> (bool, &str) {
         (
@@ -230,6 +262,32 @@ impl<P> MergeSettings<P> {
         &self.into_branches
     }
 
+    /// The merges which update "into" branches after merging into this branch.
+    ///
+    /// Each entry is a `(source, target)` pair of branch names, in the order they are performed.
+    fn update_merges(&self) -> Vec<(String, String)> {
+        let mut sorter = TopologicalSort::new();
+        self.add_topo_links(&mut sorter);
+
+        let mut order = Vec::new();
+        while let Some(branch) = sorter.pop() {
+            order.push(branch);
+        }
+
+        let mut edges = Vec::new();
+        let mut pending = vec![(self.branch.as_str(), self.into_branches.as_slice())];
+        while let Some((source, intos)) = pending.pop() {
+            for into_branch in intos {
+                edges.push((source.to_string(), into_branch.name().to_string()));
+                pending.push((into_branch.name(), into_branch.chain_branches()));
+            }
+        }
+        edges.sort_by_key(|(_, target)| order.iter().position(|branch| branch == target));
+        edges.dedup();
+
+        edges
+    }
+
     fn add_topo_links(&self, sorter: &mut TopologicalSort<String>) {
         self.into_branches.iter().for_each(|into_branch| {
             debug!(
@@ -243,6 +301,63 @@ impl<P> MergeSettings<P> {
     }
 }
 
+/// Why a merge request may not be merged into a branch.
+enum MergeBlocker {
+    /// The merge request may not be merged; the report explains why.
+    Unmergeable(ConflictReport),
+    /// The merge request conflicts with the branch.
+    Conflicts(ConflictReport),
+    /// The merge policy rejects the merge for the given reasons.
+    Rejected(Vec<String>),
+}
+
+impl MergeBlocker {
+    /// Render a comment for the merge request.
+    fn render(&self, branch: &str) -> String {
+        match self {
+            MergeBlocker::Unmergeable(report) | MergeBlocker::Conflicts(report) => report.render(),
+            MergeBlocker::Rejected(reasons) => {
+                format!(
+                    "This merge request may not be merged into `{}` because:\n\n  - {}",
+                    branch,
+                    reasons.iter().join("  \n  - "),
+                )
+            },
+        }
+    }
+
+    /// The outcome of a preview which is blocked.
+    fn into_outcome(self) -> MergePreviewOutcome {
+        match self {
+            MergeBlocker::Unmergeable(report) => {
+                MergePreviewOutcome::Unmergeable {
+                    reason: report.headline().into(),
+                }
+            },
+            MergeBlocker::Conflicts(report) => {
+                MergePreviewOutcome::Conflicts {
+                    paths: report.paths().to_vec(),
+                }
+            },
+            MergeBlocker::Rejected(reasons) => {
+                MergePreviewOutcome::Rejected {
+                    reasons,
+                }
+            },
+        }
+    }
+}
+
+/// The plan for merging a merge request into a branch.
+enum MergePlan<T> {
+    /// The branch may be fast-forwarded to the merge request.
+    FastForward,
+    /// The merge was performed.
+    Ready(T),
+    /// The merge request may not be merged.
+    Blocked(MergeBlocker),
+}
+
 /// Information required when performing a merge.
 pub struct MergeInformation<'a> {
     /// The name of the topic that is being merged.
@@ -308,7 +423,9 @@ impl<'a> Merger<'a> {
         renamer.insert(settings.branch.clone(), settings.merge_name());
         settings.add_topo_links(&mut sorter);
         let refs = iter::once((settings.branch.clone(), commit_id, settings.into_branches()));
-        let push_refs = self.perform_update_merges(sorter, refs, &info, renamer)?;
+        let signing_keys = iter::once((settings.branch(), settings.signing_key())).collect();
+        let push_refs =
+            self.perform_update_merges(sorter, refs, &info, renamer, &signing_keys)?;
         self.push_refs(settings.quiet, push_refs)
     }
 
@@ -341,6 +458,9 @@ impl<'a> Merger<'a> {
     /// This takes the sorted set of branches which have been merged into by a merge request and
     /// updates all of their "into" branches so that they are always synchronized.
     ///
+    /// Update merges into a branch in `signing_keys` are signed with its key. Update merges into
+    /// other branches are signed with the key of the branch being merged.
+    ///
     /// Returns a vector of commits which need to be pushed to the given branches on the remote.
     pub fn perform_update_merges<'b, I>(
         &self,
@@ -348,6 +468,7 @@ impl<'a> Merger<'a> {
         refs: I,
         info: &MergeInformation<'b>,
         renamer: HashMap<String, (bool, &str)>,
+        signing_keys: &HashMap<&str, Option<&SigningKey>>,
     ) -> MergeResult<Vec<(CommitId, String)>>
     where
         I: IntoIterator<Item = (String, CommitId, &'b [IntoBranch])>,
@@ -429,7 +550,13 @@ impl<'a> Merger<'a> {
                         );
 
                         // Fold using the newly created merge commit.
-                        Ok(merge_command.commit(commit_message)?)
+                        let merge_commit = merge_command.commit(commit_message)?;
+                        let signing_key = signing_keys
+                            .get(target_branch.as_str())
+                            .or_else(|| signing_keys.get(source_branch.as_str()))
+                            .copied()
+                            .flatten();
+                        self.sign(signing_key, merge_commit)
                     },
                 )?;
 
@@ -471,23 +598,22 @@ impl<'a> Merger<'a> {
             .collect())
     }
 
-    /// Create a merge commit for the merge request into the branch.
-    pub fn create_merge<'b, P>(
+    /// Plan the merge of the merge request into the branch.
+    ///
+    /// This performs the checks shared by merging and previewing: whether the topic is mergeable
+    /// at all, whether the branch may be fast-forwarded, whether the topic conflicts with the
+    /// branch, and whether the policy allows the merge. If a commit is required, `step` is given
+    /// the prepared merge command and the trailers for commit messages.
+    fn plan_merge<P, F, T>(
         &self,
         settings: &MergeSettings<P>,
-        info: &MergeInformation<'b>,
         commit_id: &CommitId,
-    ) -> StepResult<CommitId>
+        step: F,
+    ) -> MergeResult<MergePlan<T>>
     where
         P: MergePolicy,
+        F: FnOnce(MergeCommand, String) -> MergeResult<Either<T, MergeBlocker>>,
     {
-        info!(
-            target: "ghostflow/merge",
-            "preparing to merge {} into {}",
-            self.mr.url,
-            settings.branch,
-        );
-
         let branch_id = CommitId::new(&settings.branch);
 
         // Determine if the topic is mergeable at all.
@@ -495,56 +621,264 @@ impl<'a> Merger<'a> {
         let bases = if let MergeStatus::Mergeable(bases) = merge_status {
             bases
         } else {
-            self.send_mr_comment(&unmerged_status_message(&settings.branch, &merge_status));
-            return Ok(Right(MergeActionResult::Failed));
+            let mut report =
+                ConflictReport::new(unmerged_status_message(&settings.branch, &merge_status));
+            if let MergeStatus::NoCommonHistory = merge_status {
+                report.reproduce_merge(&settings.branch, self.mr);
+            }
+            return Ok(MergePlan::Blocked(MergeBlocker::Unmergeable(report)));
         };
 
         // Check the desired topology and the potential.
         if settings.merge_topology.allow_fast_forward() {
-            let can_fast_forward = self
-                .ctx
-                .git()
-                .arg("merge-base")
-                .arg("--is-ancestor")
-                .arg(&settings.branch)
-                .arg(commit_id.as_str())
-                .status()
-                .map_err(|err| GitError::subcommand("merge-base --is-ancestor", err))?;
-
-            if can_fast_forward.success() {
-                // Use the to-be-merged commit as the merge commit.
-                return Ok(Left(commit_id.clone()));
+            if self.can_fast_forward(settings, commit_id)? {
+                return Ok(MergePlan::FastForward);
             } else if settings.merge_topology.must_fast_forward() {
-                // A fast-forward merge is not possible.
-                self.send_mr_comment(&no_fast_forward_possible_message(&settings.branch));
-                return Ok(Right(MergeActionResult::Failed));
+                let report =
+                    ConflictReport::new(no_fast_forward_possible_message(&settings.branch));
+                return Ok(MergePlan::Blocked(MergeBlocker::Unmergeable(report)));
             }
         }
 
         // Prepare a work area to perform the actual merge.
         let workarea = self.ctx.prepare(&branch_id)?;
         let merge_result = workarea.setup_merge(&bases, &branch_id, commit_id)?;
-        let mut merge_command = match merge_result {
+        let merge_command = match merge_result {
             git_workarea::MergeResult::Conflict(conflicts) => {
-                let mut conflict_paths = conflicts
-                    .iter()
-                    .map(|conflict| conflict.path().to_string_lossy())
-                    .dedup();
-                self.send_mr_comment(&format!(
-                    "This merge request contains conflicts with `{}` in \
-                     the following paths:\n\n  - `{}`",
+                let mut report = ConflictReport::new(format!(
+                    "This merge request contains conflicts with `{}` in the following paths:",
                     settings.branch,
-                    conflict_paths.join("`\n  - `"),
                 ));
-                return Ok(Right(MergeActionResult::Failed));
+                report
+                    .conflicts(&conflicts)
+                    .reproduce_merge(&settings.branch, self.mr);
+                return Ok(MergePlan::Blocked(MergeBlocker::Conflicts(report)));
             },
             git_workarea::MergeResult::Ready(command) => command,
         };
 
-        // Add authorship information to the commit message. Committer information is provided by
-        // the default git environment.
-        merge_command.author(info.who).author_date(info.when);
+        let trailers = match self.policy_trailers(settings) {
+            Ok(trailers) => trailers,
+            Err(reasons) => {
+                return Ok(MergePlan::Blocked(MergeBlocker::Rejected(reasons)));
+            },
+        };
+
+        Ok(match step(merge_command, trailers)? {
+            Left(res) => MergePlan::Ready(res),
+            Right(blocker) => MergePlan::Blocked(blocker),
+        })
+    }
 
+    /// Create a merge commit for the merge request into the branch.
+    pub fn create_merge<'b, P>(
+        &self,
+        settings: &MergeSettings<P>,
+        info: &MergeInformation<'b>,
+        commit_id: &CommitId,
+    ) -> StepResult<CommitId>
+    where
+        P: MergePolicy,
+    {
+        info!(
+            target: "ghostflow/merge",
+            "preparing to merge {} into {}",
+            self.mr.url,
+            settings.branch,
+        );
+
+        let branch_id = CommitId::new(&settings.branch);
+
+        let plan = self.plan_merge(settings, commit_id, |mut merge_command, trailers| {
+            // Add authorship information to the commit message. Committer information is
+            // provided by the default git environment.
+            merge_command.author(info.who).author_date(info.when);
+
+            match settings.merge_topology {
+                MergeTopology::Squash => {
+                    let commit_message = self.squash_commit_message(
+                        settings,
+                        info.topic_name,
+                        commit_id,
+                        &trailers,
+                    )?;
+
+                    info!(
+                        target: "ghostflow/merge",
+                        "squashing {} into {}",
+                        self.mr.url,
+                        settings.branch,
+                    );
+
+                    let merge_commit = merge_command.commit(commit_message.clone())?;
+                    let author = CommitAuthorship {
+                        name: self.mr.author.name.clone(),
+                        email: self.mr.author.email.clone(),
+                        date: info.when.to_rfc2822(),
+                    };
+                    let squash_commit =
+                        self.commit_tree(&merge_commit, &branch_id, &author, &commit_message)?;
+                    Ok(Left(self.sign(settings.signing_key(), squash_commit)?))
+                },
+                MergeTopology::RebaseThenFastForward => {
+                    info!(
+                        target: "ghostflow/merge",
+                        "rebasing {} onto {}",
+                        self.mr.url,
+                        settings.branch,
+                    );
+
+                    let commits = self.topic_commits(settings, commit_id)?;
+                    self.rebase_commits(
+                        settings,
+                        Some(info),
+                        settings.signing_key(),
+                        &commits,
+                        &trailers,
+                    )
+                },
+                MergeTopology::NoFastForward
+                | MergeTopology::FastForwardIfPossible
+                | MergeTopology::FastForwardOnly => {
+                    let commit_message = self.build_commit_message(
+                        settings,
+                        info.topic_name,
+                        commit_id,
+                        &trailers,
+                    )?;
+
+                    info!(
+                        target: "ghostflow/merge",
+                        "merging {} into {}",
+                        self.mr.url,
+                        settings.branch,
+                    );
+
+                    let merge_commit = merge_command.commit(commit_message)?;
+                    Ok(Left(self.sign(settings.signing_key(), merge_commit)?))
+                },
+            }
+        })?;
+
+        Ok(match plan {
+            // Use the to-be-merged commit as the merge commit.
+            MergePlan::FastForward => Left(commit_id.clone()),
+            MergePlan::Ready(commit) => Left(commit),
+            MergePlan::Blocked(blocker) => {
+                self.send_mr_comment(&blocker.render(&settings.branch));
+                Right(MergeActionResult::Failed)
+            },
+        })
+    }
+
+    /// Preview the merge of the merge request into the branch.
+    ///
+    /// This performs the same checks as a merge and builds the commit messages which would be
+    /// used, but updates no refs and pushes nothing. Rebases are performed on unreferenced
+    /// commits to find conflicts between the rebased commits and the branch.
+    pub fn preview_mr<P>(
+        &self,
+        settings: &MergeSettings<P>,
+        topic_name: &str,
+    ) -> MergeResult<MergePreview>
+    where
+        P: MergePolicy,
+    {
+        let preview = |outcome| {
+            Ok(MergePreview {
+                branch: settings.branch.clone(),
+                topology: settings.merge_topology,
+                outcome,
+                update_merges: settings.update_merges(),
+            })
+        };
+
+        if self.mr.work_in_progress {
+            return preview(MergePreviewOutcome::Unmergeable {
+                reason: "This merge request is marked as a Work in Progress and may not be merged."
+                    .into(),
+            });
+        }
+
+        // Fetch the commit into the merge's git context.
+        self.project.service.fetch_mr(self.ctx, self.mr)?;
+
+        let commit_id = &self.mr.commit.id;
+        let plan = self.plan_merge(settings, commit_id, |_, trailers| {
+            Ok(match settings.merge_topology {
+                MergeTopology::Squash => {
+                    Left(MergePreviewOutcome::Squash {
+                        commit_message: self.squash_commit_message(
+                            settings,
+                            topic_name,
+                            commit_id,
+                            &trailers,
+                        )?,
+                    })
+                },
+                MergeTopology::RebaseThenFastForward => {
+                    let commits = self.topic_commits(settings, commit_id)?;
+                    self.rebase_commits(settings, None, None, &commits, &trailers)?
+                        .map_left(|_| {
+                            MergePreviewOutcome::Rebase {
+                                commits: commits.len(),
+                                trailers,
+                            }
+                        })
+                },
+                MergeTopology::NoFastForward
+                | MergeTopology::FastForwardIfPossible
+                | MergeTopology::FastForwardOnly => {
+                    Left(MergePreviewOutcome::Merge {
+                        commit_message: self.build_commit_message(
+                            settings,
+                            topic_name,
+                            commit_id,
+                            &trailers,
+                        )?,
+                    })
+                },
+            })
+        })?;
+
+        preview(match plan {
+            MergePlan::FastForward => {
+                MergePreviewOutcome::FastForward {
+                    commit: commit_id.clone(),
+                }
+            },
+            MergePlan::Ready(outcome) => outcome,
+            MergePlan::Blocked(blocker) => blocker.into_outcome(),
+        })
+    }
+
+    /// Whether the branch may be fast-forwarded to a commit.
+    fn can_fast_forward<P>(
+        &self,
+        settings: &MergeSettings<P>,
+        commit_id: &CommitId,
+    ) -> MergeResult<bool> {
+        let is_ancestor = self
+            .ctx
+            .git()
+            .arg("merge-base")
+            .arg("--is-ancestor")
+            .arg(&settings.branch)
+            .arg(commit_id.as_str())
+            .status()
+            .map_err(|err| GitError::subcommand("merge-base --is-ancestor", err))?;
+
+        Ok(is_ancestor.success())
+    }
+
+    /// Filter the trailers of the merge request through the policy.
+    ///
+    /// Returns the trailer summary for commit messages or the reasons the policy rejects the
+    /// merge.
+    fn policy_trailers<P>(&self, settings: &MergeSettings<P>) -> Result<String, Vec<String>>
+    where
+        P: MergePolicy,
+    {
         let mut mr_policy = settings.policy.for_mr(self.mr);
 
         self.trailers
@@ -554,31 +888,178 @@ impl<'a> Merger<'a> {
                 mr_policy.process_trailer(trailer, user_opt.as_ref())
             });
 
-        let trailers = match mr_policy.result() {
-            Ok(trailers) => trailers.into_iter().unique(),
-            Err(reasons) => {
-                let reason = reasons.into_iter().join("  \n  - ");
-                self.send_mr_comment(&format!(
-                    "This merge request may not be merged into `{}` \
-                     because:\n\n  \
-                     - {}",
-                    settings.branch, reason,
-                ));
-                return Ok(Right(MergeActionResult::Failed));
-            },
+        mr_policy
+            .result()
+            .map(|trailers| self.trailer_summary(trailers.into_iter().unique()))
+    }
+
+    /// Rebase the commits of the topic onto the target branch.
+    ///
+    /// The trailers are appended to the message of each rebased commit. If `info` is given, it is
+    /// used as the author of the intermediate commits; otherwise the repository's identity is
+    /// used. Returns the rebased topic or why it may not be rebased.
+    fn rebase_commits<P>(
+        &self,
+        settings: &MergeSettings<P>,
+        info: Option<&MergeInformation<'_>>,
+        signing_key: Option<&SigningKey>,
+        commits: &[Vec<CommitId>],
+        trailers: &str,
+    ) -> MergeResult<Either<CommitId, MergeBlocker>> {
+        if commits.iter().any(|ids| ids.len() != 2) {
+            let report = ConflictReport::new(merge_commits_rebase_message(&settings.branch));
+            return Ok(Right(MergeBlocker::Unmergeable(report)));
+        }
+
+        let mut new_base = CommitId::new(&settings.branch);
+        for ids in commits {
+            let (commit, parent) = (&ids[0], &ids[1]);
+
+            let workarea = self.ctx.prepare(&new_base)?;
+            let merge_result = workarea.setup_merge(&[parent.clone()], &new_base, commit)?;
+            let mut pick_command = match merge_result {
+                git_workarea::MergeResult::Conflict(conflicts) => {
+                    let mut report = ConflictReport::new(format!(
+                        "This merge request may not be rebased onto `{}` because {} conflicts in \
+                         the following paths:",
+                        settings.branch, commit,
+                    ));
+                    report
+                        .conflicts(&conflicts)
+                        .reproduce_rebase(&settings.branch, self.mr);
+                    return Ok(Right(MergeBlocker::Conflicts(report)));
+                },
+                git_workarea::MergeResult::Ready(command) => command,
+            };
+            if let Some(info) = info {
+                pick_command.author(info.who).author_date(info.when);
+            }
+            let picked = pick_command.commit(format!("Rebase {}", commit))?;
+
+            let (author, message) = self.commit_info(commit)?;
+            let message = if trailers.is_empty() {
+                format!("{}\n", message.trim_end())
+            } else {
+                format!("{}\n\n{}", message.trim_end(), trailers)
+            };
+            let rebased = self.commit_tree(&picked, &new_base, &author, &message)?;
+            new_base = self.sign(signing_key, rebased)?;
+        }
+
+        Ok(Left(new_base))
+    }
+
+    /// Sign a commit if a key is given.
+    fn sign(&self, key: Option<&SigningKey>, commit: CommitId) -> MergeResult<CommitId> {
+        Ok(if let Some(key) = key {
+            signing::sign_commit(self.ctx, &commit, key)?
+        } else {
+            commit
+        })
+    }
+
+    /// The commits on the topic which are not on the target branch, oldest first.
+    ///
+    /// Each entry is the commit followed by its parents.
+    fn topic_commits<P>(
+        &self,
+        settings: &MergeSettings<P>,
+        commit_id: &CommitId,
+    ) -> MergeResult<Vec<Vec<CommitId>>> {
+        let rev_list = self
+            .ctx
+            .git()
+            .arg("rev-list")
+            .arg("--reverse")
+            .arg("--topo-order")
+            .arg("--parents")
+            .arg(format!("{}..{}", settings.branch, commit_id))
+            .output()
+            .map_err(|err| GitError::subcommand("rev-list", err))?;
+        if !rev_list.status.success() {
+            return Err(MergeError::list_topic(&rev_list.stderr));
+        }
+
+        Ok(String::from_utf8_lossy(&rev_list.stdout)
+            .lines()
+            .map(|line| {
+                line.split_whitespace()
+                    .map(CommitId::new)
+                    .collect::<Vec<_>>()
+            })
+            .collect())
+    }
+
+    /// Read the authorship and message of a commit.
+    fn commit_info(&self, commit: &CommitId) -> MergeResult<(CommitAuthorship, String)> {
+        let log = self
+            .ctx
+            .git()
+            .arg("log")
+            .arg("--max-count=1")
+            .arg("--format=%an%x00%ae%x00%aI%x00%B")
+            .arg(commit.as_str())
+            .output()
+            .map_err(|err| GitError::subcommand("log", err))?;
+        if !log.status.success() {
+            return Err(MergeError::commit_info(commit.clone(), &log.stderr));
+        }
+
+        let log_output = String::from_utf8_lossy(&log.stdout);
+        let mut fields = log_output.splitn(4, '\0');
+        let mut field = || fields.next().unwrap_or_default().to_string();
+        let author = CommitAuthorship {
+            name: field(),
+            email: field(),
+            date: field(),
         };
 
-        let commit_message =
-            self.build_commit_message(settings, info.topic_name, commit_id, trailers)?;
+        Ok((author, field()))
+    }
 
-        info!(
-            target: "ghostflow/merge",
-            "merging {} into {}",
-            self.mr.url,
-            settings.branch,
-        );
+    /// Create a commit using the tree of another commit.
+    fn commit_tree(
+        &self,
+        tree_of: &CommitId,
+        parent: &CommitId,
+        author: &CommitAuthorship,
+        message: &str,
+    ) -> MergeResult<CommitId> {
+        let mut commit_tree = self
+            .ctx
+            .git()
+            .arg("commit-tree")
+            .arg(format!("{}^{{tree}}", tree_of))
+            .arg("-p")
+            .arg(parent.as_str())
+            .env("GIT_AUTHOR_NAME", &author.name)
+            .env("GIT_AUTHOR_EMAIL", &author.email)
+            .env("GIT_AUTHOR_DATE", &author.date)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| GitError::subcommand("commit-tree (spawn)", err))?;
+
+        {
+            let commit_tree_stdin = commit_tree
+                .stdin
+                .as_mut()
+                .expect("expected commit-tree to have a stdin");
+            commit_tree_stdin
+                .write_all(message.as_bytes())
+                .map_err(MergeError::write_commit_message)?;
+        }
 
-        Ok(Left(merge_command.commit(commit_message)?))
+        let commit_tree = commit_tree
+            .wait_with_output()
+            .map_err(|err| GitError::subcommand("commit-tree (wait)", err))?;
+        if !commit_tree.status.success() {
+            return Err(MergeError::commit_tree(&commit_tree.stderr));
+        }
+
+        let new_commit = String::from_utf8_lossy(&commit_tree.stdout);
+        Ok(CommitId::new(new_commit.trim()))
     }
 
     /// Push the results of a merge action to the remote repository.
@@ -624,17 +1105,53 @@ impl<'a> Merger<'a> {
     }
 
     /// Build a commit message for a merge request.
-    fn build_commit_message<I, P>(
+    fn build_commit_message<P>(
         &self,
         settings: &MergeSettings<P>,
         topic_name: &str,
         commit_id: &CommitId,
-        trailers: I,
-    ) -> MergeResult<String>
-    where
-        I: IntoIterator<Item = Trailer>,
-        P: MergePolicy,
-    {
+        trailer_summary: &str,
+    ) -> MergeResult<String> {
+        let topic_summary = self.topic_summary();
+        let log_summary = self.log_summary(settings, commit_id)?;
+
+        Ok(format!(
+            "Merge topic '{}'{}\n\
+             \n\
+             {}{}{}",
+            topic_name,
+            into_branch_summary(settings),
+            topic_summary,
+            log_summary,
+            trailer_summary,
+        ))
+    }
+
+    /// Build the commit message for a squashed merge request.
+    fn squash_commit_message<P>(
+        &self,
+        settings: &MergeSettings<P>,
+        topic_name: &str,
+        commit_id: &CommitId,
+        trailer_summary: &str,
+    ) -> MergeResult<String> {
+        let topic_summary = self.topic_summary();
+
+        Ok(if topic_summary.is_empty() {
+            format!(
+                "Squash topic '{}'{}\n\n{}{}",
+                topic_name,
+                into_branch_summary(settings),
+                self.log_summary(settings, commit_id)?,
+                trailer_summary,
+            )
+        } else {
+            format!("{}{}", topic_summary, trailer_summary)
+        })
+    }
+
+    /// The topic summary from the `message` block of the merge request description.
+    fn topic_summary(&self) -> String {
         let mut topic_summary = self
             .mr
             .description
@@ -655,6 +1172,15 @@ impl<'a> Merger<'a> {
             topic_summary.push('\n');
         }
 
+        topic_summary
+    }
+
+    /// A summary of the commits on the topic which are not yet on the target branch.
+    fn log_summary<P>(
+        &self,
+        settings: &MergeSettings<P>,
+        commit_id: &CommitId,
+    ) -> MergeResult<String> {
         let mut log_command = self.ctx.git();
         log_command
             .arg("log")
@@ -694,26 +1220,22 @@ impl<'a> Merger<'a> {
             log_summary.push('\n');
         }
 
-        let trailer_summary = trailers
+        Ok(log_summary)
+    }
+
+    /// The trailers for the merge request, one per line.
+    fn trailer_summary<I>(&self, trailers: I) -> String
+    where
+        I: IntoIterator<Item = Trailer>,
+    {
+        trailers
             .into_iter()
             .chain(iter::once(Trailer::new(
                 "Merge-request",
                 &self.mr.reference,
             )))
             .map(|trailer| format!("{}\n", trailer))
-            .join("");
-
-        let into_branch = match settings.merge_name() {
-            (true, _) => String::new(),
-            (false, name) => format!(" into {}", name),
-        };
-
-        Ok(format!(
-            "Merge topic '{}'{}\n\
-             \n\
-             {}{}{}",
-            topic_name, into_branch, topic_summary, log_summary, trailer_summary,
-        ))
+            .join("")
     }
 
     /// Send a comment to a merge request.
@@ -737,6 +1259,24 @@ impl<'a> Merger<'a> {
     }
 }
 
+/// Authorship information for a commit.
+struct CommitAuthorship {
+    /// The name of the author.
+    name: String,
+    /// The email of the author.
+    email: String,
+    /// When the commit was authored.
+    date: String,
+}
+
+/// The ` into <branch>` part of a commit summary for a branch.
+fn into_branch_summary<P>(settings: &MergeSettings<P>) -> String {
+    match settings.merge_name() {
+        (true, _) => String::new(),
+        (false, name) => format!(" into {}", name),
+    }
+}
+
 /// The status message for a merge status.
 fn unmerged_status_message(branch: &str, reason: &MergeStatus) -> String {
     let reason_message = match *reason {
@@ -751,6 +1291,14 @@ fn unmerged_status_message(branch: &str, reason: &MergeStatus) -> String {
     )
 }
 
+/// The comment for a topic which cannot be rebased due to merge commits.
+fn merge_commits_rebase_message(branch: &str) -> String {
+    format!(
+        "This merge request may not be rebased onto `{}` because it contains merge commits.",
+        branch,
+    )
+}
+
 /// The comment for a failed fast forward merge.
 fn no_fast_forward_possible_message(branch: &str) -> String {
     format!(
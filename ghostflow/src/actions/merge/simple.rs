@@ -4,8 +4,10 @@
 
 use chrono::{DateTime, Utc};
 use git_workarea::{GitContext, Identity};
+use log::error;
 
 use crate::actions::merge::prelude_impl::*;
+use crate::actions::merge::MergePreview;
 use crate::host::{HostedProject, MergeRequest};
 
 /// Implementation of the `merge` action.
@@ -72,4 +74,28 @@ where
         };
         merger.merge_mr(&self.settings, info)
     }
+
+    /// Preview the merge of a merge request into the branch.
+    ///
+    /// The merge policy, commit message, fast-forward eligibility, and updates to "into" branches
+    /// are computed as they would be for `merge_mr`, but nothing is pushed. If `post` is set, the
+    /// preview is also posted as a comment on the merge request.
+    pub fn preview_mr(&self, mr: &MergeRequest, post: bool) -> MergeResult<MergePreview> {
+        let merger = Merger::new(&self.ctx, &self.project, mr)?;
+        let preview = merger.preview_mr(&self.settings, &mr.source_branch)?;
+
+        if post {
+            if let Err(err) = self.project.service.post_mr_comment(mr, &preview.render()) {
+                error!(
+                    target: "ghostflow/merge",
+                    "failed to post a comment to merge request: {}, {}: {:?}",
+                    self.project.name,
+                    mr.id,
+                    err,
+                );
+            }
+        }
+
+        Ok(preview)
+    }
 }
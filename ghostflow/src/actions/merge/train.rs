@@ -0,0 +1,705 @@
+//! Merge trains.
+//!
+//! A merge train stages a batch of merge requests, waits for the result of testing the stage, and
+//! then merges the entire batch into the target branch with a single atomic push. If testing
+//! fails, the batch is bisected to find the first merge request which causes the failure. It is
+//! then unstaged and the remainder of the batch is tested again.
+
+use std::collections::HashMap;
+use std::iter;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use either::{Left, Right};
+use git_topic_stage::{CandidateTopic, IntegrationResult, StagedTopic};
+use git_workarea::{CommitId, GitContext, GitError, Identity};
+use log::{error, info, warn};
+use thiserror::Error;
+use topological_sort::TopologicalSort;
+
+use crate::actions::merge::prelude_impl::*;
+use crate::actions::stage::{Stage, StageError, TagStagePolicy};
+use crate::host::{
+    Commit, CommitStatusState, HostedProject, HostingServiceError, MergeRequest, PipelineState,
+};
+
+/// Errors which may occur when running a merge train.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum MergeTrainError {
+    /// The target branch could not be resolved.
+    #[error("failed to resolve the `{}` branch: {}", branch, output)]
+    ResolveBranch {
+        /// The name of the branch.
+        branch: String,
+        /// Output from `git rev-parse`.
+        output: String,
+    },
+    /// The target branch could not be updated.
+    #[error("failed to update the `{}` ref: {}", refname, output)]
+    UpdateBranch {
+        /// The name of the ref.
+        refname: String,
+        /// Output from `git update-ref`.
+        output: String,
+    },
+    /// The stage is not based on the target branch.
+    #[error(
+        "the stage is based on {}, but the `{}` branch is at {}",
+        stage_base,
+        branch,
+        branch_commit
+    )]
+    StaleStage {
+        /// The name of the branch.
+        branch: String,
+        /// The commit of the branch.
+        branch_commit: CommitId,
+        /// The base commit of the stage.
+        stage_base: CommitId,
+    },
+    /// Pipelines were requested, but the service does not support them.
+    #[error("the hosting service does not support pipelines for commits")]
+    NoPipelineService {},
+    /// Failure to execute a `git` command.
+    #[error("git error: {}", source)]
+    Git {
+        /// The source of the error.
+        #[from]
+        source: GitError,
+    },
+    /// Failure to perform a merge.
+    #[error("merge error: {}", source)]
+    Merge {
+        /// The source of the error.
+        #[from]
+        source: MergeError,
+    },
+    /// Failure to perform a stage operation.
+    #[error("stage error: {}", source)]
+    Stage {
+        /// The source of the error.
+        #[from]
+        source: StageError,
+    },
+    /// The hosting service returned an error.
+    #[error("hosting service error: {}", source)]
+    HostingService {
+        /// The source of the error.
+        #[from]
+        source: HostingServiceError,
+    },
+}
+
+impl MergeTrainError {
+    fn resolve_branch(branch: String, output: &[u8]) -> Self {
+        MergeTrainError::ResolveBranch {
+            branch,
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
+
+    fn update_branch(refname: String, output: &[u8]) -> Self {
+        MergeTrainError::UpdateBranch {
+            refname,
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
+
+    fn stale_stage(branch: String, branch_commit: CommitId, stage_base: CommitId) -> Self {
+        MergeTrainError::StaleStage {
+            branch,
+            branch_commit,
+            stage_base,
+        }
+    }
+
+    fn no_pipeline_service() -> Self {
+        MergeTrainError::NoPipelineService {}
+    }
+}
+
+type MergeTrainResult<T> = Result<T, MergeTrainError>;
+
+/// Where to read test results for the merge train from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeTrainTests {
+    /// A commit status with the given name on the head of the stage.
+    CommitStatus(String),
+    /// The latest pipeline for the head of the stage.
+    ///
+    /// The hosting service must support looking up pipelines by commit.
+    Pipelines,
+}
+
+/// The overall status of a merge train run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeTrainStatus {
+    /// The batch was merged and pushed.
+    Merged,
+    /// No merge requests remained in the batch.
+    Empty,
+    /// The merge was successful, but pushing it failed.
+    PushFailed,
+    /// Testing the batch did not complete in time.
+    TimedOut,
+}
+
+/// The result of a merge train run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeTrainOutcome {
+    /// The status of the run.
+    pub status: MergeTrainStatus,
+    /// The IDs of the merge requests which were merged.
+    pub merged: Vec<u64>,
+    /// The IDs of the merge requests which were dropped from the train.
+    pub rejected: Vec<u64>,
+}
+
+/// The state of testing for a batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TestState {
+    /// Testing is still underway.
+    Pending,
+    /// Testing passed.
+    Pass,
+    /// Testing failed.
+    Fail,
+    /// Testing did not complete in time.
+    TimedOut,
+}
+
+/// The result of bisecting a failed batch.
+enum Bisection {
+    /// The merge request at the index is the first to cause testing to fail.
+    Culprit(usize),
+    /// Testing a part of the batch did not complete in time.
+    TimedOut,
+}
+
+/// The result of merging a batch.
+enum BatchMerge {
+    /// The batch was merged.
+    Merged(MergeActionResult),
+    /// A merge request in the batch could not be merged.
+    Rejected(usize),
+}
+
+/// Implementation of a merge train.
+///
+/// The stage used by the train should be dedicated to it; any topics already on the stage are
+/// tested along with the batch, but are not merged.
+pub struct MergeTrain<P> {
+    /// The settings for merging into the target branch.
+    settings: MergeSettings<P>,
+    /// The context to use for Git actions.
+    ctx: GitContext,
+    /// The project of the target branch.
+    project: HostedProject,
+    /// The stage used to test batches.
+    stage: Stage,
+    /// Where to read test results from.
+    tests: MergeTrainTests,
+    /// The reason to use when tagging the stage for testing.
+    tag_reason: String,
+    /// The date format to use when tagging the stage for testing.
+    ref_date_format: String,
+    /// How long to wait between polling for test results.
+    poll_interval: Duration,
+    /// How long to wait for test results.
+    timeout: Duration,
+}
+
+impl<P> MergeTrain<P> {
+    /// Create a new merge train.
+    pub fn new(
+        ctx: GitContext,
+        project: HostedProject,
+        settings: MergeSettings<P>,
+        stage: Stage,
+        tests: MergeTrainTests,
+    ) -> Self {
+        Self {
+            settings,
+            ctx,
+            project,
+            stage,
+            tests,
+            tag_reason: "train".into(),
+            ref_date_format: "%Y%m%d%H%M%S".into(),
+            poll_interval: Duration::from_secs(60),
+            timeout: Duration::from_secs(6 * 60 * 60),
+        }
+    }
+
+    /// The reason to use when tagging the stage for testing.
+    ///
+    /// Defaults to `train`.
+    pub fn tag_reason<R>(&mut self, reason: R) -> &mut Self
+    where
+        R: Into<String>,
+    {
+        self.tag_reason = reason.into();
+        self
+    }
+
+    /// The date format to use for stage tags.
+    pub fn ref_date_format<F>(&mut self, format: F) -> &mut Self
+    where
+        F: Into<String>,
+    {
+        self.ref_date_format = format.into();
+        self
+    }
+
+    /// How often to poll for test results.
+    pub fn poll_interval(&mut self, interval: Duration) -> &mut Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// How long to wait for test results before giving up.
+    ///
+    /// This applies to each test of a full batch and to bisecting a failed batch as a whole.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The stage used by the train.
+    pub fn stage(&self) -> &Stage {
+        &self.stage
+    }
+
+    /// The stage used by the train.
+    pub fn stage_mut(&mut self) -> &mut Stage {
+        &mut self.stage
+    }
+}
+
+impl<P> MergeTrain<P>
+where
+    P: MergePolicy,
+{
+    /// Run a merge train for a batch of merge requests.
+    ///
+    /// The merge requests are merged in the given order.
+    pub fn run(
+        &mut self,
+        mrs: &[MergeRequest],
+        who: &Identity,
+        when: DateTime<Utc>,
+    ) -> MergeTrainResult<MergeTrainOutcome> {
+        info!(
+            target: "ghostflow/merge",
+            "running a merge train of {} merge requests into {}",
+            mrs.len(),
+            self.settings.branch(),
+        );
+
+        let base = self.branch_commit()?;
+        if self.stage.stager().base() != &base {
+            return Err(MergeTrainError::stale_stage(
+                self.settings.branch().into(),
+                base,
+                self.stage.stager().base().clone(),
+            ));
+        }
+
+        for mr in mrs {
+            self.stage.stage_merge_request(mr, who, when)?;
+        }
+
+        // Merge requests which failed to stage have already been told why.
+        let mut rejected = Vec::new();
+        let mut batch = Vec::new();
+        for mr in mrs {
+            if self.is_staged(mr) {
+                batch.push(mr);
+            } else {
+                rejected.push(mr.id);
+            }
+        }
+
+        while !batch.is_empty() {
+            let deadline = Instant::now() + self.timeout;
+            let timed_out = match self.test_stage(deadline)? {
+                TestState::Pass => false,
+                TestState::Fail => {
+                    match self.bisect(&batch)? {
+                        Bisection::Culprit(culprit) => {
+                            let mr = batch.remove(culprit);
+                            self.stage.unstage_update_merge_request(
+                                mr,
+                                "after failing testing as part of a merge train",
+                            )?;
+                            rejected.push(mr.id);
+                            continue;
+                        },
+                        Bisection::TimedOut => true,
+                    }
+                },
+                TestState::TimedOut => true,
+                TestState::Pending => unreachable!("pending tests are waited upon"),
+            };
+            if timed_out {
+                return Ok(MergeTrainOutcome {
+                    status: MergeTrainStatus::TimedOut,
+                    merged: Vec::new(),
+                    rejected,
+                });
+            }
+
+            match self.merge_batch(&batch, who, when)? {
+                BatchMerge::Rejected(idx) => {
+                    let mr = batch.remove(idx);
+                    self.stage.unstage_update_merge_request(
+                        mr,
+                        "because it could not be merged as part of a merge train",
+                    )?;
+                    rejected.push(mr.id);
+                },
+                BatchMerge::Merged(MergeActionResult::Success) => {
+                    let merged = batch.iter().map(|mr| mr.id).collect::<Vec<_>>();
+                    for mr in &batch {
+                        self.send_mr_comment(
+                            mr,
+                            "This merge request has been merged as part of a merge train.",
+                        );
+                    }
+
+                    // Move the stage to the new state of the branch. This removes the merged
+                    // topics from the stage.
+                    let new_base = self.branch_commit()?;
+                    let commit = Commit {
+                        repo: self.project.service.repo(&self.project.name)?,
+                        refname: Some(self.settings.branch().into()),
+                        id: new_base,
+                        last_pipeline: None,
+                    };
+                    self.stage.base_branch_update(&commit, who, when)?;
+
+                    return Ok(MergeTrainOutcome {
+                        status: MergeTrainStatus::Merged,
+                        merged,
+                        rejected,
+                    });
+                },
+                BatchMerge::Merged(_) => {
+                    for mr in &batch {
+                        self.send_mr_comment(
+                            mr,
+                            "This merge request was merged as part of a merge train, but pushing \
+                             to the remote failed.",
+                        );
+                    }
+
+                    return Ok(MergeTrainOutcome {
+                        status: MergeTrainStatus::PushFailed,
+                        merged: Vec::new(),
+                        rejected,
+                    });
+                },
+            }
+        }
+
+        Ok(MergeTrainOutcome {
+            status: MergeTrainStatus::Empty,
+            merged: Vec::new(),
+            rejected,
+        })
+    }
+
+    /// Whether the current state of a merge request is on the stage.
+    fn is_staged(&self, mr: &MergeRequest) -> bool {
+        self.stage
+            .stager()
+            .find_topic_by_id(mr.id)
+            .map_or(false, |staged| staged.commit() == &mr.commit.id)
+    }
+
+    /// Find the index of the first merge request which causes testing to fail.
+    ///
+    /// The full batch is known to fail, so prefixes of the batch are tested to find the smallest
+    /// failing prefix. Topics on the stage which are not part of the batch are tested along with
+    /// each prefix. The stage is restored to the full batch afterwards.
+    ///
+    /// The timeout applies to the bisection as a whole.
+    fn bisect(&mut self, batch: &[&MergeRequest]) -> MergeTrainResult<Bisection> {
+        info!(
+            target: "ghostflow/merge",
+            "bisecting a failed merge train into {}",
+            self.settings.branch(),
+        );
+
+        let staged = self.stage.stager().topics().to_vec();
+
+        let deadline = Instant::now() + self.timeout;
+
+        // The empty prefix passes and the full batch fails.
+        let mut good = 0;
+        let mut bad = batch.len();
+        let mut timed_out = false;
+        while bad - good > 1 {
+            let mid = good + (bad - good) / 2;
+
+            self.restage(&staged, batch, &batch[..mid])?;
+            match self.test_stage(deadline)? {
+                TestState::Pass => good = mid,
+                TestState::Fail => bad = mid,
+                TestState::TimedOut => {
+                    timed_out = true;
+                    break;
+                },
+                TestState::Pending => unreachable!("pending tests are waited upon"),
+            }
+        }
+
+        self.restage(&staged, batch, batch)?;
+
+        Ok(if timed_out {
+            Bisection::TimedOut
+        } else {
+            Bisection::Culprit(bad - 1)
+        })
+    }
+
+    /// Reset the stage to contain only part of the batch.
+    ///
+    /// Topics which are not part of the batch are kept on the stage in their original order.
+    fn restage(
+        &mut self,
+        staged: &[StagedTopic],
+        batch: &[&MergeRequest],
+        keep: &[&MergeRequest],
+    ) -> MergeTrainResult<()> {
+        let stager = self.stage.stager_mut();
+        stager.clear();
+
+        let topics = staged.iter().filter(|staged| {
+            let id = staged.topic.id;
+            let in_batch = batch.iter().any(|mr| mr.id == id);
+            !in_batch || keep.iter().any(|mr| mr.id == id)
+        });
+        for staged in topics {
+            let candidate = CandidateTopic {
+                old_id: None,
+                new_id: staged.topic.clone(),
+            };
+            let stage_result = stager.stage(candidate).map_err(StageError::from)?;
+            for result in &stage_result.results {
+                if !matches!(result, IntegrationResult::Staged(_)) {
+                    warn!(
+                        target: "ghostflow/merge",
+                        "failed to restage {} for a merge train",
+                        result.topic().url,
+                    );
+                }
+            }
+        }
+
+        self.stage.update_head_ref()?;
+
+        Ok(())
+    }
+
+    /// Tag the stage and wait for the test results of its head until the deadline.
+    fn test_stage(&mut self, deadline: Instant) -> MergeTrainResult<TestState> {
+        self.stage.tag_stage(
+            &self.tag_reason,
+            &self.ref_date_format,
+            TagStagePolicy::KeepTopics,
+        )?;
+
+        let head = self.stage.stager().head().clone();
+        loop {
+            let state = self.test_state(&head)?;
+            if state != TestState::Pending {
+                return Ok(state);
+            }
+
+            if Instant::now() >= deadline {
+                warn!(
+                    target: "ghostflow/merge",
+                    "timed out waiting for tests of {} for a merge train",
+                    head,
+                );
+
+                return Ok(TestState::TimedOut);
+            }
+
+            thread::sleep(self.poll_interval);
+        }
+    }
+
+    /// The current test state of the head of a stage.
+    fn test_state(&self, head: &CommitId) -> MergeTrainResult<TestState> {
+        let commit = self.project.commit(head)?;
+
+        match self.tests {
+            MergeTrainTests::CommitStatus(ref name) => {
+                let statuses = self.project.service.get_commit_statuses(&commit)?;
+                let state = statuses
+                    .iter()
+                    .find(|status| &status.name == name)
+                    .map_or(TestState::Pending, |status| {
+                        match status.state {
+                            CommitStatusState::Pending | CommitStatusState::Running => {
+                                TestState::Pending
+                            },
+                            CommitStatusState::Success => TestState::Pass,
+                            CommitStatusState::Failed => TestState::Fail,
+                        }
+                    });
+
+                Ok(state)
+            },
+            MergeTrainTests::Pipelines => {
+                let service = self
+                    .project
+                    .service
+                    .clone()
+                    .as_pipeline_service()
+                    .ok_or_else(MergeTrainError::no_pipeline_service)?;
+
+                let latest = service
+                    .pipelines_for_commit(&commit)?
+                    .ok_or_else(MergeTrainError::no_pipeline_service)?
+                    .into_iter()
+                    .filter(|pipeline| &pipeline.commit.id == head)
+                    .max_by_key(|pipeline| pipeline.id);
+                let state = match latest.map(|pipeline| pipeline.state) {
+                    Some(PipelineState::Failed) | Some(PipelineState::Canceled) => TestState::Fail,
+                    Some(PipelineState::Success) => TestState::Pass,
+                    Some(PipelineState::Manual) | Some(PipelineState::InProgress) | None => {
+                        TestState::Pending
+                    },
+                };
+
+                Ok(state)
+            },
+        }
+    }
+
+    /// Merge a tested batch into the target branch.
+    ///
+    /// Each merge request is merged on top of the previous one locally and the result is pushed
+    /// at once.
+    fn merge_batch(
+        &self,
+        batch: &[&MergeRequest],
+        who: &Identity,
+        when: DateTime<Utc>,
+    ) -> MergeTrainResult<BatchMerge> {
+        let original = self.branch_commit()?;
+
+        let mut mergers = Vec::with_capacity(batch.len());
+        for (idx, mr) in batch.iter().enumerate() {
+            let merger = Merger::new(&self.ctx, &self.project, mr)?;
+            let info = MergeInformation {
+                topic_name: &mr.source_branch,
+                who,
+                when,
+            };
+
+            if let Right(_) = merger.prep_mr()? {
+                self.update_branch(&original)?;
+                return Ok(BatchMerge::Rejected(idx));
+            }
+
+            match merger.create_merge(&self.settings, &info, &mr.commit.id)? {
+                Left(commit_id) => self.update_branch(&commit_id)?,
+                Right(_) => {
+                    self.update_branch(&original)?;
+                    return Ok(BatchMerge::Rejected(idx));
+                },
+            }
+
+            mergers.push((merger, info));
+        }
+
+        let (merger, info) = if let Some(last) = mergers.last() {
+            last
+        } else {
+            return Ok(BatchMerge::Merged(MergeActionResult::Success));
+        };
+
+        let mut sorter = TopologicalSort::new();
+        let mut renamer = HashMap::new();
+        let branch = self.settings.branch();
+        renamer.insert(branch.into(), self.settings.merge_name());
+        self.settings.into_branches().iter().for_each(|into_branch| {
+            sorter.add_dependency(branch, into_branch.name());
+            into_branch.add_topo_links(&mut sorter);
+        });
+        let refs = Some((
+            branch.into(),
+            self.branch_commit()?,
+            self.settings.into_branches(),
+        ));
+        let signing_keys = iter::once((branch, self.settings.signing_key())).collect();
+        let push_refs = merger.perform_update_merges(sorter, refs, info, renamer, &signing_keys)?;
+        let res = merger.push_refs(true, push_refs)?;
+
+        if res != MergeActionResult::Success {
+            self.update_branch(&original)?;
+        }
+
+        Ok(BatchMerge::Merged(res))
+    }
+
+    /// The current commit of the target branch.
+    fn branch_commit(&self) -> MergeTrainResult<CommitId> {
+        let refname = format!("refs/heads/{}", self.settings.branch());
+        let rev_parse = self
+            .ctx
+            .git()
+            .arg("rev-parse")
+            .arg("--verify")
+            .arg(&refname)
+            .output()
+            .map_err(|err| GitError::subcommand("rev-parse", err))?;
+        if !rev_parse.status.success() {
+            return Err(MergeTrainError::resolve_branch(
+                self.settings.branch().into(),
+                &rev_parse.stderr,
+            ));
+        }
+
+        Ok(CommitId::new(
+            String::from_utf8_lossy(&rev_parse.stdout).trim(),
+        ))
+    }
+
+    /// Update the local target branch.
+    fn update_branch(&self, commit: &CommitId) -> MergeTrainResult<()> {
+        let refname = format!("refs/heads/{}", self.settings.branch());
+        let update_ref = self
+            .ctx
+            .git()
+            .arg("update-ref")
+            .arg(&refname)
+            .arg(commit.as_str())
+            .output()
+            .map_err(|err| GitError::subcommand("update-ref", err))?;
+        if !update_ref.status.success() {
+            return Err(MergeTrainError::update_branch(refname, &update_ref.stderr));
+        }
+
+        Ok(())
+    }
+
+    /// Send a comment to a merge request.
+    fn send_mr_comment(&self, mr: &MergeRequest, content: &str) {
+        if let Err(err) = self.project.service.post_mr_comment(mr, content) {
+            error!(
+                target: "ghostflow/merge",
+                "failed to post a comment to merge request: {}, {}: {:?}",
+                self.project.name,
+                mr.id,
+                err,
+            );
+        }
+    }
+}
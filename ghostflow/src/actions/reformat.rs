@@ -10,12 +10,16 @@
 //! files within the repository which contain configuration files for the formatter.
 
 use std::collections::hash_map::HashMap;
+use std::collections::BTreeMap;
 use std::fmt::{self, Write as _};
+use std::fs;
 use std::io::{self, Read, Write};
 use std::iter;
 use std::os::unix::process::ExitStatusExt;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::thread;
 use std::time::Duration;
 
 use chrono::{DateTime, FixedOffset};
@@ -29,6 +33,11 @@ use wait_timeout::ChildExt;
 
 use crate::host::{HostedProject, MergeRequest};
 
+mod cache;
+pub use self::cache::FormatCache;
+pub use self::cache::FormatCacheError;
+use self::cache::{FormatCacheKey, FormatCacheResult, FormatterFingerprint};
+
 /// The stage of the format execution.
 #[derive(Debug, Clone, Copy)]
 pub enum FormatExecStage {
@@ -90,6 +99,29 @@ pub enum FormatterError {
         #[source]
         source: io::Error,
     },
+    /// The workarea does not have a work tree for a filter formatter to use.
+    #[error("no work tree is available to format {}", path.as_str())]
+    NoWorkTree {
+        /// The path to format.
+        path: FileName,
+    },
+    /// Failure to read a file to pass to a filter formatter.
+    #[error("failed to read {}: {}", path.display(), source)]
+    ReadFile {
+        /// The path to the file.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to write the output of a filter formatter to the object database.
+    #[error("failed to write the formatted contents of {}: {}", path.as_str(), output)]
+    WriteBlob {
+        /// The path which was formatted.
+        path: FileName,
+        /// The output of `git hash-object`.
+        output: String,
+    },
     /// Failure to check an attribute of a file.
     #[error("git attribute error: {}", source)]
     Attribute {
@@ -97,6 +129,13 @@ pub enum FormatterError {
         #[from]
         source: git_checks_core::AttributeError,
     },
+    /// Failure to execute a `git` command.
+    #[error("git error: {}", source)]
+    Git {
+        /// The source of the error.
+        #[from]
+        source: GitError,
+    },
 }
 
 impl FormatterError {
@@ -124,13 +163,72 @@ impl FormatterError {
             source,
         }
     }
+
+    fn no_work_tree(path: FileName) -> Self {
+        FormatterError::NoWorkTree {
+            path,
+        }
+    }
+
+    fn read_file(path: PathBuf, source: io::Error) -> Self {
+        FormatterError::ReadFile {
+            path,
+            source,
+        }
+    }
+
+    fn write_blob(path: FileName, output: &[u8]) -> Self {
+        FormatterError::WriteBlob {
+            path,
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
 }
 
 type FormatterResult<T> = Result<T, FormatterError>;
 
+/// How a formatter expects to be invoked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatterProtocol {
+    /// The formatter is run once per file.
+    ///
+    /// It is passed the path to the file, followed by the value of the `format.{kind}`
+    /// attribute if one is set. The file is formatted in place.
+    PerFile,
+    /// The formatter is run once for many files.
+    ///
+    /// It is passed the value of the `format.{kind}` attribute if one is set, followed by `--`
+    /// and the paths to the files. Files are formatted in place. Only files sharing an attribute
+    /// value are passed together. When an invocation fails, its paths are split up and the
+    /// formatter is run again on each half so that only the files which actually fail are
+    /// reported; the formatter should therefore be idempotent.
+    Batch {
+        /// The maximum total length of the paths passed in a single invocation.
+        ///
+        /// A single path longer than the limit is still passed on its own.
+        max_args_len: usize,
+    },
+    /// The formatter is run once per file as a filter.
+    ///
+    /// It is passed the path to the file (for informational purposes), followed by the value of
+    /// the `format.{kind}` attribute if one is set. The contents of the file are given on
+    /// standard input and the formatted contents are expected on standard output. If the
+    /// formatter succeeds, its output is written to the object database and recorded in the
+    /// index directly; the file in the work tree is left untouched. Because of this, files
+    /// handled by a filter formatter should not be selected by any other formatter.
+    Filter,
+}
+
+impl Default for FormatterProtocol {
+    fn default() -> Self {
+        FormatterProtocol::PerFile
+    }
+}
+
 /// A formatter for source code in a repository.
 ///
-/// The formatter is passed the file it is expected to format as its only argument.
+/// By default, the formatter is passed the file it is expected to format as its only argument.
+/// See `FormatterProtocol` for other ways to invoke a formatter.
 ///
 /// Generally, formatters should be idempotent so that lines are not changed multiple times
 /// over the course of a topic.
@@ -148,6 +246,8 @@ pub struct Formatter {
     ///
     /// If the formatter exceeds this timeout, it is considered to have failed.
     timeout: Option<Duration>,
+    /// How the formatter is invoked.
+    protocol: FormatterProtocol,
 }
 
 /// How long to wait for a timed-out formatter to respond to `SIGKILL` before leaving it as a
@@ -176,6 +276,7 @@ impl Formatter {
             formatter,
             config_files: Vec::new(),
             timeout: None,
+            protocol: FormatterProtocol::default(),
         })
     }
 
@@ -195,13 +296,62 @@ impl Formatter {
         self
     }
 
+    /// Set how the formatter is invoked.
+    pub fn with_protocol(&mut self, protocol: FormatterProtocol) -> &mut Self {
+        self.protocol = protocol;
+        self
+    }
+
     /// Format a path within the repository.
     fn format_path<'a>(
         &self,
         workarea: &GitWorkArea,
         path: &'a FileName,
         attr_value: Option<String>,
-    ) -> FormatterResult<Option<&'a FileName>> {
+    ) -> FormatterResult<FormatOutcome<'a>> {
+        match self.protocol {
+            FormatterProtocol::PerFile => {
+                let mut cmd = Command::new(&self.formatter);
+                workarea.cd_to_work_tree(&mut cmd);
+                cmd.arg(path.as_path());
+                if let Some(attr_value) = attr_value {
+                    cmd.arg(attr_value);
+                }
+
+                let run = self.run(cmd, None)?;
+                Ok(if self.succeeded(&run) {
+                    FormatOutcome::InPlace
+                } else {
+                    FormatOutcome::Failed(path)
+                })
+            },
+            FormatterProtocol::Batch {
+                ..
+            } => {
+                let failed = self.format_batch(workarea, &[path], attr_value.as_deref())?;
+                Ok(if failed.is_empty() {
+                    FormatOutcome::InPlace
+                } else {
+                    FormatOutcome::Failed(path)
+                })
+            },
+            FormatterProtocol::Filter => self.format_path_filter(workarea, path, attr_value),
+        }
+    }
+
+    /// Format a path by passing its contents through the formatter.
+    ///
+    /// The formatted contents are written to a blob; the work tree is not modified.
+    fn format_path_filter<'a>(
+        &self,
+        workarea: &GitWorkArea,
+        path: &'a FileName,
+        attr_value: Option<String>,
+    ) -> FormatterResult<FormatOutcome<'a>> {
+        let source = cache::work_tree_path(workarea, path)
+            .ok_or_else(|| FormatterError::no_work_tree(path.clone()))?;
+        let input = fs::read(&source).map_err(|err| FormatterError::read_file(source, err))?;
+
         let mut cmd = Command::new(&self.formatter);
         workarea.cd_to_work_tree(&mut cmd);
         cmd.arg(path.as_path());
@@ -209,43 +359,153 @@ impl Formatter {
             cmd.arg(attr_value);
         }
 
-        let (success, output) = if let Some(timeout) = self.timeout {
-            let mut child = cmd
+        let run = self.run(cmd, Some(input))?;
+        if !self.succeeded(&run) {
+            return Ok(FormatOutcome::Failed(path));
+        }
+
+        let blob = Self::write_blob(workarea, path, &run.stdout)?;
+        Ok(FormatOutcome::Blob(path, blob))
+    }
+
+    /// Write the formatted contents of a path to the object database.
+    fn write_blob(
+        workarea: &GitWorkArea,
+        path: &FileName,
+        contents: &[u8],
+    ) -> FormatterResult<CommitId> {
+        let mut hash_object = workarea
+            .git()
+            .arg("hash-object")
+            .arg("-w")
+            .arg("--stdin")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| GitError::subcommand("hash-object", err))?;
+
+        // `git hash-object` reads all of its input before writing anything, so the input may be
+        // written before collecting the output.
+        let written = hash_object
+            .stdin
+            .take()
+            .expect("spawned with stdin")
+            .write_all(contents);
+        let output = hash_object
+            .wait_with_output()
+            .map_err(|err| GitError::subcommand("hash-object", err))?;
+        if !output.status.success() {
+            return Err(FormatterError::write_blob(path.clone(), &output.stderr));
+        }
+        written.map_err(|err| GitError::subcommand("hash-object", err))?;
+
+        Ok(CommitId::new(
+            String::from_utf8_lossy(&output.stdout).trim(),
+        ))
+    }
+
+    /// Format a set of paths with a single invocation of the formatter.
+    ///
+    /// If the formatter fails, the batch is bisected to find the paths which fail on their own.
+    fn format_batch<'a>(
+        &self,
+        workarea: &GitWorkArea,
+        paths: &[&'a FileName],
+        attr_value: Option<&str>,
+    ) -> FormatterResult<Vec<&'a FileName>> {
+        let mut cmd = Command::new(&self.formatter);
+        workarea.cd_to_work_tree(&mut cmd);
+        if let Some(attr_value) = attr_value {
+            cmd.arg(attr_value);
+        }
+        cmd.arg("--").args(paths.iter().map(|path| path.as_path()));
+
+        let run = self.run(cmd, None)?;
+        if self.succeeded(&run) {
+            return Ok(Vec::new());
+        }
+        if paths.len() == 1 {
+            return Ok(paths.to_vec());
+        }
+
+        let (left, right) = paths.split_at(paths.len() / 2);
+        let mut failed = self.format_batch(workarea, left, attr_value)?;
+        failed.extend(self.format_batch(workarea, right, attr_value)?);
+
+        Ok(failed)
+    }
+
+    /// Whether a formatter run succeeded or not.
+    ///
+    /// Failures are logged.
+    fn succeeded(&self, run: &FormatterRun) -> bool {
+        if !run.success {
+            info!(
+                target: "ghostflow/reformat",
+                "failed to run the {} formatter: {}",
+                self.kind,
+                run.output,
+            );
+        }
+
+        run.success
+    }
+
+    /// Run the formatter.
+    ///
+    /// If `input` is given, it is written to the formatter's standard input and its standard
+    /// output is collected. Otherwise, the formatter reads nothing and its output is discarded.
+    fn run(&self, mut cmd: Command, input: Option<Vec<u8>>) -> FormatterResult<FormatterRun> {
+        let is_filter = input.is_some();
+        let mut child = cmd
+            .stdin(if is_filter {
+                Stdio::piped()
+            } else {
                 // Formatters should not read anything.
-                .stdin(Stdio::null())
+                Stdio::null()
+            })
+            .stdout(if is_filter {
+                Stdio::piped()
+            } else {
                 // The output goes nowhere.
-                .stdout(Stdio::null())
-                // But we want any error messages from them (for logging purposes). If this pipe
-                // fills up buffers, it will deadlock and the timeout will "save" us. Any process
-                // outputting this much error messages probably is very unhappy anyways.
-                .stderr(Stdio::piped())
-                .spawn()
-                .map_err(|err| {
-                    FormatterError::exec_formatter(
-                        self.formatter.clone(),
-                        FormatExecStage::Run,
-                        err,
-                    )
-                })?;
+                Stdio::null()
+            })
+            // But we want any error messages from them (for logging purposes).
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| {
+                FormatterError::exec_formatter(self.formatter.clone(), FormatExecStage::Run, err)
+            })?;
+
+        // Pipes are serviced by threads so that a formatter producing (or expecting) a lot of data
+        // cannot deadlock against us.
+        let stdin_thread = child.stdin.take().zip(input).map(|(mut stdin, input)| {
+            thread::spawn(move || {
+                // Formatters may exit without reading all of their input; their exit status is
+                // what matters.
+                let _ = stdin.write_all(&input);
+            })
+        });
+        let stdout_thread = child.stdout.take().map(|mut stdout| {
+            thread::spawn(move || {
+                let mut bytes = Vec::new();
+                stdout.read_to_end(&mut bytes).map(|_| bytes)
+            })
+        });
+        let mut stderr = child.stderr.take().expect("spawned with stderr");
+        let stderr_thread = thread::spawn(move || {
+            let mut bytes = Vec::new();
+            stderr.read_to_end(&mut bytes).map(|_| bytes)
+        });
+
+        let status = if let Some(timeout) = self.timeout {
             let check = child.wait_timeout(timeout).map_err(|err| {
                 FormatterError::exec_formatter(self.formatter.clone(), FormatExecStage::Wait, err)
             })?;
 
             if let Some(status) = check {
-                let stderr = child.stderr.expect("spawned with stderr");
-                let bytes_output = stderr
-                    .bytes()
-                    .collect::<Result<Vec<u8>, _>>()
-                    .map_err(|err| FormatterError::collect_stderr(self.formatter.clone(), err))?;
-                (
-                    status.success(),
-                    format!(
-                        "failed with exit code {:?}, signal {:?}, output: {:?}",
-                        status.code(),
-                        status.signal(),
-                        String::from_utf8_lossy(&bytes_output),
-                    ),
-                )
+                status
             } else {
                 child.kill().map_err(|err| {
                     FormatterError::exec_formatter(
@@ -268,32 +528,106 @@ impl Formatter {
                         self.kind,
                     );
                 }
-                (false, "timeout reached".into())
+
+                // The pipe threads are left to finish on their own; children of the formatter
+                // may still be holding the pipes open.
+                return Ok(FormatterRun {
+                    success: false,
+                    output: "timeout reached".into(),
+                    stdout: Vec::new(),
+                });
             }
         } else {
-            let check = cmd.output().map_err(|err| {
-                FormatterError::exec_formatter(self.formatter.clone(), FormatExecStage::Run, err)
-            })?;
-            (
-                check.status.success(),
-                String::from_utf8_lossy(&check.stderr).into_owned(),
-            )
+            child.wait().map_err(|err| {
+                FormatterError::exec_formatter(self.formatter.clone(), FormatExecStage::Wait, err)
+            })?
         };
 
-        Ok(if success {
-            None
-        } else {
-            info!(
-                target: "ghostflow/reformat",
-                "failed to run the {} formatter: {}",
-                self.kind,
-                output,
-            );
-            Some(path)
+        if let Some(stdin_thread) = stdin_thread {
+            let _ = stdin_thread.join();
+        }
+        let collect = |handle: thread::JoinHandle<io::Result<Vec<u8>>>| {
+            handle
+                .join()
+                .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::Other, "reader panicked")))
+                .map_err(|err| FormatterError::collect_stderr(self.formatter.clone(), err))
+        };
+        let stdout = stdout_thread.map(collect).transpose()?.unwrap_or_default();
+        let stderr = collect(stderr_thread)?;
+
+        Ok(FormatterRun {
+            success: status.success(),
+            output: format!(
+                "failed with exit code {:?}, signal {:?}, output: {:?}",
+                status.code(),
+                status.signal(),
+                String::from_utf8_lossy(&stderr),
+            ),
+            stdout,
         })
     }
 }
 
+/// The result of formatting a path.
+enum FormatOutcome<'a> {
+    /// The file was formatted in the work tree.
+    InPlace,
+    /// The formatted contents of the file were written to a blob.
+    Blob(&'a FileName, CommitId),
+    /// The formatter failed on the file.
+    Failed(&'a FileName),
+}
+
+/// The result of looking up a path in the format cache.
+enum CacheLookup {
+    /// The formatted file was restored from the cache.
+    Restored,
+    /// The formatted contents of the file are in a cached blob.
+    Cached(CommitId),
+    /// The file needs to be formatted; the key may be used to store the result.
+    Miss(FormatCacheKey),
+    /// The cache may not be used for the file.
+    Unavailable,
+}
+
+/// The result of running a formatter.
+struct FormatterRun {
+    /// Whether the formatter succeeded.
+    success: bool,
+    /// A description of the result for logging.
+    output: String,
+    /// The standard output of a filter formatter.
+    stdout: Vec<u8>,
+}
+
+/// Split paths into batches whose arguments fit within a length limit.
+///
+/// Each batch contains at least one item, even if it alone exceeds the limit.
+fn batches<T, F>(items: Vec<T>, max_args_len: usize, path: F) -> Vec<Vec<T>>
+where
+    F: Fn(&T) -> &FileName,
+{
+    let mut batches = Vec::new();
+    let mut batch = Vec::new();
+    let mut batch_len = 0;
+
+    for item in items {
+        // Account for the separator between arguments.
+        let len = path(&item).as_str().len() + 1;
+        if !batch.is_empty() && batch_len + len > max_args_len {
+            batches.push(std::mem::take(&mut batch));
+            batch_len = 0;
+        }
+        batch_len += len;
+        batch.push(item);
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+
+    batches
+}
+
 /// Reasons for listing files.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ListFilesReason {
@@ -301,6 +635,8 @@ pub enum ListFilesReason {
     Deleted,
     /// Files a formatter created.
     Untracked,
+    /// Files formatted by a filter formatter.
+    Staged,
 }
 
 impl fmt::Display for ListFilesReason {
@@ -308,6 +644,7 @@ impl fmt::Display for ListFilesReason {
         let what = match self {
             ListFilesReason::Deleted => "deleted",
             ListFilesReason::Untracked => "untracked",
+            ListFilesReason::Staged => "staged",
         };
 
         write!(f, "{}", what)
@@ -365,6 +702,12 @@ pub enum ReformatError {
         /// Output from `git add`.
         output: String,
     },
+    /// The output of filter formatters could not be recorded in the index.
+    #[error("failed to update the index with reformatted files: {}", output)]
+    UpdateIndex {
+        /// Output from `git update-index`.
+        output: String,
+    },
     /// The reformatted tree could not be written.
     #[error("failed to write the reformatted tree: {}", output)]
     WriteTree {
@@ -516,6 +859,12 @@ impl ReformatError {
         }
     }
 
+    fn update_index(output: &[u8]) -> Self {
+        ReformatError::UpdateIndex {
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
+
     fn write_tree(output: &[u8]) -> Self {
         ReformatError::WriteTree {
             output: String::from_utf8_lossy(output).into(),
@@ -611,6 +960,8 @@ pub struct Reformat {
     formatters: Vec<Formatter>,
     /// Whether to push the result or not.
     push_result: bool,
+    /// A cache of formatter results.
+    cache: Option<Arc<FormatCache>>,
 }
 
 impl Reformat {
@@ -621,6 +972,7 @@ impl Reformat {
             project,
             formatters: Vec::new(),
             push_result: true,
+            cache: None,
         }
     }
 
@@ -639,6 +991,15 @@ impl Reformat {
         self
     }
 
+    /// Use a cache of formatter results.
+    ///
+    /// Blobs which have been formatted before with the same formatter, configuration, and
+    /// attribute value are not formatted again. The cache may be shared between actions.
+    pub fn format_cache(&mut self, cache: Arc<FormatCache>) -> &mut Self {
+        self.cache = Some(cache);
+        self
+    }
+
     /// Reformat the entire tree through a merge request.
     ///
     /// This method rewrites the entire tree as part of a merge request by rewriting the `HEAD` of
@@ -833,38 +1194,78 @@ impl Reformat {
     ) -> ReformatResult<CommitId> {
         // Collect the set of files which fail their formatters.
         let check_ctx = CheckGitContext::new(workarea, mr.author.identity());
-        let failed_paths = self
+        let outcomes = self
             .formatters
             .iter()
             .map(|formatter| {
-                // Run paths handled by the formatters according to their attributes.
+                // Select paths handled by the formatters according to their attributes.
                 let attr = format!("format.{}", formatter.kind);
-                paths
+                let fingerprint = self.formatter_fingerprint(formatter, commit);
+                let selected = paths
                     .par_iter()
                     .map(|path| {
                         let state = check_ctx.check_attr(&attr, path.as_path())?;
-                        match state {
-                            AttributeState::Set => {
-                                formatter.format_path(check_ctx.workarea(), path, None)
-                            },
-                            AttributeState::Value(v) => {
-                                formatter.format_path(check_ctx.workarea(), path, Some(v))
-                            },
-                            _ => Ok(None),
+                        Ok(match state {
+                            AttributeState::Set => Some((*path, None)),
+                            AttributeState::Value(v) => Some((*path, Some(v))),
+                            _ => None,
+                        })
+                    })
+                    .collect::<Vec<FormatterResult<_>>>()
+                    .into_iter()
+                    .collect::<FormatterResult<Vec<_>>>()?
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>();
+
+                if let FormatterProtocol::Batch {
+                    max_args_len,
+                } = formatter.protocol
+                {
+                    return Ok(Self::format_paths_batched(
+                        fingerprint.as_ref(),
+                        formatter,
+                        check_ctx.workarea(),
+                        selected,
+                        max_args_len,
+                    )?
+                    .into_iter()
+                    .map(FormatOutcome::Failed)
+                    .collect::<Vec<_>>());
+                }
+
+                selected
+                    .into_par_iter()
+                    .map(|(path, attr_value)| {
+                        if let Some((cache, fingerprint)) = fingerprint.as_ref() {
+                            Self::format_path_cached(
+                                cache,
+                                fingerprint,
+                                formatter,
+                                check_ctx.workarea(),
+                                path,
+                                attr_value,
+                            )
+                        } else {
+                            formatter.format_path(check_ctx.workarea(), path, attr_value)
                         }
                     })
                     .collect::<Vec<FormatterResult<_>>>()
                     .into_iter()
                     .collect::<FormatterResult<Vec<_>>>()
             })
-            .collect::<Vec<FormatterResult<_>>>()
-            .into_iter()
-            .collect::<FormatterResult<Vec<_>>>()?
-            .into_iter()
-            .flatten()
-            .filter_map(|path| path.map(FileName::as_str))
-            .unique()
-            .collect::<Vec<_>>();
+            .collect::<FormatterResult<Vec<_>>>()?;
+
+        let mut failed_paths = Vec::new();
+        let mut blobs = Vec::new();
+        for outcome in outcomes.into_iter().flatten() {
+            match outcome {
+                FormatOutcome::InPlace => (),
+                FormatOutcome::Blob(path, blob) => blobs.push((path, blob)),
+                FormatOutcome::Failed(path) => failed_paths.push(path.as_str()),
+            }
+        }
+        let failed_paths = failed_paths.into_iter().unique().collect::<Vec<_>>();
 
         if !failed_paths.is_empty() {
             self.send_mr_comment(
@@ -891,6 +1292,9 @@ impl Reformat {
             return Err(ReformatError::add_files(&add.stderr));
         }
 
+        // Filter formatters leave the work tree alone, so their output goes in after `git add`.
+        Self::update_index(check_ctx.workarea(), &blobs)?;
+
         let write_tree = check_ctx
             .git()
             .arg("write-tree")
@@ -904,6 +1308,352 @@ impl Reformat {
         ))
     }
 
+    /// Record the blobs written by filter formatters in the index of a workarea.
+    fn update_index(workarea: &GitWorkArea, blobs: &[(&FileName, CommitId)]) -> ReformatResult<()> {
+        // An empty list of files causes `ls-files` to list all files.
+        if blobs.is_empty() {
+            return Ok(());
+        }
+
+        let ls_files = workarea
+            .git()
+            .arg("ls-files")
+            .arg("-s")
+            .arg("-z")
+            .arg("--")
+            .args(blobs.iter().map(|(path, _)| path.as_path()))
+            .output()
+            .map_err(|err| GitError::subcommand("ls-files -s", err))?;
+        if !ls_files.status.success() {
+            return Err(ReformatError::list_files(
+                ListFilesReason::Staged,
+                &ls_files.stderr,
+            ));
+        }
+        let ls_files_output = String::from_utf8_lossy(&ls_files.stdout);
+        let modes = ls_files_output
+            .split('\0')
+            .filter_map(|entry| {
+                let (info, path) = entry.split_once('\t')?;
+                let mode = info.split(' ').next()?;
+                Some((path, mode))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let index_info = blobs
+            .iter()
+            .filter_map(|(path, blob)| {
+                let mode = modes.get(path.as_str())?;
+                Some(format!("{} {}\t{}\0", mode, blob, path.as_str()))
+            })
+            .collect::<String>();
+
+        let mut update_index = workarea
+            .git()
+            .arg("update-index")
+            .arg("-z")
+            .arg("--index-info")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| GitError::subcommand("update-index", err))?;
+        let written = update_index
+            .stdin
+            .take()
+            .expect("spawned with stdin")
+            .write_all(index_info.as_bytes());
+        let update_index = update_index
+            .wait_with_output()
+            .map_err(|err| GitError::subcommand("update-index", err))?;
+        if !update_index.status.success() {
+            return Err(ReformatError::update_index(&update_index.stderr));
+        }
+        written.map_err(|err| GitError::subcommand("update-index", err))?;
+
+        Ok(())
+    }
+
+    /// Compute the cache fingerprint for a formatter on a commit.
+    ///
+    /// Failures are logged and disable caching for the formatter.
+    fn formatter_fingerprint<'a>(
+        &'a self,
+        formatter: &Formatter,
+        commit: &CommitId,
+    ) -> Option<(&'a FormatCache, FormatterFingerprint)> {
+        let cache = self.cache.as_ref()?;
+
+        let config_blobs = match self.config_blobs(formatter, commit) {
+            Ok(blobs) => blobs,
+            Err(err) => {
+                warn!(
+                    target: "ghostflow/reformat",
+                    "failed to find the configuration files for the {} formatter in {}; not \
+                     using the cache: {:?}",
+                    formatter.kind,
+                    commit,
+                    err,
+                );
+                return None;
+            },
+        };
+        let config_blobs = config_blobs
+            .iter()
+            .map(|(path, blob)| (path.as_str(), blob.clone()))
+            .collect::<Vec<_>>();
+
+        match cache.fingerprint(&formatter.kind, &formatter.formatter, &config_blobs) {
+            Ok(fingerprint) => Some((cache.as_ref(), fingerprint)),
+            Err(err) => {
+                warn!(
+                    target: "ghostflow/reformat",
+                    "failed to fingerprint the {} formatter; not using the cache: {:?}",
+                    formatter.kind,
+                    err,
+                );
+                None
+            },
+        }
+    }
+
+    /// The blobs of a formatter's configuration files in a commit.
+    fn config_blobs(
+        &self,
+        formatter: &Formatter,
+        commit: &CommitId,
+    ) -> FormatCacheResult<Vec<(String, Option<CommitId>)>> {
+        let blobs = if formatter.config_files.is_empty() {
+            HashMap::new()
+        } else {
+            let ls_tree = self
+                .ctx
+                .git()
+                .arg("ls-tree")
+                .arg("-z")
+                .arg(commit.as_str())
+                .arg("--")
+                .args(&formatter.config_files)
+                .output()
+                .map_err(|err| GitError::subcommand("ls-tree", err))?;
+            if !ls_tree.status.success() {
+                return Err(FormatCacheError::list_config_files(
+                    commit.clone(),
+                    &ls_tree.stderr,
+                ));
+            }
+
+            String::from_utf8_lossy(&ls_tree.stdout)
+                .split('\0')
+                .filter_map(|entry| {
+                    let (info, path) = entry.split_once('\t')?;
+                    let blob = info.split(' ').nth(2)?;
+                    Some((path.to_string(), CommitId::new(blob)))
+                })
+                .collect()
+        };
+
+        Ok(formatter
+            .config_files
+            .iter()
+            .map(|path| (path.clone(), blobs.get(path).cloned()))
+            .collect())
+    }
+
+    /// Format a path, using the cache where possible.
+    ///
+    /// Cache failures are not fatal; the formatter is run instead.
+    fn format_path_cached<'a>(
+        cache: &FormatCache,
+        fingerprint: &FormatterFingerprint,
+        formatter: &Formatter,
+        workarea: &GitWorkArea,
+        path: &'a FileName,
+        attr_value: Option<String>,
+    ) -> FormatterResult<FormatOutcome<'a>> {
+        // Filter formatters do not touch the work tree, so neither does the cache.
+        let restore = formatter.protocol != FormatterProtocol::Filter;
+        let lookup = Self::lookup_cached(
+            cache,
+            fingerprint,
+            workarea,
+            path,
+            attr_value.as_deref(),
+            restore,
+        );
+        let key = match lookup {
+            CacheLookup::Restored => return Ok(FormatOutcome::InPlace),
+            CacheLookup::Cached(blob) => return Ok(FormatOutcome::Blob(path, blob)),
+            CacheLookup::Miss(key) => key,
+            CacheLookup::Unavailable => {
+                return formatter.format_path(workarea, path, attr_value);
+            },
+        };
+
+        let outcome = formatter.format_path(workarea, path, attr_value)?;
+        match &outcome {
+            FormatOutcome::InPlace => Self::store_cached(cache, &key, workarea, path, None),
+            FormatOutcome::Blob(_, blob) => {
+                Self::store_cached(cache, &key, workarea, path, Some(blob))
+            },
+            FormatOutcome::Failed(_) => (),
+        }
+
+        Ok(outcome)
+    }
+
+    /// Format paths in batches, using the cache where possible.
+    ///
+    /// Paths are grouped by their attribute value so that each invocation of the formatter
+    /// receives the same arguments for all of its paths.
+    fn format_paths_batched<'a>(
+        fingerprint: Option<&(&FormatCache, FormatterFingerprint)>,
+        formatter: &Formatter,
+        workarea: &GitWorkArea,
+        paths: Vec<(&'a FileName, Option<String>)>,
+        max_args_len: usize,
+    ) -> FormatterResult<Vec<&'a FileName>> {
+        let mut pending: BTreeMap<Option<String>, Vec<_>> = BTreeMap::new();
+        for (path, attr_value) in paths {
+            let key = if let Some((cache, fingerprint)) = fingerprint {
+                let lookup = Self::lookup_cached(
+                    cache,
+                    fingerprint,
+                    workarea,
+                    path,
+                    attr_value.as_deref(),
+                    true,
+                );
+                match lookup {
+                    CacheLookup::Restored => continue,
+                    // Only returned when not restoring.
+                    CacheLookup::Cached(_) => unreachable!(),
+                    CacheLookup::Miss(key) => Some(key),
+                    CacheLookup::Unavailable => None,
+                }
+            } else {
+                None
+            };
+
+            pending.entry(attr_value).or_default().push((path, key));
+        }
+
+        let batches = pending
+            .into_iter()
+            .flat_map(|(attr_value, entries)| {
+                batches(entries, max_args_len, |(path, _)| *path)
+                    .into_iter()
+                    .map(move |batch| (attr_value.clone(), batch))
+            })
+            .collect::<Vec<_>>();
+
+        Ok(batches
+            .into_par_iter()
+            .map(|(attr_value, batch)| {
+                let paths = batch.iter().map(|(path, _)| *path).collect::<Vec<_>>();
+                let failed = formatter.format_batch(workarea, &paths, attr_value.as_deref())?;
+                if let Some((cache, _)) = fingerprint {
+                    let formatted = batch.iter().filter(|(path, _)| !failed.contains(path));
+                    for (path, key) in formatted {
+                        if let Some(key) = key.as_ref() {
+                            Self::store_cached(cache, key, workarea, path, None);
+                        }
+                    }
+                }
+
+                Ok(failed)
+            })
+            .collect::<Vec<FormatterResult<_>>>()
+            .into_iter()
+            .collect::<FormatterResult<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+
+    /// Look up the formatting of a path in the cache.
+    ///
+    /// If `restore` is set, a cached result is written to the work tree.
+    fn lookup_cached(
+        cache: &FormatCache,
+        fingerprint: &FormatterFingerprint,
+        workarea: &GitWorkArea,
+        path: &FileName,
+        attr_value: Option<&str>,
+        restore: bool,
+    ) -> CacheLookup {
+        let input = match cache::hash_work_tree_file(workarea, path, false) {
+            Ok(input) => input,
+            Err(err) => {
+                warn!(
+                    target: "ghostflow/reformat",
+                    "failed to hash {}; not using the cache: {:?}",
+                    path.as_str(),
+                    err,
+                );
+                return CacheLookup::Unavailable;
+            },
+        };
+        let key = fingerprint.key(attr_value, &input);
+
+        match cache.get(&key) {
+            Ok(Some(output)) => {
+                if !restore {
+                    return CacheLookup::Cached(output);
+                }
+
+                match cache::restore_work_tree_file(workarea, path, &output) {
+                    Ok(()) => return CacheLookup::Restored,
+                    Err(err) => {
+                        warn!(
+                            target: "ghostflow/reformat",
+                            "failed to restore the cached formatting of {} ({}): {:?}",
+                            path.as_str(),
+                            output,
+                            err,
+                        );
+                    },
+                }
+            },
+            Ok(None) => (),
+            Err(err) => {
+                warn!(
+                    target: "ghostflow/reformat",
+                    "failed to query the format cache: {:?}",
+                    err,
+                );
+            },
+        }
+
+        CacheLookup::Miss(key)
+    }
+
+    /// Store the formatting of a path in the cache.
+    ///
+    /// The formatted contents are read from the work tree unless a blob is given.
+    fn store_cached(
+        cache: &FormatCache,
+        key: &FormatCacheKey,
+        workarea: &GitWorkArea,
+        path: &FileName,
+        blob: Option<&CommitId>,
+    ) {
+        let stored = if let Some(blob) = blob {
+            cache.insert(key, blob)
+        } else {
+            cache::hash_work_tree_file(workarea, path, true)
+                .and_then(|output| cache.insert(key, &output))
+        };
+        if let Err(err) = stored {
+            warn!(
+                target: "ghostflow/reformat",
+                "failed to store the formatting of {} in the cache: {:?}",
+                path.as_str(),
+                err,
+            );
+        }
+    }
+
     /// Push a new head to the source repository of the merge request.
     ///
     /// This creates a comment about the result of the reformatting.
@@ -1191,3 +1941,58 @@ impl Reformat {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use git_checks_core::FileName;
+
+    use crate::actions::reformat::batches;
+
+    fn names(paths: &[&str]) -> Vec<FileName> {
+        paths
+            .iter()
+            .map(|path| FileName::new(*path).unwrap())
+            .collect()
+    }
+
+    fn batch_strs(batches: Vec<Vec<FileName>>) -> Vec<Vec<String>> {
+        batches
+            .into_iter()
+            .map(|batch| batch.iter().map(|path| path.as_str().into()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_batches_empty() {
+        let batches = batches(Vec::<FileName>::new(), 10, |path| path);
+        assert!(batches.is_empty());
+    }
+
+    #[test]
+    fn test_batches_split_on_length() {
+        let paths = names(&["a.c", "b.c", "c.c", "d.c", "e.c"]);
+        let batches = batches(paths, 8, |path| path);
+        assert_eq!(
+            batch_strs(batches),
+            [
+                vec!["a.c".to_string(), "b.c".into()],
+                vec!["c.c".into(), "d.c".into()],
+                vec!["e.c".into()],
+            ],
+        );
+    }
+
+    #[test]
+    fn test_batches_long_path() {
+        let paths = names(&["a.c", "very/long/path.c", "b.c"]);
+        let batches = batches(paths, 8, |path| path);
+        assert_eq!(
+            batch_strs(batches),
+            [
+                vec!["a.c".to_string()],
+                vec!["very/long/path.c".into()],
+                vec!["b.c".into()],
+            ],
+        );
+    }
+}
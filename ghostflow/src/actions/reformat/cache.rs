@@ -0,0 +1,474 @@
+//! A persistent cache of formatter results.
+//!
+//! The output of a formatter is determined by the formatter itself, its configuration files, the
+//! `format.{kind}` attribute value, and the content being formatted. The cache maps these inputs
+//! to the blob the formatter produced so that unchanged content is never formatted twice. Entries
+//! are stored as files beneath a directory so that the cache may be shared between processes.
+
+use std::collections::hash_map::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+use digest::Digest;
+use git_checks_core::FileName;
+use git_workarea::{CommitId, GitError, GitWorkArea};
+use sha2::Sha256;
+use tempfile::NamedTempFile;
+use thiserror::Error;
+
+/// Errors which may occur when using a format cache.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum FormatCacheError {
+    /// Failure to create a directory for the cache.
+    #[error("failed to create the cache directory {}: {}", path.display(), source)]
+    CreateDirectory {
+        /// The path to the directory.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to read a formatter to compute its digest.
+    #[error("failed to read the formatter {}: {}", formatter.display(), source)]
+    ReadFormatter {
+        /// The path to the formatter.
+        formatter: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to read a cache entry.
+    #[error("failed to read the cache entry {}: {}", path.display(), source)]
+    ReadEntry {
+        /// The path to the entry.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to list the configuration files of a formatter.
+    #[error("failed to list formatter configuration files in {}: {}", commit, output)]
+    ListConfigFiles {
+        /// The commit containing the configuration files.
+        commit: CommitId,
+        /// The output of `git ls-tree`.
+        output: String,
+    },
+    /// Failure to hash a file in the work tree.
+    #[error("failed to hash {}: {}", path.as_str(), output)]
+    HashFile {
+        /// The path to the file.
+        path: FileName,
+        /// The output of `git hash-object`.
+        output: String,
+    },
+    /// Failure to read a cached blob.
+    #[error("failed to read the blob {}: {}", blob, output)]
+    ReadBlob {
+        /// The blob.
+        blob: CommitId,
+        /// The output of `git cat-file`.
+        output: String,
+    },
+    /// The work tree of a workarea could not be found.
+    #[error("failed to find the work tree for {}", path.as_str())]
+    NoWorkTree {
+        /// The path which was to be written.
+        path: FileName,
+    },
+    /// Failure to write a file in the work tree.
+    #[error("failed to write {}: {}", path.display(), source)]
+    WriteFile {
+        /// The path to the file.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failure to run git.
+    #[error("git error: {}", source)]
+    Git {
+        /// The source of the error.
+        #[from]
+        source: GitError,
+    },
+    /// Failure to write a cache entry.
+    #[error("failed to write the cache entry {}: {}", path.display(), source)]
+    WriteEntry {
+        /// The path to the entry.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+}
+
+impl FormatCacheError {
+    fn create_directory(path: PathBuf, source: io::Error) -> Self {
+        FormatCacheError::CreateDirectory {
+            path,
+            source,
+        }
+    }
+
+    fn read_formatter(formatter: PathBuf, source: io::Error) -> Self {
+        FormatCacheError::ReadFormatter {
+            formatter,
+            source,
+        }
+    }
+
+    fn read_entry(path: PathBuf, source: io::Error) -> Self {
+        FormatCacheError::ReadEntry {
+            path,
+            source,
+        }
+    }
+
+    fn write_entry(path: PathBuf, source: io::Error) -> Self {
+        FormatCacheError::WriteEntry {
+            path,
+            source,
+        }
+    }
+
+    pub(crate) fn list_config_files(commit: CommitId, output: &[u8]) -> Self {
+        FormatCacheError::ListConfigFiles {
+            commit,
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
+
+    fn hash_file(path: FileName, output: &[u8]) -> Self {
+        FormatCacheError::HashFile {
+            path,
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
+
+    fn read_blob(blob: CommitId, output: &[u8]) -> Self {
+        FormatCacheError::ReadBlob {
+            blob,
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
+
+    fn no_work_tree(path: FileName) -> Self {
+        FormatCacheError::NoWorkTree {
+            path,
+        }
+    }
+
+    fn write_file(path: PathBuf, source: io::Error) -> Self {
+        FormatCacheError::WriteFile {
+            path,
+            source,
+        }
+    }
+}
+
+pub(crate) type FormatCacheResult<T> = Result<T, FormatCacheError>;
+
+/// Add a length-prefixed field to a digest.
+///
+/// The length prefix keeps adjacent fields from being ambiguous.
+fn digest_field(digest: &mut Sha256, field: &[u8]) {
+    digest.update((field.len() as u64).to_be_bytes());
+    digest.update(field);
+}
+
+/// Add an optional field to a digest.
+fn digest_optional_field(digest: &mut Sha256, field: Option<&[u8]>) {
+    if let Some(field) = field {
+        digest.update([1]);
+        digest_field(digest, field);
+    } else {
+        digest.update([0]);
+    }
+}
+
+/// The inputs to a formatter which are shared by all of the paths it formats in a commit.
+#[derive(Debug, Clone)]
+pub(crate) struct FormatterFingerprint {
+    /// The digest of the formatter inputs.
+    digest: Sha256,
+}
+
+impl FormatterFingerprint {
+    /// The cache key for formatting a blob with the given attribute value.
+    pub(crate) fn key(&self, attr_value: Option<&str>, input: &CommitId) -> FormatCacheKey {
+        let mut digest = self.digest.clone();
+        digest_optional_field(&mut digest, attr_value.map(str::as_bytes));
+        digest_field(&mut digest, input.as_str().as_bytes());
+
+        FormatCacheKey(format!("{:x}", digest.finalize()))
+    }
+}
+
+/// A key into the format cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FormatCacheKey(String);
+
+/// A persistent cache of formatter results.
+#[derive(Debug)]
+pub struct FormatCache {
+    /// The directory holding the cache entries.
+    root: PathBuf,
+    /// Digests of formatter executables which have been computed.
+    formatter_digests: Mutex<HashMap<PathBuf, String>>,
+}
+
+impl FormatCache {
+    /// Open a format cache stored in the given directory.
+    ///
+    /// The directory is created if it does not exist.
+    pub fn new<P>(root: P) -> FormatCacheResult<Self>
+    where
+        P: Into<PathBuf>,
+    {
+        let root = root.into();
+        fs::create_dir_all(&root)
+            .map_err(|err| FormatCacheError::create_directory(root.clone(), err))?;
+
+        Ok(Self {
+            root,
+            formatter_digests: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The directory holding the cache entries.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Compute the fingerprint of a formatter.
+    ///
+    /// The `config_blobs` are the configuration files of the formatter and the blob they have in
+    /// the commit being formatted (if present).
+    pub(crate) fn fingerprint(
+        &self,
+        kind: &str,
+        formatter: &Path,
+        config_blobs: &[(&str, Option<CommitId>)],
+    ) -> FormatCacheResult<FormatterFingerprint> {
+        let formatter_digest = self.formatter_digest(formatter)?;
+
+        let mut digest = Sha256::new();
+        digest_field(&mut digest, kind.as_bytes());
+        digest_field(&mut digest, formatter_digest.as_bytes());
+        digest.update((config_blobs.len() as u64).to_be_bytes());
+        for (path, blob) in config_blobs {
+            digest_field(&mut digest, path.as_bytes());
+            digest_optional_field(&mut digest, blob.as_ref().map(|blob| blob.as_str().as_bytes()));
+        }
+
+        Ok(FormatterFingerprint {
+            digest,
+        })
+    }
+
+    /// Look up the result of a formatter.
+    pub(crate) fn get(&self, key: &FormatCacheKey) -> FormatCacheResult<Option<CommitId>> {
+        let path = self.entry_path(key);
+        let mut contents = String::new();
+        match File::open(&path) {
+            Ok(mut file) => {
+                file.read_to_string(&mut contents)
+                    .map_err(|err| FormatCacheError::read_entry(path, err))?;
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(FormatCacheError::read_entry(path, err)),
+        }
+
+        let blob = contents.trim();
+        Ok(if blob.is_empty() {
+            None
+        } else {
+            Some(CommitId::new(blob))
+        })
+    }
+
+    /// Store the result of a formatter.
+    pub(crate) fn insert(&self, key: &FormatCacheKey, output: &CommitId) -> FormatCacheResult<()> {
+        let path = self.entry_path(key);
+        let dir = path.parent().expect("cache entries are always in a directory");
+        fs::create_dir_all(dir)
+            .map_err(|err| FormatCacheError::create_directory(dir.into(), err))?;
+
+        // Write to a temporary file and rename it into place so that concurrent readers never
+        // see a partial entry.
+        let mut file = NamedTempFile::new_in(dir)
+            .map_err(|err| FormatCacheError::write_entry(path.clone(), err))?;
+        writeln!(file, "{}", output)
+            .map_err(|err| FormatCacheError::write_entry(path.clone(), err))?;
+        file.persist(&path)
+            .map_err(|err| FormatCacheError::write_entry(path, err.error))?;
+
+        Ok(())
+    }
+
+    /// The digest of a formatter executable.
+    fn formatter_digest(&self, formatter: &Path) -> FormatCacheResult<String> {
+        let mut digests = self
+            .formatter_digests
+            .lock()
+            .expect("format cache digest lock poisoned");
+        if let Some(digest) = digests.get(formatter) {
+            return Ok(digest.clone());
+        }
+
+        let contents = fs::read(formatter)
+            .map_err(|err| FormatCacheError::read_formatter(formatter.into(), err))?;
+        let digest = format!("{:x}", Sha256::digest(&contents));
+        digests.insert(formatter.into(), digest.clone());
+
+        Ok(digest)
+    }
+
+    /// The path to the file for a cache entry.
+    fn entry_path(&self, key: &FormatCacheKey) -> PathBuf {
+        let (prefix, rest) = key.0.split_at(2);
+        self.root.join(prefix).join(rest)
+    }
+}
+
+/// The path to a file within the work tree of a workarea.
+pub(crate) fn work_tree_path(workarea: &GitWorkArea, path: &FileName) -> Option<PathBuf> {
+    let mut cmd = Command::new("git");
+    workarea.cd_to_work_tree(&mut cmd);
+    cmd.get_current_dir().map(|dir| dir.join(path.as_path()))
+}
+
+/// Compute the blob for a file in the work tree of a workarea.
+///
+/// If `write` is set, the blob is also written to the object database.
+pub(crate) fn hash_work_tree_file(
+    workarea: &GitWorkArea,
+    path: &FileName,
+    write: bool,
+) -> FormatCacheResult<CommitId> {
+    let mut cmd = workarea.git();
+    workarea.cd_to_work_tree(&mut cmd);
+    cmd.arg("hash-object");
+    if write {
+        cmd.arg("-w");
+    }
+    let hash_object = cmd
+        .arg("--")
+        .arg(path.as_path())
+        .output()
+        .map_err(|err| GitError::subcommand("hash-object", err))?;
+    if !hash_object.status.success() {
+        return Err(FormatCacheError::hash_file(
+            path.clone(),
+            &hash_object.stderr,
+        ));
+    }
+
+    Ok(CommitId::new(
+        String::from_utf8_lossy(&hash_object.stdout).trim(),
+    ))
+}
+
+/// Replace a file in the work tree of a workarea with the contents of a blob.
+pub(crate) fn restore_work_tree_file(
+    workarea: &GitWorkArea,
+    path: &FileName,
+    blob: &CommitId,
+) -> FormatCacheResult<()> {
+    let cat_file = workarea
+        .git()
+        .arg("cat-file")
+        .arg("blob")
+        .arg(blob.as_str())
+        .output()
+        .map_err(|err| GitError::subcommand("cat-file", err))?;
+    if !cat_file.status.success() {
+        return Err(FormatCacheError::read_blob(blob.clone(), &cat_file.stderr));
+    }
+
+    let target =
+        work_tree_path(workarea, path).ok_or_else(|| FormatCacheError::no_work_tree(path.clone()))?;
+    fs::write(&target, &cat_file.stdout).map_err(|err| FormatCacheError::write_file(target, err))
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use git_workarea::CommitId;
+    use tempfile::TempDir;
+
+    use crate::actions::reformat::cache::FormatCache;
+
+    fn setup() -> (TempDir, FormatCache) {
+        let tempdir = TempDir::new().unwrap();
+        let formatter = tempdir.path().join("format.sh");
+        fs::write(&formatter, "#!/bin/sh\n").unwrap();
+        let cache = FormatCache::new(tempdir.path().join("cache")).unwrap();
+        (tempdir, cache)
+    }
+
+    #[test]
+    fn test_format_cache_roundtrip() {
+        let (tempdir, cache) = setup();
+        let formatter = tempdir.path().join("format.sh");
+        let input = CommitId::new("5716ca5987cbf97d6bb54920bea6adde242d87e6");
+        let output = CommitId::new("0b76e5aa5d6e4d4b0d5f0e6d3c2e8c6a0f6a1d2e");
+
+        let fingerprint = cache
+            .fingerprint("kind", &formatter, &[(".config", None)])
+            .unwrap();
+        let key = fingerprint.key(Some("value"), &input);
+
+        assert_eq!(cache.get(&key).unwrap(), None);
+        cache.insert(&key, &output).unwrap();
+        assert_eq!(cache.get(&key).unwrap(), Some(output));
+    }
+
+    #[test]
+    fn test_format_cache_key_inputs() {
+        let (tempdir, cache) = setup();
+        let formatter = tempdir.path().join("format.sh");
+        let input = CommitId::new("5716ca5987cbf97d6bb54920bea6adde242d87e6");
+        let config = CommitId::new("e69de29bb2d1d6434b8b29ae775ad8c2e48c5391");
+
+        let base = cache.fingerprint("kind", &formatter, &[]).unwrap();
+        let other_kind = cache.fingerprint("other", &formatter, &[]).unwrap();
+        let with_config = cache
+            .fingerprint("kind", &formatter, &[(".config", Some(config))])
+            .unwrap();
+
+        let key = base.key(None, &input);
+        assert_eq!(key, base.key(None, &input));
+        assert_ne!(key, base.key(Some(""), &input));
+        assert_ne!(key, base.key(None, &config));
+        assert_ne!(key, other_kind.key(None, &input));
+        assert_ne!(key, with_config.key(None, &input));
+    }
+
+    #[test]
+    fn test_format_cache_formatter_contents() {
+        let (tempdir, cache) = setup();
+        let formatter = tempdir.path().join("format.sh");
+        let input = CommitId::new("5716ca5987cbf97d6bb54920bea6adde242d87e6");
+        let key = cache
+            .fingerprint("kind", &formatter, &[])
+            .unwrap()
+            .key(None, &input);
+
+        // Formatters with different contents have different keys.
+        let new_formatter = tempdir.path().join("format2.sh");
+        fs::write(&new_formatter, "#!/bin/sh\nexit 0\n").unwrap();
+        let new_key = cache
+            .fingerprint("kind", &new_formatter, &[])
+            .unwrap()
+            .key(None, &input);
+
+        assert_ne!(key, new_key);
+    }
+}
@@ -6,10 +6,11 @@
 //! testing on a collection of branches which are on their way into the main integration branch.
 
 use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use git_topic_stage::{
-    CandidateTopic, IntegrationResult, Stager, StagerError, Topic, UnstageReason,
+    CandidateTopic, IntegrationResult, StagedTopic, Stager, StagerError, Topic, UnstageReason,
 };
 use git_workarea::{GitError, Identity, MergeStatus};
 use itertools::Itertools;
@@ -17,6 +18,8 @@ use log::{error, info, warn};
 use thiserror::Error;
 
 use crate::host::{Commit, CommitStatusState, HostedProject, HostingServiceError, MergeRequest};
+use crate::utils::conflicts::{self, ConflictReport};
+use crate::utils::{signing, SigningError, SigningKey};
 
 /// Operations on a stage ref.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -87,6 +90,13 @@ pub enum StageError {
         #[from]
         source: HostingServiceError,
     },
+    /// Failure to sign a stage tag.
+    #[error("signing error: {}", source)]
+    Signing {
+        /// The source of the error.
+        #[from]
+        source: SigningError,
+    },
 }
 
 impl StageError {
@@ -116,6 +126,19 @@ impl Default for TagStagePolicy {
     }
 }
 
+/// A topic which will be staged again after being unstaged by an update to the base branch.
+#[derive(Debug, Clone)]
+struct PendingRetry {
+    /// The topic to stage.
+    topic: Topic,
+    /// The priority of the topic.
+    priority: i64,
+    /// The number of retries which have already been attempted.
+    attempts: usize,
+    /// When the retry should be attempted.
+    after: DateTime<Utc>,
+}
+
 /// Implementation of the `stage` action.
 ///
 /// The stage is a collection of topic branches which should be tested together. The stage is meant
@@ -123,6 +146,11 @@ impl Default for TagStagePolicy {
 /// to and removed from the staging branch. If any topic is updated, it is removed from the stage
 /// and put at the end of the set of topics ready for merging. Additionally, if the base of the
 /// stage updates, the entire stage is recreated.
+///
+/// Each topic has a priority (`0` by default). Topics are integrated in order of decreasing
+/// priority; topics with the same priority are integrated in the order they were staged.
+/// Priorities are not stored in the stage ref, so topics on a stage loaded from an existing
+/// branch start with the default priority.
 #[derive(Debug)]
 pub struct Stage {
     /// The target branch for the stage.
@@ -135,6 +163,22 @@ pub struct Stage {
     ///
     /// Errors always create comments.
     quiet: bool,
+    /// The prefix for labels which set the priority of a merge request.
+    priority_label_prefix: Option<String>,
+    /// The priorities of staged topics, by merge request ID.
+    ///
+    /// These are not stored in the stage ref and do not survive a new `Stage`.
+    priorities: HashMap<u64, i64>,
+    /// The delay before retrying a topic unstaged by an update to the base branch.
+    retry_backoff: Option<Duration>,
+    /// The number of times a topic will be retried.
+    retry_attempts: usize,
+    /// Topics waiting to be staged again, by merge request ID.
+    ///
+    /// These are not stored in the stage ref and do not survive a new `Stage`.
+    retries: BTreeMap<u64, PendingRetry>,
+    /// The key to sign stage tags with.
+    signing_key: Option<SigningKey>,
 }
 
 impl Stage {
@@ -148,6 +192,12 @@ impl Stage {
             stager,
             project,
             quiet: false,
+            priority_label_prefix: None,
+            priorities: HashMap::new(),
+            retry_backoff: None,
+            retry_attempts: 0,
+            retries: BTreeMap::new(),
+            signing_key: None,
         };
 
         stage.update_head_ref()?;
@@ -164,11 +214,70 @@ impl Stage {
         self
     }
 
+    /// Take the priority of merge requests from their labels.
+    ///
+    /// Labels of the form `{prefix}{N}` (e.g., `priority:10`) set the priority of the merge request
+    /// to `N`. If multiple labels match, the highest priority is used.
+    pub fn priority_labels<P>(&mut self, prefix: P) -> &mut Self
+    where
+        P: Into<String>,
+    {
+        self.priority_label_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Retry topics which are unstaged by an update to the base branch.
+    ///
+    /// A topic which fails to merge after an update to the base branch is staged again once
+    /// `backoff` has passed. Each subsequent failure doubles the delay until `attempts` retries
+    /// have been made. Retries are performed by `retry_unstaged`.
+    pub fn retry_unstaged_topics(&mut self, backoff: Duration, attempts: usize) -> &mut Self {
+        self.retry_backoff = Some(backoff);
+        self.retry_attempts = attempts;
+        self
+    }
+
+    /// Sign the tags created by `tag_stage`.
+    ///
+    /// Tag refs point to signed, annotated tag objects rather than directly to the stage commit.
+    pub fn sign_tags_with(&mut self, key: SigningKey) -> &mut Self {
+        self.signing_key = Some(key);
+        self
+    }
+
+    /// The priority of a merge request.
+    ///
+    /// Priority labels take precedence, followed by the priority of the topic if it is already
+    /// staged.
+    pub fn mr_priority(&self, mr: &MergeRequest) -> i64 {
+        let label_priority = self.priority_label_prefix.as_ref().and_then(|prefix| {
+            mr.labels
+                .iter()
+                .filter_map(|label| label.strip_prefix(prefix.as_str()))
+                .filter_map(|priority| priority.trim().parse().ok())
+                .max()
+        });
+
+        label_priority.unwrap_or_else(|| self.priority_of(mr.id))
+    }
+
+    /// When the next retry of an unstaged topic is due, if any.
+    pub fn next_retry(&self) -> Option<DateTime<Utc>> {
+        self.retries.values().map(|retry| retry.after).min()
+    }
+
     /// A reference to the internal stager.
     pub fn stager(&self) -> &Stager {
         &self.stager
     }
 
+    /// A mutable reference to the internal stager.
+    ///
+    /// Callers are responsible for pushing the new state of the stage using `update_head_ref`.
+    pub(crate) fn stager_mut(&mut self) -> &mut Stager {
+        &mut self.stager
+    }
+
     /// Update the base commit for the stage.
     ///
     /// Note that this function does no checking to ensure that the given commit is related to the
@@ -203,31 +312,166 @@ impl Stage {
             new_id: Topic::new(commit.id.clone(), who.clone(), when, 0, "base", "url"),
         };
 
-        self.update_stage_base(candidate)?;
+        self.update_stage_base(candidate, when)?;
+        self.prune_priorities();
+        self.update_head_ref()
+    }
+
+    /// Stage topics which were unstaged by an update to the base branch.
+    ///
+    /// Only topics whose back-off has expired by `now` are retried. A topic which has been
+    /// updated since it was unstaged is not retried; the update decides its fate instead.
+    pub fn retry_unstaged(&mut self, now: DateTime<Utc>) -> StageResult<()> {
+        let due = self
+            .retries
+            .values()
+            .filter(|retry| retry.after <= now)
+            .map(|retry| retry.topic.id)
+            .collect::<Vec<_>>();
+        if due.is_empty() {
+            return Ok(());
+        }
+
+        for id in due {
+            if let Some(retry) = self.retries.remove(&id) {
+                self.retry_topic(retry, now)?;
+            }
+        }
+
+        self.prune_priorities();
         self.update_head_ref()
     }
 
+    /// Stage a topic again after it was unstaged by an update to the base branch.
+    fn retry_topic(&mut self, retry: PendingRetry, now: DateTime<Utc>) -> StageResult<()> {
+        let mr = self.hosted_mr(&retry.topic)?;
+        if mr.commit.id != retry.topic.commit || self.stager.find_topic_by_id(mr.id).is_some() {
+            info!(
+                target: "ghostflow/stage",
+                "not retrying {}; it has changed since it was unstaged",
+                mr.url,
+            );
+
+            return Ok(());
+        }
+
+        info!(
+            target: "ghostflow/stage",
+            "retrying {} against the updated base",
+            mr.url,
+        );
+
+        let candidate = CandidateTopic {
+            old_id: None,
+            new_id: retry.topic.clone(),
+        };
+        let (result, restaged_topics) = self.stage_ordered(candidate, retry.priority)?;
+
+        let update_reason = mr_update_reason(&mr);
+        for topic in &restaged_topics {
+            self.update_mr_state(topic, false, &update_reason)?;
+        }
+
+        match result {
+            Some(IntegrationResult::Staged(_)) => {
+                self.send_mr_commit_status(&mr, CommitStatusState::Success, "staged");
+                self.send_mr_comment(
+                    &mr,
+                    &format!(
+                        "This merge request has been restaged automatically after the update to \
+                         the {} branch; the retry succeeded.",
+                        self.branch,
+                    ),
+                );
+            },
+            Some(IntegrationResult::Unstaged(ref topic, ref reason)) => {
+                let attempts = retry.attempts + 1;
+                let next = self.schedule_retry(topic, retry.priority, attempts, now);
+                self.send_mr_commit_status(
+                    &mr,
+                    CommitStatusState::Failed,
+                    &format!("failed to merge: {}", unstaged_status_desc(reason)),
+                );
+
+                let prefix = format!(
+                    "The automatic retry (attempt {}) to restage this merge request after the \
+                     update to the {} branch failed due to ",
+                    attempts, self.branch,
+                );
+                let mut msg = self.unstaged_report(&mr, reason, &prefix).render();
+                if let Some(next) = next {
+                    msg.push_str(&format!("\n\nStaging will be retried again after {}.", next));
+                } else {
+                    msg.push_str("\n\nNo further retries will be made.");
+                }
+                self.send_mr_comment(&mr, &msg);
+            },
+            Some(ref result @ IntegrationResult::Unmerged(..)) => {
+                self.update_mr_state(result, false, "")?;
+            },
+            None => (),
+        }
+
+        Ok(())
+    }
+
+    /// Schedule a retry for a topic unstaged by an update to the base branch.
+    ///
+    /// Returns when the retry will happen, if it was scheduled.
+    fn schedule_retry(
+        &mut self,
+        topic: &Topic,
+        priority: i64,
+        attempts: usize,
+        now: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        let backoff = self.retry_backoff?;
+        if attempts >= self.retry_attempts {
+            return None;
+        }
+
+        // Double the delay for each attempt (within reason).
+        let factor = 1 << attempts.min(16);
+        let after = now + backoff * factor;
+        self.retries.insert(
+            topic.id,
+            PendingRetry {
+                topic: topic.clone(),
+                priority,
+                attempts,
+                after,
+            },
+        );
+
+        Some(after)
+    }
+
     /// Add a merge request to the stage.
     fn stage_merge_request_impl(
         &mut self,
         mr: &MergeRequest,
         topic_name: &str,
+        priority: i64,
         who: &Identity,
         when: DateTime<Utc>,
     ) -> StageResult<()> {
         info!(
             target: "ghostflow/stage",
-            "attempting to stage {}",
+            "attempting to stage {} with priority {}",
             mr.url,
+            priority,
         );
 
+        // An explicit request supersedes any pending retry.
+        self.retries.remove(&mr.id);
+
         // Fetch the MR commit into the stager's git context.
         self.project
             .service
             .fetch_mr(self.stager.git_context(), mr)?;
 
         let old_commit = if let Some(staged) = self.stager.find_topic_by_id(mr.id) {
-            if &mr.commit.id == staged.commit() {
+            if &mr.commit.id == staged.commit() && priority == self.priority_of(mr.id) {
                 self.send_info_mr_comment(
                     mr,
                     "This topic has already been staged; ignoring the request to stage.",
@@ -291,7 +535,8 @@ impl Stage {
         };
 
         // Update the stage.
-        self.update_stage_mr(candidate, old_hosted_commit, mr)?;
+        self.update_stage_mr(candidate, priority, old_hosted_commit, mr)?;
+        self.prune_priorities();
         // Push the new stage state to the remote.
         self.update_head_ref()
     }
@@ -307,17 +552,34 @@ impl Stage {
     where
         N: AsRef<str>,
     {
-        self.stage_merge_request_impl(mr, name.as_ref(), who, when)
+        let priority = self.mr_priority(mr);
+        self.stage_merge_request_impl(mr, name.as_ref(), priority, who, when)
     }
 
     /// Add a merge request to the stage.
+    ///
+    /// The priority of the merge request is determined by `mr_priority`.
     pub fn stage_merge_request(
         &mut self,
         mr: &MergeRequest,
         who: &Identity,
         when: DateTime<Utc>,
     ) -> StageResult<()> {
-        self.stage_merge_request_impl(mr, &mr.source_branch, who, when)
+        let priority = self.mr_priority(mr);
+        self.stage_merge_request_impl(mr, &mr.source_branch, priority, who, when)
+    }
+
+    /// Add a merge request to the stage with an explicit priority.
+    ///
+    /// This is intended for priorities requested by `Do: stage --priority=<N>` commands.
+    pub fn stage_merge_request_with_priority(
+        &mut self,
+        mr: &MergeRequest,
+        priority: i64,
+        who: &Identity,
+        when: DateTime<Utc>,
+    ) -> StageResult<()> {
+        self.stage_merge_request_impl(mr, &mr.source_branch, priority, who, when)
     }
 
     /// Unstage a merge request.
@@ -327,6 +589,8 @@ impl Stage {
         success_msg: &str,
         missing_msg: Option<&str>,
     ) -> StageResult<()> {
+        self.retries.remove(&mr.id);
+
         let staged_topic_opt = self.stager.find_topic_by_id(mr.id).cloned();
 
         if let Some(staged_topic) = staged_topic_opt {
@@ -344,6 +608,7 @@ impl Stage {
                 self.update_mr_state(topic, false, &mr_update_reason(mr))?;
             }
 
+            self.prune_priorities();
             // Push the new stage state to the remote.
             self.update_head_ref()?
         } else if let Some(msg) = missing_msg {
@@ -442,21 +707,109 @@ impl Stage {
             }
         }
 
+        self.prune_priorities();
         // Push the new stage to the remote.
         self.update_head_ref()
     }
 
+    /// The priority of a topic on the stage.
+    fn priority_of(&self, id: u64) -> i64 {
+        self.priorities.get(&id).copied().unwrap_or(0)
+    }
+
+    /// Forget the priorities of topics which are no longer on the stage.
+    fn prune_priorities(&mut self) {
+        let staged = self
+            .stager
+            .topics()
+            .iter()
+            .map(|staged| staged.topic.id)
+            .collect::<HashSet<_>>();
+        self.priorities.retain(|id, _| staged.contains(id));
+    }
+
+    /// Stage a candidate topic according to its priority.
+    ///
+    /// Topics with a lower priority are removed from the stage and integrated again after the
+    /// candidate. Returns the result for the candidate and the results for any other topics which
+    /// were integrated again.
+    fn stage_ordered(
+        &mut self,
+        candidate: CandidateTopic,
+        priority: i64,
+    ) -> StageResult<(Option<IntegrationResult>, Vec<IntegrationResult>)> {
+        let id = candidate.new_id.id;
+        let deferred = {
+            let topics = self.stager.topics();
+            topics
+                .iter()
+                .position(|staged| {
+                    staged.topic.id != id && self.priority_of(staged.topic.id) < priority
+                })
+                .map(|split| {
+                    topics[split..]
+                        .iter()
+                        .filter(|staged| staged.topic.id != id)
+                        .cloned()
+                        .collect::<Vec<StagedTopic>>()
+                })
+                .unwrap_or_default()
+        };
+        self.priorities.insert(id, priority);
+
+        let mut restaged = Vec::new();
+
+        // Remove topics from the end so that the remaining topics are not integrated again.
+        for staged in deferred.iter().rev() {
+            let stage_result = self.stager.unstage(staged.clone())?;
+            restaged.extend(
+                stage_result
+                    .results
+                    .into_iter()
+                    .filter(|result| result.topic().id != id),
+            );
+        }
+
+        let mut results = self.stager.stage(candidate)?.results;
+        let new_topic = results.pop();
+        restaged.extend(results);
+
+        for staged in deferred {
+            let candidate = CandidateTopic {
+                old_id: None,
+                new_id: staged.topic,
+            };
+            restaged.extend(self.stager.stage(candidate)?.results);
+        }
+
+        Ok((new_topic, restaged))
+    }
+
     /// Update the base of the stage.
-    fn update_stage_base(&mut self, candidate: CandidateTopic) -> StageResult<()> {
+    fn update_stage_base(
+        &mut self,
+        candidate: CandidateTopic,
+        when: DateTime<Utc>,
+    ) -> StageResult<()> {
         let stage_result = self.stager.stage(candidate)?;
+        let update_reason = format!("an update to the {} branch causing ", self.branch);
 
         // Update topics have been punted off of the stage (successfully staged commits are fine).
         for topic in &stage_result.results {
-            self.update_mr_state(
-                topic,
-                false,
-                &format!("an update to the {} branch causing ", self.branch),
-            )?;
+            let retry = if let IntegrationResult::Unstaged(ref topic, _) = *topic {
+                let priority = self.priority_of(topic.id);
+                self.schedule_retry(topic, priority, 0, when)
+            } else {
+                None
+            };
+            let note = retry.map(|after| {
+                format!(
+                    "Staging will be retried automatically against the new base after {}.",
+                    after,
+                )
+            });
+
+            self.update_mr_state_impl(topic, false, &update_reason, note.as_deref())?;
         }
 
         Ok(())
@@ -466,10 +819,11 @@ impl Stage {
     fn update_stage_mr(
         &mut self,
         candidate: CandidateTopic,
+        priority: i64,
         old_commit: Option<&Commit>,
         mr: &MergeRequest,
     ) -> StageResult<()> {
-        let stage_result = self.stager.stage(candidate)?;
+        let (new_topic, restaged_topics) = self.stage_ordered(candidate, priority)?;
 
         // We use success here because it was successfully unstaged. A failure would cause a old
         // commits to never be shown as "passing" where this information might be useful at a
@@ -478,28 +832,23 @@ impl Stage {
             self.send_commit_status(commit, CommitStatusState::Success, "unstaged");
         }
 
-        // If no results were made, the base branch was updated and no topics were already staged;
-        // everything is fine.
-        let results = stage_result.results[..].split_last();
-        if let Some((new_topic, restaged_topics)) = results {
-            let update_reason = mr_update_reason(mr);
-
-            // Update topics have been punted off of the stage (successfully staged commits are
-            // fine).
-            for topic in restaged_topics {
-                self.update_mr_state(topic, false, &update_reason)?;
-            }
+        // Update topics have been punted off of the stage (successfully staged commits are fine).
+        let update_reason = mr_update_reason(mr);
+        for topic in &restaged_topics {
+            self.update_mr_state(topic, false, &update_reason)?;
+        }
 
-            self.update_mr_state(new_topic, true, "")?;
+        if let Some(new_topic) = new_topic {
+            self.update_mr_state(&new_topic, true, "")?;
         }
 
         Ok(())
     }
 
     /// Update the `HEAD` ref of the stage.
-    fn update_head_ref(&self) -> StageResult<()> {
+    pub(crate) fn update_head_ref(&self) -> StageResult<()> {
         let ctx = self.stager.git_context();
-        let refname = format!("refs/stage/{}/head", self.branch);
+        let refname = self.head_ref();
 
         let update_ref = ctx
             .git()
@@ -544,18 +893,26 @@ impl Stage {
     ) -> StageResult<(DateTime<Utc>, String)> {
         let ctx = self.stager.git_context();
         let now = Utc::now();
-        let refname = format!(
-            "refs/stage/{}/{}/{}",
-            self.branch,
-            reason,
-            now.format(date_format),
-        );
+        let tag_name = format!("stage/{}/{}/{}", self.branch, reason, now.format(date_format));
+        let refname = format!("refs/{}", tag_name);
+
+        let target = if let Some(key) = self.signing_key.as_ref() {
+            let message = format!(
+                "Stage of {} for {} testing as of {}.",
+                self.branch,
+                reason,
+                now.format(date_format),
+            );
+            signing::signed_tag(ctx, self.stager.head(), &tag_name, &message, key)?
+        } else {
+            self.stager.head().clone()
+        };
 
         let update_ref = ctx
             .git()
             .arg("update-ref")
             .arg(&refname)
-            .arg(self.stager.head().as_str())
+            .arg(target.as_str())
             .arg("0000000000000000000000000000000000000000")
             .output()
             .map_err(|err| GitError::subcommand("update-ref", err))?;
@@ -573,7 +930,7 @@ impl Stage {
             .git()
             .arg("update-ref")
             .arg(&reason_refname)
-            .arg(self.stager.head().as_str())
+            .arg(target.as_str())
             .output()
             .map_err(|err| GitError::subcommand("update-ref reason", err))?;
         if !update_ref_tagged.status.success() {
@@ -618,6 +975,19 @@ impl Stage {
         result: &IntegrationResult,
         post_success: bool,
         update_reason: &str,
+    ) -> StageResult<()> {
+        self.update_mr_state_impl(result, post_success, update_reason, None)
+    }
+
+    /// Update the state of a merge request after being staged.
+    ///
+    /// The note, if any, is appended to the comment for unstaged topics.
+    fn update_mr_state_impl(
+        &self,
+        result: &IntegrationResult,
+        post_success: bool,
+        update_reason: &str,
+        note: Option<&str>,
     ) -> StageResult<()> {
         let mr = self.hosted_mr(result.topic())?;
         match *result {
@@ -634,7 +1004,16 @@ impl Stage {
                     CommitStatusState::Failed,
                     &format!("failed to merge: {}", unstaged_status_desc(reason)),
                 );
-                self.send_mr_comment(&mr, &unstaged_status_message(reason, update_reason));
+                let prefix = format!(
+                    "This merge request has been unstaged due to {}",
+                    update_reason,
+                );
+                let mut msg = self.unstaged_report(&mr, reason, &prefix).render();
+                if let Some(note) = note {
+                    msg.push_str("\n\n");
+                    msg.push_str(note);
+                }
+                self.send_mr_comment(&mr, &msg);
             },
             IntegrationResult::Unmerged(_, ref reason) => {
                 let (status, desc) = unmerged_status_desc(reason);
@@ -646,13 +1025,62 @@ impl Stage {
                     Self::send_mr_comment
                 };
 
-                comment_method(self, &mr, &unmerged_status_message(reason));
+                let mut report = ConflictReport::new(unmerged_status_message(reason));
+                if let MergeStatus::NoCommonHistory = *reason {
+                    report.reproduce_merge(self.stager.base().as_str(), &mr);
+                }
+                comment_method(self, &mr, &report.render());
             },
         }
 
         Ok(())
     }
 
+    /// The name of the ref tracking the `HEAD` of the stage.
+    fn head_ref(&self) -> String {
+        format!("refs/stage/{}/head", self.branch)
+    }
+
+    /// A report of why a topic could not be staged.
+    ///
+    /// The report starts with `prefix` and names the other staged topics which change the
+    /// conflicting paths. The reproduction merges into the current stage commit rather than the
+    /// stage ref since the ref moves as topics are staged.
+    fn unstaged_report(
+        &self,
+        mr: &MergeRequest,
+        reason: &UnstageReason,
+        prefix: &str,
+    ) -> ConflictReport {
+        let mut report = match *reason {
+            UnstageReason::MergeConflict(ref conflicts) => {
+                let mut report = ConflictReport::new(format!(
+                    "{}merge conflicts in the following paths:",
+                    prefix,
+                ));
+                report.conflicts(conflicts);
+                report
+            },
+        };
+
+        let conflict_paths = report.paths().iter().cloned().collect::<BTreeSet<_>>();
+        let ctx = self.stager.git_context();
+        let base = self.stager.base();
+        for staged in self.stager.topics() {
+            if staged.topic.id == mr.id {
+                continue;
+            }
+
+            let changed = conflicts::changed_paths(ctx, base, staged.commit());
+            if !changed.is_disjoint(&conflict_paths) {
+                report.topic(&staged.topic.name, &staged.topic.url);
+            }
+        }
+
+        report.reproduce_merge(self.stager.head().as_str(), mr);
+        report
+    }
+
     /// Set the commit status to a merge request.
     fn send_mr_commit_status(&self, mr: &MergeRequest, status: CommitStatusState, desc: &str) {
         let status = mr.create_commit_status(status, "ghostflow-stager", desc);
@@ -720,28 +1148,6 @@ fn mr_update_reason(mr: &MergeRequest) -> String {
     )
 }
 
-/// The status message for an unstaged topic.
-fn unstaged_status_message(reason: &UnstageReason, update_reason: &str) -> String {
-    let reason_message = match *reason {
-        UnstageReason::MergeConflict(ref conflicts) => {
-            let mut conflict_paths = conflicts
-                .iter()
-                .map(|conflict| conflict.path().to_string_lossy())
-                .dedup();
-
-            format!(
-                "merge conflicts in the following paths:\n\n  - `{}`",
-                conflict_paths.join("`\n  - `")
-            )
-        },
-    };
-
-    format!(
-        "This merge request has been unstaged due to {}{}",
-        update_reason, reason_message,
-    )
-}
-
 /// The description for a merge status.
 fn unmerged_status_desc(reason: &MergeStatus) -> (CommitStatusState, &str) {
     match *reason {
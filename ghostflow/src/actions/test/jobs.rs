@@ -52,6 +52,15 @@ pub enum TestJobsError {
         #[source]
         source: serde_json::Error,
     },
+    /// Failure to move a test job file into the queue.
+    #[error("failed to queue the job {}: {}", path.display(), source)]
+    QueueJob {
+        /// The path to the test job.
+        path: PathBuf,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
 }
 
 impl TestJobsError {
@@ -75,6 +84,13 @@ impl TestJobsError {
             source,
         }
     }
+
+    fn queue_job(path: PathBuf, source: io::Error) -> Self {
+        TestJobsError::QueueJob {
+            path,
+            source,
+        }
+    }
 }
 
 type TestJobsResult<T> = Result<T, TestJobsError>;
@@ -145,21 +161,35 @@ impl TestJobs {
     }
 
     /// Queue a job into the target directory.
+    ///
+    /// The job is written to a temporary file in the queue first and then renamed so that
+    /// consumers of the queue never see a partially written job.
     fn queue_job(&self, data: Value) -> TestJobsResult<()> {
         let rndpart = rand::thread_rng()
             .sample_iter(&rand::distributions::Alphanumeric)
             .map(char::from)
             .take(12)
             .collect::<String>();
-        let job_path = self
-            .queue
-            .join(format!("{}-{}.json", Utc::now().to_rfc3339(), rndpart));
-        let mut job_file = File::create(&job_path)
-            .map_err(|err| TestJobsError::create_job(job_path.clone(), err))?;
-        serde_json::to_writer(&mut job_file, &data)
-            .map_err(|err| TestJobsError::write_job(job_path, err))?;
+        let job_name = format!("{}-{}", Utc::now().to_rfc3339(), rndpart);
+        let tmp_path = self.queue.join(format!(".{}.json.tmp", job_name));
+        let job_path = self.queue.join(format!("{}.json", job_name));
+
+        let write_res = File::create(&tmp_path)
+            .map_err(|err| TestJobsError::create_job(tmp_path.clone(), err))
+            .and_then(|mut job_file| {
+                serde_json::to_writer(&mut job_file, &data)
+                    .map_err(|err| TestJobsError::write_job(tmp_path.clone(), err))
+            })
+            .and_then(|()| {
+                fs::rename(&tmp_path, &job_path)
+                    .map_err(|err| TestJobsError::queue_job(job_path, err))
+            });
+        if write_res.is_err() {
+            // Do not leave partial jobs behind.
+            let _ = fs::remove_file(&tmp_path);
+        }
 
-        Ok(())
+        write_res
     }
 
     /// Send a comment to a merge request.
@@ -11,6 +11,7 @@ pub use self::traits::HostedProject;
 pub use self::traits::HostingService;
 pub use self::traits::HostingServiceError;
 
+pub use self::types::AnnotationLevel;
 pub use self::types::Award;
 pub use self::types::CheckStatus;
 pub use self::types::Comment;
@@ -21,4 +22,5 @@ pub use self::types::Issue;
 pub use self::types::MergeRequest;
 pub use self::types::PendingCommitStatus;
 pub use self::types::Repo;
+pub use self::types::ReviewAnnotation;
 pub use self::types::User;
@@ -61,6 +61,16 @@ pub trait HostedPipelineService: HostingService {
         &self,
         mr: &MergeRequest,
     ) -> Result<Option<Vec<Pipeline>>, HostingServiceError>;
+    /// Get all of the pipelines for a commit.
+    ///
+    /// Returns `None` if the service cannot look up pipelines by commit.
+    fn pipelines_for_commit(
+        &self,
+        commit: &Commit,
+    ) -> Result<Option<Vec<Pipeline>>, HostingServiceError> {
+        let _ = commit;
+        Ok(None)
+    }
     /// Get all of the jobs for a pipeline.
     fn pipeline_jobs(
         &self,
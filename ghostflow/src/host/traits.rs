@@ -180,6 +180,21 @@ pub trait HostingService: Send + Sync {
         Ok(())
     }
 
+    /// Create a review of a merge request with annotations.
+    ///
+    /// Services which cannot attach messages to locations should ignore the annotations; their
+    /// messages are also part of the description.
+    fn post_review_with_annotations(
+        &self,
+        status: PendingCommitStatus,
+        mr: &MergeRequest,
+        description: &str,
+        annotations: &[ReviewAnnotation],
+    ) -> Result<(), HostingServiceError> {
+        let _ = annotations;
+        self.post_review(status, mr, description)
+    }
+
     /// Get awards on a merge request.
     fn get_mr_awards(&self, mr: &MergeRequest) -> Result<Vec<Award>, HostingServiceError>;
 
@@ -153,6 +153,8 @@ pub struct MergeRequest {
     pub reference: String,
     /// Whether the source branch should be removed when merging.
     pub remove_source_branch: bool,
+    /// The labels for the merge request.
+    pub labels: Vec<String>,
 }
 
 impl MergeRequest {
@@ -213,6 +215,32 @@ pub struct CommitStatus {
     pub target_url: Option<String>,
 }
 
+/// The severity of a review annotation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnotationLevel {
+    /// The annotation is informational.
+    Notice,
+    /// The annotation is a warning.
+    Warning,
+    /// The annotation is a failure.
+    Failure,
+}
+
+/// A review message attached to a location within a merge request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReviewAnnotation {
+    /// The commit the message applies to (if known).
+    pub commit: Option<CommitId>,
+    /// The path the message applies to.
+    pub path: String,
+    /// The line within the path (if known).
+    pub line: Option<u64>,
+    /// The severity of the message.
+    pub level: AnnotationLevel,
+    /// The message.
+    pub message: String,
+}
+
 /// A user on the service.
 #[derive(Debug, Clone)]
 pub struct User {
@@ -1,15 +1,17 @@
 
 mod log;
 mod mock;
-mod utils;
+pub(crate) mod utils;
 
 mod check;
 mod clone;
+mod commands;
 mod dashboard;
 mod data;
 mod follow;
 mod merge;
 mod reformat;
+mod signing;
 mod stage;
 mod test_jobs;
 mod test_pipelines;
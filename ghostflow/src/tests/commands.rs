@@ -0,0 +1,354 @@
+use crate::actions::commands::{
+    AccessLevel, Command, CommandHandler, CommandKind, CommandStatus, Commands, HandlerResult,
+    StaticAccessLevels,
+};
+use crate::host::{Comment, HostingService, MergeRequest};
+use crate::tests::mock::MockMutation;
+use crate::tests::utils::TestProject;
+
+/// A call made to a command handler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HandlerCall {
+    /// The command which was performed.
+    kind: CommandKind,
+    /// The merge request the command was for.
+    mr: u64,
+    /// The user who requested the command.
+    author: String,
+    /// The `--priority` argument, if given.
+    priority: Option<String>,
+}
+
+/// A command handler which records the commands it performs.
+#[derive(Debug, Default)]
+struct RecordingHandler {
+    /// The calls made to the handler.
+    calls: Vec<HandlerCall>,
+    /// A command which fails when performed.
+    fail: Option<CommandKind>,
+}
+
+impl RecordingHandler {
+    fn record(&mut self, mr: &MergeRequest, command: &Command) -> HandlerResult {
+        self.calls.push(HandlerCall {
+            kind: command.kind,
+            mr: mr.id,
+            author: command.author.handle.clone(),
+            priority: command.arguments.value("priority").map(Into::into),
+        });
+
+        if self.fail == Some(command.kind) {
+            return Err("the handler broke".into());
+        }
+
+        Ok(())
+    }
+}
+
+impl CommandHandler for RecordingHandler {
+    fn supports(&self, kind: CommandKind) -> bool {
+        kind != CommandKind::Merge
+    }
+
+    fn check(&mut self, mr: &MergeRequest, command: &Command) -> HandlerResult {
+        self.record(mr, command)
+    }
+
+    fn merge(&mut self, mr: &MergeRequest, command: &Command) -> HandlerResult {
+        self.record(mr, command)
+    }
+
+    fn reformat(&mut self, mr: &MergeRequest, command: &Command) -> HandlerResult {
+        self.record(mr, command)
+    }
+
+    fn stage(&mut self, mr: &MergeRequest, command: &Command) -> HandlerResult {
+        self.record(mr, command)
+    }
+
+    fn test(&mut self, mr: &MergeRequest, command: &Command) -> HandlerResult {
+        self.record(mr, command)
+    }
+
+    fn unstage(&mut self, mr: &MergeRequest, command: &Command) -> HandlerResult {
+        self.record(mr, command)
+    }
+}
+
+fn setup() -> (TestProject, MergeRequest) {
+    let project = TestProject::new();
+    let base = project.commit(&[], &[("README", "base\n")], "base");
+    project.set_branch("main", &base);
+    let topic = project.commit(&[&base], &[("README", "topic\n")], "topic");
+    let mr = project.add_mr(1, "topic", "main", &topic);
+
+    (project, mr)
+}
+
+/// Commands where `reviewer` is a maintainer and everyone else is a contributor.
+fn commands(project: &TestProject) -> Commands<StaticAccessLevels> {
+    let mut access = StaticAccessLevels::new(AccessLevel::Contributor);
+    access.user("reviewer", AccessLevel::Maintainer);
+    Commands::new(project.service.clone(), access)
+}
+
+/// Add a comment to the merge request.
+fn comment(project: &TestProject, mr: &MergeRequest, author: &str, content: &str) -> Comment {
+    project
+        .service
+        .add_comment(TestProject::NAME, mr.id, author, content)
+        .unwrap();
+    project.service.get_mr_comments(mr).unwrap().pop().unwrap()
+}
+
+/// The comments posted by the action.
+fn replies(project: &TestProject) -> Vec<String> {
+    project
+        .service
+        .take_mutations()
+        .into_iter()
+        .map(|mutation| {
+            if let MockMutation::MrComment {
+                project,
+                id,
+                content,
+            } = mutation
+            {
+                assert_eq!(project, TestProject::NAME);
+                assert_eq!(id, 1);
+                content
+            } else {
+                panic!("unexpected mutation: {:?}", mutation);
+            }
+        })
+        .collect()
+}
+
+fn call(kind: CommandKind, author: &str, priority: Option<&str>) -> HandlerCall {
+    HandlerCall {
+        kind,
+        mr: 1,
+        author: author.into(),
+        priority: priority.map(Into::into),
+    }
+}
+
+#[test]
+fn test_commands_completed() {
+    let (project, mr) = setup();
+    let comment = comment(
+        &project,
+        &mr,
+        "reviewer",
+        "Looks good.\n\nDo: check\n  Do: stage --priority=3\n",
+    );
+    let mut handler = RecordingHandler::default();
+
+    let outcomes = commands(&project)
+        .handle_comment(&mr, &comment, &mut handler)
+        .unwrap();
+
+    assert_eq!(outcomes.len(), 2);
+    assert!(outcomes
+        .iter()
+        .all(|outcome| matches!(outcome.status, CommandStatus::Completed)));
+    assert_eq!(outcomes[0].command.comment_id, comment.id);
+    assert_eq!(
+        handler.calls,
+        [
+            call(CommandKind::Check, "reviewer", None),
+            call(CommandKind::Stage, "reviewer", Some("3")),
+        ],
+    );
+    assert_eq!(
+        replies(&project),
+        ["@reviewer:\n\nCompleted: `check`, `stage`."],
+    );
+}
+
+#[test]
+fn test_commands_no_commands() {
+    let (project, mr) = setup();
+    let comment = comment(&project, &mr, "reviewer", "Please do: check this.");
+    let mut handler = RecordingHandler::default();
+
+    let outcomes = commands(&project)
+        .handle_comment(&mr, &comment, &mut handler)
+        .unwrap();
+
+    assert!(outcomes.is_empty());
+    assert!(handler.calls.is_empty());
+    assert!(replies(&project).is_empty());
+}
+
+#[test]
+fn test_commands_denied() {
+    let (project, mr) = setup();
+    let comment = comment(&project, &mr, "author", "Do: stage\nDo: check");
+    let mut handler = RecordingHandler::default();
+
+    let outcomes = commands(&project)
+        .handle_comment(&mr, &comment, &mut handler)
+        .unwrap();
+
+    assert_eq!(outcomes.len(), 2);
+    if let CommandStatus::Denied {
+        level,
+        required,
+    } = outcomes[0].status
+    {
+        assert_eq!(level, AccessLevel::Contributor);
+        assert_eq!(required, AccessLevel::Developer);
+    } else {
+        panic!("unexpected status: {:?}", outcomes[0].status);
+    }
+    assert!(matches!(outcomes[1].status, CommandStatus::Completed));
+
+    // Denied commands never reach the handler.
+    assert_eq!(handler.calls, [call(CommandKind::Check, "author", None)]);
+    assert_eq!(
+        replies(&project),
+        [
+            "@author:\n\nCompleted: `check`.\n\nErrors:\n\n  - `stage` requires the developer \
+             access level; you have contributor",
+        ],
+    );
+}
+
+#[test]
+fn test_commands_require() {
+    let (project, mr) = setup();
+    let comment = comment(&project, &mr, "author", "Do: stage");
+    let mut handler = RecordingHandler::default();
+
+    let mut commands = commands(&project);
+    commands.require(CommandKind::Stage, AccessLevel::Contributor);
+    let outcomes = commands
+        .handle_comment(&mr, &comment, &mut handler)
+        .unwrap();
+
+    assert!(matches!(outcomes[0].status, CommandStatus::Completed));
+    assert_eq!(handler.calls, [call(CommandKind::Stage, "author", None)]);
+}
+
+#[test]
+fn test_commands_unsupported_and_failed() {
+    let (project, mr) = setup();
+    let comment = comment(&project, &mr, "reviewer", "Do: merge\nDo: unstage");
+    let mut handler = RecordingHandler {
+        fail: Some(CommandKind::Unstage),
+        ..Default::default()
+    };
+
+    let outcomes = commands(&project)
+        .handle_comment(&mr, &comment, &mut handler)
+        .unwrap();
+
+    assert!(matches!(outcomes[0].status, CommandStatus::Unsupported));
+    assert!(matches!(outcomes[1].status, CommandStatus::Failed { .. }));
+    assert_eq!(handler.calls, [call(CommandKind::Unstage, "reviewer", None)]);
+    assert_eq!(
+        replies(&project),
+        [
+            "@reviewer:\n\nErrors:\n\n  - `merge` is not supported for this project\n  - \
+             `unstage` failed: the handler broke",
+        ],
+    );
+}
+
+#[test]
+fn test_commands_help() {
+    let (project, mr) = setup();
+    let comment = comment(&project, &mr, "author", "Do: frobnicate\nDo: check");
+    let mut handler = RecordingHandler::default();
+
+    let outcomes = commands(&project)
+        .handle_comment(&mr, &comment, &mut handler)
+        .unwrap();
+
+    // Valid commands are still performed.
+    assert_eq!(outcomes.len(), 1);
+    assert_eq!(handler.calls, [call(CommandKind::Check, "author", None)]);
+
+    let replies = replies(&project);
+    assert_eq!(replies.len(), 1);
+    assert!(replies[0].starts_with(
+        "@author:\n\nCompleted: `check`.\n\nErrors:\n\n  - unknown command `frobnicate`\n\n\
+         Commands are given on lines starting with `Do:` in a comment:\n\n",
+    ));
+    assert!(replies[0].contains("  - `stage`: "));
+    assert!(replies[0].contains("    - `--priority=<value>`: "));
+    assert!(replies[0].ends_with("  - `help`: show this message"));
+}
+
+#[test]
+fn test_commands_quiet() {
+    let (project, mr) = setup();
+    let mut handler = RecordingHandler::default();
+    let mut commands = commands(&project);
+    commands.quiet();
+
+    // Completed commands are not acknowledged.
+    let check = comment(&project, &mr, "reviewer", "Do: check");
+    commands.handle_comment(&mr, &check, &mut handler).unwrap();
+    assert!(replies(&project).is_empty());
+
+    // Problems are still reported, without the completed commands.
+    let denied = comment(&project, &mr, "author", "Do: check\nDo: unstage");
+    commands.handle_comment(&mr, &denied, &mut handler).unwrap();
+    assert_eq!(
+        replies(&project),
+        [
+            "@author:\n\nErrors:\n\n  - `unstage` requires the developer access level; you \
+             have contributor",
+        ],
+    );
+
+    // Requests for help are answered.
+    let help = comment(&project, &mr, "author", "Do: help");
+    commands.handle_comment(&mr, &help, &mut handler).unwrap();
+    let replies = replies(&project);
+    assert_eq!(replies.len(), 1);
+    assert!(replies[0].starts_with("@author:\n\nCommands are given on lines starting with "));
+
+    assert_eq!(
+        handler.calls,
+        [
+            call(CommandKind::Check, "reviewer", None),
+            call(CommandKind::Check, "author", None),
+        ],
+    );
+}
+
+#[test]
+fn test_commands_handle_mr() {
+    let (project, mr) = setup();
+    comment(&project, &mr, "author", "Do: check");
+    comment(&project, &mr, "reviewer", "Do: stage");
+    let mut handler = RecordingHandler::default();
+
+    let outcomes = commands(&project)
+        .handle_mr(&mr, None, &mut handler)
+        .unwrap();
+
+    assert_eq!(outcomes.len(), 2);
+    assert_eq!(
+        handler.calls,
+        [
+            call(CommandKind::Check, "author", None),
+            call(CommandKind::Stage, "reviewer", None),
+        ],
+    );
+    assert_eq!(
+        replies(&project),
+        ["@author:\n\nCompleted: `check`.", "@reviewer:\n\nCompleted: `stage`."],
+    );
+
+    // Replies from the service are not treated as commands.
+    let later = project.service.get_mr_comments(&mr).unwrap()[1].created_at;
+    let outcomes = commands(&project)
+        .handle_mr(&mr, Some(later), &mut handler)
+        .unwrap();
+    assert!(outcomes.is_empty());
+    assert!(replies(&project).is_empty());
+}
@@ -0,0 +1,304 @@
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+use git_workarea::CommitId;
+
+use crate::actions::dashboard::{Dashboard, DashboardResults};
+use crate::host::{Commit, CommitStatusState, HostingService};
+use crate::tests::mock::{MockMutation, MockService};
+use crate::tests::utils::{http_stub, StubRequest};
+
+/// A minimal stand-in for a dashboard results API.
+///
+/// Each request is answered with the next response in the list.
+fn dashboard_stub(responses: Vec<(u16, &'static str)>) -> (String, mpsc::Receiver<StubRequest>) {
+    let count = responses.len();
+    let mut responses = responses.into_iter();
+    http_stub(count, move |_| {
+        let (status, body) = responses.next().expect("more requests than responses");
+        (status, body.into())
+    })
+}
+
+fn status_of(mutation: &MockMutation) -> (CommitStatusState, &str) {
+    if let MockMutation::CommitStatus {
+        state,
+        description,
+        ..
+    } = mutation
+    {
+        (*state, description.as_str())
+    } else {
+        panic!("unexpected mutation: {:?}", mutation);
+    }
+}
+
+#[test]
+fn test_dashboard_live_status() {
+    let (endpoint, paths) = dashboard_stub(vec![
+        (404, ""),
+        (200, r#"{"summary": {"passed": 10, "failed": 0, "pending": 2}}"#),
+        (200, r#"{"summary": {"passed": 12, "failed": 0, "pending": 0}}"#),
+        (200, r#"{"summary": {"passed": 11, "failed": 1, "pending": 0}}"#),
+    ]);
+
+    let service = MockService::new(MockService::make_user("ghostflow"));
+    let repo = service.add_project("base", None).unwrap();
+    let commit = Commit {
+        repo,
+        refname: Some("refs/heads/main".into()),
+        id: CommitId::new("0123456789abcdef0123456789abcdef01234567"),
+        last_pipeline: None,
+    };
+
+    let mut results = DashboardResults::new(
+        format!("{}/results/{{branch_name}}/{{commit}}", endpoint),
+        "summary.passed",
+        "summary.failed",
+    );
+    results.pending("summary.pending");
+    let mut dashboard = Dashboard::new(
+        service.clone() as Arc<dyn HostingService>,
+        "ci/dashboard",
+        "https://dashboard.example.com/{commit}",
+        "dashboard results",
+    );
+    dashboard.live_status(results);
+
+    for _ in 0..4 {
+        dashboard.post_for_commit(&commit).unwrap();
+    }
+
+    assert_eq!(
+        paths.recv().unwrap().path,
+        "/results/main/0123456789abcdef0123456789abcdef01234567",
+    );
+
+    let mutations = service.take_mutations();
+    let statuses = mutations.iter().map(status_of).collect::<Vec<_>>();
+    assert_eq!(
+        statuses,
+        [
+            (
+                CommitStatusState::Pending,
+                "dashboard results (no results yet)",
+            ),
+            (
+                CommitStatusState::Running,
+                "dashboard results (10 passed, 0 failed, 2 pending)",
+            ),
+            (
+                CommitStatusState::Success,
+                "dashboard results (12 passed, 0 failed, 0 pending)",
+            ),
+            (
+                CommitStatusState::Failed,
+                "dashboard results (11 passed, 1 failed, 0 pending)",
+            ),
+        ],
+    );
+}
+
+#[test]
+fn test_dashboard_live_status_missing_count() {
+    let (endpoint, _paths) = dashboard_stub(vec![(200, r#"{"passed": 1}"#)]);
+
+    let service = MockService::new(MockService::make_user("ghostflow"));
+    let repo = service.add_project("base", None).unwrap();
+    let commit = Commit {
+        repo,
+        refname: None,
+        id: CommitId::new("0123456789abcdef0123456789abcdef01234567"),
+        last_pipeline: None,
+    };
+
+    let mut dashboard = Dashboard::new(
+        service.clone() as Arc<dyn HostingService>,
+        "ci/dashboard",
+        "https://dashboard.example.com/{commit}",
+        "dashboard results",
+    );
+    dashboard.live_status(DashboardResults::new(
+        format!("{}/results", endpoint),
+        "passed",
+        "failed",
+    ));
+
+    dashboard.post_for_commit(&commit).unwrap();
+
+    // The status links to the dashboard and reports the error.
+    let mutations = service.take_mutations();
+    assert_eq!(mutations.len(), 1);
+    if let MockMutation::CommitStatus {
+        state,
+        description,
+        target_url,
+        ..
+    } = &mutations[0]
+    {
+        assert_eq!(*state, CommitStatusState::Pending);
+        assert_eq!(
+            *description,
+            format!(
+                "dashboard results (failed to query results: dashboard results from {}/results \
+                 do not have a count at `failed`)",
+                endpoint,
+            ),
+        );
+        assert_eq!(
+            target_url.as_deref(),
+            Some("https://dashboard.example.com/0123456789abcdef0123456789abcdef01234567"),
+        );
+    } else {
+        panic!("unexpected mutation: {:?}", mutations[0]);
+    }
+}
+
+#[test]
+fn test_dashboard_live_status_query_errors() {
+    let (endpoint, _paths) = dashboard_stub(vec![(500, "oops"), (200, "not json")]);
+
+    let service = MockService::new(MockService::make_user("ghostflow"));
+    let repo = service.add_project("base", None).unwrap();
+    let commit = Commit {
+        repo,
+        refname: None,
+        id: CommitId::new("0123456789abcdef0123456789abcdef01234567"),
+        last_pipeline: None,
+    };
+
+    let mut dashboard = Dashboard::new(
+        service.clone() as Arc<dyn HostingService>,
+        "ci/dashboard",
+        "https://dashboard.example.com/{commit}",
+        "dashboard results",
+    );
+    dashboard.live_status(DashboardResults::new(
+        format!("{}/results", endpoint),
+        "passed",
+        "failed",
+    ));
+
+    dashboard.post_for_commit(&commit).unwrap();
+    dashboard.post_for_commit(&commit).unwrap();
+
+    let mutations = service.take_mutations();
+    let statuses = mutations.iter().map(status_of).collect::<Vec<_>>();
+    assert_eq!(statuses.len(), 2);
+    assert_eq!(statuses[0].0, CommitStatusState::Pending);
+    assert!(statuses[0].1.contains("(status: Some(500)): oops"));
+    assert_eq!(statuses[1].0, CommitStatusState::Pending);
+    assert!(statuses[1].1.contains("invalid dashboard results"));
+}
+
+/// Create a dashboard which polls for results.
+fn polling_dashboard(
+    service: &Arc<MockService>,
+    endpoint: &str,
+    timeout: Duration,
+) -> Dashboard {
+    let mut results = DashboardResults::new(
+        format!("{}/results", endpoint),
+        "summary.passed",
+        "summary.failed",
+    );
+    results
+        .pending("summary.pending")
+        .poll(Duration::from_millis(10), timeout);
+    let mut dashboard = Dashboard::new(
+        service.clone() as Arc<dyn HostingService>,
+        "ci/dashboard",
+        "https://dashboard.example.com/{commit}",
+        "dashboard results",
+    );
+    dashboard.live_status(results);
+    dashboard
+}
+
+#[test]
+fn test_dashboard_live_status_poll() {
+    let (endpoint, requests) = dashboard_stub(vec![
+        (404, ""),
+        (200, r#"{"summary": {"passed": 0, "failed": 0, "pending": 0}}"#),
+        (200, r#"{"summary": {"passed": 10, "failed": 1, "pending": 2}}"#),
+        (200, r#"{"summary": {"passed": 12, "failed": 1, "pending": 0}}"#),
+    ]);
+
+    let service = MockService::new(MockService::make_user("ghostflow"));
+    let repo = service.add_project("base", None).unwrap();
+    let commit = Commit {
+        repo,
+        refname: None,
+        id: CommitId::new("0123456789abcdef0123456789abcdef01234567"),
+        last_pipeline: None,
+    };
+
+    let dashboard = polling_dashboard(&service, &endpoint, Duration::from_secs(60));
+    dashboard.post_for_commit(&commit).unwrap();
+
+    // The dashboard is queried until the results settle.
+    assert_eq!(requests.iter().count(), 4);
+
+    // The status is updated whenever the results change.
+    let mutations = service.take_mutations();
+    let statuses = mutations.iter().map(status_of).collect::<Vec<_>>();
+    assert_eq!(
+        statuses,
+        [
+            (
+                CommitStatusState::Pending,
+                "dashboard results (waiting for results)",
+            ),
+            (
+                CommitStatusState::Pending,
+                "dashboard results (0 passed, 0 failed, 0 pending)",
+            ),
+            (
+                CommitStatusState::Failed,
+                "dashboard results (10 passed, 1 failed, 2 pending)",
+            ),
+            (
+                CommitStatusState::Failed,
+                "dashboard results (12 passed, 1 failed, 0 pending)",
+            ),
+        ],
+    );
+}
+
+#[test]
+fn test_dashboard_live_status_poll_timeout() {
+    let pending = r#"{"summary": {"passed": 1, "failed": 0, "pending": 1}}"#;
+    let (endpoint, _requests) = dashboard_stub(vec![(200, pending); 1000]);
+
+    let service = MockService::new(MockService::make_user("ghostflow"));
+    let repo = service.add_project("base", None).unwrap();
+    let commit = Commit {
+        repo,
+        refname: None,
+        id: CommitId::new("0123456789abcdef0123456789abcdef01234567"),
+        last_pipeline: None,
+    };
+
+    let timeout = Duration::from_millis(200);
+    let dashboard = polling_dashboard(&service, &endpoint, timeout);
+    let start = Instant::now();
+    dashboard.post_for_commit(&commit).unwrap();
+    assert!(start.elapsed() >= timeout);
+
+    // The last results are kept once the timeout expires; unchanged results are posted once.
+    let mutations = service.take_mutations();
+    let statuses = mutations.iter().map(status_of).collect::<Vec<_>>();
+    assert_eq!(
+        statuses,
+        [
+            (
+                CommitStatusState::Pending,
+                "dashboard results (waiting for results)",
+            ),
+            (
+                CommitStatusState::Running,
+                "dashboard results (1 passed, 0 failed, 1 pending)",
+            ),
+        ],
+    );
+}
@@ -0,0 +1,160 @@
+use std::fs;
+use std::time::Duration;
+
+use git_workarea::GitContext;
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+
+use crate::actions::data::{Data, DataActionResult, LocalDirectoryDestination};
+use crate::host::Repo;
+use crate::tests::utils::{git, git_input, init_bare};
+
+/// The SHA256 digest of `hello`.
+const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+fn data_ref() -> String {
+    format!("refs/data/SHA256/{}", HELLO_SHA256)
+}
+
+/// Create a repository with a data ref in it.
+fn data_repo() -> (TempDir, GitContext, Repo) {
+    let (dir, remote) = init_bare();
+    let blob = git_input(&remote, &["hash-object", "-w", "--stdin"], b"hello");
+    git(&remote, &["update-ref", &data_ref(), &blob]);
+
+    let repo = Repo {
+        name: "data".into(),
+        url: dir.path().to_string_lossy().into(),
+        forked_from: None,
+    };
+
+    (dir, remote, repo)
+}
+
+fn has_ref(ctx: &GitContext, refname: &str) -> bool {
+    !git(ctx, &["for-each-ref", "--format=%(refname)", refname]).is_empty()
+}
+
+#[test]
+fn test_data_keeps_refs_on_destination_failure() {
+    let (_remote_dir, remote, repo) = data_repo();
+    let (_local_dir, ctx) = init_bare();
+    let target = TempDir::new().unwrap();
+    // A file where the destination expects a directory cannot be stored into.
+    let blocked = target.path().join("blocked");
+    fs::write(&blocked, "").unwrap();
+
+    let mut data = Data::new(ctx.clone());
+    data.add_data_destination(LocalDirectoryDestination::new(&blocked))
+        .attempts(1)
+        .retry_delay(Duration::from_secs(0));
+
+    let res = data.fetch_data(&repo).unwrap();
+    if let DataActionResult::DataPushed(reports) = res {
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].error.is_some());
+    } else {
+        panic!("unexpected result: {:?}", res);
+    }
+
+    // The data is still available for the next run.
+    assert!(has_ref(&remote, &data_ref()));
+    assert!(has_ref(&ctx, &data_ref()));
+
+    // Once the destination works, the data is mirrored and the refs are removed.
+    fs::remove_file(&blocked).unwrap();
+    let res = data.fetch_data(&repo).unwrap();
+    if let DataActionResult::DataPushed(reports) = res {
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].error, None);
+    } else {
+        panic!("unexpected result: {:?}", res);
+    }
+
+    let stored = fs::read_to_string(blocked.join("SHA256").join(HELLO_SHA256)).unwrap();
+    assert_eq!(stored, "hello");
+    assert!(!has_ref(&remote, &data_ref()));
+    assert!(!has_ref(&ctx, &data_ref()));
+}
+
+#[test]
+fn test_data_keep_refs() {
+    let (_remote_dir, remote, repo) = data_repo();
+    let (_local_dir, ctx) = init_bare();
+    let target = TempDir::new().unwrap();
+
+    let mut data = Data::new(ctx.clone());
+    data.add_data_destination(LocalDirectoryDestination::new(target.path()))
+        .keep_refs();
+
+    data.fetch_data(&repo).unwrap();
+
+    assert!(target.path().join("SHA256").join(HELLO_SHA256).exists());
+    assert!(has_ref(&remote, &data_ref()));
+    assert!(has_ref(&ctx, &data_ref()));
+}
+
+/// Add a tree data ref to the repository.
+///
+/// Returns the name of the ref and the listing which the ref's hash is over.
+fn add_tree_ref(ctx: &GitContext, entries: &[u8], listing: &[u8]) -> String {
+    let tree = git_input(ctx, &["mktree", "-z"], entries);
+    let refname = format!("refs/data/SHA256/{:x}", Sha256::digest(listing));
+    git(ctx, &["update-ref", &refname, &tree]);
+    refname
+}
+
+#[test]
+fn test_data_tree_non_utf8_path() {
+    let (_remote_dir, remote, repo) = data_repo();
+    let (_local_dir, ctx) = init_bare();
+    let target = TempDir::new().unwrap();
+
+    let blob = git(&remote, &["rev-parse", &data_ref()]);
+    let entries = [
+        format!("100644 blob {}\t", blob).as_bytes(),
+        &b"caf\xe9\0"[..],
+    ]
+    .concat();
+    // The path is hashed as-is rather than after a lossy conversion to UTF-8.
+    let listing = [HELLO_SHA256.as_bytes(), &b"  caf\xe9\n"[..]].concat();
+    let tree_ref = add_tree_ref(&remote, &entries, &listing);
+
+    let mut data = Data::new(ctx.clone());
+    data.add_data_destination(LocalDirectoryDestination::new(target.path()));
+
+    data.fetch_data(&repo).unwrap();
+
+    let listing_name = tree_ref.rsplit('/').next().unwrap();
+    let stored = fs::read(target.path().join("SHA256").join(listing_name)).unwrap();
+    assert_eq!(stored, listing);
+    assert!(target.path().join("SHA256").join(HELLO_SHA256).exists());
+    assert!(!has_ref(&ctx, &tree_ref));
+}
+
+#[test]
+fn test_data_tree_with_submodule() {
+    let (_remote_dir, remote, repo) = data_repo();
+    let (_local_dir, ctx) = init_bare();
+    let target = TempDir::new().unwrap();
+
+    let blob = git(&remote, &["rev-parse", &data_ref()]);
+    let entries = format!(
+        "100644 blob {}\tfile\0160000 commit {}\tsub\0",
+        blob,
+        "1".repeat(40),
+    );
+    let listing = format!("{}  file\n", HELLO_SHA256);
+    let tree_ref = add_tree_ref(&remote, entries.as_bytes(), listing.as_bytes());
+
+    let mut data = Data::new(ctx.clone());
+    data.add_data_destination(LocalDirectoryDestination::new(target.path()))
+        .keep_refs();
+
+    data.fetch_data(&repo).unwrap();
+
+    // The tree is rejected, but other data refs are still mirrored.
+    assert!(!has_ref(&ctx, &tree_ref));
+    assert!(has_ref(&ctx, &data_ref()));
+    assert!(target.path().join("SHA256").join(HELLO_SHA256).exists());
+}
@@ -0,0 +1,116 @@
+use chrono::{Duration, TimeZone, Utc};
+use git_workarea::GitContext;
+use tempfile::TempDir;
+
+use crate::actions::follow::{Follow, FollowError};
+use crate::tests::utils::{git, init_bare};
+
+/// Point `main` at a new commit, optionally on top of the current one.
+fn commit(ctx: &GitContext, message: &str, on_top: bool) -> String {
+    let tree = git(ctx, &["mktree"]);
+    let mut args = vec!["commit-tree", &tree, "-m", message];
+    let parent;
+    if on_top {
+        parent = git(ctx, &["rev-parse", "refs/heads/main"]);
+        args.extend(["-p", parent.as_str()]);
+    }
+    let commit = git(ctx, &args);
+    git(ctx, &["update-ref", "refs/heads/main", &commit]);
+    commit
+}
+
+fn setup() -> (TempDir, GitContext, TempDir, GitContext) {
+    let (local_dir, local) = init_bare();
+    let (remote_dir, remote) = init_bare();
+    git(
+        &local,
+        &["remote", "add", "origin", remote_dir.path().to_str().unwrap()],
+    );
+    commit(&local, "initial", false);
+    (local_dir, local, remote_dir, remote)
+}
+
+fn remote_refs(remote: &GitContext, prefix: &str) -> Vec<String> {
+    let refs = git(remote, &["for-each-ref", "--format=%(refname)", prefix]);
+    refs.lines().map(Into::into).collect()
+}
+
+#[test]
+fn test_follow_history_retention() {
+    let (_local_dir, local, _remote_dir, remote) = setup();
+
+    let mut follow = Follow::new(local.clone(), "main");
+    follow.history_retention(Duration::days(7));
+
+    let start = Utc.timestamp_opt(1609556645, 0).unwrap();
+    follow.update_at("nightly", start).unwrap();
+    commit(&local, "second", true);
+    follow.update_at("nightly", start + Duration::days(1)).unwrap();
+    commit(&local, "third", true);
+    follow.update_at("nightly", start + Duration::days(10)).unwrap();
+
+    assert_eq!(
+        remote_refs(&remote, "refs/follow/"),
+        ["refs/follow/main/nightly"],
+    );
+    assert_eq!(
+        remote_refs(&remote, "refs/follow-history/"),
+        ["refs/follow-history/main/nightly/20210112T030405Z"],
+    );
+    assert_eq!(
+        git(&remote, &["rev-parse", "refs/follow/main/nightly"]),
+        git(&local, &["rev-parse", "refs/heads/main"]),
+    );
+}
+
+#[test]
+fn test_follow_fast_forward_only() {
+    let (_local_dir, local, _remote_dir, remote) = setup();
+
+    let mut follow = Follow::new(local.clone(), "main");
+    follow.fast_forward_only();
+
+    let when = Utc.timestamp_opt(1609556645, 0).unwrap();
+    follow.update_at("good", when).unwrap();
+    let good = commit(&local, "second", true);
+    follow.update_at("good", when).unwrap();
+    assert_eq!(git(&remote, &["rev-parse", "refs/follow/main/good"]), good);
+
+    // Rewrite history so that `main` no longer contains the followed commit.
+    let rewound = commit(&local, "rewritten", false);
+    let err = follow.update_at("good", when).unwrap_err();
+    if let FollowError::NotFastForward {
+        branch,
+        refname,
+    } = err
+    {
+        assert_eq!(branch, "main");
+        assert_eq!(refname, "refs/follow/main/good");
+    } else {
+        panic!("unexpected error: {:?}", err);
+    }
+    assert_eq!(git(&remote, &["rev-parse", "refs/follow/main/good"]), good);
+
+    follow.force_update_at("good", when).unwrap();
+    assert_eq!(
+        git(&remote, &["rev-parse", "refs/follow/main/good"]),
+        rewound,
+    );
+}
+
+#[test]
+fn test_follow_update() {
+    let (_local_dir, local, _remote_dir, remote) = setup();
+
+    let follow = Follow::new(local.clone(), "main");
+    follow.update("nightly").unwrap();
+
+    assert_eq!(
+        remote_refs(&remote, "refs/"),
+        ["refs/follow/main/nightly"],
+    );
+    assert_eq!(
+        git(&remote, &["rev-parse", "refs/follow/main/nightly"]),
+        git(&local, &["rev-parse", "refs/heads/main"]),
+    );
+}
@@ -0,0 +1,1087 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use git_topic_stage::Stager;
+use git_workarea::CommitId;
+
+use crate::actions::merge::backport::{parse_backport_directives, BackportDirective};
+use crate::actions::merge::{
+    IntoBranch, Merge, MergeActionResult, MergeMany, MergePolicyFilter, MergePreview,
+    MergePreviewOutcome, MergeSettings, MergeTopology, MergeTrain, MergeTrainOutcome,
+    MergeTrainStatus, MergeTrainTests,
+};
+use crate::actions::stage::Stage;
+use crate::host::{MergeRequest, PipelineState, User};
+use crate::tests::utils::{git, TestProject, EMAIL, NAME};
+use crate::utils::Trailer;
+
+/// A merge policy which accepts all trailers and rejects merge requests with `Rejected-by`.
+#[derive(Debug, Default, Clone)]
+struct TestPolicy {
+    trailers: Vec<Trailer>,
+    rejections: Vec<String>,
+}
+
+impl MergePolicyFilter for TestPolicy {
+    fn process_trailer(&mut self, trailer: &Trailer, _: Option<&User>) {
+        if trailer.token == "Rejected-by" {
+            self.rejections.push(format!("rejected by {}", trailer.value));
+        } else {
+            self.trailers.push(trailer.clone());
+        }
+    }
+
+    fn result(self) -> Result<Vec<Trailer>, Vec<String>> {
+        if self.rejections.is_empty() {
+            Ok(self.trailers)
+        } else {
+            Err(self.rejections)
+        }
+    }
+}
+
+fn directive(branch: &str, commit: Option<&str>) -> BackportDirective {
+    BackportDirective {
+        branch: branch.into(),
+        commit: commit.map(Into::into),
+    }
+}
+
+#[test]
+fn test_parse_backport_directives() {
+    assert_eq!(parse_backport_directives("No directives here."), []);
+    assert_eq!(
+        parse_backport_directives(
+            "Fix the frobnicator.\n\
+             \n\
+             Backport: release-2.4\n  \
+             Backport: release-2.3:HEAD~2\n\
+             Backport: release-2.2 : HEAD^\n",
+        ),
+        [
+            Ok(directive("release-2.4", None)),
+            Ok(directive("release-2.3", Some("HEAD~2"))),
+            Ok(directive("release-2.2", Some("HEAD^"))),
+        ],
+    );
+}
+
+#[test]
+fn test_parse_backport_directives_invalid() {
+    assert_eq!(
+        parse_backport_directives(
+            "Backport:\n\
+             Backport: :HEAD~1\n\
+             Backport: release-2.4:\n\
+             Backport: release 2.4\n",
+        ),
+        [
+            Err("Backport:".into()),
+            Err("Backport: :HEAD~1".into()),
+            Err("Backport: release-2.4:".into()),
+            Err("Backport: release 2.4".into()),
+        ],
+    );
+}
+
+/// A project with `main` and `release` branches and a two-commit topic targeting `main`.
+///
+/// Only `reviewer` may request backports.
+fn directives_project() -> (TestProject, MergeMany, [String; 2], MergeRequest) {
+    let project = TestProject::new();
+    let base = project.commit(&[], &[("README", "base\n")], "base");
+    project.set_branch("main", &base);
+    project.set_branch("release", &base);
+
+    let fix = project.commit(
+        &[&base],
+        &[("README", "base\n"), ("fix.txt", "fix\n")],
+        "fix a bug",
+    );
+    let feature = project.commit(
+        &[&fix],
+        &[
+            ("README", "base\n"),
+            ("fix.txt", "fix\n"),
+            ("feature.txt", "feature\n"),
+        ],
+        "add a feature",
+    );
+    let mr = project.add_mr(1, "topic", "main", &feature);
+
+    let mut merge = MergeMany::new(project.ctx.clone(), project.project.clone());
+    merge.backport_requesters(vec!["reviewer"]);
+
+    (project, merge, [fix, feature], mr)
+}
+
+fn directives_settings(branches: &[&str]) -> Vec<MergeSettings<TestPolicy>> {
+    branches
+        .iter()
+        .map(|branch| MergeSettings::new(*branch, TestPolicy::default()))
+        .collect()
+}
+
+fn add_directive(project: &TestProject, author: &str, content: &str) {
+    project
+        .service
+        .add_comment(TestProject::NAME, 1, author, content)
+        .unwrap();
+}
+
+#[test]
+fn test_merge_directives() {
+    let (project, merge, [fix, feature], mr) = directives_project();
+    add_directive(&project, "reviewer", "Backport: release:HEAD~1");
+
+    let res = merge
+        .merge_mr_with_directives(
+            &mr,
+            &TestProject::identity(),
+            Utc::now(),
+            &directives_settings(&["main", "release"]),
+        )
+        .unwrap();
+
+    assert_eq!(res, MergeActionResult::Success);
+    assert_eq!(
+        git(&project.remote, &["rev-parse", "refs/heads/main^2"]),
+        feature,
+    );
+    assert_eq!(
+        git(&project.remote, &["rev-parse", "refs/heads/release^2"]),
+        fix,
+    );
+}
+
+#[test]
+fn test_merge_directives_invalid() {
+    let (project, merge, _, mr) = directives_project();
+    let main = project.remote_branch("main");
+    let release = project.remote_branch("release");
+    let unrelated = project.commit(&[&main], &[("README", "other\n")], "unrelated");
+    add_directive(&project, "author", "Backport: release");
+    add_directive(&project, "reviewer", "Backport: missing");
+    add_directive(&project, "reviewer", "Backport: release:HEAD~2");
+    add_directive(
+        &project,
+        "reviewer",
+        &format!("Backport: release:{}", unrelated),
+    );
+    add_directive(&project, "reviewer", "Backport: release:HEAD~5");
+    add_directive(&project, "reviewer", "Backport: release 2");
+
+    let res = merge
+        .merge_mr_with_directives(
+            &mr,
+            &TestProject::identity(),
+            Utc::now(),
+            &directives_settings(&["main", "release"]),
+        )
+        .unwrap();
+
+    assert_eq!(res, MergeActionResult::Failed);
+    assert_eq!(
+        project.service.mr_comments(TestProject::NAME, 1),
+        [format!(
+            "This merge request may not be merged because of invalid backport requests:\n\n  \
+             - @author is not allowed to request backports (`release`)\n  \
+             - `missing` is not a branch which accepts backports\n  \
+             - `HEAD~2` is not part of this merge request (`release:HEAD~2`)\n  \
+             - `{0}` is not part of this merge request (`release:{0}`)\n  \
+             - `HEAD~5` does not name a commit (`release:HEAD~5`)\n  \
+             - `Backport: release 2` is not a valid backport directive",
+            unrelated,
+        )],
+    );
+
+    // Nothing is merged.
+    assert_eq!(project.remote_branch("main"), main);
+    assert_eq!(project.remote_branch("release"), release);
+}
+
+#[test]
+fn test_merge_directives_unknown_target() {
+    let (project, merge, _, mr) = directives_project();
+    let release = project.remote_branch("release");
+    add_directive(&project, "reviewer", "Backport: release");
+
+    let res = merge
+        .merge_mr_with_directives(
+            &mr,
+            &TestProject::identity(),
+            Utc::now(),
+            &directives_settings(&["release"]),
+        )
+        .unwrap();
+
+    assert_eq!(res, MergeActionResult::Failed);
+    assert_eq!(
+        project.service.mr_comments(TestProject::NAME, 1),
+        ["This merge request may not be merged because of invalid backport requests:\n\n  \
+          - the target branch `main` does not accept merges"],
+    );
+    assert_eq!(project.remote_branch("release"), release);
+}
+
+#[test]
+fn test_merge_preview_render() {
+    let preview = MergePreview {
+        branch: "main".into(),
+        topology: MergeTopology::NoFastForward,
+        outcome: MergePreviewOutcome::Merge {
+            commit_message: "Merge topic 'topic'\n\nReviewed-by: Reviewer <r@example.com>\n".into(),
+        },
+        update_merges: vec![("main".into(), "next".into())],
+    };
+
+    assert_eq!(
+        preview.render(),
+        "Merge preview for `main` (nothing has been merged or pushed):\n\
+         \n\
+         The topic would be merged with the following commit message:\n\
+         \n\
+         ```\n\
+         Merge topic 'topic'\n\
+         \n\
+         Reviewed-by: Reviewer <r@example.com>\n\
+         ```\n\
+         \n\
+         The following branches would then be updated:\n\
+         \n  \
+         - `main` into `next`",
+    );
+}
+
+#[test]
+fn test_merge_preview_render_rejected() {
+    let preview = MergePreview {
+        branch: "main".into(),
+        topology: MergeTopology::Squash,
+        outcome: MergePreviewOutcome::Rejected {
+            reasons: vec!["not reviewed".into(), "rejected by @someone".into()],
+        },
+        update_merges: vec![("main".into(), "next".into())],
+    };
+
+    assert!(!preview.outcome.is_mergeable());
+    assert_eq!(
+        preview.render(),
+        "Merge preview for `main` (nothing has been merged or pushed):\n\
+         \n\
+         The topic may not be merged because:\n\
+         \n  \
+         - not reviewed\n  \
+         - rejected by @someone",
+    );
+}
+
+/// A date format for stage tags which does not collide within a test.
+const TAG_DATE_FORMAT: &str = "%Y%m%d%H%M%S%.9f";
+
+/// A project with a `main` branch and topics which each add a single file to it.
+fn train_project(topics: u64) -> (TestProject, String, Vec<MergeRequest>) {
+    let project = TestProject::new();
+    let base = project.commit(&[], &[("README", "base\n")], "base");
+    project.set_branch("main", &base);
+
+    let mrs = (1..=topics)
+        .map(|id| {
+            let file = format!("topic-{}", id);
+            let commit = project.commit(
+                &[&base],
+                &[("README", "base\n"), (&file, "content\n")],
+                &format!("add {}", file),
+            );
+            project.add_mr(id, &file, "main", &commit)
+        })
+        .collect();
+
+    (project, base, mrs)
+}
+
+/// Simulate CI for the project.
+///
+/// Pipelines for a commit get the state `ci` returns for the IDs of the topics merged into it.
+fn set_ci<F>(project: &TestProject, ci: F)
+where
+    F: Fn(&[u64]) -> PipelineState + Send + 'static,
+{
+    let ctx = project.ctx.clone();
+    project.service.set_ci(move |commit| {
+        let topics = git(&ctx, &["ls-tree", "--name-only", commit.id.as_str()])
+            .lines()
+            .filter_map(|name| name.strip_prefix("topic-"))
+            .map(|id| id.parse().unwrap())
+            .collect::<Vec<u64>>();
+        Some(ci(&topics))
+    });
+}
+
+/// The state of a pipeline which fails if any of the `failing` topics are present.
+fn fail_with(topics: &[u64], failing: &[u64]) -> PipelineState {
+    if topics.iter().any(|topic| failing.contains(topic)) {
+        PipelineState::Failed
+    } else {
+        PipelineState::Success
+    }
+}
+
+/// Create a merge train into `main` which does not wait for pending results.
+fn make_train(project: &TestProject, base: &str) -> MergeTrain<TestPolicy> {
+    let stager = Stager::new(&project.ctx, CommitId::new(base), TestProject::identity());
+    let stage = Stage::new(stager, "main", project.project.clone()).unwrap();
+    let settings = MergeSettings::new("main", TestPolicy::default());
+    let mut train = MergeTrain::new(
+        project.ctx.clone(),
+        project.project.clone(),
+        settings,
+        stage,
+        MergeTrainTests::Pipelines,
+    );
+    train
+        .poll_interval(Duration::from_secs(0))
+        .timeout(Duration::from_secs(0))
+        .ref_date_format(TAG_DATE_FORMAT);
+    train
+}
+
+/// The IDs of the topics on the train's stage.
+fn staged_ids(train: &MergeTrain<TestPolicy>) -> Vec<u64> {
+    train
+        .stage()
+        .stager()
+        .topics()
+        .iter()
+        .map(|staged| staged.topic.id)
+        .collect()
+}
+
+/// The subjects of the first-parent history of `main` on the service.
+fn remote_main_log(project: &TestProject) -> Vec<String> {
+    git(
+        &project.remote,
+        &["log", "--first-parent", "--format=%s", "refs/heads/main"],
+    )
+    .lines()
+    .map(Into::into)
+    .collect()
+}
+
+#[test]
+fn test_merge_train_bisect() {
+    let (project, base, mrs) = train_project(4);
+    let who = TestProject::identity();
+    set_ci(&project, |topics| fail_with(topics, &[3]));
+
+    let mut train = make_train(&project, &base);
+    // A topic which is on the stage, but not part of the batch.
+    train
+        .stage_mut()
+        .stage_merge_request(&mrs[0], &who, Utc::now())
+        .unwrap();
+
+    let outcome = train.run(&mrs[1..], &who, Utc::now()).unwrap();
+
+    assert_eq!(
+        outcome,
+        MergeTrainOutcome {
+            status: MergeTrainStatus::Merged,
+            merged: vec![2, 4],
+            rejected: vec![3],
+        },
+    );
+    assert_eq!(
+        remote_main_log(&project),
+        ["Merge topic 'topic-4' into main", "Merge topic 'topic-2' into main", "base"],
+    );
+    // The topic outside of the batch survives bisection and the merge.
+    assert_eq!(staged_ids(&train), [1]);
+    assert!(project
+        .service
+        .mr_comments(TestProject::NAME, 3)
+        .iter()
+        .any(|comment| {
+            comment == "This merge request has been unstaged after failing testing as part of a \
+                        merge train."
+        }));
+    for id in &[2, 4] {
+        assert!(project
+            .service
+            .mr_comments(TestProject::NAME, *id)
+            .iter()
+            .any(|comment| {
+                comment == "This merge request has been merged as part of a merge train."
+            }));
+    }
+}
+
+#[test]
+fn test_merge_train_bisect_restage() {
+    let (project, base, mrs) = train_project(5);
+    let who = TestProject::identity();
+    set_ci(&project, |topics| fail_with(topics, &[4, 5]));
+
+    let mut train = make_train(&project, &base);
+    train
+        .stage_mut()
+        .stage_merge_request(&mrs[0], &who, Utc::now())
+        .unwrap();
+
+    // Each failure is found by bisection and the rest of the batch is tested again.
+    let outcome = train.run(&mrs[1..], &who, Utc::now()).unwrap();
+
+    assert_eq!(
+        outcome,
+        MergeTrainOutcome {
+            status: MergeTrainStatus::Merged,
+            merged: vec![2, 3],
+            rejected: vec![4, 5],
+        },
+    );
+    assert_eq!(
+        remote_main_log(&project),
+        ["Merge topic 'topic-3' into main", "Merge topic 'topic-2' into main", "base"],
+    );
+    assert_eq!(staged_ids(&train), [1]);
+}
+
+#[test]
+fn test_merge_train_merge_rejected() {
+    let (project, base, mrs) = train_project(3);
+    let who = TestProject::identity();
+    set_ci(&project, |_| PipelineState::Success);
+    project
+        .service
+        .add_comment(TestProject::NAME, 3, "reviewer", "-1")
+        .unwrap();
+
+    let mut train = make_train(&project, &base);
+    let outcome = train.run(&mrs, &who, Utc::now()).unwrap();
+
+    assert_eq!(
+        outcome,
+        MergeTrainOutcome {
+            status: MergeTrainStatus::Merged,
+            merged: vec![1, 2],
+            rejected: vec![3],
+        },
+    );
+    assert_eq!(
+        remote_main_log(&project),
+        ["Merge topic 'topic-2' into main", "Merge topic 'topic-1' into main", "base"],
+    );
+    assert!(project
+        .service
+        .mr_comments(TestProject::NAME, 3)
+        .iter()
+        .any(|comment| comment.starts_with("This merge request may not be merged into `main`")));
+    assert!(staged_ids(&train).is_empty());
+}
+
+#[test]
+fn test_merge_train_timed_out() {
+    let (project, base, mrs) = train_project(2);
+    let who = TestProject::identity();
+    set_ci(&project, |topics| {
+        if topics.contains(&2) {
+            PipelineState::InProgress
+        } else {
+            PipelineState::Success
+        }
+    });
+
+    let mut train = make_train(&project, &base);
+    let outcome = train.run(&mrs, &who, Utc::now()).unwrap();
+
+    assert_eq!(
+        outcome,
+        MergeTrainOutcome {
+            status: MergeTrainStatus::TimedOut,
+            merged: Vec::new(),
+            rejected: Vec::new(),
+        },
+    );
+    // Nothing is pushed and the batch remains on the stage.
+    assert_eq!(project.remote_branch("main"), base);
+    assert_eq!(staged_ids(&train), [1, 2]);
+}
+
+#[test]
+fn test_merge_train_bisect_prefix_passes() {
+    let (project, base, mrs) = train_project(3);
+    let who = TestProject::identity();
+    // Each topic passes on its own, but the second and third fail together.
+    set_ci(&project, |topics| {
+        if topics.contains(&2) && topics.contains(&3) {
+            PipelineState::Failed
+        } else {
+            PipelineState::Success
+        }
+    });
+
+    let mut train = make_train(&project, &base);
+    let outcome = train.run(&mrs, &who, Utc::now()).unwrap();
+
+    assert_eq!(
+        outcome,
+        MergeTrainOutcome {
+            status: MergeTrainStatus::Merged,
+            merged: vec![1, 2],
+            rejected: vec![3],
+        },
+    );
+    assert_eq!(
+        remote_main_log(&project),
+        ["Merge topic 'topic-2' into main", "Merge topic 'topic-1' into main", "base"],
+    );
+    assert!(staged_ids(&train).is_empty());
+}
+
+#[test]
+fn test_merge_train_bisect_timed_out() {
+    let (project, base, mrs) = train_project(3);
+    let who = TestProject::identity();
+    // The full batch fails, but testing its prefixes does not finish.
+    set_ci(&project, |topics| {
+        if topics.contains(&3) {
+            PipelineState::Failed
+        } else {
+            PipelineState::InProgress
+        }
+    });
+
+    let mut train = make_train(&project, &base);
+    let outcome = train.run(&mrs, &who, Utc::now()).unwrap();
+
+    // Nothing is blamed for the failure.
+    assert_eq!(
+        outcome,
+        MergeTrainOutcome {
+            status: MergeTrainStatus::TimedOut,
+            merged: Vec::new(),
+            rejected: Vec::new(),
+        },
+    );
+    assert_eq!(project.remote_branch("main"), base);
+    assert_eq!(staged_ids(&train), [1, 2, 3]);
+    assert!(!project
+        .service
+        .mr_comments(TestProject::NAME, 3)
+        .iter()
+        .any(|comment| comment.contains("unstaged")));
+}
+
+/// The trailers added to merges of merge requests reviewed in `topology_project`.
+const TOPOLOGY_TRAILERS: &str = "Reviewed-by: reviewer name <reviewer@example.com>\n\
+                                 Merge-request: !1\n";
+
+/// A project with a reviewed two-commit topic and a `main` branch which has moved on since.
+///
+/// The topic's second commit reverts the first commit's change to `README`, so the topic as a
+/// whole does not conflict with `main`, but its first commit does if `conflict` is set.
+struct TopologyProject {
+    project: TestProject,
+    main: String,
+    commits: [String; 2],
+    mr: MergeRequest,
+}
+
+impl TopologyProject {
+    fn new(conflict: bool) -> Self {
+        let project = TestProject::new();
+        let base = project.commit(&[], &[("README", "base\n")], "base");
+        let main_readme = if conflict { "main\n" } else { "base\n" };
+        let main = project.commit(
+            &[&base],
+            &[("README", main_readme), ("main.txt", "main\n")],
+            "main",
+        );
+        project.set_branch("main", &main);
+
+        let first = project.commit(
+            &[&base],
+            &[("README", "topic\n"), ("one.txt", "one\n")],
+            "add one\n\nWith a body.",
+        );
+        let second = project.commit(
+            &[&first],
+            &[("README", "base\n"), ("one.txt", "one\n"), ("two.txt", "two\n")],
+            "add two",
+        );
+        let mr = project.add_mr(1, "topic", "main", &second);
+        project
+            .service
+            .add_comment(TestProject::NAME, 1, "reviewer", "+2")
+            .unwrap();
+
+        Self {
+            project,
+            main,
+            commits: [first, second],
+            mr,
+        }
+    }
+
+    fn merge(&self, settings: MergeSettings<TestPolicy>) -> MergeActionResult {
+        let merge = Merge::new(
+            self.project.ctx.clone(),
+            self.project.project.clone(),
+            settings,
+        );
+        merge
+            .merge_mr(&self.mr, &TestProject::identity(), Utc::now())
+            .unwrap()
+    }
+
+    fn remote(&self, args: &[&str]) -> String {
+        git(&self.project.remote, args)
+    }
+
+    fn short(&self, commit: &str) -> String {
+        git(&self.project.ctx, &["rev-parse", "--short", commit])
+    }
+}
+
+fn topology_settings(topology: MergeTopology) -> MergeSettings<TestPolicy> {
+    let mut settings = MergeSettings::new("main", TestPolicy::default());
+    settings.merge_topology(topology);
+    settings
+}
+
+#[test]
+fn test_merge_squash() {
+    let topology = TopologyProject::new(false);
+
+    let res = topology.merge(topology_settings(MergeTopology::Squash));
+    assert_eq!(res, MergeActionResult::Success);
+
+    // A single, non-merge commit on top of `main`.
+    let squashed = topology.project.remote_branch("main");
+    assert_eq!(
+        topology.remote(&["rev-list", "--parents", "--max-count=1", &squashed]),
+        format!("{} {}", squashed, topology.main),
+    );
+    assert_eq!(
+        topology.remote(&["ls-tree", "--name-only", &squashed]),
+        "README\nmain.txt\none.txt\ntwo.txt",
+    );
+    // The merge request's author is the author of the commit.
+    assert_eq!(
+        topology.remote(&["log", "--max-count=1", "--format=%an <%ae>", &squashed]),
+        "author name <author@example.com>",
+    );
+    // The trailers follow the summary of the topic.
+    assert_eq!(
+        topology.remote(&["log", "--max-count=1", "--format=%B", &squashed]),
+        format!(
+            "Squash topic 'topic' into main\n\
+             \n\
+             {} add two\n\
+             {} add one\n\
+             \n\
+             {}",
+            topology.short(&topology.commits[1]),
+            topology.short(&topology.commits[0]),
+            TOPOLOGY_TRAILERS.trim_end(),
+        ),
+    );
+}
+
+#[test]
+fn test_merge_squash_message_block() {
+    let mut topology = TopologyProject::new(false);
+    topology.mr.description = "Some discussion.\n\
+                               \n\
+                               ```message\n\
+                               Add one and two\n\
+                               \n\
+                               They are useful.\n\
+                               ```\n"
+        .into();
+
+    let res = topology.merge(topology_settings(MergeTopology::Squash));
+    assert_eq!(res, MergeActionResult::Success);
+
+    let squashed = topology.project.remote_branch("main");
+    assert_eq!(
+        topology.remote(&["log", "--max-count=1", "--format=%B", &squashed]),
+        format!(
+            "Add one and two\n\nThey are useful.\n\n{}",
+            TOPOLOGY_TRAILERS.trim_end(),
+        ),
+    );
+}
+
+#[test]
+fn test_merge_rebase() {
+    let topology = TopologyProject::new(false);
+
+    let res = topology.merge(topology_settings(MergeTopology::RebaseThenFastForward));
+    assert_eq!(res, MergeActionResult::Success);
+
+    // Each commit of the topic is replayed onto `main` without any merge commits.
+    let tip = topology.project.remote_branch("main");
+    let history = topology.remote(&[
+        "rev-list",
+        "--parents",
+        &format!("{}..{}", topology.main, tip),
+    ]);
+    let history = history
+        .lines()
+        .map(|line| line.split(' ').collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].len(), 2);
+    assert_eq!(history[1], [history[0][1], topology.main.as_str()]);
+    assert_eq!(
+        topology.remote(&["ls-tree", "--name-only", &tip]),
+        "README\nmain.txt\none.txt\ntwo.txt",
+    );
+
+    // The original authorship is kept and the trailers are appended to every commit.
+    let log = topology.remote(&[
+        "log",
+        "--format=%an <%ae>%n%B%x00",
+        &format!("{}..{}", topology.main, tip),
+    ]);
+    let entries = log
+        .split('\0')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        entries,
+        [
+            format!(
+                "{} <{}>\nadd two\n\n{}",
+                NAME,
+                EMAIL,
+                TOPOLOGY_TRAILERS.trim_end(),
+            ),
+            format!(
+                "{} <{}>\nadd one\n\nWith a body.\n\n{}",
+                NAME,
+                EMAIL,
+                TOPOLOGY_TRAILERS.trim_end(),
+            ),
+        ],
+    );
+}
+
+#[test]
+fn test_merge_rebase_conflict() {
+    let topology = TopologyProject::new(true);
+
+    // The first commit of the topic conflicts even though the topic as a whole does not.
+    let res = topology.merge(topology_settings(MergeTopology::RebaseThenFastForward));
+    assert_eq!(res, MergeActionResult::Failed);
+    assert_eq!(topology.project.remote_branch("main"), topology.main);
+
+    let comments = topology.project.service.mr_comments(TestProject::NAME, 1);
+    assert_eq!(comments.len(), 1);
+    assert!(comments[0].starts_with(&format!(
+        "This merge request may not be rebased onto `main` because {} conflicts in the \
+         following paths:\n\
+         \n  \
+         - `README`",
+        topology.commits[0],
+    )));
+    assert!(comments[0].contains(&format!("git cherry-pick ..{}", topology.commits[1])));
+
+    // Squashing only considers the topic as a whole.
+    let res = topology.merge(topology_settings(MergeTopology::Squash));
+    assert_eq!(res, MergeActionResult::Success);
+}
+
+#[test]
+fn test_merge_rebase_merge_commits() {
+    let topology = TopologyProject::new(false);
+    let project = &topology.project;
+
+    // A topic containing a merge commit may not be rebased.
+    let side = project.commit(&[&topology.main], &[("side.txt", "side\n")], "side");
+    let merge = project.commit(
+        &[&topology.commits[1], &side],
+        &[("README", "base\n"), ("side.txt", "side\n")],
+        "merge side",
+    );
+    let mut mr = topology.mr.clone();
+    mr.commit.id = CommitId::new(&merge);
+    project.push_branch("topic", &merge);
+    project.service.add_merge_request(mr.clone());
+
+    let merge = Merge::new(
+        project.ctx.clone(),
+        project.project.clone(),
+        topology_settings(MergeTopology::RebaseThenFastForward),
+    );
+    let res = merge
+        .merge_mr(&mr, &TestProject::identity(), Utc::now())
+        .unwrap();
+    assert_eq!(res, MergeActionResult::Failed);
+    assert_eq!(project.remote_branch("main"), topology.main);
+}
+
+#[test]
+fn test_merge_topologies_into_branches() {
+    for &topology_kind in &[MergeTopology::Squash, MergeTopology::RebaseThenFastForward] {
+        let topology = TopologyProject::new(false);
+        let next = topology.project.commit(
+            &[&topology.main],
+            &[("README", "base\n"), ("main.txt", "main\n"), ("next.txt", "next\n")],
+            "next",
+        );
+        topology.project.set_branch("next", &next);
+
+        let mut settings = topology_settings(topology_kind);
+        settings.add_into_branches(vec![IntoBranch::new("next")]);
+        let res = topology.merge(settings);
+        assert_eq!(res, MergeActionResult::Success, "{:?}", topology_kind);
+
+        // `next` merges the new state of `main` rather than the topic itself.
+        let main = topology.project.remote_branch("main");
+        let next_tip = topology.project.remote_branch("next");
+        assert_eq!(
+            topology.remote(&["rev-list", "--parents", "--max-count=1", &next_tip]),
+            format!("{} {} {}", next_tip, next, main),
+            "{:?}",
+            topology_kind,
+        );
+        assert_eq!(
+            topology.remote(&["log", "--max-count=1", "--format=%s", &next_tip]),
+            "Merge branch 'main' into next",
+        );
+    }
+}
+
+impl TopologyProject {
+    fn preview(&self, settings: MergeSettings<TestPolicy>) -> MergePreview {
+        let merge = Merge::new(
+            self.project.ctx.clone(),
+            self.project.project.clone(),
+            settings,
+        );
+        merge.preview_mr(&self.mr, false).unwrap()
+    }
+}
+
+#[test]
+fn test_merge_preview_merge() {
+    let topology = TopologyProject::new(false);
+
+    let mut settings = topology_settings(MergeTopology::NoFastForward);
+    settings.add_into_branches(vec![IntoBranch::new("next")]);
+    let preview = topology.preview(settings);
+
+    assert_eq!(preview.branch, "main");
+    assert_eq!(preview.topology, MergeTopology::NoFastForward);
+    assert_eq!(
+        preview.update_merges,
+        [("main".to_string(), "next".to_string())],
+    );
+    if let MergePreviewOutcome::Merge {
+        commit_message,
+    } = &preview.outcome
+    {
+        assert!(commit_message.starts_with("Merge topic 'topic' into main\n"));
+        assert!(commit_message.ends_with(TOPOLOGY_TRAILERS));
+    } else {
+        panic!("unexpected outcome: {:?}", preview.outcome);
+    }
+
+    // Nothing is pushed or posted.
+    assert_eq!(topology.project.remote_branch("main"), topology.main);
+    assert!(topology
+        .project
+        .service
+        .mr_comments(TestProject::NAME, 1)
+        .is_empty());
+}
+
+#[test]
+fn test_merge_preview_post() {
+    let topology = TopologyProject::new(false);
+
+    let merge = Merge::new(
+        topology.project.ctx.clone(),
+        topology.project.project.clone(),
+        topology_settings(MergeTopology::NoFastForward),
+    );
+    let preview = merge.preview_mr(&topology.mr, true).unwrap();
+
+    assert_eq!(
+        topology.project.service.mr_comments(TestProject::NAME, 1),
+        [preview.render()],
+    );
+    assert_eq!(topology.project.remote_branch("main"), topology.main);
+}
+
+#[test]
+fn test_merge_preview_fast_forward() {
+    let project = TestProject::new();
+    let main = project.commit(&[], &[("README", "base\n")], "base");
+    project.set_branch("main", &main);
+    let topic = project.commit(&[&main], &[("README", "topic\n")], "topic");
+    let mr = project.add_mr(1, "topic", "main", &topic);
+
+    let merge = Merge::new(
+        project.ctx.clone(),
+        project.project.clone(),
+        topology_settings(MergeTopology::FastForwardIfPossible),
+    );
+    let preview = merge.preview_mr(&mr, false).unwrap();
+
+    assert_eq!(
+        preview.outcome,
+        MergePreviewOutcome::FastForward {
+            commit: CommitId::new(&topic),
+        },
+    );
+    assert_eq!(project.remote_branch("main"), main);
+}
+
+#[test]
+fn test_merge_preview_fast_forward_only() {
+    let topology = TopologyProject::new(false);
+
+    // `main` has moved on, so the topic may not be fast-forwarded.
+    let preview = topology.preview(topology_settings(MergeTopology::FastForwardOnly));
+    assert!(matches!(
+        preview.outcome,
+        MergePreviewOutcome::Unmergeable { .. }
+    ));
+}
+
+#[test]
+fn test_merge_preview_squash() {
+    let topology = TopologyProject::new(false);
+
+    let preview = topology.preview(topology_settings(MergeTopology::Squash));
+    assert_eq!(
+        preview.outcome,
+        MergePreviewOutcome::Squash {
+            commit_message: format!(
+                "Squash topic 'topic' into main\n\
+                 \n\
+                 {} add two\n\
+                 {} add one\n\
+                 \n\
+                 {}",
+                topology.short(&topology.commits[1]),
+                topology.short(&topology.commits[0]),
+                TOPOLOGY_TRAILERS,
+            ),
+        },
+    );
+    assert_eq!(topology.project.remote_branch("main"), topology.main);
+}
+
+#[test]
+fn test_merge_preview_rebase() {
+    let topology = TopologyProject::new(false);
+
+    let preview = topology.preview(topology_settings(MergeTopology::RebaseThenFastForward));
+    assert_eq!(
+        preview.outcome,
+        MergePreviewOutcome::Rebase {
+            commits: 2,
+            trailers: TOPOLOGY_TRAILERS.into(),
+        },
+    );
+    assert_eq!(topology.project.remote_branch("main"), topology.main);
+}
+
+#[test]
+fn test_merge_preview_rebase_conflict() {
+    let topology = TopologyProject::new(true);
+
+    // The first commit of the topic conflicts even though the topic as a whole does not.
+    let preview = topology.preview(topology_settings(MergeTopology::RebaseThenFastForward));
+    assert_eq!(
+        preview.outcome,
+        MergePreviewOutcome::Conflicts {
+            paths: vec!["README".into()],
+        },
+    );
+
+    let preview = topology.preview(topology_settings(MergeTopology::Squash));
+    assert!(preview.outcome.is_mergeable());
+}
+
+#[test]
+fn test_merge_preview_rebase_merge_commits() {
+    let topology = TopologyProject::new(false);
+    let project = &topology.project;
+
+    let side = project.commit(&[&topology.main], &[("side.txt", "side\n")], "side");
+    let merge = project.commit(
+        &[&topology.commits[1], &side],
+        &[("README", "base\n"), ("side.txt", "side\n")],
+        "merge side",
+    );
+    let mut mr = topology.mr.clone();
+    mr.commit.id = CommitId::new(&merge);
+    project.push_branch("topic", &merge);
+    project.service.add_merge_request(mr.clone());
+
+    let merge = Merge::new(
+        project.ctx.clone(),
+        project.project.clone(),
+        topology_settings(MergeTopology::RebaseThenFastForward),
+    );
+    let preview = merge.preview_mr(&mr, false).unwrap();
+    assert!(matches!(
+        preview.outcome,
+        MergePreviewOutcome::Unmergeable { .. }
+    ));
+}
+
+#[test]
+fn test_merge_preview_conflicts() {
+    let mut topology = TopologyProject::new(true);
+
+    // Only the first commit of the topic conflicts with `main`.
+    topology.mr.commit.id = CommitId::new(&topology.commits[0]);
+    topology.project.push_branch("topic", &topology.commits[0]);
+
+    let preview = topology.preview(topology_settings(MergeTopology::NoFastForward));
+    assert_eq!(
+        preview.outcome,
+        MergePreviewOutcome::Conflicts {
+            paths: vec!["README".into()],
+        },
+    );
+    // Conflicts suppress the list of updated branches.
+    assert!(!preview.render().contains("would then be updated"));
+}
+
+#[test]
+fn test_merge_preview_rejected() {
+    let topology = TopologyProject::new(false);
+    topology
+        .project
+        .service
+        .add_comment(TestProject::NAME, 1, "reviewer", "-1")
+        .unwrap();
+
+    let preview = topology.preview(topology_settings(MergeTopology::NoFastForward));
+    assert_eq!(
+        preview.outcome,
+        MergePreviewOutcome::Rejected {
+            reasons: vec!["rejected by reviewer name <reviewer@example.com>".into()],
+        },
+    );
+}
+
+#[test]
+fn test_merge_preview_work_in_progress() {
+    let mut topology = TopologyProject::new(false);
+    topology.mr.work_in_progress = true;
+
+    let preview = topology.preview(topology_settings(MergeTopology::NoFastForward));
+    assert!(matches!(
+        preview.outcome,
+        MergePreviewOutcome::Unmergeable { .. }
+    ));
+}
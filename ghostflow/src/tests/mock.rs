@@ -0,0 +1,717 @@
+//! An in-memory hosting service for testing actions.
+//!
+//! The service keeps all of its state in memory and uses a local bare repository for each
+//! project as the "remote". Every mutation made through the `HostingService` or
+//! `HostedPipelineService` interfaces is recorded so that tests may assert on the actions'
+//! behavior.
+
+// Not every test uses every part of the mock.
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::mem;
+use std::process::Command;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use chrono::Utc;
+use git_workarea::{CommitId, GitContext};
+use itertools::Itertools;
+use tempfile::TempDir;
+use thiserror::Error;
+
+use crate::host::*;
+
+/// Errors from the mock service.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum MockError {
+    /// The project does not exist.
+    #[error("unknown project: {}", project)]
+    UnknownProject {
+        /// The name of the project.
+        project: String,
+    },
+    /// The user does not exist.
+    #[error("unknown user: {}", user)]
+    UnknownUser {
+        /// The handle of the user.
+        user: String,
+    },
+    /// The merge request does not exist.
+    #[error("unknown merge request: {}!{}", project, id)]
+    UnknownMergeRequest {
+        /// The name of the project.
+        project: String,
+        /// The ID of the merge request.
+        id: u64,
+    },
+    /// The issue does not exist.
+    #[error("unknown issue: {}#{}", project, id)]
+    UnknownIssue {
+        /// The name of the project.
+        project: String,
+        /// The ID of the issue.
+        id: u64,
+    },
+    /// The job does not exist.
+    #[error("unknown job: {}", id)]
+    UnknownJob {
+        /// The ID of the job.
+        id: u64,
+    },
+    /// Failure to create the remote repository.
+    #[error("failed to initialize remote repository: {}", output)]
+    InitRemote {
+        /// Output from `git init`.
+        output: String,
+    },
+}
+
+impl MockError {
+    fn unknown_project(project: &str) -> Self {
+        MockError::UnknownProject {
+            project: project.into(),
+        }
+    }
+
+    fn unknown_user(user: &str) -> Self {
+        MockError::UnknownUser {
+            user: user.into(),
+        }
+    }
+
+    fn unknown_merge_request(project: &str, id: u64) -> Self {
+        MockError::UnknownMergeRequest {
+            project: project.into(),
+            id,
+        }
+    }
+
+    fn unknown_issue(project: &str, id: u64) -> Self {
+        MockError::UnknownIssue {
+            project: project.into(),
+            id,
+        }
+    }
+
+    fn unknown_job(id: u64) -> Self {
+        MockError::UnknownJob {
+            id,
+        }
+    }
+
+    fn init_remote(output: &[u8]) -> Self {
+        MockError::InitRemote {
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
+}
+
+impl From<MockError> for HostingServiceError {
+    fn from(err: MockError) -> Self {
+        HostingServiceError::host(err)
+    }
+}
+
+type MockResult<T> = Result<T, MockError>;
+
+/// A mutation performed through the service interfaces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockMutation {
+    /// A comment was posted to a merge request.
+    MrComment {
+        /// The project of the merge request.
+        project: String,
+        /// The ID of the merge request.
+        id: u64,
+        /// The content of the comment.
+        content: String,
+    },
+    /// A commit status was posted.
+    CommitStatus {
+        /// The project of the commit.
+        project: String,
+        /// The commit the status is for.
+        commit: CommitId,
+        /// The refname of the commit.
+        refname: Option<String>,
+        /// The state of the status.
+        state: CommitStatusState,
+        /// The name of the status.
+        name: String,
+        /// The description of the status.
+        description: String,
+        /// The target URL of the status.
+        target_url: Option<String>,
+    },
+    /// Labels were added to an issue.
+    IssueLabels {
+        /// The project of the issue.
+        project: String,
+        /// The ID of the issue.
+        id: u64,
+        /// The labels which were added.
+        labels: Vec<String>,
+    },
+    /// A job was triggered.
+    TriggerJob {
+        /// The ID of the job.
+        id: u64,
+        /// The user the job was triggered as.
+        user: Option<String>,
+    },
+}
+
+type MrKey = (String, u64);
+
+/// A callback which decides the state of new pipelines for a commit.
+type MockCi = Box<dyn Fn(&Commit) -> Option<PipelineState> + Send>;
+
+/// The first ID used for pipelines created by the mock CI.
+const CI_PIPELINE_ID_START: u64 = 1_000_000;
+
+fn mr_key(mr: &MergeRequest) -> MrKey {
+    (mr.target_repo.name.clone(), mr.id)
+}
+
+/// The state of the mock service.
+#[derive(Default)]
+struct MockData {
+    users: HashMap<String, User>,
+    projects: HashMap<String, Repo>,
+    remotes: Vec<TempDir>,
+    merge_requests: HashMap<MrKey, MergeRequest>,
+    comments: HashMap<MrKey, Vec<Comment>>,
+    awards: HashMap<MrKey, Vec<Award>>,
+    statuses: HashMap<(String, CommitId), Vec<CommitStatus>>,
+    issues: HashMap<(String, u64), Issue>,
+    closes: HashMap<MrKey, Vec<(String, u64)>>,
+    pipelines: HashMap<MrKey, Vec<Pipeline>>,
+    commit_pipelines: HashMap<(String, CommitId), Vec<Pipeline>>,
+    jobs: HashMap<u64, Vec<PipelineJob>>,
+    ci: Option<MockCi>,
+    mutations: Vec<MockMutation>,
+    next_comment: u64,
+    next_pipeline: u64,
+}
+
+impl MockData {
+    fn project(&self, project: &str) -> MockResult<&Repo> {
+        self.projects
+            .get(project)
+            .ok_or_else(|| MockError::unknown_project(project))
+    }
+
+    fn merge_request(&self, project: &str, id: u64) -> MockResult<&MergeRequest> {
+        self.merge_requests
+            .get(&(project.into(), id))
+            .ok_or_else(|| MockError::unknown_merge_request(project, id))
+    }
+
+    fn add_comment(&mut self, key: MrKey, author: User, content: &str, is_system: bool) {
+        self.next_comment += 1;
+        let comment = Comment {
+            id: format!("{}", self.next_comment),
+            is_system,
+            is_branch_update: false,
+            created_at: Utc::now(),
+            author,
+            content: content.into(),
+        };
+        self.comments.entry(key).or_insert_with(Vec::new).push(comment);
+    }
+}
+
+/// An in-memory hosting service.
+pub struct MockService {
+    /// The user the service acts as.
+    user: User,
+    /// The data for the service.
+    data: Mutex<MockData>,
+}
+
+impl MockService {
+    /// Create a new service acting as the given user.
+    pub fn new(user: User) -> Arc<Self> {
+        let mut data = MockData::default();
+        data.users.insert(user.handle.clone(), user.clone());
+
+        Arc::new(Self {
+            user,
+            data: Mutex::new(data),
+        })
+    }
+
+    fn data(&self) -> MutexGuard<MockData> {
+        self.data
+            .lock()
+            .expect("the mock service data lock should not be poisoned")
+    }
+
+    /// Create a user.
+    pub fn make_user(handle: &str) -> User {
+        User {
+            handle: handle.into(),
+            name: format!("{} name", handle),
+            email: format!("{}@example.com", handle),
+        }
+    }
+
+    /// Add a user to the service.
+    pub fn add_user(&self, user: User) {
+        self.data().users.insert(user.handle.clone(), user);
+    }
+
+    /// Add a project to the service.
+    ///
+    /// A bare repository is created for the project which is used as its remote.
+    pub fn add_project(&self, name: &str, forked_from: Option<&str>) -> MockResult<Repo> {
+        let remote = TempDir::new().expect("failed to create a temporary directory");
+        let init = Command::new("git")
+            .arg("init")
+            .arg("--bare")
+            .arg(remote.path())
+            .output()
+            .expect("failed to run git init");
+        if !init.status.success() {
+            return Err(MockError::init_remote(&init.stderr));
+        }
+
+        let mut data = self.data();
+        let forked_from = forked_from
+            .map(|parent| data.project(parent).map(|repo| Box::new(repo.clone())))
+            .transpose()?;
+        let repo = Repo {
+            name: name.into(),
+            url: remote.path().to_string_lossy().into(),
+            forked_from,
+        };
+        data.remotes.push(remote);
+        data.projects.insert(name.into(), repo.clone());
+
+        Ok(repo)
+    }
+
+    /// A git context for the remote repository of a project.
+    pub fn remote(&self, project: &str) -> MockResult<GitContext> {
+        self.data()
+            .project(project)
+            .map(|repo| GitContext::new(&repo.url))
+    }
+
+    /// Add a merge request to the service.
+    ///
+    /// The merge request is indexed by its target repository and ID.
+    pub fn add_merge_request(&self, mr: MergeRequest) {
+        self.data().merge_requests.insert(mr_key(&mr), mr);
+    }
+
+    /// Update a merge request on the service.
+    pub fn update_merge_request<F>(&self, project: &str, id: u64, update: F) -> MockResult<()>
+    where
+        F: FnOnce(&mut MergeRequest),
+    {
+        let mut data = self.data();
+        let mr = data
+            .merge_requests
+            .get_mut(&(project.into(), id))
+            .ok_or_else(|| MockError::unknown_merge_request(project, id))?;
+        update(mr);
+        Ok(())
+    }
+
+    /// Add a comment to a merge request from a user.
+    pub fn add_comment(
+        &self,
+        project: &str,
+        id: u64,
+        author: &str,
+        content: &str,
+    ) -> MockResult<()> {
+        let mut data = self.data();
+        data.merge_request(project, id)?;
+        let author = data
+            .users
+            .get(author)
+            .cloned()
+            .ok_or_else(|| MockError::unknown_user(author))?;
+        data.add_comment((project.into(), id), author, content, false);
+        Ok(())
+    }
+
+    /// Mark the merge request's source branch as having been updated.
+    pub fn add_branch_update(&self, project: &str, id: u64) -> MockResult<()> {
+        let mut data = self.data();
+        data.merge_request(project, id)?;
+        data.next_comment += 1;
+        let comment = Comment {
+            id: format!("{}", data.next_comment),
+            is_system: true,
+            is_branch_update: true,
+            created_at: Utc::now(),
+            author: self.user.clone(),
+            content: "added 1 commit".into(),
+        };
+        data.comments
+            .entry((project.into(), id))
+            .or_insert_with(Vec::new)
+            .push(comment);
+        Ok(())
+    }
+
+    /// Add an award to a merge request from a user.
+    pub fn add_award(&self, project: &str, id: u64, author: &str, name: &str) -> MockResult<()> {
+        let mut data = self.data();
+        data.merge_request(project, id)?;
+        let author = data
+            .users
+            .get(author)
+            .cloned()
+            .ok_or_else(|| MockError::unknown_user(author))?;
+        data.awards
+            .entry((project.into(), id))
+            .or_insert_with(Vec::new)
+            .push(Award {
+                name: name.into(),
+                author,
+            });
+        Ok(())
+    }
+
+    /// Add an issue to the service.
+    pub fn add_issue(&self, issue: Issue) {
+        self.data()
+            .issues
+            .insert((issue.repo.name.clone(), issue.id), issue);
+    }
+
+    /// Mark an issue as being closed by a merge request.
+    pub fn close_issue_by_mr(&self, mr_project: &str, mr: u64, project: &str, issue: u64) {
+        self.data()
+            .closes
+            .entry((mr_project.into(), mr))
+            .or_insert_with(Vec::new)
+            .push((project.into(), issue));
+    }
+
+    /// Add a pipeline for a merge request.
+    pub fn add_pipeline(
+        &self,
+        project: &str,
+        id: u64,
+        pipeline: Pipeline,
+        jobs: Vec<PipelineJob>,
+    ) {
+        let mut data = self.data();
+        data.jobs.insert(pipeline.id, jobs);
+        data.pipelines
+            .entry((project.into(), id))
+            .or_insert_with(Vec::new)
+            .push(pipeline);
+    }
+
+    /// Run pipelines for commits on demand.
+    ///
+    /// When the pipelines for a commit are first requested, `ci` is asked for the state of a
+    /// pipeline for it. No pipeline is created if it returns `None`.
+    pub fn set_ci<F>(&self, ci: F)
+    where
+        F: Fn(&Commit) -> Option<PipelineState> + Send + 'static,
+    {
+        self.data().ci = Some(Box::new(ci));
+    }
+
+    /// Set the state of a job.
+    pub fn set_job_state(&self, id: u64, state: PipelineState) -> MockResult<()> {
+        let mut data = self.data();
+        let job = data
+            .jobs
+            .values_mut()
+            .flatten()
+            .find(|job| job.id == id)
+            .ok_or_else(|| MockError::unknown_job(id))?;
+        job.state = state;
+        Ok(())
+    }
+
+    /// Set the state of a pipeline.
+    pub fn set_pipeline_state(&self, id: u64, state: PipelineState) {
+        let mut data = self.data();
+        let MockData {
+            pipelines,
+            commit_pipelines,
+            ..
+        } = &mut *data;
+        pipelines
+            .values_mut()
+            .chain(commit_pipelines.values_mut())
+            .flatten()
+            .filter(|pipeline| pipeline.id == id)
+            .for_each(|pipeline| pipeline.state = state);
+    }
+
+    /// The mutations which have been performed on the service.
+    pub fn mutations(&self) -> Vec<MockMutation> {
+        self.data().mutations.clone()
+    }
+
+    /// Take the mutations which have been performed on the service.
+    ///
+    /// The list of mutations is cleared.
+    pub fn take_mutations(&self) -> Vec<MockMutation> {
+        self.data().mutations.drain(..).collect()
+    }
+
+    /// The comments posted to a merge request by the service.
+    pub fn mr_comments(&self, project: &str, id: u64) -> Vec<String> {
+        self.data()
+            .mutations
+            .iter()
+            .filter_map(|mutation| {
+                match mutation {
+                    MockMutation::MrComment {
+                        project: mr_project,
+                        id: mr_id,
+                        content,
+                    } if mr_project == project && *mr_id == id => Some(content.clone()),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// The labels on an issue.
+    pub fn issue_labels(&self, project: &str, id: u64) -> MockResult<Vec<String>> {
+        self.data()
+            .issues
+            .get(&(project.into(), id))
+            .map(|issue| issue.labels.clone())
+            .ok_or_else(|| MockError::unknown_issue(project, id))
+    }
+}
+
+impl HostingService for MockService {
+    fn as_pipeline_service(self: Arc<Self>) -> Option<Arc<dyn HostedPipelineService>> {
+        Some(self)
+    }
+
+    fn service_user(&self) -> &User {
+        &self.user
+    }
+
+    fn user(&self, project: &str, user: &str) -> Result<User, HostingServiceError> {
+        let data = self.data();
+        data.project(project)?;
+        Ok(data
+            .users
+            .get(user)
+            .cloned()
+            .ok_or_else(|| MockError::unknown_user(user))?)
+    }
+
+    fn commit(&self, project: &str, commit: &CommitId) -> Result<Commit, HostingServiceError> {
+        let data = self.data();
+        let repo = data.project(project)?;
+
+        Ok(Commit {
+            repo: repo.clone(),
+            refname: None,
+            id: commit.clone(),
+            last_pipeline: None,
+        })
+    }
+
+    fn merge_request(&self, project: &str, id: u64) -> Result<MergeRequest, HostingServiceError> {
+        Ok(self.data().merge_request(project, id)?.clone())
+    }
+
+    fn repo(&self, project: &str) -> Result<Repo, HostingServiceError> {
+        Ok(self.data().project(project)?.clone())
+    }
+
+    fn get_mr_comments(&self, mr: &MergeRequest) -> Result<Vec<Comment>, HostingServiceError> {
+        Ok(self
+            .data()
+            .comments
+            .get(&mr_key(mr))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn post_mr_comment(&self, mr: &MergeRequest, content: &str) -> Result<(), HostingServiceError> {
+        let mut data = self.data();
+        data.merge_request(&mr.target_repo.name, mr.id)?;
+        data.add_comment(mr_key(mr), self.user.clone(), content, false);
+        data.mutations.push(MockMutation::MrComment {
+            project: mr.target_repo.name.clone(),
+            id: mr.id,
+            content: content.into(),
+        });
+        Ok(())
+    }
+
+    fn get_commit_statuses(
+        &self,
+        commit: &Commit,
+    ) -> Result<Vec<CommitStatus>, HostingServiceError> {
+        let data = self.data();
+        let statuses = data
+            .statuses
+            .get(&(commit.repo.name.clone(), commit.id.clone()))
+            .map(|statuses| {
+                // Only the latest status for each name is returned.
+                statuses
+                    .iter()
+                    .rev()
+                    .unique_by(|status| status.name.clone())
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        Ok(statuses)
+    }
+
+    fn post_commit_status(&self, status: PendingCommitStatus) -> Result<(), HostingServiceError> {
+        let mut data = self.data();
+        let project = &status.commit.repo.name;
+        data.project(project)?;
+
+        data.statuses
+            .entry((project.clone(), status.commit.id.clone()))
+            .or_insert_with(Vec::new)
+            .push(CommitStatus {
+                state: status.state,
+                author: self.user.clone(),
+                refname: status.commit.refname.clone(),
+                name: status.name.into(),
+                description: status.description.into(),
+                target_url: status.target_url.map(Into::into),
+            });
+        data.mutations.push(MockMutation::CommitStatus {
+            project: project.clone(),
+            commit: status.commit.id.clone(),
+            refname: status.commit.refname.clone(),
+            state: status.state,
+            name: status.name.into(),
+            description: status.description.into(),
+            target_url: status.target_url.map(Into::into),
+        });
+        Ok(())
+    }
+
+    fn get_mr_awards(&self, mr: &MergeRequest) -> Result<Vec<Award>, HostingServiceError> {
+        Ok(self
+            .data()
+            .awards
+            .get(&mr_key(mr))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn issues_closed_by_mr(&self, mr: &MergeRequest) -> Result<Vec<Issue>, HostingServiceError> {
+        let data = self.data();
+        let issues = data
+            .closes
+            .get(&mr_key(mr))
+            .map(|closes| {
+                closes
+                    .iter()
+                    .map(|key| {
+                        data.issues
+                            .get(key)
+                            .cloned()
+                            .ok_or_else(|| MockError::unknown_issue(&key.0, key.1))
+                    })
+                    .collect::<MockResult<Vec<_>>>()
+            })
+            .transpose()?
+            .unwrap_or_default();
+
+        Ok(issues)
+    }
+
+    fn add_issue_labels(&self, issue: &Issue, labels: &[&str]) -> Result<(), HostingServiceError> {
+        let mut data = self.data();
+        let stored = data
+            .issues
+            .get_mut(&(issue.repo.name.clone(), issue.id))
+            .ok_or_else(|| MockError::unknown_issue(&issue.repo.name, issue.id))?;
+        let existing = mem::take(&mut stored.labels);
+        stored.labels = existing
+            .into_iter()
+            .chain(labels.iter().map(|&label| label.into()))
+            .unique()
+            .collect();
+
+        data.mutations.push(MockMutation::IssueLabels {
+            project: issue.repo.name.clone(),
+            id: issue.id,
+            labels: labels.iter().map(|&label| label.into()).collect(),
+        });
+        Ok(())
+    }
+}
+
+impl HostedPipelineService for MockService {
+    fn pipelines_for_mr(
+        &self,
+        mr: &MergeRequest,
+    ) -> Result<Option<Vec<Pipeline>>, HostingServiceError> {
+        Ok(self.data().pipelines.get(&mr_key(mr)).cloned())
+    }
+
+    fn pipelines_for_commit(
+        &self,
+        commit: &Commit,
+    ) -> Result<Option<Vec<Pipeline>>, HostingServiceError> {
+        let mut data = self.data();
+        data.project(&commit.repo.name)?;
+
+        let key = (commit.repo.name.clone(), commit.id.clone());
+        if !data.commit_pipelines.contains_key(&key) {
+            let state = data.ci.as_ref().and_then(|ci| ci(commit));
+            let pipelines = if let Some(state) = state {
+                data.next_pipeline += 1;
+                vec![Pipeline {
+                    state,
+                    commit: commit.clone(),
+                    id: CI_PIPELINE_ID_START + data.next_pipeline,
+                }]
+            } else {
+                Vec::new()
+            };
+            data.commit_pipelines.insert(key.clone(), pipelines);
+        }
+
+        Ok(data.commit_pipelines.get(&key).cloned())
+    }
+
+    fn pipeline_jobs(
+        &self,
+        pipeline: &Pipeline,
+    ) -> Result<Option<Vec<PipelineJob>>, HostingServiceError> {
+        Ok(self.data().jobs.get(&pipeline.id).cloned())
+    }
+
+    fn trigger_job(
+        &self,
+        job: &PipelineJob,
+        user: Option<&str>,
+    ) -> Result<(), HostingServiceError> {
+        let mut data = self.data();
+        let stored = data
+            .jobs
+            .values_mut()
+            .flatten()
+            .find(|stored| stored.id == job.id)
+            .ok_or_else(|| MockError::unknown_job(job.id))?;
+        stored.state = PipelineState::InProgress;
+
+        data.mutations.push(MockMutation::TriggerJob {
+            id: job.id,
+            user: user.map(Into::into),
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use git_workarea::CommitId;
+use tempfile::TempDir;
+
+use crate::actions::reformat::{FormatCache, Formatter, FormatterProtocol, Reformat, ReformatError};
+use crate::host::MergeRequest;
+use crate::tests::utils::{git, TestProject};
+
+const FORMAT_CONFIG: &str = "foo bar\n";
+
+fn formatter(kind: &str, protocol: FormatterProtocol) -> Formatter {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("test")
+        .join(format!("format.{}", kind));
+    let mut formatter = Formatter::new(kind, path).unwrap();
+    formatter
+        .add_config_files(vec!["format-config"])
+        .with_protocol(protocol);
+    formatter
+}
+
+/// Create a merge request adding the given files.
+fn setup(attributes: &str, files: &[(&str, &str)]) -> (TestProject, CommitId, MergeRequest) {
+    let project = TestProject::new();
+    let base_files = [
+        (".gitattributes", attributes),
+        ("format-config", FORMAT_CONFIG),
+    ];
+    let base = project.commit(&[], &base_files, "base");
+    project.set_branch("master", &base);
+
+    let topic_files = base_files
+        .iter()
+        .chain(files.iter())
+        .cloned()
+        .collect::<Vec<_>>();
+    let topic = project.commit(&[&base], &topic_files, "topic");
+    let mr = project.add_mr(1, "topic", "master", &topic);
+
+    (project, CommitId::new(base), mr)
+}
+
+fn reformat(project: &TestProject, formatter: Formatter) -> Reformat {
+    let mut reformat = Reformat::new(project.ctx.clone(), project.project.clone());
+    reformat.add_formatters(vec![formatter]).push_result(false);
+    reformat
+}
+
+fn contents(project: &TestProject, commit: &CommitId, path: &str) -> String {
+    git(
+        &project.ctx,
+        &["cat-file", "blob", &format!("{}:{}", commit, path)],
+    )
+}
+
+fn assert_failed(project: &TestProject, res: Result<CommitId, ReformatError>, expected: &[&str]) {
+    if let Err(ReformatError::ReformatFailed {
+        paths, ..
+    }) = res
+    {
+        assert_eq!(paths, expected);
+    } else {
+        panic!("unexpected result: {:?}", res);
+    }
+
+    let comments = project.service.mr_comments(TestProject::NAME, 1);
+    assert_eq!(comments.len(), 1);
+    assert!(comments[0].starts_with("Failed to format the following files in "));
+}
+
+#[test]
+fn test_reformat_batch_split() {
+    let names = (0..20)
+        .map(|idx| format!("file-{:02}.txt", idx))
+        .collect::<Vec<_>>();
+    let mut files = names
+        .iter()
+        .map(|name| (name.as_str(), "foo\n"))
+        .collect::<Vec<_>>();
+    files.push(("value.txt", "foo ARG\n"));
+    let (project, base, mr) = setup(
+        "*.txt format.batch\nvalue.txt format.batch=value\n",
+        &files,
+    );
+
+    // Only a few paths fit in each invocation.
+    let protocol = FormatterProtocol::Batch {
+        max_args_len: 40,
+    };
+    let new_head = reformat(&project, formatter("batch", protocol))
+        .reformat_mr(&base, &mr)
+        .unwrap();
+
+    for name in &names {
+        assert_eq!(contents(&project, &new_head, name), "bar");
+    }
+    assert_eq!(contents(&project, &new_head, "value.txt"), "bar value");
+}
+
+#[test]
+fn test_reformat_batch_failure() {
+    let (project, base, mr) = setup(
+        "*.txt format.batch=invalid\n",
+        &[("a.txt", "foo\n"), ("b.txt", "foo\n")],
+    );
+
+    let protocol = FormatterProtocol::Batch {
+        max_args_len: 1024,
+    };
+    let res = reformat(&project, formatter("batch", protocol)).reformat_mr(&base, &mr);
+
+    assert_failed(&project, res, &["a.txt", "b.txt"]);
+}
+
+#[test]
+fn test_reformat_batch_partial_failure() {
+    let (project, base, mr) = setup(
+        "*.txt format.batch\n",
+        &[
+            ("a.txt", "foo\n"),
+            ("b.txt", "INVALID\n"),
+            ("c.txt", "foo\n"),
+            ("d.txt", "foo\n"),
+        ],
+    );
+
+    // All of the paths fit into a single invocation.
+    let protocol = FormatterProtocol::Batch {
+        max_args_len: 1024,
+    };
+    let res = reformat(&project, formatter("batch", protocol)).reformat_mr(&base, &mr);
+
+    // Only the path which fails on its own is reported.
+    assert_failed(&project, res, &["b.txt"]);
+}
+
+#[test]
+fn test_reformat_filter() {
+    let (project, base, mr) = setup(
+        "*.txt format.filter\nvalue.txt format.filter=value\n",
+        &[("plain.txt", "foo\n"), ("value.txt", "foo ARG\n")],
+    );
+
+    let new_head = reformat(&project, formatter("filter", FormatterProtocol::Filter))
+        .reformat_mr(&base, &mr)
+        .unwrap();
+
+    assert_eq!(contents(&project, &new_head, "plain.txt"), "bar");
+    assert_eq!(contents(&project, &new_head, "value.txt"), "bar value");
+}
+
+#[test]
+fn test_reformat_filter_large() {
+    // Far more than fits in a pipe buffer in either direction.
+    let input = "foo\n".repeat(256 * 1024);
+    let (project, base, mr) = setup("*.txt format.filter\n", &[("large.txt", input.as_str())]);
+
+    let new_head = reformat(&project, formatter("filter", FormatterProtocol::Filter))
+        .reformat_mr(&base, &mr)
+        .unwrap();
+
+    let expected = "bar\n".repeat(256 * 1024);
+    assert_eq!(
+        contents(&project, &new_head, "large.txt"),
+        expected.trim_end(),
+    );
+}
+
+#[test]
+fn test_reformat_filter_failure() {
+    let (project, base, mr) = setup(
+        "*.txt format.filter\nbad.txt format.filter=invalid\n",
+        &[("bad.txt", "foo\n"), ("good.txt", "foo\n")],
+    );
+
+    let res = reformat(&project, formatter("filter", FormatterProtocol::Filter))
+        .reformat_mr(&base, &mr);
+
+    assert_failed(&project, res, &["bad.txt"]);
+}
+
+#[test]
+fn test_reformat_filter_cached() {
+    let (project, base, mr) = setup("*.txt format.filter\n", &[("a.txt", "foo\n")]);
+    let cache_dir = TempDir::new().unwrap();
+    let cache = Arc::new(FormatCache::new(cache_dir.path()).unwrap());
+
+    let mut reformat = reformat(&project, formatter("filter", FormatterProtocol::Filter));
+    reformat.format_cache(cache);
+
+    let first = reformat.reformat_mr(&base, &mr).unwrap();
+    // The second run uses the cached blob.
+    let second = reformat.reformat_mr(&base, &mr).unwrap();
+
+    assert_eq!(first, second);
+    assert_eq!(contents(&project, &second, "a.txt"), "bar");
+}
@@ -0,0 +1,282 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use git_workarea::{CommitId, GitContext};
+use tempfile::TempDir;
+
+use crate::tests::utils::{git, git_input, git_raw, init_bare_in, EMAIL, NAME};
+use crate::utils::signing::{sign_commit, signed_tag};
+use crate::utils::SigningKey;
+
+/// Generate an SSH key and a file of signers which allows it.
+fn ssh_key(dir: &Path) -> (SigningKey, String) {
+    let key = dir.join("key");
+    let keygen = Command::new("ssh-keygen")
+        .arg("-q")
+        .arg("-t")
+        .arg("ed25519")
+        .arg("-N")
+        .arg("")
+        .arg("-C")
+        .arg(EMAIL)
+        .arg("-f")
+        .arg(&key)
+        .status()
+        .unwrap();
+    assert!(keygen.success());
+
+    let public_key = fs::read_to_string(dir.join("key.pub")).unwrap();
+    let allowed_signers = dir.join("allowed_signers");
+    fs::write(&allowed_signers, format!("{} {}", EMAIL, public_key)).unwrap();
+
+    (
+        SigningKey::ssh(key),
+        format!(
+            "gpg.ssh.allowedSignersFile={}",
+            allowed_signers.to_str().unwrap(),
+        ),
+    )
+}
+
+/// Generate an OpenPGP key in a new GnuPG home directory.
+fn openpgp_key(dir: &Path) -> SigningKey {
+    let home = dir.join("gnupg");
+    fs::create_dir(&home).unwrap();
+    let keygen = Command::new("gpg")
+        .env("GNUPGHOME", &home)
+        .arg("--batch")
+        .arg("--quiet")
+        .arg("--passphrase")
+        .arg("")
+        .arg("--quick-generate-key")
+        .arg(format!("{} <{}>", NAME, EMAIL))
+        .arg("ed25519")
+        .arg("sign")
+        .arg("never")
+        .status()
+        .unwrap();
+    assert!(keygen.success());
+
+    SigningKey::OpenPgp {
+        key_id: EMAIL.into(),
+        home: Some(home),
+    }
+}
+
+/// The raw contents of a commit without its signature header.
+fn unsigned_contents(ctx: &GitContext, commit: &CommitId) -> Vec<u8> {
+    let raw = git_raw(ctx, &["cat-file", "commit", commit.as_str()]);
+    let text = String::from_utf8(raw).unwrap();
+    let (header, message) = text.split_once("\n\n").unwrap();
+
+    let mut in_signature = false;
+    let header = header
+        .lines()
+        .filter(|line| {
+            in_signature = line.starts_with("gpgsig ") || (in_signature && line.starts_with(' '));
+            !in_signature
+        })
+        .map(|line| format!("{}\n", line))
+        .collect::<String>();
+
+    format!("{}\n{}", header, message).into_bytes()
+}
+
+fn setup() -> (TempDir, GitContext, SigningKey, String, CommitId) {
+    let dir = TempDir::new().unwrap();
+    let ctx = init_bare_in(&dir.path().join("repo"));
+    let (key, allowed_signers) = ssh_key(dir.path());
+
+    let tree = git(&ctx, &["mktree"]);
+    let base = git(&ctx, &["commit-tree", &tree, "-m", "base"]);
+    let commit = git(
+        &ctx,
+        &["commit-tree", &tree, "-p", &base, "-m", "unsigned\n\nbody"],
+    );
+
+    (dir, ctx, key, allowed_signers, CommitId::new(commit))
+}
+
+#[test]
+fn test_sign_commit_ssh() {
+    let (_dir, ctx, key, allowed_signers, commit) = setup();
+
+    let signed = sign_commit(&ctx, &commit, &key).unwrap();
+    assert_ne!(signed, commit);
+
+    git(
+        &ctx,
+        &["-c", "gpg.format=ssh", "-c", &allowed_signers, "verify-commit", signed.as_str()],
+    );
+
+    // Everything other than the signature is identical, including the message's bytes.
+    assert_eq!(
+        unsigned_contents(&ctx, &signed),
+        git_raw(&ctx, &["cat-file", "commit", commit.as_str()]),
+    );
+}
+
+#[test]
+fn test_sign_commit_message_bytes() {
+    let (_dir, ctx, key, _, parent) = setup();
+    let tree = git(&ctx, &["rev-parse", &format!("{}^{{tree}}", parent)]);
+
+    // Messages without a trailing newline or with trailing whitespace are kept as-is.
+    for message in &["no newline", "trailing\n\n\n", "  indented\n"] {
+        let commit = git_input(
+            &ctx,
+            &["commit-tree", &tree, "-p", parent.as_str()],
+            message.as_bytes(),
+        );
+        let commit = CommitId::new(commit);
+
+        let signed = sign_commit(&ctx, &commit, &key).unwrap();
+
+        let raw = git_raw(&ctx, &["cat-file", "commit", commit.as_str()]);
+        assert!(raw.ends_with(message.as_bytes()));
+        assert_eq!(unsigned_contents(&ctx, &signed), raw);
+    }
+}
+
+#[test]
+fn test_sign_commit_resign() {
+    let (_dir, ctx, key, allowed_signers, commit) = setup();
+
+    // Signing a signed commit replaces the signature.
+    let signed = sign_commit(&ctx, &commit, &key).unwrap();
+    let resigned = sign_commit(&ctx, &signed, &key).unwrap();
+
+    git(
+        &ctx,
+        &["-c", "gpg.format=ssh", "-c", &allowed_signers, "verify-commit", resigned.as_str()],
+    );
+    assert_eq!(
+        unsigned_contents(&ctx, &resigned),
+        git_raw(&ctx, &["cat-file", "commit", commit.as_str()]),
+    );
+}
+
+#[test]
+fn test_sign_commit_extra_headers() {
+    let (_dir, ctx, key, allowed_signers, parent) = setup();
+    let tree = git(&ctx, &["rev-parse", &format!("{}^{{tree}}", parent)]);
+
+    // Headers which `git commit-tree` cannot recreate are kept.
+    let ident = format!("{} <{}> 1700000000 +0000", NAME, EMAIL);
+    let raw = format!(
+        "tree {tree}\nparent {parent}\nauthor {ident}\ncommitter {ident}\n\
+         encoding ISO-8859-1\n\
+         mergetag object {parent}\n type commit\n tag v1.0\n tagger {ident}\n \n Version 1.0.\n\
+         \nmerge with extra headers\n",
+        tree = tree,
+        parent = parent,
+        ident = ident,
+    );
+    let commit = git_input(
+        &ctx,
+        &["hash-object", "-t", "commit", "-w", "--stdin"],
+        raw.as_bytes(),
+    );
+    let commit = CommitId::new(commit);
+
+    let signed = sign_commit(&ctx, &commit, &key).unwrap();
+
+    git(
+        &ctx,
+        &["-c", "gpg.format=ssh", "-c", &allowed_signers, "verify-commit", signed.as_str()],
+    );
+    assert_eq!(unsigned_contents(&ctx, &signed), raw.as_bytes());
+}
+
+#[test]
+fn test_sign_commit_openpgp() {
+    let (dir, ctx, _, _, commit) = setup();
+    let key = openpgp_key(dir.path());
+    let home = match key {
+        SigningKey::OpenPgp {
+            home: Some(ref home),
+            ..
+        } => home.clone(),
+        _ => unreachable!(),
+    };
+
+    let signed = sign_commit(&ctx, &commit, &key).unwrap();
+    assert_ne!(signed, commit);
+
+    let verify = ctx
+        .git()
+        .env("GNUPGHOME", &home)
+        .arg("verify-commit")
+        .arg(signed.as_str())
+        .output()
+        .unwrap();
+    assert!(
+        verify.status.success(),
+        "failed to verify: {}",
+        String::from_utf8_lossy(&verify.stderr),
+    );
+
+    let raw = git(&ctx, &["cat-file", "commit", signed.as_str()]);
+    assert!(raw.contains("\ngpgsig -----BEGIN PGP SIGNATURE-----\n"));
+    assert_eq!(
+        unsigned_contents(&ctx, &signed),
+        git_raw(&ctx, &["cat-file", "commit", commit.as_str()]),
+    );
+}
+
+#[test]
+fn test_signed_tag_openpgp() {
+    let (dir, ctx, _, _, commit) = setup();
+    let key = openpgp_key(dir.path());
+    let home = match key {
+        SigningKey::OpenPgp {
+            home: Some(ref home),
+            ..
+        } => home.clone(),
+        _ => unreachable!(),
+    };
+
+    let tag = signed_tag(&ctx, &commit, "stage/main/nightly", "Nightly stage.", &key).unwrap();
+
+    let verify = ctx
+        .git()
+        .env("GNUPGHOME", &home)
+        .arg("verify-tag")
+        .arg(tag.as_str())
+        .output()
+        .unwrap();
+    assert!(
+        verify.status.success(),
+        "failed to verify: {}",
+        String::from_utf8_lossy(&verify.stderr),
+    );
+}
+
+#[test]
+fn test_signed_tag_ssh() {
+    let (_dir, ctx, key, allowed_signers, commit) = setup();
+
+    let tag = signed_tag(
+        &ctx,
+        &commit,
+        "stage/main/nightly/20240101",
+        "Stage of main for nightly testing.",
+        &key,
+    )
+    .unwrap();
+
+    assert_eq!(git(&ctx, &["cat-file", "-t", tag.as_str()]), "tag");
+    assert_eq!(
+        git(&ctx, &["rev-parse", &format!("{}^{{commit}}", tag)]),
+        commit.as_str(),
+    );
+    git(
+        &ctx,
+        &["-c", "gpg.format=ssh", "-c", &allowed_signers, "verify-tag", tag.as_str()],
+    );
+
+    let contents = git(&ctx, &["cat-file", "tag", tag.as_str()]);
+    assert!(contents.contains("\ntag stage/main/nightly/20240101\n"));
+    assert!(contents.contains("\n\nStage of main for nightly testing.\n"));
+}
@@ -0,0 +1,227 @@
+use chrono::{Duration, Utc};
+use git_topic_stage::Stager;
+use git_workarea::CommitId;
+
+use crate::actions::stage::Stage;
+use crate::host::{Commit, MergeRequest};
+use crate::tests::utils::{git, TestProject};
+
+/// A project with a `main` branch and topics which each add a single file to it.
+fn stage_project(topics: u64) -> (TestProject, String, Vec<MergeRequest>) {
+    let project = TestProject::new();
+    let base = project.commit(&[], &[("README", "base\n")], "base");
+    project.set_branch("main", &base);
+
+    let mrs = (1..=topics)
+        .map(|id| {
+            let file = format!("topic-{}", id);
+            let commit = project.commit(
+                &[&base],
+                &[("README", "base\n"), (&file, "content\n")],
+                &format!("add {}", file),
+            );
+            project.add_mr(id, &file, "main", &commit)
+        })
+        .collect();
+
+    (project, base, mrs)
+}
+
+fn make_stage(project: &TestProject, base: &str) -> Stage {
+    let stager = Stager::new(&project.ctx, CommitId::new(base), TestProject::identity());
+    Stage::new(stager, "main", project.project.clone()).unwrap()
+}
+
+/// The IDs of the topics on the stage.
+fn staged_ids(stage: &Stage) -> Vec<u64> {
+    stage
+        .stager()
+        .topics()
+        .iter()
+        .map(|staged| staged.topic.id)
+        .collect()
+}
+
+#[test]
+fn test_stage_priority_order() {
+    let (project, base, mut mrs) = stage_project(5);
+    let who = TestProject::identity();
+    let when = Utc::now();
+    let mut stage = make_stage(&project, &base);
+    stage.priority_labels("priority:");
+
+    stage.stage_merge_request(&mrs[0], &who, when).unwrap();
+    stage.stage_merge_request(&mrs[1], &who, when).unwrap();
+    assert_eq!(staged_ids(&stage), [1, 2]);
+
+    // Higher priorities are integrated before lower priorities.
+    stage
+        .stage_merge_request_with_priority(&mrs[2], 5, &who, when)
+        .unwrap();
+    assert_eq!(staged_ids(&stage), [3, 1, 2]);
+
+    // Lower priorities are integrated after everything else.
+    stage
+        .stage_merge_request_with_priority(&mrs[3], -1, &who, when)
+        .unwrap();
+    assert_eq!(staged_ids(&stage), [3, 1, 2, 4]);
+
+    // Changing the priority of a staged topic moves it.
+    stage
+        .stage_merge_request_with_priority(&mrs[1], 10, &who, when)
+        .unwrap();
+    assert_eq!(staged_ids(&stage), [2, 3, 1, 4]);
+
+    // Topics with the same priority keep the order in which they were staged.
+    mrs[4].labels.push("priority:5".into());
+    assert_eq!(stage.mr_priority(&mrs[4]), 5);
+    stage.stage_merge_request(&mrs[4], &who, when).unwrap();
+    assert_eq!(staged_ids(&stage), [2, 3, 5, 1, 4]);
+
+    // The stage ref on the service matches the stage.
+    assert_eq!(
+        git(&project.remote, &["rev-parse", "refs/stage/main/head"]),
+        stage.stager().head().as_str(),
+    );
+}
+
+#[test]
+fn test_stage_conflict_report() {
+    let project = TestProject::new();
+    let base = project.commit(&[], &[("README", "base\n")], "base");
+    project.set_branch("main", &base);
+    let first = project.commit(&[&base], &[("README", "first\n")], "first");
+    let first = project.add_mr(1, "first", "main", &first);
+    let second = project.commit(&[&base], &[("README", "second\n")], "second");
+    let second = project.add_mr(2, "second", "main", &second);
+
+    let who = TestProject::identity();
+    let when = Utc::now();
+    let mut stage = make_stage(&project, &base);
+    stage.stage_merge_request(&first, &who, when).unwrap();
+    stage.stage_merge_request(&second, &who, when).unwrap();
+    assert_eq!(staged_ids(&stage), [1]);
+
+    let comments = project.service.mr_comments(TestProject::NAME, 2);
+    let report = comments.last().unwrap();
+    assert!(report.contains("merge conflicts in the following paths:\n\n  - `README`"));
+    assert!(report.contains(&format!(
+        "The following topics also change these paths:\n\n  - [first]({})",
+        first.url,
+    )));
+
+    // The reproduction uses the stage commit rather than the moving stage ref.
+    let head = stage.stager().head().clone();
+    assert!(report.contains(&format!(
+        "git fetch origin {}\ngit checkout --detach FETCH_HEAD\n",
+        head,
+    )));
+    assert!(report.contains(&format!("git merge --no-ff {}", second.commit.id)));
+    assert!(!report.contains("refs/stage/"));
+}
+
+#[test]
+fn test_stage_retry_backoff() {
+    let (project, base, mrs) = stage_project(1);
+    let who = TestProject::identity();
+    let when = Utc::now();
+    let backoff = Duration::minutes(5);
+    let mut stage = make_stage(&project, &base);
+    stage.retry_unstaged_topics(backoff, 2);
+
+    // Stage a topic which conflicts with the next update of the base branch.
+    let topic = project.commit(&[&base], &[("README", "topic\n")], "topic");
+    let mut mr = mrs[0].clone();
+    mr.commit.id = CommitId::new(&topic);
+    project.push_branch(&mr.source_branch, &topic);
+    project.service.add_merge_request(mr.clone());
+    stage.stage_merge_request(&mr, &who, when).unwrap();
+    assert_eq!(staged_ids(&stage), [1]);
+    assert_eq!(stage.next_retry(), None);
+
+    let update = project.commit(&[&base], &[("README", "update\n")], "update");
+    project.set_branch("main", &update);
+    let commit = Commit {
+        repo: project.repo.clone(),
+        refname: Some("main".into()),
+        id: CommitId::new(&update),
+        last_pipeline: None,
+    };
+    stage.base_branch_update(&commit, &who, when).unwrap();
+    assert!(staged_ids(&stage).is_empty());
+
+    // The first retry waits for the back-off.
+    let first = when + backoff;
+    assert_eq!(stage.next_retry(), Some(first));
+    stage.retry_unstaged(first - Duration::seconds(1)).unwrap();
+    assert_eq!(stage.next_retry(), Some(first));
+
+    // Each failure doubles the delay.
+    stage.retry_unstaged(first).unwrap();
+    let second = first + backoff * 2;
+    assert_eq!(stage.next_retry(), Some(second));
+    assert!(staged_ids(&stage).is_empty());
+
+    // No retries are scheduled after the last attempt.
+    stage.retry_unstaged(second).unwrap();
+    assert_eq!(stage.next_retry(), None);
+    assert!(staged_ids(&stage).is_empty());
+
+    let comments = project.service.mr_comments(TestProject::NAME, 1);
+    assert!(comments.iter().any(|comment| {
+        comment.contains("The automatic retry (attempt 1)")
+            && comment.ends_with(&format!("Staging will be retried again after {}.", second))
+    }));
+    assert!(comments.iter().any(|comment| {
+        comment.contains("The automatic retry (attempt 2)")
+            && comment.ends_with("No further retries will be made.")
+    }));
+}
+
+#[test]
+fn test_stage_retry_success() {
+    let (project, base, mrs) = stage_project(1);
+    let who = TestProject::identity();
+    let when = Utc::now();
+    let backoff = Duration::minutes(5);
+    let mut stage = make_stage(&project, &base);
+    stage.retry_unstaged_topics(backoff, 2);
+
+    let topic = project.commit(&[&base], &[("README", "topic\n")], "topic");
+    let mut mr = mrs[0].clone();
+    mr.commit.id = CommitId::new(&topic);
+    project.push_branch(&mr.source_branch, &topic);
+    project.service.add_merge_request(mr.clone());
+    stage.stage_merge_request(&mr, &who, when).unwrap();
+
+    let update = project.commit(&[&base], &[("README", "update\n")], "update");
+    project.set_branch("main", &update);
+    let commit = Commit {
+        repo: project.repo.clone(),
+        refname: Some("main".into()),
+        id: CommitId::new(&update),
+        last_pipeline: None,
+    };
+    stage.base_branch_update(&commit, &who, when).unwrap();
+    assert!(staged_ids(&stage).is_empty());
+
+    // The base branch is fixed before the retry.
+    let fixed = project.commit(&[&update], &[("README", "base\n")], "revert");
+    project.set_branch("main", &fixed);
+    let commit = Commit {
+        id: CommitId::new(&fixed),
+        ..commit
+    };
+    // An update of the base alone does not retry the topic; the back-off still applies.
+    stage.base_branch_update(&commit, &who, when).unwrap();
+    assert_eq!(stage.next_retry(), Some(when + backoff));
+
+    stage.retry_unstaged(when + backoff).unwrap();
+    assert_eq!(staged_ids(&stage), [1]);
+    assert_eq!(stage.next_retry(), None);
+    assert!(project
+        .service
+        .mr_comments(TestProject::NAME, 1)
+        .iter()
+        .any(|comment| comment.ends_with("the retry succeeded.")));
+}
@@ -0,0 +1,248 @@
+//! Utilities shared between tests.
+
+// Not every test uses every utility.
+#![allow(dead_code)]
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+use git_workarea::{CommitId, GitContext, Identity};
+use tempfile::TempDir;
+
+use crate::host::{Commit, HostedProject, MergeRequest, Repo};
+use crate::tests::mock::MockService;
+pub use crate::utils::{http_stub, StubRequest};
+
+/// The name used for commits created by tests.
+pub const NAME: &str = "Ghostflow Testing";
+/// The email used for commits created by tests.
+pub const EMAIL: &str = "ghostflow@example.com";
+
+/// Run a `git` command which is expected to succeed.
+///
+/// Returns its output with surrounding whitespace trimmed.
+pub fn git(ctx: &GitContext, args: &[&str]) -> String {
+    String::from_utf8(git_raw(ctx, args))
+        .unwrap()
+        .trim()
+        .into()
+}
+
+/// Run a `git` command which is expected to succeed.
+///
+/// Returns its output as-is.
+pub fn git_raw(ctx: &GitContext, args: &[&str]) -> Vec<u8> {
+    let output = ctx
+        .git()
+        .env("GIT_AUTHOR_NAME", NAME)
+        .env("GIT_AUTHOR_EMAIL", EMAIL)
+        .env("GIT_COMMITTER_NAME", NAME)
+        .env("GIT_COMMITTER_EMAIL", EMAIL)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "git {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr),
+    );
+    output.stdout
+}
+
+/// Run a `git` command which is expected to succeed with the given input.
+///
+/// Returns its output with surrounding whitespace trimmed.
+pub fn git_input(ctx: &GitContext, args: &[&str], input: &[u8]) -> String {
+    let mut child = ctx
+        .git()
+        .env("GIT_AUTHOR_NAME", NAME)
+        .env("GIT_AUTHOR_EMAIL", EMAIL)
+        .env("GIT_COMMITTER_NAME", NAME)
+        .env("GIT_COMMITTER_EMAIL", EMAIL)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "git {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr),
+    );
+    String::from_utf8(output.stdout).unwrap().trim().into()
+}
+
+/// Write a tree containing the given files.
+pub fn tree(ctx: &GitContext, files: &[(&str, &str)]) -> String {
+    let entries = files
+        .iter()
+        .map(|(path, contents)| {
+            let blob = git_input(ctx, &["hash-object", "-w", "--stdin"], contents.as_bytes());
+            format!("100644 blob {}\t{}\n", blob, path)
+        })
+        .collect::<String>();
+    git_input(ctx, &["mktree"], entries.as_bytes())
+}
+
+/// Create a commit containing the given files on top of the given parents.
+pub fn commit(ctx: &GitContext, parents: &[&str], files: &[(&str, &str)], message: &str) -> String {
+    let tree = tree(ctx, files);
+    let mut args = vec!["commit-tree", tree.as_str(), "-m", message];
+    for parent in parents {
+        args.push("-p");
+        args.push(parent);
+    }
+    git(ctx, &args)
+}
+
+/// Create a bare repository in a new temporary directory.
+pub fn init_bare() -> (TempDir, GitContext) {
+    let dir = TempDir::new().unwrap();
+    let ctx = init_bare_in(dir.path());
+    (dir, ctx)
+}
+
+/// Create a bare repository at the given path.
+pub fn init_bare_in(dir: &Path) -> GitContext {
+    let init = Command::new("git")
+        .arg("init")
+        .arg("--bare")
+        .arg("--quiet")
+        .arg(dir)
+        .status()
+        .unwrap();
+    assert!(init.success());
+    GitContext::new(dir)
+}
+
+/// A project on a mock service along with a local clone of it.
+pub struct TestProject {
+    /// The service hosting the project.
+    pub service: Arc<MockService>,
+    /// The project.
+    pub project: HostedProject,
+    /// The repository of the project.
+    pub repo: Repo,
+    /// The local repository; its `origin` is the project's repository.
+    pub ctx: GitContext,
+    /// The project's repository on the service.
+    pub remote: GitContext,
+    /// The directory holding the local repository.
+    _dir: TempDir,
+}
+
+impl TestProject {
+    /// The name of the project.
+    pub const NAME: &'static str = "base";
+
+    /// Create a new project.
+    pub fn new() -> Self {
+        let service = MockService::new(MockService::make_user("ghostflow"));
+        service.add_user(MockService::make_user("author"));
+        service.add_user(MockService::make_user("reviewer"));
+        let repo = service.add_project(Self::NAME, None).unwrap();
+        let remote = service.remote(Self::NAME).unwrap();
+
+        let (dir, ctx) = init_bare();
+        git(&ctx, &["remote", "add", "origin", &repo.url]);
+        // Commits created by actions use the committer from the repository configuration.
+        git(&ctx, &["config", "user.name", NAME]);
+        git(&ctx, &["config", "user.email", EMAIL]);
+
+        let project = HostedProject {
+            name: Self::NAME.into(),
+            service: service.clone(),
+        };
+
+        Self {
+            service,
+            project,
+            repo,
+            ctx,
+            remote,
+            _dir: dir,
+        }
+    }
+
+    /// The identity used to perform actions.
+    pub fn identity() -> Identity {
+        Identity::new(NAME, EMAIL)
+    }
+
+    /// Create a commit in the local repository.
+    pub fn commit(&self, parents: &[&str], files: &[(&str, &str)], message: &str) -> String {
+        commit(&self.ctx, parents, files, message)
+    }
+
+    /// Point a branch at a commit both locally and on the service.
+    pub fn set_branch(&self, branch: &str, commit: &str) {
+        let refname = format!("refs/heads/{}", branch);
+        git(&self.ctx, &["update-ref", &refname, commit]);
+        self.push_branch(branch, commit);
+    }
+
+    /// Point a branch at a commit on the service.
+    pub fn push_branch(&self, branch: &str, commit: &str) {
+        git(
+            &self.ctx,
+            &[
+                "push",
+                "--quiet",
+                "--force",
+                "origin",
+                &format!("{}:refs/heads/{}", commit, branch),
+            ],
+        );
+    }
+
+    /// The commit of a branch on the service.
+    pub fn remote_branch(&self, branch: &str) -> String {
+        git(&self.remote, &["rev-parse", &format!("refs/heads/{}", branch)])
+    }
+
+    /// Create a merge request for a topic.
+    ///
+    /// The topic branch is pushed to the service.
+    pub fn add_mr(
+        &self,
+        id: u64,
+        source_branch: &str,
+        target_branch: &str,
+        commit: &str,
+    ) -> MergeRequest {
+        self.push_branch(source_branch, commit);
+
+        let author = self.service.user(Self::NAME, "author").unwrap();
+        let mr = MergeRequest {
+            source_repo: Some(self.repo.clone()),
+            source_branch: source_branch.into(),
+            target_repo: self.repo.clone(),
+            target_branch: target_branch.into(),
+            id,
+            url: format!("https://example.com/{}/merge_requests/{}", Self::NAME, id),
+            work_in_progress: false,
+            description: String::new(),
+            old_commit: None,
+            commit: Commit {
+                repo: self.repo.clone(),
+                refname: Some(source_branch.into()),
+                id: CommitId::new(commit),
+                last_pipeline: None,
+            },
+            author,
+            reference: format!("!{}", id),
+            remove_source_branch: false,
+            labels: Vec::new(),
+        };
+        self.service.add_merge_request(mr.clone());
+
+        mr
+    }
+}
@@ -1,8 +1,19 @@
+pub(crate) mod conflicts;
+#[cfg(any(test, feature = "http-stub"))]
+mod http_stub;
 pub mod mr;
+pub(crate) mod signing;
 mod template_string;
 mod trailer;
 
-pub(crate) use self::template_string::TemplateString;
+#[cfg(any(test, feature = "http-stub"))]
+pub use self::http_stub::{http_stub, StubRequest};
+
+pub use self::signing::SigningError;
+pub use self::signing::SigningKey;
+
+pub use self::template_string::TemplateError;
+pub use self::template_string::TemplateString;
 
 pub use self::trailer::Trailer;
 pub use self::trailer::TrailerRef;
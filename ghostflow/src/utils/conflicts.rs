@@ -0,0 +1,286 @@
+//! Diagnostics for merge requests which cannot be merged.
+//!
+//! When a topic fails to merge, the comment posted to the merge request lists the conflicting
+//! paths, any other topics which touch those paths, and a sequence of `git` commands which may be
+//! used to reproduce the failure locally.
+
+use std::collections::BTreeSet;
+
+use git_workarea::{CommitId, Conflict, GitContext, GitError};
+use itertools::Itertools;
+use log::warn;
+
+use crate::host::MergeRequest;
+
+/// A structured report of why a merge request could not be merged.
+#[derive(Debug, Clone)]
+pub(crate) struct ConflictReport {
+    /// The leading sentence of the report.
+    headline: String,
+    /// The paths which conflict.
+    paths: Vec<String>,
+    /// Other topics which touch the conflicting paths (as Markdown links).
+    topics: Vec<String>,
+    /// Commands to reproduce the failure.
+    reproduce: Vec<String>,
+}
+
+impl ConflictReport {
+    /// Create a new report.
+    pub(crate) fn new<H>(headline: H) -> Self
+    where
+        H: Into<String>,
+    {
+        Self {
+            headline: headline.into(),
+            paths: Vec::new(),
+            topics: Vec::new(),
+            reproduce: Vec::new(),
+        }
+    }
+
+    /// Add the paths of a set of conflicts to the report.
+    pub(crate) fn conflicts(&mut self, conflicts: &[Conflict]) -> &mut Self {
+        self.paths.extend(conflict_paths(conflicts));
+        self
+    }
+
+    /// Name another topic which conflicts with the merge request.
+    pub(crate) fn topic(&mut self, name: &str, url: &str) -> &mut Self {
+        self.topics.push(format!("[{}]({})", name, url));
+        self
+    }
+
+    /// The leading sentence of the report.
+    pub(crate) fn headline(&self) -> &str {
+        &self.headline
+    }
+
+    /// The conflicting paths in the report.
+    pub(crate) fn paths(&self) -> &[String] {
+        &self.paths
+    }
+
+    /// Add commands to reproduce merging the merge request into a ref of the remote.
+    pub(crate) fn reproduce_merge(&mut self, base: &str, mr: &MergeRequest) -> &mut Self {
+        self.reproduce_fetch(base, mr);
+        self.reproduce.push(format!("git merge --no-ff {}", mr.commit.id));
+        self
+    }
+
+    /// Add commands to reproduce rebasing the merge request onto a ref of the remote.
+    pub(crate) fn reproduce_rebase(&mut self, base: &str, mr: &MergeRequest) -> &mut Self {
+        self.reproduce_fetch(base, mr);
+        self.reproduce.push(format!("git cherry-pick ..{}", mr.commit.id));
+        self
+    }
+
+    /// Add commands to check out a ref of the remote and fetch the merge request.
+    fn reproduce_fetch(&mut self, base: &str, mr: &MergeRequest) {
+        self.reproduce.push(format!("git fetch origin {}", base));
+        self.reproduce.push("git checkout --detach FETCH_HEAD".into());
+        if let Some(source_repo) = mr.source_repo.as_ref() {
+            self.reproduce.push(format!(
+                "git fetch {} {}",
+                source_repo.url, mr.source_branch,
+            ));
+        } else {
+            self.reproduce.push(format!("git fetch origin {}", mr.commit.id));
+        }
+    }
+
+    /// Render the report as a comment.
+    pub(crate) fn render(&self) -> String {
+        let mut content = self.headline.clone();
+
+        if !self.paths.is_empty() {
+            content.push_str(&format!("\n\n  - `{}`", self.paths.iter().join("`\n  - `")));
+        }
+
+        if !self.topics.is_empty() {
+            content.push_str(&format!(
+                "\n\nThe following topics also change these paths:\n\n  - {}",
+                self.topics.iter().join("\n  - "),
+            ));
+        }
+
+        if !self.reproduce.is_empty() {
+            content.push_str(&format!(
+                "\n\nTo reproduce this locally:\n\n```sh\n{}\n```",
+                self.reproduce.iter().join("\n"),
+            ));
+        }
+
+        content
+    }
+}
+
+/// The paths of a set of conflicts.
+///
+/// Paths with multiple conflicting entries are listed once.
+pub(crate) fn conflict_paths(conflicts: &[Conflict]) -> Vec<String> {
+    conflicts
+        .iter()
+        .map(|conflict| conflict.path().to_string_lossy().into_owned())
+        .dedup()
+        .collect()
+}
+
+/// The paths changed by a topic relative to a base commit.
+///
+/// Diagnostics are best-effort, so failures are logged and result in an empty set.
+pub(crate) fn changed_paths(
+    ctx: &GitContext,
+    base: &CommitId,
+    commit: &CommitId,
+) -> BTreeSet<String> {
+    let diff = ctx
+        .git()
+        .arg("diff")
+        .arg("--name-only")
+        .arg("-z")
+        .arg(format!("{}...{}", base, commit))
+        .output()
+        .map_err(|err| GitError::subcommand("diff --name-only", err));
+    let diff = match diff {
+        Ok(diff) if diff.status.success() => diff,
+        Ok(diff) => {
+            warn!(
+                target: "ghostflow/conflicts",
+                "failed to list the paths changed by {}: {}",
+                commit,
+                String::from_utf8_lossy(&diff.stderr),
+            );
+            return BTreeSet::new();
+        },
+        Err(err) => {
+            warn!(
+                target: "ghostflow/conflicts",
+                "failed to list the paths changed by {}: {:?}",
+                commit,
+                err,
+            );
+            return BTreeSet::new();
+        },
+    };
+
+    diff.stdout
+        .split(|&byte| byte == 0)
+        .filter(|path| !path.is_empty())
+        .map(|path| String::from_utf8_lossy(path).into_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use git_workarea::CommitId;
+
+    use crate::host::{Commit, MergeRequest, Repo, User};
+    use crate::utils::conflicts::ConflictReport;
+
+    fn mr(source_repo: Option<Repo>) -> MergeRequest {
+        let target_repo = Repo {
+            name: "upstream/project".into(),
+            url: "git@example.com:upstream/project.git".into(),
+            forked_from: None,
+        };
+
+        MergeRequest {
+            source_repo,
+            source_branch: "topic".into(),
+            target_repo: target_repo.clone(),
+            target_branch: "main".into(),
+            id: 1,
+            url: "https://example.com/upstream/project/merge_requests/1".into(),
+            work_in_progress: false,
+            description: String::new(),
+            old_commit: None,
+            commit: Commit {
+                repo: target_repo,
+                refname: Some("topic".into()),
+                id: CommitId::new("0123456789abcdef0123456789abcdef01234567"),
+                last_pipeline: None,
+            },
+            author: User {
+                handle: "contributor".into(),
+                name: "Contributor".into(),
+                email: "contributor@example.com".into(),
+            },
+            reference: "!1".into(),
+            remove_source_branch: false,
+            labels: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_conflict_report_render() {
+        let fork = Repo {
+            name: "contributor/project".into(),
+            url: "git@example.com:contributor/project.git".into(),
+            forked_from: None,
+        };
+
+        let mut report = ConflictReport::new("This merge request conflicts:");
+        report
+            .topic("other", "https://example.com/upstream/project/merge_requests/2")
+            .reproduce_merge("refs/stage/main/head", &mr(Some(fork)));
+
+        assert_eq!(
+            report.render(),
+            "This merge request conflicts:\n\
+             \n\
+             The following topics also change these paths:\n\
+             \n  \
+             - [other](https://example.com/upstream/project/merge_requests/2)\n\
+             \n\
+             To reproduce this locally:\n\
+             \n\
+             ```sh\n\
+             git fetch origin refs/stage/main/head\n\
+             git checkout --detach FETCH_HEAD\n\
+             git fetch git@example.com:contributor/project.git topic\n\
+             git merge --no-ff 0123456789abcdef0123456789abcdef01234567\n\
+             ```",
+        );
+    }
+
+    #[test]
+    fn test_conflict_report_deleted_source() {
+        let mut report = ConflictReport::new("Headline.");
+        report.reproduce_merge("main", &mr(None));
+
+        assert_eq!(
+            report.render(),
+            "Headline.\n\
+             \n\
+             To reproduce this locally:\n\
+             \n\
+             ```sh\n\
+             git fetch origin main\n\
+             git checkout --detach FETCH_HEAD\n\
+             git fetch origin 0123456789abcdef0123456789abcdef01234567\n\
+             git merge --no-ff 0123456789abcdef0123456789abcdef01234567\n\
+             ```",
+        );
+    }
+
+    #[test]
+    fn test_conflict_report_rebase() {
+        let mut report = ConflictReport::new("Headline.");
+        report.reproduce_rebase("main", &mr(None));
+
+        assert_eq!(
+            report.render(),
+            "Headline.\n\
+             \n\
+             To reproduce this locally:\n\
+             \n\
+             ```sh\n\
+             git fetch origin main\n\
+             git checkout --detach FETCH_HEAD\n\
+             git fetch origin 0123456789abcdef0123456789abcdef01234567\n\
+             git cherry-pick ..0123456789abcdef0123456789abcdef01234567\n\
+             ```",
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! A minimal HTTP server for testing clients.
+//!
+//! This is available to other crates through the `http-stub` feature so that hosting service
+//! implementations may test their clients without a live service.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::thread;
+
+/// A request received by an HTTP stub.
+#[derive(Debug)]
+pub struct StubRequest {
+    /// The method of the request.
+    pub method: String,
+    /// The path of the request.
+    pub path: String,
+    /// The headers of the request; names are lowercased.
+    pub headers: Vec<(String, String)>,
+    /// The body of the request.
+    pub body: Vec<u8>,
+}
+
+impl StubRequest {
+    /// The value of a header, if present.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A minimal HTTP server.
+///
+/// The given number of requests are each answered with the status and body returned by
+/// `respond`. Requests are sent through the returned channel once they have been answered.
+pub fn http_stub<F>(requests: usize, mut respond: F) -> (String, mpsc::Receiver<StubRequest>)
+where
+    F: FnMut(&StubRequest) -> (u16, String) + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for stream in listener.incoming().take(requests) {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut request_line = request_line.split(' ');
+            let method = request_line.next().unwrap_or_default().to_string();
+            let path = request_line.next().unwrap_or_default().to_string();
+
+            let mut headers = Vec::new();
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                let header = header.trim_end();
+                if header.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    headers.push((name.to_ascii_lowercase(), value.trim().to_string()));
+                }
+            }
+
+            let mut request = StubRequest {
+                method,
+                path,
+                headers,
+                body: Vec::new(),
+            };
+            if request.header("expect").is_some() {
+                stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").unwrap();
+            }
+            let length = request
+                .header("content-length")
+                .map_or(0, |length| length.parse().unwrap());
+            request.body.resize(length, 0);
+            reader.read_exact(&mut request.body).unwrap();
+
+            let (status, body) = respond(&request);
+            write!(
+                stream,
+                "HTTP/1.1 {} Stub\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                 Connection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body,
+            )
+            .unwrap();
+            // Tests may not care about the requests.
+            let _ = sender.send(request);
+        }
+    });
+
+    (endpoint, receiver)
+}
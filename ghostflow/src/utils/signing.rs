@@ -0,0 +1,388 @@
+//! Signing of commits and tags created by ghostflow.
+//!
+//! Signed commits and tags are assembled manually. Commits keep all of their headers (e.g.,
+//! `encoding` and `mergetag`) and tags do not need to exist under `refs/tags` to be created.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use git_workarea::{CommitId, GitContext, GitError};
+use thiserror::Error;
+
+/// Errors which may occur when signing commits or tags.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum SigningError {
+    /// Could not read the information of a commit.
+    #[error("failed to read information for {}: {}", commit, output)]
+    CommitInfo {
+        /// The commit.
+        commit: CommitId,
+        /// Output from `git cat-file`.
+        output: String,
+    },
+    /// Could not determine the identity to use for a tag.
+    #[error("failed to determine the tagger identity: {}", output)]
+    TaggerIdent {
+        /// Output from `git var`.
+        output: String,
+    },
+    /// Could not run the signing program.
+    #[error("failed to run `{}`: {}", program, source)]
+    SignSpawn {
+        /// The program used for signing.
+        program: &'static str,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// The signing program failed.
+    #[error("failed to sign with `{}`: {}", program, output)]
+    Sign {
+        /// The program used for signing.
+        program: &'static str,
+        /// Output from the program.
+        output: String,
+    },
+    /// Could not write data to a subcommand.
+    #[error("failed to write to {}: {}", command, source)]
+    WriteInput {
+        /// The command being written to.
+        command: &'static str,
+        /// The source of the error.
+        #[source]
+        source: io::Error,
+    },
+    /// Could not create a signed commit.
+    #[error("failed to create a signed commit: {}", output)]
+    WriteCommit {
+        /// Output from `git hash-object`.
+        output: String,
+    },
+    /// Could not create a signed tag.
+    #[error("failed to create a signed tag: {}", output)]
+    MakeTag {
+        /// Output from `git mktag`.
+        output: String,
+    },
+    /// Failure to execute a `git` command.
+    #[error("git error: {}", source)]
+    Git {
+        /// The source of the error.
+        #[from]
+        source: GitError,
+    },
+}
+
+impl SigningError {
+    fn commit_info(commit: CommitId, output: &[u8]) -> Self {
+        SigningError::CommitInfo {
+            commit,
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
+
+    fn tagger_ident(output: &[u8]) -> Self {
+        SigningError::TaggerIdent {
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
+
+    fn sign_spawn(program: &'static str, source: io::Error) -> Self {
+        SigningError::SignSpawn {
+            program,
+            source,
+        }
+    }
+
+    fn sign(program: &'static str, output: &[u8]) -> Self {
+        SigningError::Sign {
+            program,
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
+
+    fn write_input(command: &'static str, source: io::Error) -> Self {
+        SigningError::WriteInput {
+            command,
+            source,
+        }
+    }
+
+    fn write_commit(output: &[u8]) -> Self {
+        SigningError::WriteCommit {
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
+
+    fn make_tag(output: &[u8]) -> Self {
+        SigningError::MakeTag {
+            output: String::from_utf8_lossy(output).into(),
+        }
+    }
+}
+
+type SigningResult<T> = Result<T, SigningError>;
+
+/// A key used to sign commits and tags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SigningKey {
+    /// An OpenPGP key.
+    OpenPgp {
+        /// The ID of the key to sign with.
+        key_id: String,
+        /// The GnuPG home directory containing the key.
+        ///
+        /// If `None`, the default home directory is used.
+        home: Option<PathBuf>,
+    },
+    /// An SSH key.
+    Ssh {
+        /// The path to the private key file.
+        key: PathBuf,
+    },
+}
+
+impl SigningKey {
+    /// An OpenPGP key from the default GnuPG home directory.
+    pub fn openpgp<K>(key_id: K) -> Self
+    where
+        K: Into<String>,
+    {
+        SigningKey::OpenPgp {
+            key_id: key_id.into(),
+            home: None,
+        }
+    }
+
+    /// An SSH key from a private key file.
+    pub fn ssh<P>(key: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        SigningKey::Ssh {
+            key: key.into(),
+        }
+    }
+
+    /// Create an armored detached signature for a payload.
+    fn sign(&self, payload: &[u8]) -> SigningResult<Vec<u8>> {
+        let (program, mut command) = match self {
+            SigningKey::OpenPgp {
+                key_id,
+                home,
+            } => {
+                let mut command = Command::new("gpg");
+                command
+                    .arg("--batch")
+                    .arg("--armor")
+                    .arg("--detach-sign")
+                    .arg("--local-user")
+                    .arg(key_id);
+                if let Some(home) = home.as_ref() {
+                    command.env("GNUPGHOME", home);
+                }
+                ("gpg", command)
+            },
+            SigningKey::Ssh {
+                key,
+            } => {
+                let mut command = Command::new("ssh-keygen");
+                command
+                    .arg("-Y")
+                    .arg("sign")
+                    .arg("-n")
+                    .arg("git")
+                    .arg("-f")
+                    .arg(key);
+                ("ssh-keygen", command)
+            },
+        };
+
+        let mut sign = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| SigningError::sign_spawn(program, err))?;
+
+        {
+            let sign_stdin = sign.stdin.as_mut().expect("expected the signer to have a stdin");
+            sign_stdin
+                .write_all(payload)
+                .map_err(|err| SigningError::write_input(program, err))?;
+        }
+
+        let sign = sign
+            .wait_with_output()
+            .map_err(|err| SigningError::sign_spawn(program, err))?;
+        if !sign.status.success() {
+            return Err(SigningError::sign(program, &sign.stderr));
+        }
+
+        Ok(sign.stdout)
+    }
+}
+
+/// Whether a commit header line starts a signature header.
+fn is_signature_header(line: &[u8]) -> bool {
+    line.starts_with(b"gpgsig ") || line.starts_with(b"gpgsig-sha256 ")
+}
+
+/// Recreate a commit with a signature.
+///
+/// The new commit is identical to the original other than its signature; any existing signature
+/// is replaced. The commit object is read and written directly so that all headers and the
+/// message are reproduced byte-for-byte.
+pub(crate) fn sign_commit(
+    ctx: &GitContext,
+    commit: &CommitId,
+    key: &SigningKey,
+) -> SigningResult<CommitId> {
+    let cat_file = ctx
+        .git()
+        .arg("cat-file")
+        .arg("commit")
+        .arg(commit.as_str())
+        .output()
+        .map_err(|err| GitError::subcommand("cat-file", err))?;
+    if !cat_file.status.success() {
+        return Err(SigningError::commit_info(commit.clone(), &cat_file.stderr));
+    }
+
+    let raw = &cat_file.stdout;
+    let (header, message) = raw
+        .windows(2)
+        .position(|window| window == b"\n\n")
+        .map_or((&raw[..], &[][..]), |pos| (&raw[..pos], &raw[pos + 2..]));
+    if !header.starts_with(b"tree ") {
+        return Err(SigningError::commit_info(
+            commit.clone(),
+            b"malformed commit header",
+        ));
+    }
+
+    // The signed payload is the commit without any existing signature. Continuation lines of a
+    // header start with a space.
+    let mut payload = Vec::with_capacity(raw.len());
+    let mut in_signature = false;
+    for line in header.split(|&byte| byte == b'\n') {
+        if !line.starts_with(b" ") {
+            in_signature = is_signature_header(line);
+        }
+        if in_signature {
+            continue;
+        }
+
+        payload.extend_from_slice(line);
+        payload.push(b'\n');
+    }
+    let header_len = payload.len();
+    payload.push(b'\n');
+    payload.extend_from_slice(message);
+
+    let signature = key.sign(&payload)?;
+    let signature = String::from_utf8_lossy(&signature);
+
+    // The signature is added as the last header.
+    let mut object = Vec::with_capacity(payload.len() + signature.len() * 2);
+    object.extend_from_slice(&payload[..header_len]);
+    object.extend_from_slice(b"gpgsig ");
+    object.extend_from_slice(signature.trim_end().replace('\n', "\n ").as_bytes());
+    object.push(b'\n');
+    object.extend_from_slice(&payload[header_len..]);
+
+    let mut hash_object = ctx
+        .git()
+        .arg("hash-object")
+        .arg("-t")
+        .arg("commit")
+        .arg("-w")
+        .arg("--stdin")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| GitError::subcommand("hash-object (spawn)", err))?;
+
+    {
+        let hash_object_stdin = hash_object
+            .stdin
+            .as_mut()
+            .expect("expected hash-object to have a stdin");
+        hash_object_stdin
+            .write_all(&object)
+            .map_err(|err| SigningError::write_input("hash-object", err))?;
+    }
+
+    let hash_object = hash_object
+        .wait_with_output()
+        .map_err(|err| GitError::subcommand("hash-object (wait)", err))?;
+    if !hash_object.status.success() {
+        return Err(SigningError::write_commit(&hash_object.stderr));
+    }
+
+    let new_commit = String::from_utf8_lossy(&hash_object.stdout);
+    Ok(CommitId::new(new_commit.trim()))
+}
+
+/// Create a signed, annotated tag object for a commit.
+///
+/// The tag object is not referenced by any ref; the caller is responsible for pointing a ref at
+/// the returned object.
+pub(crate) fn signed_tag(
+    ctx: &GitContext,
+    commit: &CommitId,
+    name: &str,
+    message: &str,
+    key: &SigningKey,
+) -> SigningResult<CommitId> {
+    let var = ctx
+        .git()
+        .arg("var")
+        .arg("GIT_COMMITTER_IDENT")
+        .output()
+        .map_err(|err| GitError::subcommand("var", err))?;
+    if !var.status.success() {
+        return Err(SigningError::tagger_ident(&var.stderr));
+    }
+    let tagger = String::from_utf8_lossy(&var.stdout);
+
+    let mut tag = format!(
+        "object {}\ntype commit\ntag {}\ntagger {}\n\n{}\n",
+        commit,
+        name,
+        tagger.trim(),
+        message.trim_end(),
+    )
+    .into_bytes();
+    let signature = key.sign(&tag)?;
+    tag.extend(signature);
+
+    let mut mktag = ctx
+        .git()
+        .arg("mktag")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| GitError::subcommand("mktag (spawn)", err))?;
+
+    {
+        let mktag_stdin = mktag.stdin.as_mut().expect("expected mktag to have a stdin");
+        mktag_stdin
+            .write_all(&tag)
+            .map_err(|err| SigningError::write_input("mktag", err))?;
+    }
+
+    let mktag = mktag
+        .wait_with_output()
+        .map_err(|err| GitError::subcommand("mktag (wait)", err))?;
+    if !mktag.status.success() {
+        return Err(SigningError::make_tag(&mktag.stderr));
+    }
+
+    let tag = String::from_utf8_lossy(&mktag.stdout);
+    Ok(CommitId::new(tag.trim()))
+}
@@ -1,113 +1,414 @@
 //! Template strings.
 //!
 //! Supports replacing template parameters (`{name}`) with values from a lookup map.
+//!
+//! Parameters may be followed by filters separated by `|` which transform the value:
+//!
+//!   - `default:VALUE`: use `VALUE` if the field is unknown or empty
+//!   - `urlencode`: percent-encode the value for use in a URL
+//!   - `short`: the first 8 characters of the value (e.g., for short commit hashes)
+//!   - `lower`: convert the value to lowercase
+//!   - `upper`: convert the value to uppercase
+//!   - `truncate:N`: the first `N` characters of the value
+//!
+//! Filters are applied in order, so `{branch|default:main|urlencode}` URL-encodes the default as
+//! well. Literal braces may be written as `{{` and `}}`.
 
 use std::borrow::Cow;
 use std::collections::HashMap;
-use std::fmt::{self, Debug};
+use std::fmt::{self, Debug, Write};
 
-use lazy_static::lazy_static;
 use log::warn;
-use regex::Regex;
+use thiserror::Error;
+
+/// Errors which may occur when using template strings.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TemplateError {
+    /// An unknown filter was used.
+    #[error("unknown template filter `{}` for `{}`", filter, field)]
+    UnknownFilter {
+        /// The field using the filter.
+        field: String,
+        /// The filter.
+        filter: String,
+    },
+    /// A filter was given an invalid argument.
+    #[error("invalid argument for the `{}` template filter for `{}`: `{}`", filter, field, arg)]
+    InvalidFilterArgument {
+        /// The field using the filter.
+        field: String,
+        /// The filter.
+        filter: String,
+        /// The argument.
+        arg: String,
+    },
+    /// A field was not available when expanding a template.
+    #[error("unknown template replacement for `{}`", field)]
+    UnknownField {
+        /// The field.
+        field: String,
+    },
+}
+
+impl TemplateError {
+    fn unknown_filter(field: &str, filter: &str) -> Self {
+        TemplateError::UnknownFilter {
+            field: field.into(),
+            filter: filter.into(),
+        }
+    }
+
+    fn invalid_filter_argument(field: &str, filter: &str, arg: &str) -> Self {
+        TemplateError::InvalidFilterArgument {
+            field: field.into(),
+            filter: filter.into(),
+            arg: arg.into(),
+        }
+    }
+
+    fn unknown_field(field: &str) -> Self {
+        TemplateError::UnknownField {
+            field: field.into(),
+        }
+    }
+}
+
+type TemplateResult<T> = Result<T, TemplateError>;
+
+/// The length of values using the `short` filter.
+const SHORT_LENGTH: usize = 8;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-enum TemplatePart {
-    Literal { start: usize, end: usize },
-    NamedField { name: String },
+enum TemplateFilter {
+    Default(String),
+    UrlEncode,
+    Short,
+    Lower,
+    Upper,
+    Truncate(usize),
 }
 
-lazy_static! {
-    static ref TEMPLATE_REPLACEMENT: Regex =
-        Regex::new(r"(?P<expand>\{(?P<name>[A-Za-z0-9_]+)\})").unwrap();
+impl TemplateFilter {
+    fn parse(field: &str, filter: &str) -> TemplateResult<Self> {
+        let (name, arg) = filter
+            .split_once(':')
+            .map_or((filter, None), |(name, arg)| (name, Some(arg)));
+
+        Ok(match (name, arg) {
+            ("default", Some(arg)) => TemplateFilter::Default(arg.into()),
+            ("urlencode", None) => TemplateFilter::UrlEncode,
+            ("short", None) => TemplateFilter::Short,
+            ("lower", None) => TemplateFilter::Lower,
+            ("upper", None) => TemplateFilter::Upper,
+            ("truncate", Some(arg)) => {
+                let len = arg
+                    .parse()
+                    .map_err(|_| TemplateError::invalid_filter_argument(field, name, arg))?;
+                TemplateFilter::Truncate(len)
+            },
+            ("default", None) | ("truncate", None) => {
+                return Err(TemplateError::invalid_filter_argument(field, name, ""));
+            },
+            (_, Some(arg)) if is_known_filter(name) => {
+                return Err(TemplateError::invalid_filter_argument(field, name, arg));
+            },
+            _ => return Err(TemplateError::unknown_filter(field, name)),
+        })
+    }
+
+    fn apply<'a>(&self, value: Option<Cow<'a, str>>) -> Option<Cow<'a, str>> {
+        match self {
+            TemplateFilter::Default(default) => {
+                match value {
+                    Some(value) if !value.is_empty() => Some(value),
+                    _ => Some(Cow::Owned(default.clone())),
+                }
+            },
+            TemplateFilter::UrlEncode => value.map(|value| urlencode(&value).into()),
+            TemplateFilter::Short => value.map(|value| truncate(value, SHORT_LENGTH)),
+            TemplateFilter::Lower => value.map(|value| value.to_lowercase().into()),
+            TemplateFilter::Upper => value.map(|value| value.to_uppercase().into()),
+            TemplateFilter::Truncate(len) => value.map(|value| truncate(value, *len)),
+        }
+    }
+}
+
+fn is_known_filter(name: &str) -> bool {
+    ["default", "urlencode", "short", "lower", "upper", "truncate"].contains(&name)
+}
+
+fn truncate(value: Cow<str>, len: usize) -> Cow<str> {
+    match value.char_indices().nth(len) {
+        Some((idx, _)) => {
+            match value {
+                Cow::Borrowed(value) => Cow::Borrowed(&value[..idx]),
+                Cow::Owned(mut value) => {
+                    value.truncate(idx);
+                    Cow::Owned(value)
+                },
+            }
+        },
+        None => value,
+    }
+}
+
+fn urlencode(value: &str) -> String {
+    value.bytes().fold(String::new(), |mut encoded, byte| {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte.into());
+        } else {
+            // Writing to a `String` cannot fail.
+            let _ = write!(encoded, "%{:02X}", byte);
+        }
+        encoded
+    })
+}
+
+fn is_field_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_')
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TemplatePart {
+    Literal {
+        start: usize,
+        end: usize,
+    },
+    NamedField {
+        name: String,
+    },
+    FilteredField {
+        name: String,
+        filters: Vec<TemplateFilter>,
+    },
 }
 
+/// A string with `{name}` replacements.
+///
+/// See the module documentation for the supported syntax.
 #[derive(Clone)]
-pub(crate) struct TemplateString {
+pub struct TemplateString {
     template: String,
     parts: Vec<TemplatePart>,
+    strict: bool,
 }
 
 impl TemplateString {
-    pub(crate) fn new<T>(template: T) -> Self
+    /// Create a new template string.
+    ///
+    /// Fields with invalid filters are treated as literal text. Use `TemplateString::parse` to
+    /// detect such errors.
+    pub fn new<T>(template: T) -> Self
     where
         T: Into<String>,
     {
-        Self::new_impl(template.into())
-    }
-
-    fn new_impl(template: String) -> Self {
-        let fields = TEMPLATE_REPLACEMENT
-            .captures_iter(&template)
-            .map(|capture| {
-                let expand = capture
-                    .name("expand")
-                    .expect("the template regex should have a 'expand' group");
-                let name = capture
-                    .name("name")
-                    .expect("the template regex should have a 'name' group");
-
-                (name.as_str(), expand.start(), expand.end())
-            });
-        let mut last_end = 0;
-        let mut parts = Vec::new();
+        let template = template.into();
+        let parts = Self::parse_parts(&template, false)
+            .expect("lenient template parsing should not fail");
+
+        Self {
+            template,
+            parts,
+            strict: false,
+        }
+    }
 
-        for field in fields {
-            let (name, start, end) = field;
+    /// Parse a template string.
+    ///
+    /// Unlike `TemplateString::new`, errors in filters are reported.
+    pub fn parse<T>(template: T) -> TemplateResult<Self>
+    where
+        T: Into<String>,
+    {
+        let template = template.into();
+        let parts = Self::parse_parts(&template, true)?;
 
-            if last_end < start {
-                parts.push(TemplatePart::Literal {
-                    start: last_end,
-                    end: start,
-                })
-            }
+        Ok(Self {
+            template,
+            parts,
+            strict: false,
+        })
+    }
 
-            last_end = end;
-            parts.push(TemplatePart::NamedField {
-                name: name.into(),
-            });
+    /// Error on unknown fields.
+    ///
+    /// By default, unknown fields (without a `default` filter) expand to nothing. In strict mode,
+    /// `TemplateString::try_replace` returns an error instead.
+    pub fn strict(&mut self) -> &mut Self {
+        self.strict = true;
+        self
+    }
+
+    /// The template.
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    fn push_literal(parts: &mut Vec<TemplatePart>, start: usize, end: usize) {
+        if let Some(TemplatePart::Literal {
+            end: last_end, ..
+        }) = parts.last_mut()
+        {
+            if *last_end == start {
+                *last_end = end;
+                return;
+            }
         }
 
-        if last_end < template.len() {
-            parts.push(TemplatePart::Literal {
-                start: last_end,
-                end: template.len(),
-            })
+        parts.push(TemplatePart::Literal {
+            start,
+            end,
+        });
+    }
+
+    fn parse_field(field: &str, strict: bool) -> TemplateResult<Option<TemplatePart>> {
+        let mut segments = field.split('|');
+        let name = segments.next().unwrap_or_default();
+        if !is_field_name(name) {
+            return Ok(None);
         }
 
-        Self {
-            template,
-            parts,
+        let filters = segments
+            .map(|filter| TemplateFilter::parse(name, filter))
+            .collect::<TemplateResult<Vec<_>>>();
+        let filters = match filters {
+            Ok(filters) => filters,
+            Err(err) if strict => return Err(err),
+            Err(err) => {
+                warn!(
+                    target: "ghostflow/template_string",
+                    "treating `{{{}}}` as a literal: {}",
+                    field,
+                    err,
+                );
+                return Ok(None);
+            },
+        };
+
+        Ok(Some(if filters.is_empty() {
+            TemplatePart::NamedField {
+                name: name.into(),
+            }
+        } else {
+            TemplatePart::FilteredField {
+                name: name.into(),
+                filters,
+            }
+        }))
+    }
+
+    fn parse_parts(template: &str, strict: bool) -> TemplateResult<Vec<TemplatePart>> {
+        let bytes = template.as_bytes();
+        let mut parts = Vec::new();
+        let mut idx = 0;
+
+        while idx < bytes.len() {
+            match (bytes[idx], bytes.get(idx + 1)) {
+                // Escaped braces expand to a single brace.
+                (b'{', Some(b'{')) | (b'}', Some(b'}')) => {
+                    Self::push_literal(&mut parts, idx, idx + 1);
+                    idx += 2;
+                },
+                (b'{', _) => {
+                    let field = template[idx + 1..]
+                        .find('}')
+                        .map(|len| &template[idx + 1..idx + 1 + len]);
+                    let part = if let Some(field) = field {
+                        Self::parse_field(field, strict)?
+                    } else {
+                        None
+                    };
+
+                    if let (Some(part), Some(field)) = (part, field) {
+                        parts.push(part);
+                        idx += field.len() + 2;
+                    } else {
+                        Self::push_literal(&mut parts, idx, idx + 1);
+                        idx += 1;
+                    }
+                },
+                _ => {
+                    let end = template[idx + 1..]
+                        .find(|ch| ch == '{' || ch == '}')
+                        .map_or(template.len(), |len| idx + 1 + len);
+                    Self::push_literal(&mut parts, idx, end);
+                    idx = end;
+                },
+            }
         }
+
+        Ok(parts)
     }
 
-    pub(crate) fn replace(&self, context: &HashMap<&str, Cow<str>>) -> String {
+    fn expand(
+        &self,
+        context: &HashMap<&str, Cow<str>>,
+        unknown: &mut dyn FnMut(&str) -> TemplateResult<()>,
+    ) -> TemplateResult<String> {
         let mut result = String::new();
 
         for part in &self.parts {
-            match part {
+            let (name, filters): (&str, &[TemplateFilter]) = match part {
                 TemplatePart::Literal {
                     start,
                     end,
                 } => {
                     result.push_str(&self.template[*start..*end]);
+                    continue;
                 },
                 TemplatePart::NamedField {
                     name,
-                } => {
-                    if let Some(value) = context.get(name as &str) {
-                        result.push_str(value);
-                    } else {
-                        warn!(
-                            target: "ghostflow/template_string",
-                            "unknown template replacement for `{}`",
-                            name,
-                        );
-                    }
-                },
+                } => (name, &[]),
+                TemplatePart::FilteredField {
+                    name,
+                    filters,
+                } => (name, filters),
+            };
+
+            let value = context.get(name).map(|value| Cow::Borrowed(&**value));
+            let value = filters
+                .iter()
+                .fold(value, |value, filter| filter.apply(value));
+
+            if let Some(value) = value {
+                result.push_str(&value);
+            } else {
+                unknown(name)?;
             }
         }
 
-        result
+        Ok(result)
+    }
+
+    /// Expand the template using values from the context.
+    ///
+    /// Unknown fields expand to nothing, even in strict mode.
+    pub fn replace(&self, context: &HashMap<&str, Cow<str>>) -> String {
+        self.expand(context, &mut |name| {
+            warn!(
+                target: "ghostflow/template_string",
+                "unknown template replacement for `{}`",
+                name,
+            );
+            Ok(())
+        })
+        .expect("unknown fields are not errors")
+    }
+
+    /// Expand the template using values from the context.
+    ///
+    /// In strict mode, unknown fields are an error. Otherwise, this is the same as
+    /// `TemplateString::replace`.
+    pub fn try_replace(&self, context: &HashMap<&str, Cow<str>>) -> TemplateResult<String> {
+        if self.strict {
+            self.expand(context, &mut |name| Err(TemplateError::unknown_field(name)))
+        } else {
+            Ok(self.replace(context))
+        }
     }
 }
 
@@ -115,6 +416,7 @@ impl Debug for TemplateString {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("TemplateString")
             .field("template", &self.template)
+            .field("strict", &self.strict)
             .finish()
     }
 }
@@ -123,7 +425,7 @@ impl Debug for TemplateString {
 mod test {
     use std::borrow::Cow;
 
-    use super::{TemplatePart, TemplateString};
+    use super::{TemplateError, TemplateFilter, TemplatePart, TemplateString};
 
     #[test]
     fn test_template_string_parse_literal() {
@@ -232,4 +534,148 @@ mod test {
             "This can be a {name} replacement for value.",
         );
     }
+
+    #[test]
+    fn test_template_string_parse_escapes() {
+        let ts = TemplateString::new("{{literal}} {{{name}}}");
+        itertools::assert_equal(
+            ts.parts,
+            [
+                TemplatePart::Literal {
+                    start: 0,
+                    end: 1,
+                },
+                TemplatePart::Literal {
+                    start: 2,
+                    end: 10,
+                },
+                TemplatePart::Literal {
+                    start: 11,
+                    end: 13,
+                },
+                TemplatePart::NamedField {
+                    name: "name".into(),
+                },
+                TemplatePart::Literal {
+                    start: 20,
+                    end: 21,
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn test_template_string_parse_filters() {
+        let ts =
+            TemplateString::new("{commit|short|upper}{branch|default:main}{title|truncate:10}");
+        itertools::assert_equal(
+            ts.parts,
+            [
+                TemplatePart::FilteredField {
+                    name: "commit".into(),
+                    filters: vec![TemplateFilter::Short, TemplateFilter::Upper],
+                },
+                TemplatePart::FilteredField {
+                    name: "branch".into(),
+                    filters: vec![TemplateFilter::Default("main".into())],
+                },
+                TemplatePart::FilteredField {
+                    name: "title".into(),
+                    filters: vec![TemplateFilter::Truncate(10)],
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn test_template_string_parse_filter_errors() {
+        // Invalid filters are literals when parsing leniently.
+        let ts = TemplateString::new("{name|unknown}");
+        itertools::assert_equal(
+            ts.parts,
+            [TemplatePart::Literal {
+                start: 0,
+                end: 14,
+            }],
+        );
+
+        assert_eq!(
+            TemplateString::parse("{name|unknown}").unwrap_err(),
+            TemplateError::UnknownFilter {
+                field: "name".into(),
+                filter: "unknown".into(),
+            },
+        );
+        assert_eq!(
+            TemplateString::parse("{name|truncate:many}").unwrap_err(),
+            TemplateError::InvalidFilterArgument {
+                field: "name".into(),
+                filter: "truncate".into(),
+                arg: "many".into(),
+            },
+        );
+        assert_eq!(
+            TemplateString::parse("{name|lower:x}").unwrap_err(),
+            TemplateError::InvalidFilterArgument {
+                field: "name".into(),
+                filter: "lower".into(),
+                arg: "x".into(),
+            },
+        );
+    }
+
+    #[test]
+    fn test_template_string_replace_filters() {
+        let lookup = [
+            ("commit", Cow::Borrowed("0123456789abcdef")),
+            ("branch", Cow::Borrowed("topic/Feature Name")),
+            ("empty", Cow::Borrowed("")),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        let ts = TemplateString::new("{commit|short}");
+        assert_eq!(ts.replace(&lookup), "01234567");
+
+        let ts = TemplateString::new("{branch|urlencode}");
+        assert_eq!(ts.replace(&lookup), "topic%2FFeature%20Name");
+
+        let ts = TemplateString::new("{branch|lower} {branch|upper}");
+        assert_eq!(ts.replace(&lookup), "topic/feature name TOPIC/FEATURE NAME");
+
+        let ts = TemplateString::new("{branch|truncate:5}");
+        assert_eq!(ts.replace(&lookup), "topic");
+
+        let ts = TemplateString::new("{missing|default:none} {empty|default:none}");
+        assert_eq!(ts.replace(&lookup), "none none");
+
+        let ts = TemplateString::new("{missing|default:a b|urlencode}");
+        assert_eq!(ts.replace(&lookup), "a%20b");
+
+        let ts = TemplateString::new("{{{commit|short}}}");
+        assert_eq!(ts.replace(&lookup), "{01234567}");
+    }
+
+    #[test]
+    fn test_template_string_strict() {
+        let lookup = [("name", Cow::Borrowed("value"))].iter().cloned().collect();
+
+        let mut ts = TemplateString::new("{name} {missing}");
+        assert_eq!(ts.try_replace(&lookup).unwrap(), "value ");
+
+        ts.strict();
+        assert_eq!(
+            ts.try_replace(&lookup).unwrap_err(),
+            TemplateError::UnknownField {
+                field: "missing".into(),
+            },
+        );
+        // Lenient replacement still works.
+        assert_eq!(ts.replace(&lookup), "value ");
+
+        let mut ts = TemplateString::new("{name} {missing|default:fallback}");
+        ts.strict();
+        assert_eq!(ts.try_replace(&lookup).unwrap(), "value fallback");
+    }
 }